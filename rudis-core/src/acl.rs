@@ -0,0 +1,398 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use sha2::{Digest, Sha256};
+
+use crate::commands::{CommandInfo, COMMAND_LIST};
+use crate::{escape_bytes, ByteString, Command, Response};
+
+pub const CATEGORIES: &[&str] = &["all", "read", "write", "fast", "slow", "admin", "dangerous", "connection"];
+
+const MAX_LOG_ENTRIES: usize = 128;
+
+fn in_category(info: &CommandInfo, category: &str) -> bool {
+    let has = |flag: &[u8]| info.flags.contains(&flag);
+    match category {
+        "all" => true,
+        "read" => has(b"readonly"),
+        "write" => has(b"write"),
+        "fast" => has(b"fast"),
+        "slow" => !has(b"fast"),
+        "admin" | "dangerous" => has(b"admin"),
+        "connection" => has(b"no_auth"),
+        _ => false,
+    }
+}
+
+pub fn category_commands(category: &str) -> anyhow::Result<impl Iterator<Item=&'static CommandInfo>> {
+    anyhow::ensure!(CATEGORIES.contains(&category), "Unknown category '{category}'");
+    let category = category.to_string();
+    Ok(COMMAND_LIST.iter().map(|&(_, info)| info).filter(move |info| in_category(info, &category)))
+}
+
+pub fn glob_matches(pattern: &[u8], s: &[u8]) -> bool {
+    match (str::from_utf8(pattern), str::from_utf8(s)) {
+        (Ok(pattern), Ok(s)) => glob_match::glob_match(pattern, s),
+        _ => false,
+    }
+}
+
+fn hash_password(password: &[u8]) -> String {
+    Sha256::digest(password).iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct KeyPattern {
+    pattern: ByteString,
+    read: bool,
+    write: bool,
+}
+
+impl KeyPattern {
+    fn describe(&self) -> String {
+        let prefix = match (self.read, self.write) {
+            (true, false) => "%R~",
+            (false, true) => "%W~",
+            _ => "~",
+        };
+        format!("{prefix}{}", escape_bytes(&self.pattern))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct User {
+    enabled: bool,
+    nopass: bool,
+    passwords: BTreeSet<String>,
+    commands: HashSet<&'static [u8]>,
+    command_rules: Vec<String>,
+    keys: Vec<KeyPattern>,
+    channels: Vec<ByteString>,
+}
+
+impl User {
+    pub fn new() -> Self {
+        Self { command_rules: vec!["-@all".to_string()], ..Self::default() }
+    }
+
+    fn set_category(&mut self, category: &str, allowed: bool) -> anyhow::Result<()> {
+        for info in category_commands(category)? {
+            self.set_command(info.name, allowed);
+        }
+        Ok(())
+    }
+
+    fn set_command(&mut self, name: &'static [u8], allowed: bool) {
+        if allowed {
+            self.commands.insert(name);
+        } else {
+            self.commands.remove(name);
+        }
+    }
+
+    pub fn apply_rule(&mut self, rule: &[u8]) -> anyhow::Result<()> {
+        let rule_str = str::from_utf8(rule).map_err(|_| anyhow::anyhow!("Error in ACL SETUSER modifier: non-utf8 rule"))?;
+        match rule_str.to_ascii_lowercase().as_str() {
+            "on" => self.enabled = true,
+            "off" => self.enabled = false,
+            "nopass" => {
+                self.nopass = true;
+                self.passwords.clear();
+            }
+            "resetpass" => {
+                self.nopass = false;
+                self.passwords.clear();
+            }
+            "allkeys" => self.keys = vec![KeyPattern { pattern: b"*".to_vec(), read: true, write: true }],
+            "resetkeys" => self.keys.clear(),
+            "allchannels" => self.channels = vec![b"*".to_vec()],
+            "resetchannels" => self.channels.clear(),
+            "allcommands" => return self.apply_rule(b"+@all"),
+            "nocommands" => return self.apply_rule(b"-@all"),
+            "reset" => *self = Self::new(),
+            _ => return self.apply_pattern_rule(rule, rule_str),
+        }
+        Ok(())
+    }
+
+    fn apply_pattern_rule(&mut self, rule: &[u8], rule_str: &str) -> anyhow::Result<()> {
+        match rule[0] {
+            b'>' => {
+                self.passwords.insert(hash_password(&rule[1..]));
+                self.nopass = false;
+            }
+            b'<' => {
+                anyhow::ensure!(self.passwords.remove(&hash_password(&rule[1..])), "Error in ACL SETUSER modifier '{rule_str}': no such password");
+            }
+            b'#' => {
+                let hash = rule_str[1..].to_ascii_lowercase();
+                anyhow::ensure!(hash.len() == 64 && hash.bytes().all(|b| b.is_ascii_hexdigit()), "Error in ACL SETUSER modifier '{rule_str}': The password hash must be exactly 64 characters and contain only lowercase hexadecimal characters");
+                self.passwords.insert(hash);
+                self.nopass = false;
+            }
+            b'!' => {
+                anyhow::ensure!(self.passwords.remove(&rule_str[1..].to_ascii_lowercase()), "Error in ACL SETUSER modifier '{rule_str}': no such password");
+            }
+            b'~' => self.keys.push(KeyPattern { pattern: rule[1..].to_vec(), read: true, write: true }),
+            b'%' => {
+                let (perms, pattern) = rule_str[1..].split_once('~').ok_or_else(|| anyhow::anyhow!("Error in ACL SETUSER modifier '{rule_str}': Syntax error"))?;
+                let perms = perms.to_ascii_uppercase();
+                anyhow::ensure!(!perms.is_empty() && perms.chars().all(|c| c == 'R' || c == 'W'), "Error in ACL SETUSER modifier '{rule_str}': Syntax error");
+                self.keys.push(KeyPattern { pattern: pattern.as_bytes().to_vec(), read: perms.contains('R'), write: perms.contains('W') });
+            }
+            b'&' => self.channels.push(rule[1..].to_vec()),
+            b'+' | b'-' => {
+                let allowed = rule[0] == b'+';
+                let name = rule_str[1..].to_ascii_lowercase();
+                if let Some(category) = name.strip_prefix('@') {
+                    self.set_category(category, allowed)?;
+                    if category == "all" {
+                        self.command_rules.clear();
+                    }
+                } else {
+                    let &(_, info) = COMMAND_LIST.iter()
+                        .find(|(_, info)| info.name == name.as_bytes())
+                        .ok_or_else(|| anyhow::anyhow!("Error in ACL SETUSER modifier '{rule_str}': Unknown command"))?;
+                    self.set_command(info.name, allowed);
+                }
+                self.command_rules.push(format!("{}{name}", rule[0] as char));
+            }
+            _ => anyhow::bail!("Error in ACL SETUSER modifier '{rule_str}': Syntax error"),
+        }
+        Ok(())
+    }
+
+    pub fn check_password(&self, password: &[u8]) -> bool {
+        self.enabled && (self.nopass || self.passwords.contains(&hash_password(password)))
+    }
+
+    pub fn can_run(&self, info: &CommandInfo) -> bool {
+        self.commands.contains(info.name)
+    }
+
+    pub fn can_access_key(&self, key: &[u8], write: bool) -> bool {
+        self.keys.iter().any(|p| (if write { p.write } else { p.read }) && glob_matches(&p.pattern, key))
+    }
+
+    /// Patterns subscribed to with PSUBSCRIBE must be allowed literally, as they could match
+    /// any channel.
+    pub fn can_access_channel(&self, channel: &[u8], pattern: bool) -> bool {
+        self.channels.iter().any(|p| if pattern { p == b"*" || p == channel } else { glob_matches(p, channel) })
+    }
+
+    fn flags(&self) -> Vec<&'static str> {
+        let mut flags = vec![if self.enabled { "on" } else { "off" }];
+        if self.nopass {
+            flags.push("nopass");
+        }
+        flags
+    }
+
+    fn describe_keys(&self) -> String {
+        self.keys.iter().map(|k| k.describe()).collect::<Vec<_>>().join(" ")
+    }
+
+    fn describe_channels(&self) -> String {
+        self.channels.iter().map(|c| format!("&{}", escape_bytes(c))).collect::<Vec<_>>().join(" ")
+    }
+
+    pub fn describe(&self) -> String {
+        let mut rules = self.flags().iter().map(|f| f.to_string()).collect::<Vec<_>>();
+        rules.extend(self.passwords.iter().map(|p| format!("#{p}")));
+        rules.push(self.describe_keys());
+        rules.push(match self.channels.is_empty() {
+            true => "resetchannels".to_string(),
+            false => self.describe_channels(),
+        });
+        rules.extend(self.command_rules.iter().cloned());
+        rules.retain(|r| !r.is_empty());
+        rules.join(" ")
+    }
+
+    pub fn as_response(&self) -> Response {
+        let fields = [
+            ("flags", Response::string_array(self.flags().iter().map(|f| f.as_bytes().to_vec()))),
+            ("passwords", Response::string_array(self.passwords.iter().map(|p| p.as_bytes().to_vec()))),
+            ("commands", Response::BulkString(self.command_rules.join(" ").into_bytes())),
+            ("keys", Response::BulkString(self.describe_keys().into_bytes())),
+            ("channels", Response::BulkString(self.describe_channels().into_bytes())),
+            ("selectors", Response::Array(Vec::new())),
+        ];
+        Response::Array(fields.into_iter().flat_map(|(k, v)| [Response::BulkString(k.as_bytes().to_vec()), v]).collect())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct LogEntry {
+    id: u64,
+    count: u64,
+    reason: &'static str,
+    object: ByteString,
+    username: ByteString,
+    client_id: u64,
+    created: SystemTime,
+    updated: SystemTime,
+}
+
+impl LogEntry {
+    fn as_response(&self) -> Response {
+        let millis = |t: SystemTime| t.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as i64;
+        let age = SystemTime::now().duration_since(self.created).unwrap_or_default().as_secs_f64();
+        let fields = [
+            ("count", Response::Number(self.count as _)),
+            ("reason", Response::BulkString(self.reason.as_bytes().to_vec())),
+            ("context", Response::BulkString(b"toplevel".to_vec())),
+            ("object", Response::BulkString(self.object.clone())),
+            ("username", Response::BulkString(self.username.clone())),
+            ("age-seconds", Response::float(age)),
+            ("client-info", Response::BulkString(format!("id={}", self.client_id).into_bytes())),
+            ("entry-id", Response::Number(self.id as _)),
+            ("timestamp-created", Response::Number(millis(self.created))),
+            ("timestamp-last-updated", Response::Number(millis(self.updated))),
+        ];
+        Response::Array(fields.into_iter().flat_map(|(k, v)| [Response::BulkString(k.as_bytes().to_vec()), v]).collect())
+    }
+}
+
+pub struct Acl {
+    users: BTreeMap<ByteString, User>,
+    log: VecDeque<LogEntry>,
+    next_log_id: u64,
+    file: Option<PathBuf>,
+}
+
+impl Default for Acl {
+    fn default() -> Self {
+        let mut users = BTreeMap::new();
+        users.insert(b"default".to_vec(), Self::default_user());
+        Self { users, log: VecDeque::new(), next_log_id: 0, file: None }
+    }
+}
+
+impl Acl {
+    fn default_user() -> User {
+        let mut user = User::new();
+        for rule in ["on", "nopass", "allkeys", "allchannels", "allcommands"] {
+            user.apply_rule(rule.as_bytes()).expect("default rules are valid");
+        }
+        user
+    }
+
+    pub fn user(&self, name: &[u8]) -> Option<&User> {
+        self.users.get(name)
+    }
+
+    pub fn users(&self) -> impl Iterator<Item=(&[u8], &User)> {
+        self.users.iter().map(|(name, user)| (name.as_slice(), user))
+    }
+
+    pub fn set_user(&mut self, name: ByteString, rules: &[ByteString]) -> anyhow::Result<()> {
+        let mut user = self.users.get(&name).cloned().unwrap_or_else(User::new);
+        for rule in rules {
+            anyhow::ensure!(!rule.is_empty(), "Error in ACL SETUSER modifier '': Syntax error");
+            user.apply_rule(rule)?;
+        }
+        self.users.insert(name, user);
+        Ok(())
+    }
+
+    pub fn del_user(&mut self, name: &[u8]) -> anyhow::Result<bool> {
+        anyhow::ensure!(name != b"default", "The 'default' user cannot be removed");
+        Ok(self.users.remove(name).is_some())
+    }
+
+    pub fn default_user_nopass(&self) -> bool {
+        self.users.get(b"default".as_slice()).is_some_and(|u| u.enabled && u.nopass)
+    }
+
+    /// Whether `username` may read any key, as required by the patterns of SORT.
+    pub fn can_read_all_keys(&self, username: &[u8]) -> bool {
+        self.users.get(username).is_some_and(|u| u.keys.iter().any(|p| p.read && p.pattern == b"*"))
+    }
+
+    pub fn authenticate(&self, username: &[u8], password: &[u8]) -> bool {
+        self.users.get(username).is_some_and(|u| u.check_password(password))
+    }
+
+    /// Returns the denial reason, if `username` may not run `cmd`.
+    pub fn check(&self, username: &[u8], info: &CommandInfo, cmd: &Command) -> Result<(), (&'static str, ByteString)> {
+        let Some(user) = self.users.get(username).filter(|u| u.enabled) else {
+            return Err(("command", info.name.to_vec()));
+        };
+        if !user.can_run(info) {
+            return Err(("command", info.name.to_vec()));
+        }
+        if let Some((key, _)) = info.key_accesses(cmd).into_iter().find(|&(key, write)| !user.can_access_key(key, write)) {
+            return Err(("key", key.to_vec()));
+        }
+        let (channels, pattern) = match info.name {
+            b"publish" => (cmd.arg(0).into_iter().collect(), false),
+            b"subscribe" => ((0..cmd.arg_count()).filter_map(|i| cmd.arg(i)).collect(), false),
+            b"psubscribe" => ((0..cmd.arg_count()).filter_map(|i| cmd.arg(i)).collect(), true),
+            _ => (Vec::new(), false),
+        };
+        match channels.into_iter().find(|channel| !user.can_access_channel(channel, pattern)) {
+            Some(channel) => Err(("channel", channel.to_vec())),
+            None => Ok(()),
+        }
+    }
+
+    pub fn log_denial(&mut self, reason: &'static str, object: ByteString, username: ByteString, client_id: u64) {
+        let now = SystemTime::now();
+        if let Some(e) = self.log.iter_mut().find(|e| e.reason == reason && e.object == object && e.username == username) {
+            e.count += 1;
+            e.updated = now;
+            e.client_id = client_id;
+            return;
+        }
+        self.log.push_front(LogEntry { id: self.next_log_id, count: 1, reason, object, username, client_id, created: now, updated: now });
+        self.next_log_id += 1;
+        self.log.truncate(MAX_LOG_ENTRIES);
+    }
+
+    pub fn log_response(&self, count: usize) -> Response {
+        Response::Array(self.log.iter().take(count).map(|e| e.as_response()).collect())
+    }
+
+    pub fn reset_log(&mut self) {
+        self.log.clear();
+    }
+
+    pub fn set_file(&mut self, file: Option<PathBuf>) {
+        self.file = file;
+    }
+
+    fn file(&self) -> anyhow::Result<&PathBuf> {
+        self.file.as_ref().ok_or_else(|| anyhow::anyhow!("This Redis instance is not configured to use an ACL file. You may want to specify users via the ACL SETUSER command and then issue a CONFIG REWRITE (assuming you have a Redis configuration file set) in order to store users in the Redis configuration."))
+    }
+
+    /// Replaces all users with the ones in the ACL file, leaving everything untouched on error.
+    pub fn load(&mut self) -> anyhow::Result<()> {
+        let file = self.file()?;
+        let contents = std::fs::read(file)?;
+        let mut users = Acl { users: BTreeMap::new(), ..Acl::default() };
+        for (i, line) in contents.split(|&b| b == b'\n').enumerate() {
+            let context = || format!("{}:{}", file.display(), i + 1);
+            let mut words = line.split(|b| b.is_ascii_whitespace()).filter(|w| !w.is_empty());
+            let Some(first) = words.next() else { continue };
+            anyhow::ensure!(first == b"user", "{}: line should start with user keyword", context());
+            let name = words.next().ok_or_else(|| anyhow::anyhow!("{}: missing username", context()))?;
+            anyhow::ensure!(users.user(name).is_none(), "{}: duplicate user '{}' found", context(), escape_bytes(name));
+            let rules = words.map(|w| w.to_vec()).collect::<Vec<_>>();
+            users.set_user(name.to_vec(), &rules).map_err(|e| anyhow::anyhow!("{}: {e}", context()))?;
+        }
+        users.users.entry(b"default".to_vec()).or_insert_with(Self::default_user);
+        self.users = users.users;
+        Ok(())
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        let mut contents = String::new();
+        for (name, user) in &self.users {
+            contents += &format!("user {} {}\n", escape_bytes(name), user.describe());
+        }
+        std::fs::write(self.file()?, contents)?;
+        Ok(())
+    }
+}
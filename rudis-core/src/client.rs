@@ -0,0 +1,37 @@
+use std::collections::{HashSet, VecDeque};
+use std::net::SocketAddr;
+use std::time::Instant;
+
+use crate::{ByteString, Command, Tracking};
+
+/// Why a client is waiting for its reply.
+#[derive(Debug, Clone)]
+pub enum Blocked {
+    /// WAIT, until `numreplicas` replicas acknowledged `offset`.
+    Wait { offset: u64, numreplicas: usize, deadline: Option<Instant> },
+    /// MIGRATE, until the server transferred the keys, see `Database::migrated`.
+    Migrate,
+    /// A write to keys held by the MIGRATE of `client`, until it is done.
+    MigratingKeys { client: u64 },
+}
+
+/// Per-connection state, owned by the `Database` and selected with `Database::select_client`.
+#[derive(Debug, Clone, Default)]
+pub struct Client {
+    /// The user this client authenticated as, if any.
+    pub user: Option<ByteString>,
+    /// The peer address, unknown for unix sockets.
+    pub addr: Option<SocketAddr>,
+    pub blocked: Option<Blocked>,
+    /// Set by ASKING, lets the next command access a slot being imported.
+    pub asking: bool,
+    /// Commands received while blocked, executed in order once the client is unblocked.
+    pub deferred: VecDeque<Command>,
+    /// Channels and patterns subscribed to with SUBSCRIBE and PSUBSCRIBE.
+    pub channels: HashSet<ByteString>,
+    pub patterns: HashSet<ByteString>,
+    /// Set by CLIENT TRACKING, see `Tracking`.
+    pub tracking: Option<Tracking>,
+    /// Set by CLIENT CACHING, applies to the next command only.
+    pub caching: Option<bool>,
+}
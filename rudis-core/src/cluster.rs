@@ -0,0 +1,425 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::commands::CommandInfo;
+use crate::{random_id, Command, Database};
+
+pub const SLOTS: usize = 16384;
+
+/// CRC-16/XMODEM, used to map keys to hash slots.
+pub fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0, |mut crc, &b| {
+        crc ^= (b as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+        }
+        crc
+    })
+}
+
+/// Only the part between the first `{` and the following `}` is hashed if it is not empty,
+/// so that related keys can be forced into the same slot.
+pub fn key_hash_slot(key: &[u8]) -> u16 {
+    let tag = key.iter().position(|&b| b == b'{').and_then(|start| {
+        let len = key[start + 1..].iter().position(|&b| b == b'}')?;
+        (len > 0).then(|| &key[start + 1..start + 1 + len])
+    });
+    crc16(tag.unwrap_or(key)) % SLOTS as u16
+}
+
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub id: String,
+    pub ip: String,
+    pub port: u16,
+    pub config_epoch: u64,
+    /// Whether our link to the node works, always true for ourselves.
+    pub connected: bool,
+    /// Nodes we were asked to MEET but never talked to have a temporary id.
+    pub handshake: bool,
+}
+
+impl Node {
+    pub fn addr(&self) -> String {
+        format!("{}:{}", self.ip, self.port)
+    }
+}
+
+/// A line of CLUSTER NODES, which is also the format of the cluster config file and of gossip.
+struct NodeLine {
+    node: Node,
+    myself: bool,
+    slots: Vec<u16>,
+    migrating: Vec<(u16, String)>,
+    importing: Vec<(u16, String)>,
+}
+
+fn parse_slot(s: &str) -> anyhow::Result<u16> {
+    let slot = s.parse::<u16>().map_err(|_| anyhow::anyhow!("Invalid or out of range slot"))?;
+    anyhow::ensure!((slot as usize) < SLOTS, "Invalid or out of range slot");
+    Ok(slot)
+}
+
+fn parse_node_line(line: &str) -> anyhow::Result<NodeLine> {
+    let fields = line.split_whitespace().collect::<Vec<_>>();
+    anyhow::ensure!(fields.len() >= 8, "invalid node line: {line}");
+    let addr = fields[1].split('@').next().unwrap_or_default();
+    let (ip, port) = addr.rsplit_once(':').ok_or_else(|| anyhow::anyhow!("invalid node address: {addr}"))?;
+    let flags = fields[2].split(',').collect::<Vec<_>>();
+    let node = Node {
+        id: fields[0].to_string(),
+        ip: ip.to_string(),
+        port: port.parse()?,
+        config_epoch: fields[6].parse()?,
+        connected: fields[7] == "connected",
+        handshake: flags.contains(&"handshake"),
+    };
+    let mut line = NodeLine { node, myself: flags.contains(&"myself"), slots: Vec::new(), migrating: Vec::new(), importing: Vec::new() };
+    for slots in &fields[8..] {
+        if let Some(state) = slots.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            if let Some((slot, id)) = state.split_once("->-") {
+                line.migrating.push((parse_slot(slot)?, id.to_string()));
+            } else if let Some((slot, id)) = state.split_once("-<-") {
+                line.importing.push((parse_slot(slot)?, id.to_string()));
+            }
+            continue;
+        }
+        let (start, end) = slots.split_once('-').unwrap_or((slots, slots));
+        line.slots.extend(parse_slot(start)?..=parse_slot(end)?);
+    }
+    Ok(line)
+}
+
+/// How a slot is being moved with CLUSTER SETSLOT.
+pub enum SlotState {
+    Migrating(String),
+    Importing(String),
+    Stable,
+    Node(String),
+}
+
+pub struct Cluster {
+    myself: String,
+    current_epoch: u64,
+    nodes: BTreeMap<String, Node>,
+    owners: Vec<Option<String>>,
+    migrating: BTreeMap<u16, String>,
+    importing: BTreeMap<u16, String>,
+    file: PathBuf,
+}
+
+impl Cluster {
+    /// Loads the cluster config file, or starts a new cluster of one node if it does not exist.
+    pub fn load(file: &Path, ip: &str, port: u16) -> anyhow::Result<Self> {
+        let mut cluster = Self {
+            myself: random_id(),
+            current_epoch: 0,
+            nodes: BTreeMap::new(),
+            owners: vec![None; SLOTS],
+            migrating: BTreeMap::new(),
+            importing: BTreeMap::new(),
+            file: file.to_path_buf(),
+        };
+        let contents = match std::fs::read_to_string(file) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => anyhow::bail!("failed to read cluster config file {}: {e}", file.display()),
+        };
+        for line in contents.lines().filter(|l| !l.trim().is_empty()) {
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            if fields[0] == "vars" {
+                if let Some(i) = fields.iter().position(|&f| f == "currentEpoch") {
+                    cluster.current_epoch = fields.get(i + 1).and_then(|e| e.parse().ok()).unwrap_or_default();
+                }
+                continue;
+            }
+            let line = parse_node_line(line).map_err(|e| anyhow::anyhow!("{}: {e}", file.display()))?;
+            if line.myself {
+                cluster.myself = line.node.id.clone();
+                cluster.migrating.extend(line.migrating);
+                cluster.importing.extend(line.importing);
+            }
+            for slot in line.slots {
+                cluster.owners[slot as usize] = Some(line.node.id.clone());
+            }
+            cluster.nodes.insert(line.node.id.clone(), Node { connected: false, ..line.node });
+        }
+        // the address may have changed since the file was written
+        let myself = Node { id: cluster.myself.clone(), ip: ip.to_string(), port, config_epoch: 0, connected: true, handshake: false };
+        let myself = cluster.nodes.entry(cluster.myself.clone()).or_insert(myself);
+        (myself.ip, myself.port, myself.connected) = (ip.to_string(), port, true);
+        cluster.save()?;
+        Ok(cluster)
+    }
+
+    /// Writes the cluster config file, called after every change so the node id and slots survive restarts.
+    pub fn save(&self) -> anyhow::Result<()> {
+        let contents = format!("{}vars currentEpoch {} lastVoteEpoch 0\n", self.describe(), self.current_epoch);
+        std::fs::write(&self.file, contents)
+            .map_err(|e| anyhow::anyhow!("failed to write cluster config file {}: {e}", self.file.display()))
+    }
+
+    pub fn myself(&self) -> &Node {
+        &self.nodes[&self.myself]
+    }
+
+    pub fn current_epoch(&self) -> u64 {
+        self.current_epoch
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item=&Node> {
+        self.nodes.values()
+    }
+
+    pub fn node(&self, id: &str) -> Option<&Node> {
+        self.nodes.get(id)
+    }
+
+    /// The nodes other than ourselves, which the server keeps links to.
+    pub fn peers(&self) -> impl Iterator<Item=&Node> {
+        self.nodes.values().filter(|n| n.id != self.myself)
+    }
+
+    pub fn owner(&self, slot: u16) -> Option<&Node> {
+        self.owners[slot as usize].as_ref().map(|id| &self.nodes[id])
+    }
+
+    pub fn assigned_slots(&self) -> usize {
+        self.owners.iter().filter(|o| o.is_some()).count()
+    }
+
+    /// Contiguous slot ranges served by `id`.
+    pub fn slot_ranges(&self, id: &str) -> Vec<(u16, u16)> {
+        let mut ranges = Vec::<(u16, u16)>::new();
+        for slot in (0..SLOTS as u16).filter(|&s| self.owners[s as usize].as_deref() == Some(id)) {
+            match ranges.last_mut() {
+                Some((_, end)) if *end + 1 == slot => *end = slot,
+                _ => ranges.push((slot, slot)),
+            }
+        }
+        ranges
+    }
+
+    pub fn migrating(&self, slot: u16) -> Option<&Node> {
+        self.migrating.get(&slot).and_then(|id| self.nodes.get(id))
+    }
+
+    pub fn is_importing(&self, slot: u16) -> bool {
+        self.importing.contains_key(&slot)
+    }
+
+    pub fn add_slots(&mut self, slots: &[u16]) -> anyhow::Result<()> {
+        for &slot in slots {
+            anyhow::ensure!(self.owners[slot as usize].is_none(), "Slot {slot} is already busy");
+        }
+        for &slot in slots {
+            self.owners[slot as usize] = Some(self.myself.clone());
+            self.importing.remove(&slot);
+        }
+        self.save()
+    }
+
+    pub fn del_slots(&mut self, slots: &[u16]) -> anyhow::Result<()> {
+        for &slot in slots {
+            anyhow::ensure!(self.owners[slot as usize].is_some(), "Slot {slot} is already unassigned");
+        }
+        for &slot in slots {
+            self.owners[slot as usize] = None;
+        }
+        self.save()
+    }
+
+    pub fn set_slot(&mut self, slot: u16, state: SlotState) -> anyhow::Result<()> {
+        let known = |id: &str| match self.nodes.get(id) {
+            Some(_) => Ok(id.to_string()),
+            None => Err(anyhow::anyhow!("I don't know about node {id}")),
+        };
+        let owned = self.owners[slot as usize].as_deref() == Some(self.myself.as_str());
+        match state {
+            SlotState::Migrating(id) => {
+                anyhow::ensure!(owned, "I'm not the owner of hash slot {slot}");
+                self.migrating.insert(slot, known(&id)?);
+            }
+            SlotState::Importing(id) => {
+                anyhow::ensure!(!owned, "I'm already the owner of hash slot {slot}");
+                self.importing.insert(slot, known(&id)?);
+            }
+            SlotState::Stable => {
+                self.migrating.remove(&slot);
+                self.importing.remove(&slot);
+            }
+            SlotState::Node(id) => {
+                let id = known(&id)?;
+                self.migrating.remove(&slot);
+                // the new owner bumps its epoch so that its claim wins when gossiped
+                if id == self.myself && self.importing.remove(&slot).is_some() {
+                    self.current_epoch += 1;
+                    self.nodes.get_mut(&self.myself).expect("myself is known").config_epoch = self.current_epoch;
+                }
+                self.owners[slot as usize] = Some(id);
+            }
+        }
+        self.save()
+    }
+
+    pub fn meet(&mut self, ip: &str, port: u16) -> anyhow::Result<()> {
+        if self.nodes.values().any(|n| n.ip == ip && n.port == port) {
+            return Ok(());
+        }
+        let node = Node { id: random_id(), ip: ip.to_string(), port, config_epoch: 0, connected: false, handshake: true };
+        self.nodes.insert(node.id.clone(), node);
+        self.save()
+    }
+
+    fn remove_node(&mut self, id: &str) -> Option<Node> {
+        for owner in self.owners.iter_mut().filter(|o| o.as_deref() == Some(id)) {
+            *owner = None;
+        }
+        self.migrating.retain(|_, target| target != id);
+        self.importing.retain(|_, source| source != id);
+        self.nodes.remove(id)
+    }
+
+    pub fn forget(&mut self, id: &str) -> anyhow::Result<()> {
+        anyhow::ensure!(id != self.myself, "I tried hard but I can't forget myself...");
+        anyhow::ensure!(self.remove_node(id).is_some(), "Unknown node {id}");
+        self.save()
+    }
+
+    /// Merges the CLUSTER NODES output of the node at `ip:port`, or marks it as disconnected on `None`.
+    ///
+    /// Nodes are only trusted about themselves: their slots are taken over when unassigned or when
+    /// their config epoch is greater than the one of the current owner, other nodes they know about
+    /// are added so that we link to them as well.
+    pub fn gossip(&mut self, ip: &str, port: u16, nodes: Option<&str>) -> anyhow::Result<()> {
+        let Some(nodes) = nodes else {
+            if let Some(node) = self.nodes.values_mut().find(|n| n.ip == ip && n.port == port) {
+                node.connected = false;
+            }
+            return Ok(());
+        };
+        let before = self.describe();
+        for line in nodes.lines().filter(|l| !l.trim().is_empty()) {
+            let line = parse_node_line(line)?;
+            let id = line.node.id.clone();
+            if id == self.myself || line.node.handshake {
+                continue;
+            }
+            self.current_epoch = self.current_epoch.max(line.node.config_epoch);
+            if !line.myself {
+                self.nodes.entry(id).or_insert(Node { connected: false, ..line.node });
+                continue;
+            }
+            // the node we polled, which replaces its placeholder from MEET
+            let stale = self.nodes.values().filter(|n| n.id != id && n.ip == ip && n.port == port).map(|n| n.id.clone()).collect::<Vec<_>>();
+            for stale in stale {
+                self.remove_node(&stale);
+            }
+            let node = self.nodes.entry(id.clone()).or_insert(line.node.clone());
+            (node.ip, node.port, node.config_epoch, node.connected, node.handshake) = (ip.to_string(), port, line.node.config_epoch, true, false);
+            for slot in line.slots {
+                let claim = match &self.owners[slot as usize] {
+                    None => true,
+                    Some(owner) => *owner != id && self.nodes.get(owner).is_none_or(|o| o.config_epoch < line.node.config_epoch),
+                };
+                if claim {
+                    self.owners[slot as usize] = Some(id.clone());
+                }
+            }
+        }
+        if self.describe() != before {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// The CLUSTER NODES output.
+    pub fn describe(&self) -> String {
+        let mut res = String::new();
+        for node in self.nodes.values() {
+            let myself = node.id == self.myself;
+            let flags = match (myself, node.handshake) {
+                (true, _) => "myself,master",
+                (false, true) => "handshake",
+                (false, false) => "master",
+            };
+            let link = if node.connected { "connected" } else { "disconnected" };
+            // we gossip over the client port, there is no separate cluster bus port
+            res += &format!("{} {}:{}@{} {flags} - 0 0 {} {link}", node.id, node.ip, node.port, node.port, node.config_epoch);
+            for (start, end) in self.slot_ranges(&node.id) {
+                res += &match start == end {
+                    true => format!(" {start}"),
+                    false => format!(" {start}-{end}"),
+                };
+            }
+            if myself {
+                for (slot, id) in &self.migrating {
+                    res += &format!(" [{slot}->-{id}]");
+                }
+                for (slot, id) in &self.importing {
+                    res += &format!(" [{slot}-<-{id}]");
+                }
+            }
+            res += "\n";
+        }
+        res
+    }
+}
+
+impl Database {
+    pub fn cluster(&mut self) -> anyhow::Result<&mut Cluster> {
+        self.cluster.as_mut().ok_or_else(|| anyhow::anyhow!("This instance has cluster support disabled"))
+    }
+
+    pub fn is_cluster_enabled(&self) -> bool {
+        self.cluster.is_some()
+    }
+
+    /// The keys in `slot` that did not expire.
+    pub fn keys_in_slot(&self, slot: u16) -> impl Iterator<Item=&[u8]> {
+        let keys = self.slot_keys.get(slot as usize).into_iter().flatten();
+        keys.filter(|k| !self.is_expired(k)).map(|k| k.as_slice())
+    }
+
+    /// Adds a key to the index of its slot in cluster mode, as done for keys being created.
+    pub(crate) fn index_slot_key(&mut self, key: &[u8]) {
+        if self.slot_keys.is_empty() {
+            return;
+        }
+        let keys = &mut self.slot_keys[key_hash_slot(key) as usize];
+        if !keys.contains(key) {
+            keys.insert(key.to_vec());
+        }
+    }
+
+    /// Removes a key from the index of its slot, as done for keys being deleted.
+    pub(crate) fn unindex_slot_key(&mut self, key: &[u8]) {
+        if !self.slot_keys.is_empty() {
+            self.slot_keys[key_hash_slot(key) as usize].remove(key);
+        }
+    }
+
+    /// Makes sure all the keys of the command are served by this node, otherwise returns a MOVED
+    /// or ASK redirection, or CROSSSLOT when the keys are in different slots.
+    pub(crate) fn check_cluster_redirect(&self, info: &CommandInfo, cmd: &Command, asking: bool) -> anyhow::Result<()> {
+        let Some(cluster) = &self.cluster else { return Ok(()) };
+        let keys = info.keys(cmd);
+        let Some(slot) = keys.first().map(|k| key_hash_slot(k)) else { return Ok(()) };
+        anyhow::ensure!(keys.iter().all(|k| key_hash_slot(k) == slot), "CROSSSLOT Keys in request don't hash to the same slot");
+        let asking = asking || info.flags.contains(&b"asking".as_slice());
+        match cluster.owner(slot) {
+            Some(owner) if owner.id == cluster.myself => {
+                let Some(target) = cluster.migrating(slot) else { return Ok(()) };
+                let missing = keys.iter().filter(|k| self.peek(k).is_none()).count();
+                match missing {
+                    0 => Ok(()),
+                    n if n == keys.len() => anyhow::bail!("ASK {slot} {}", target.addr()),
+                    _ => anyhow::bail!("TRYAGAIN Multiple keys request during rehashing of slot"),
+                }
+            }
+            _ if asking && cluster.is_importing(slot) => Ok(()),
+            Some(owner) => anyhow::bail!("MOVED {slot} {}", owner.addr()),
+            None => anyhow::bail!("CLUSTERDOWN Hash slot not served"),
+        }
+    }
+}
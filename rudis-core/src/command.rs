@@ -98,6 +98,21 @@ impl Command {
         &self.cmd
     }
 
+    /// Remaining argument `i`, not counting the command name.
+    pub fn arg(&self, i: usize) -> Option<&[u8]> {
+        self.args.get(i).map(|a| a.as_slice())
+    }
+
+    pub fn arg_count(&self) -> usize {
+        self.args.len()
+    }
+
+    /// The command in the RESP format, as propagated to replicas.
+    pub fn encode(&self) -> ByteString {
+        let args = std::iter::once(self.cmd.as_bytes().to_vec()).chain(self.args.iter().cloned());
+        crate::replication::encode(args.collect())
+    }
+
     pub fn pop_arg(&mut self) -> Option<ByteString> {
         self.args.pop_front()
     }
@@ -0,0 +1,197 @@
+use super::CommandInfo;
+use crate::acl::{category_commands, CATEGORIES};
+use crate::command::Command;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"acl",
+    arity: -2,
+    flags: &[
+        b"admin",
+        b"noscript",
+        b"loading",
+        b"stale",
+    ],
+    first_key: 0,
+    last_key: 0,
+    step: 0,
+};
+
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let mut subcommand = cmd.parse_partial_args::<ByteString>()?;
+    subcommand.make_ascii_lowercase();
+    let res = match subcommand.as_slice() {
+        b"setuser" => {
+            let (name, rules) = cmd.parse_args::<(ByteString, Vec<ByteString>)>()?;
+            db.acl().set_user(name, &rules)?;
+            Response::SimpleString(b"OK".to_vec())
+        }
+        b"getuser" => {
+            let name = cmd.parse_args::<ByteString>()?;
+            db.acl().user(&name).map(|u| u.as_response()).unwrap_or_default()
+        }
+        b"deluser" => {
+            let names = cmd.parse_args::<Vec<ByteString>>()?;
+            anyhow::ensure!(!names.is_empty(), "wrong number of arguments for 'acl|deluser' command");
+            let mut deleted = 0;
+            for name in names {
+                deleted += db.acl().del_user(&name)? as i64;
+            }
+            Response::Number(deleted)
+        }
+        b"list" => {
+            cmd.ensure_empty()?;
+            Response::string_array(db.acl().users().map(|(name, user)| {
+                let mut line = b"user ".to_vec();
+                line.extend_from_slice(name);
+                line.push(b' ');
+                line.extend_from_slice(user.describe().as_bytes());
+                line
+            }))
+        }
+        b"users" => {
+            cmd.ensure_empty()?;
+            Response::string_array(db.acl().users().map(|(name, _)| name.to_vec()))
+        }
+        b"whoami" => {
+            cmd.ensure_empty()?;
+            db.username().map(Response::BulkString).unwrap_or_default()
+        }
+        b"cat" => match cmd.parse_args::<Option<ByteString>>()? {
+            Some(category) => {
+                let category = String::from_utf8_lossy(&category).to_ascii_lowercase();
+                Response::string_array(category_commands(&category)?.map(|info| info.name.to_vec()))
+            }
+            None => Response::string_array(CATEGORIES.iter().map(|c| c.as_bytes().to_vec())),
+        },
+        b"log" => {
+            if cmd.parse_option("RESET") {
+                cmd.ensure_empty()?;
+                db.acl().reset_log();
+                Response::SimpleString(b"OK".to_vec())
+            } else {
+                let count = cmd.parse_args::<Option<i64>>()?.unwrap_or(10);
+                anyhow::ensure!(count >= 0, "count should be greater than or equal to 0");
+                db.acl().log_response(count as _)
+            }
+        }
+        b"load" => {
+            cmd.ensure_empty()?;
+            db.acl().load()?;
+            Response::SimpleString(b"OK".to_vec())
+        }
+        b"save" => {
+            cmd.ensure_empty()?;
+            db.acl().save()?;
+            Response::SimpleString(b"OK".to_vec())
+        }
+        _ => anyhow::bail!("unknown subcommand '{}'", String::from_utf8_lossy(&subcommand)),
+    };
+    Ok(res)
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "acl whoami"                                       => "default";
+    "acl users"                                        => ["default"];
+    "acl setuser alice on >secret ~cached:* +get +acl" => "OK";
+    "acl setuser bob"                                  => "OK";
+    "acl users"                                        => ["alice", "bob", "default"];
+    "acl list"                                         => [
+        "user alice on #2bb80d537b1da3e38bd30361aa855686bde0eacd7162fef6a25fe97bf527a25b ~cached:* resetchannels -@all +get +acl",
+        "user bob off resetchannels -@all",
+        "user default on nopass ~* &* +@all",
+    ];
+    "acl getuser carol"                                => ();
+    "acl deluser bob carol"                            => 1;
+    "acl cat"                                          => ["all", "read", "write", "fast", "slow", "admin", "dangerous", "connection"];
+    "acl cat connection"                               => ["auth", "hello", "quit"];
+    "acl log"                                          => [];
+    "auth alice secret"                                => "OK";
+    "acl whoami"                                       => "alice";
+    "get cached:x"                                     => ();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Database, Response};
+    use crate::test_utils::exec;
+
+    #[test]
+    fn test_permissions() {
+        let mut db = Database::default();
+        exec(&mut db, "acl setuser alice on nopass %R~r:* ~rw:* +@read +set").unwrap();
+        exec(&mut db, "auth alice x").unwrap();
+        assert_eq!(exec(&mut db, "get r:1").unwrap(), Response::Nil);
+        assert_eq!(exec(&mut db, "set rw:1 x").unwrap(), Response::SimpleString(b"OK".to_vec()));
+        assert_eq!(exec(&mut db, "set r:1 x").unwrap_err().to_string(), "NOPERM No permissions to access a key");
+        assert_eq!(exec(&mut db, "mget rw:1 other").unwrap_err().to_string(), "NOPERM No permissions to access a key");
+        assert_eq!(exec(&mut db, "del rw:1").unwrap_err().to_string(), "NOPERM User alice has no permissions to run the 'del' command");
+        assert!(exec(&mut db, "acl whoami").is_err());
+        exec(&mut db, "auth bob x").unwrap_err();
+
+        db.select_client(2);
+        let Response::Array(log) = exec(&mut db, "acl log 1").unwrap() else { panic!() };
+        let [Response::Array(entry)] = log.as_slice() else { panic!() };
+        assert_eq!(entry[3], Response::BulkString(b"auth".to_vec()));
+        assert_eq!(entry[9], Response::BulkString(b"bob".to_vec()));
+        assert!(matches!(exec(&mut db, "acl log").unwrap(), Response::Array(v) if v.len() == 5));
+    }
+
+    #[test]
+    fn test_key_accesses() {
+        let mut db = Database::default();
+        exec(&mut db, "acl setuser alice on nopass %R~src* ~dst* +@all").unwrap();
+        exec(&mut db, "auth alice x").unwrap();
+        // the sources of the results stored elsewhere are only read
+        assert_eq!(exec(&mut db, "sunionstore dst src1 src2").unwrap(), Response::Number(0));
+        assert!(exec(&mut db, "copy src1 dst").is_ok());
+        assert!(exec(&mut db, "sort src1 store dst").is_ok());
+        assert!(exec(&mut db, "bitop or dst src1").is_ok());
+        assert!(exec(&mut db, "pfmerge dst src1").is_ok());
+        assert_eq!(exec(&mut db, "sunionstore src1 dst").unwrap_err().to_string(), "NOPERM No permissions to access a key");
+        assert_eq!(exec(&mut db, "copy dst src1").unwrap_err().to_string(), "NOPERM No permissions to access a key");
+
+        // SORT patterns can read any key
+        assert_eq!(exec(&mut db, "sort src1 by nosort get secret_*").unwrap_err().to_string(), "GET option of SORT denied due to insufficient ACL permissions.");
+        assert_eq!(exec(&mut db, "sort src1 by w_*").unwrap_err().to_string(), "BY option of SORT denied due to insufficient ACL permissions.");
+        assert!(exec(&mut db, "sort src1 by nosort").is_ok());
+        exec(&mut db, "acl setuser alice %R~*").unwrap();
+        assert!(exec(&mut db, "sort src1 by w_* get secret_*").is_ok());
+    }
+
+    #[test]
+    fn test_channel_permissions() {
+        let mut db = Database::default();
+        db.accept_client(1, None, false).unwrap();
+        db.select_client(1);
+        exec(&mut db, "acl setuser alice on nopass &news:* +@all").unwrap();
+        exec(&mut db, "auth alice x").unwrap();
+        assert_eq!(exec(&mut db, "publish news:1 hi").unwrap(), Response::Number(0));
+        assert_eq!(exec(&mut db, "publish other hi").unwrap_err().to_string(), "NOPERM No permissions to access a channel");
+        assert_eq!(exec(&mut db, "subscribe news:1 other").unwrap_err().to_string(), "NOPERM No permissions to access a channel");
+        // a pattern can match channels the user has no access to unless allowed as is
+        assert_eq!(exec(&mut db, "psubscribe news:1*").unwrap_err().to_string(), "NOPERM No permissions to access a channel");
+        assert!(exec(&mut db, "psubscribe news:*").is_ok());
+        assert!(exec(&mut db, "subscribe news:2").is_ok());
+    }
+
+    #[test]
+    fn test_load_save() {
+        let path = std::env::temp_dir().join(format!("rudis-acl-test-{}.acl", std::process::id()));
+        let mut db = Database::default();
+        assert!(exec(&mut db, "acl save").is_err());
+        db.acl().set_file(Some(path.clone()));
+        exec(&mut db, "acl setuser alice on >pw allkeys +@all -flushall").unwrap();
+        exec(&mut db, "acl save").unwrap();
+        exec(&mut db, "acl deluser alice").unwrap();
+        exec(&mut db, "acl load").unwrap();
+        exec(&mut db, "auth alice pw").unwrap();
+        assert!(exec(&mut db, "flushall").is_err());
+
+        std::fs::write(&path, "user bob on +nonexistent\n").unwrap();
+        assert!(exec(&mut db, "acl load").is_err());
+        assert!(db.acl().user(b"alice").is_some());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -17,9 +17,11 @@ pub static INFO: CommandInfo = CommandInfo {
 
 pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
     let (key, value) = cmd.parse_args::<(ByteString, ByteString)>()?;
-    let v = db.get_or_insert_str(key)?;
+    let v = db.get_or_insert_str(key.clone())?.to_mut();
     v.extend(value);
-    Ok(Response::Number(v.len() as _))
+    let len = v.len();
+    db.notify('$', "append", &key);
+    Ok(Response::Number(len as _))
 }
 
 #[cfg(test)]
@@ -0,0 +1,21 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"asking",
+    arity: 1,
+    flags: &[
+        b"fast",
+    ],
+    first_key: 0,
+    last_key: 0,
+    step: 0,
+};
+
+pub fn run(db: &mut Database, cmd: Command) -> anyhow::Result<Response> {
+    cmd.ensure_empty()?;
+    db.cluster()?;
+    db.client().asking = true;
+    Ok(Response::SimpleString(b"OK".to_vec()))
+}
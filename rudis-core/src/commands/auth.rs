@@ -0,0 +1,63 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"auth",
+    arity: -2,
+    flags: &[
+        b"noscript",
+        b"loading",
+        b"stale",
+        b"fast",
+        b"no_auth",
+        b"allow_busy",
+    ],
+    first_key: 0,
+    last_key: 0,
+    step: 0,
+};
+
+pub fn authenticate(db: &mut Database, username: Option<&[u8]>, password: &[u8]) -> anyhow::Result<()> {
+    if username.is_none() && db.acl().default_user_nopass() {
+        anyhow::bail!("AUTH <password> called without any password configured for the default user. Are you sure your configuration is correct?");
+    }
+    let username = username.unwrap_or(b"default");
+    if !db.acl().authenticate(username, password) {
+        let client_id = db.client_id();
+        db.acl().log_denial("auth", b"AUTH".to_vec(), username.to_vec(), client_id);
+        anyhow::bail!("WRONGPASS invalid username-password pair or user is disabled.");
+    }
+    db.client().user = Some(username.to_vec());
+    Ok(())
+}
+
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let (a, b) = cmd.parse_args::<(ByteString, Option<ByteString>)>()?;
+    match b {
+        Some(password) => authenticate(db, Some(&a), &password)?,
+        None => authenticate(db, None, &a)?,
+    }
+    Ok(Response::SimpleString(b"OK".to_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Database, Response};
+    use crate::test_utils::exec;
+
+    #[test]
+    fn test_auth() {
+        let mut db = Database::default();
+        assert!(exec(&mut db, "auth pass").is_err());
+        db.set_config(&[("requirepass".to_string(), "pass".to_string())]).unwrap();
+        assert_eq!(exec(&mut db, "get x").unwrap_err().to_string(), "NOAUTH Authentication required.");
+        assert!(exec(&mut db, "auth wrong").unwrap_err().to_string().starts_with("WRONGPASS"));
+        assert!(exec(&mut db, "auth someone pass").is_err());
+        assert_eq!(exec(&mut db, "auth pass").unwrap(), Response::SimpleString(b"OK".to_vec()));
+        assert_eq!(exec(&mut db, "get x").unwrap(), Response::Nil);
+        assert_eq!(exec(&mut db, "auth default pass").unwrap(), Response::SimpleString(b"OK".to_vec()));
+        db.select_client(1);
+        assert!(exec(&mut db, "get x").is_err());
+    }
+}
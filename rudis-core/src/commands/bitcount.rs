@@ -0,0 +1,76 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"bitcount",
+    arity: -2,
+    flags: &[
+        b"readonly",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+/// Converts an inclusive range of possibly negative BYTE or BIT indexes into a range of bit
+/// offsets within a string of `len` bytes, `None` when it selects nothing.
+pub fn bit_range(len: usize, start: i64, end: i64, bits: bool) -> Option<(usize, usize)> {
+    let total = if bits { len * 8 } else { len } as i64;
+    let start = if start < 0 { total + start } else { start }.max(0);
+    let end = if end < 0 { total + end } else { end }.max(0).min(total - 1);
+    if start > end {
+        return None;
+    }
+    let (start, end) = (start as usize, end as usize);
+    Some(if bits { (start, end) } else { (start * 8, end * 8 + 7) })
+}
+
+/// Parses the optional BYTE | BIT unit of a range, returns whether it is in bits.
+pub fn parse_unit(cmd: &mut Command) -> anyhow::Result<bool> {
+    let bits = cmd.parse_option("bit");
+    if !bits {
+        cmd.parse_option("byte");
+    }
+    cmd.ensure_empty().map_err(|_| anyhow::anyhow!("syntax error"))?;
+    Ok(bits)
+}
+
+fn count_ones(s: &[u8], first: usize, last: usize) -> usize {
+    let (a, b) = (first / 8, last / 8);
+    let (head, tail) = (0xffu8 >> (first % 8), 0xffu8 << (7 - last % 8));
+    if a == b {
+        return (s[a] & head & tail).count_ones() as _;
+    }
+    let middle = s[a + 1..b].iter().map(|x| x.count_ones() as usize).sum::<usize>();
+    (s[a] & head).count_ones() as usize + middle + (s[b] & tail).count_ones() as usize
+}
+
+/// BITCOUNT key [start end [BYTE | BIT]]
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let key = cmd.parse_partial_args::<ByteString>()?;
+    let range = match cmd.arg_count() {
+        0 => None,
+        1 => anyhow::bail!("syntax error"),
+        _ => Some(cmd.parse_partial_args::<(i64, i64)>()?),
+    };
+    let bits = parse_unit(&mut cmd)?;
+    let Some(s) = db.get_str(&key)?.map(|s| s.as_bytes()) else { return Ok(Response::Number(0)) };
+    let (start, end) = range.unwrap_or((0, -1));
+    let count = bit_range(s.len(), start, end, bits).map_or(0, |(first, last)| count_ones(&s, first, last));
+    Ok(Response::Number(count as _))
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "bitcount x"             => 0;
+    "set x foobar"           => "OK";
+    "bitcount x"             => 26;
+    "bitcount x 0 0"         => 4;
+    "bitcount x 1 1"         => 6;
+    "bitcount x 1 1 byte"    => 6;
+    "bitcount x 5 30 bit"    => 17;
+    "bitcount x -2 -1"       => 7;
+    "bitcount x 3 1"         => 0;
+    "bitcount x 100 200"     => 0;
+}
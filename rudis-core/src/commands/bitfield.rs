@@ -0,0 +1,214 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::string::MAX_STRING_LEN;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"bitfield",
+    arity: -2,
+    flags: &[
+        b"write",
+        b"denyoom",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+/// An integer field of a bitmap: signed up to 64 bits, unsigned up to 63 bits so that its
+/// values fit in a reply.
+#[derive(Debug, Clone, Copy)]
+pub struct Field {
+    signed: bool,
+    bits: usize,
+    offset: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Overflow {
+    Wrap,
+    Sat,
+    Fail,
+}
+
+pub enum Op {
+    Get(Field),
+    Set(Field, i64, Overflow),
+    IncrBy(Field, i64, Overflow),
+}
+
+impl Field {
+    fn parse(cmd: &mut Command) -> anyhow::Result<Self> {
+        let (ty, offset) = cmd.parse_partial_args::<(ByteString, ByteString)>().map_err(|_| anyhow::anyhow!("syntax error"))?;
+        let invalid_type = || anyhow::anyhow!("Invalid bitfield type. Use something like i16 u8. Note that u64 is not supported but i64 is.");
+        let (signed, bits) = match ty.split_first() {
+            Some((b'i' | b'I', bits)) => (true, bits),
+            Some((b'u' | b'U', bits)) => (false, bits),
+            _ => return Err(invalid_type()),
+        };
+        let bits = std::str::from_utf8(bits).ok().and_then(|b| b.parse::<usize>().ok()).ok_or_else(invalid_type)?;
+        anyhow::ensure!(bits >= 1 && bits <= if signed { 64 } else { 63 }, invalid_type());
+        // `#n` addresses the n-th field of this width
+        let (multiplier, offset) = match offset.strip_prefix(b"#") {
+            Some(offset) => (bits, offset),
+            None => (1, offset.as_slice()),
+        };
+        let offset = std::str::from_utf8(offset).ok().and_then(|o| o.parse::<usize>().ok()).and_then(|o| o.checked_mul(multiplier));
+        let offset = offset
+            .filter(|o| o + bits <= MAX_STRING_LEN * 8)
+            .ok_or_else(|| anyhow::anyhow!("bit offset is not an integer or out of range"))?;
+        Ok(Self { signed, bits, offset })
+    }
+
+    fn get(&self, s: &[u8]) -> i64 {
+        let mut v = 0u64;
+        for i in self.offset..self.offset + self.bits {
+            let byte = s.get(i / 8).copied().unwrap_or(0);
+            v = v << 1 | ((byte >> (7 - i % 8)) & 1) as u64;
+        }
+        if self.signed && self.bits < 64 && (v >> (self.bits - 1)) & 1 == 1 {
+            (v as i128 - (1i128 << self.bits)) as i64
+        } else {
+            v as i64
+        }
+    }
+
+    fn set(&self, s: &mut Vec<u8>, value: i64) {
+        let len = (self.offset + self.bits).div_ceil(8);
+        if s.len() < len {
+            s.resize(len, 0);
+        }
+        for i in 0..self.bits {
+            let bit = ((value as u64) >> (self.bits - 1 - i)) & 1;
+            let (byte, shift) = ((self.offset + i) / 8, 7 - (self.offset + i) % 8);
+            s[byte] = (s[byte] & !(1 << shift)) | ((bit as u8) << shift);
+        }
+    }
+
+    /// Fits a value in the field according to the overflow policy, `None` when it fails.
+    fn fit(&self, v: i128, overflow: Overflow) -> Option<i64> {
+        let (min, max) = match self.signed {
+            true => (-(1i128 << (self.bits - 1)), (1i128 << (self.bits - 1)) - 1),
+            false => (0, (1i128 << self.bits) - 1),
+        };
+        if (min..=max).contains(&v) {
+            return Some(v as i64);
+        }
+        match overflow {
+            Overflow::Wrap => Some(((v - min).rem_euclid(1i128 << self.bits) + min) as i64),
+            Overflow::Sat => Some(v.clamp(min, max) as i64),
+            Overflow::Fail => None,
+        }
+    }
+}
+
+/// Parses the subcommands of BITFIELD, `OVERFLOW` applying to the ones following it.
+pub fn parse_ops(cmd: &mut Command) -> anyhow::Result<Vec<Op>> {
+    let mut ops = Vec::new();
+    let mut overflow = Overflow::Wrap;
+    while let Some(sub) = cmd.pop_arg() {
+        let op = match sub.to_ascii_lowercase().as_slice() {
+            b"get" => Op::Get(Field::parse(cmd)?),
+            b"set" => Op::Set(Field::parse(cmd)?, cmd.parse_partial_args::<i64>()?, overflow),
+            b"incrby" => Op::IncrBy(Field::parse(cmd)?, cmd.parse_partial_args::<i64>()?, overflow),
+            b"overflow" => {
+                let kind = cmd.pop_arg().unwrap_or_default().to_ascii_lowercase();
+                overflow = match kind.as_slice() {
+                    b"wrap" => Overflow::Wrap,
+                    b"sat" => Overflow::Sat,
+                    b"fail" => Overflow::Fail,
+                    _ => anyhow::bail!("Invalid OVERFLOW type specified"),
+                };
+                continue;
+            }
+            _ => anyhow::bail!("syntax error"),
+        };
+        ops.push(op);
+    }
+    Ok(ops)
+}
+
+/// Runs the subcommands in order, the key is only created when one of them writes.
+pub fn execute(db: &mut Database, key: ByteString, ops: Vec<Op>) -> anyhow::Result<Response> {
+    if ops.iter().all(|op| matches!(op, Op::Get(_))) {
+        let s = db.get_str(&key)?.map(|s| s.as_bytes()).unwrap_or_default();
+        let res = ops.iter().map(|op| match op {
+            Op::Get(field) => Response::Number(field.get(&s)),
+            _ => unreachable!(),
+        });
+        return Ok(Response::Array(res.collect()));
+    }
+    let s = db.get_or_insert_str(key.clone())?.to_mut();
+    let mut changed = false;
+    let mut res = Vec::with_capacity(ops.len());
+    for op in ops {
+        let (field, value, overflow) = match op {
+            Op::Get(field) => {
+                res.push(Response::Number(field.get(s)));
+                continue;
+            }
+            Op::Set(field, value, overflow) => (field, value as i128, overflow),
+            Op::IncrBy(field, incr, overflow) => (field, field.get(s) as i128 + incr as i128, overflow),
+        };
+        let old = field.get(s);
+        let Some(new) = field.fit(value, overflow) else {
+            res.push(Response::Nil);
+            continue;
+        };
+        field.set(s, new);
+        changed = true;
+        // SET replies with the previous value, INCRBY with the new one
+        res.push(Response::Number(if matches!(op, Op::Set(..)) { old } else { new }));
+    }
+    if changed {
+        db.notify('$', "setbit", &key);
+    }
+    Ok(Response::Array(res))
+}
+
+/// BITFIELD key [GET encoding offset | [OVERFLOW WRAP | SAT | FAIL] SET encoding offset value |
+/// [OVERFLOW WRAP | SAT | FAIL] INCRBY encoding offset increment ...]
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let key = cmd.parse_partial_args::<ByteString>()?;
+    let ops = parse_ops(&mut cmd)?;
+    execute(db, key, ops)
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "bitfield x get u8 0"                                     => [0];
+    "exists x"                                                => 0;
+    "bitfield x set i8 0 -100 get u8 0 get i8 0"              => [0, 156, -100];
+    "bitfield x set u8 #1 200 get u8 8 get u4 #2"             => [0, 200, 12];
+    "bitfield x incrby i8 0 -100"                             => [56];
+    "bitfield y incrby u2 100 1 overflow sat incrby u2 102 1" => [1, 1];
+    "bitfield y incrby u2 100 1 overflow sat incrby u2 102 1" => [2, 2];
+    "bitfield y incrby u2 100 1 overflow sat incrby u2 102 1" => [3, 3];
+    "bitfield y incrby u2 100 1 overflow sat incrby u2 102 1" => [0, 3];
+    "bitfield y overflow fail incrby u2 102 1"                => [()];
+    "bitfield z set i64 0 -1 get i64 0 get u63 1"             => [0, -1, i64::MAX];
+    "bitfield z incrby i64 0 1 overflow sat incrby i64 0 -2"  => [0, -2];
+    "bitfield z set i64 0 9223372036854775807"                => [-2];
+    "bitfield z incrby i64 0 1"                               => [i64::MIN];
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Database;
+    use crate::test_utils::exec;
+
+    #[test]
+    fn test_bitfield_errors() {
+        let mut db = Database::default();
+        let invalid_type = "Invalid bitfield type. Use something like i16 u8. Note that u64 is not supported but i64 is.";
+        assert_eq!(exec(&mut db, "bitfield x get u64 0").unwrap_err().to_string(), invalid_type);
+        assert_eq!(exec(&mut db, "bitfield x get i0 0").unwrap_err().to_string(), invalid_type);
+        assert_eq!(exec(&mut db, "bitfield x get i8 -1").unwrap_err().to_string(), "bit offset is not an integer or out of range");
+        assert_eq!(exec(&mut db, "bitfield x overflow up").unwrap_err().to_string(), "Invalid OVERFLOW type specified");
+        assert_eq!(exec(&mut db, "bitfield x get i8").unwrap_err().to_string(), "syntax error");
+        assert_eq!(exec(&mut db, "bitfield x set i8 0 1 foo").unwrap_err().to_string(), "syntax error");
+        assert_eq!(exec(&mut db, "bitfield_ro x set i8 0 1").unwrap_err().to_string(), "BITFIELD_RO only supports the GET subcommand");
+        // errors are raised before running any subcommand
+        assert_eq!(exec(&mut db, "exists x").unwrap(), crate::Response::Number(0));
+    }
+}
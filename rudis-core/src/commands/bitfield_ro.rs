@@ -0,0 +1,30 @@
+use super::bitfield::{execute, parse_ops, Op};
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"bitfield_ro",
+    arity: -2,
+    flags: &[
+        b"readonly",
+        b"fast",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+/// BITFIELD_RO key [GET encoding offset ...]
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let key = cmd.parse_partial_args::<ByteString>()?;
+    let ops = parse_ops(&mut cmd)?;
+    anyhow::ensure!(ops.iter().all(|op| matches!(op, Op::Get(_))), "BITFIELD_RO only supports the GET subcommand");
+    execute(db, key, ops)
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "setbit x 7 1"                    => 0;
+    "bitfield_ro x get u8 0 get i4 4" => [1, 1];
+}
@@ -0,0 +1,81 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{ByteString, Database, Response, Value};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"bitop",
+    arity: -4,
+    flags: &[
+        b"write",
+        b"denyoom",
+    ],
+    first_key: 2,
+    last_key: -1,
+    step: 1,
+};
+
+/// BITOP AND | OR | XOR | NOT destkey key [key ...]
+///
+/// Missing keys and the end of shorter strings count as zero bytes.
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let (op, dest, keys) = cmd.parse_args::<(ByteString, ByteString, Vec<ByteString>)>()?;
+    let op = String::from_utf8_lossy(&op).to_lowercase();
+    anyhow::ensure!(["and", "or", "xor", "not"].contains(&op.as_str()), "syntax error");
+    anyhow::ensure!(op != "not" || keys.len() == 1, "BITOP NOT must be called with a single source key.");
+    let mut sources = Vec::with_capacity(keys.len());
+    for key in &keys {
+        sources.push(db.get_str(key)?.map(|s| s.to_vec()).unwrap_or_default());
+    }
+    let len = sources.iter().map(|s| s.len()).max().unwrap_or(0);
+    let res = (0..len)
+        .map(|i| {
+            let mut bytes = sources.iter().map(|s| s.get(i).copied().unwrap_or(0));
+            let first = bytes.next().unwrap_or(0);
+            match op.as_str() {
+                "and" => bytes.fold(first, |a, b| a & b),
+                "or" => bytes.fold(first, |a, b| a | b),
+                "xor" => bytes.fold(first, |a, b| a ^ b),
+                _ => !first,
+            }
+        })
+        .collect::<Vec<_>>();
+    if res.is_empty() {
+        if db.del(&dest).is_some() {
+            db.notify('g', "del", &dest);
+        }
+    } else {
+        db.set(dest.clone(), Value::String(res.into()));
+        db.notify('$', "set", &dest);
+    }
+    Ok(Response::Number(len as _))
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "set a foof"          => "OK";
+    "set b abcdef"        => "OK";
+    "bitop and x a b"     => 6;
+    "get x"               => "`bcd\0\0";
+    "bitop or x a b"      => 6;
+    "get x"               => "goofef";
+    "bitop xor x a a"     => 4;
+    "get x"               => "\0\0\0\0";
+    "bitop not x a"       => 4;
+    "bitop and x q"       => 0;
+    "exists x"            => 0;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Database;
+    use crate::test_utils::exec;
+
+    #[test]
+    fn test_bitop_errors() {
+        let mut db = Database::default();
+        assert_eq!(exec(&mut db, "bitop not x a b").unwrap_err().to_string(), "BITOP NOT must be called with a single source key.");
+        assert_eq!(exec(&mut db, "bitop nand x a").unwrap_err().to_string(), "syntax error");
+        exec(&mut db, "lpush l a").unwrap();
+        assert_eq!(exec(&mut db, "bitop or x l").unwrap_err().to_string(), "expected string value");
+    }
+}
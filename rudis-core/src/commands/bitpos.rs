@@ -0,0 +1,66 @@
+use super::bitcount::{bit_range, parse_unit};
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"bitpos",
+    arity: -3,
+    flags: &[
+        b"readonly",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+fn find_bit(s: &[u8], bit: u8, first: usize, last: usize) -> Option<usize> {
+    // bytes made only of the other bit are skipped whole
+    let skip = if bit == 1 { 0x00 } else { 0xff };
+    let mut i = first;
+    while i <= last {
+        if i.is_multiple_of(8) && i + 7 <= last && s[i / 8] == skip {
+            i += 8;
+            continue;
+        }
+        if (s[i / 8] >> (7 - i % 8)) & 1 == bit {
+            return Some(i);
+        }
+        i += 1;
+    }
+    None
+}
+
+/// BITPOS key bit [start [end [BYTE | BIT]]]
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let (key, bit) = cmd.parse_partial_args::<(ByteString, i64)>()?;
+    anyhow::ensure!(bit == 0 || bit == 1, "The bit argument must be 1 or 0.");
+    let start = cmd.parse_partial_args::<Option<i64>>()?.unwrap_or(0);
+    let end = cmd.parse_partial_args::<Option<i64>>()?;
+    let bits = parse_unit(&mut cmd)?;
+    let Some(s) = db.get_str(&key)?.map(|s| s.as_bytes()) else { return Ok(Response::Number(if bit == 1 { -1 } else { 0 })) };
+    let Some((first, last)) = bit_range(s.len(), start, end.unwrap_or(-1), bits) else { return Ok(Response::Number(-1)) };
+    let pos = match find_bit(&s, bit as u8, first, last) {
+        Some(pos) => pos as i64,
+        // without an end the string is considered padded with zeros on the right
+        None if bit == 0 && end.is_none() => s.len() as i64 * 8,
+        None => -1,
+    };
+    Ok(Response::Number(pos))
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "bitpos x 1"                 => -1;
+    "bitpos x 0"                 => 0;
+    "setbit x 13 1"              => 0;
+    "bitpos x 1"                 => 13;
+    "bitpos x 1 2"               => -1;
+    "bitpos x 1 0 12 bit"        => -1;
+    "bitpos x 1 13 13 bit"       => 13;
+    "bitpos x 0"                 => 0;
+    "bitfield y set u16 0 65535" => [0];
+    "bitpos y 0"                 => 16;
+    "bitpos y 0 0 -1"            => -1;
+    "bitpos y 0 3 1"             => -1;
+}
@@ -0,0 +1,243 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{ByteString, Database, Response, Tracking};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"client",
+    arity: -2,
+    flags: &[
+        b"noscript",
+        b"loading",
+        b"stale",
+    ],
+    first_key: 0,
+    last_key: 0,
+    step: 0,
+};
+
+fn tracking(db: &mut Database, cmd: &mut Command) -> anyhow::Result<Response> {
+    let mut mode = cmd.parse_partial_args::<ByteString>()?;
+    mode.make_ascii_lowercase();
+    let mut tracking = Tracking::default();
+    while cmd.has_more() {
+        if let Some(redirect) = cmd.parse_named_arg("redirect") {
+            let id = String::from_utf8_lossy(&redirect).parse::<u64>().map_err(|_| anyhow::anyhow!("Invalid client ID"))?;
+            tracking.redirect = Some(id);
+        } else if let Some(prefix) = cmd.parse_named_arg("prefix") {
+            tracking.prefixes.push(prefix);
+        } else if cmd.parse_option("bcast") {
+            tracking.bcast = true;
+        } else if cmd.parse_option("optin") {
+            tracking.optin = true;
+        } else if cmd.parse_option("optout") {
+            tracking.optout = true;
+        } else if cmd.parse_option("noloop") {
+            tracking.noloop = true;
+        } else {
+            anyhow::bail!("syntax error");
+        }
+    }
+    match mode.as_slice() {
+        b"on" => db.enable_tracking(tracking)?,
+        b"off" => db.disable_tracking(),
+        _ => anyhow::bail!("syntax error"),
+    }
+    Ok(Response::SimpleString(b"OK".to_vec()))
+}
+
+fn caching(db: &mut Database, cmd: &mut Command) -> anyhow::Result<Response> {
+    let mut mode = cmd.parse_args::<ByteString>()?;
+    mode.make_ascii_lowercase();
+    let (optin, optout) = db.client().tracking.as_ref().map_or((false, false), |t| (t.optin, t.optout));
+    anyhow::ensure!(optin || optout, "ERR CLIENT CACHING can be called only when the client is in tracking mode with OPTIN or OPTOUT mode enabled");
+    let caching = match mode.as_slice() {
+        b"yes" if optin => true,
+        b"no" if optout => false,
+        b"yes" => anyhow::bail!("ERR CLIENT CACHING YES is only valid when tracking is enabled in OPTIN mode."),
+        b"no" => anyhow::bail!("ERR CLIENT CACHING NO is only valid when tracking is enabled in OPTOUT mode."),
+        _ => anyhow::bail!("syntax error"),
+    };
+    db.client().caching = Some(caching);
+    Ok(Response::SimpleString(b"OK".to_vec()))
+}
+
+fn tracking_info(db: &mut Database) -> Response {
+    let client = db.client();
+    let mut flags = Vec::new();
+    let (redirect, prefixes) = match &client.tracking {
+        None => {
+            flags.push(b"off".to_vec());
+            (-1, Vec::new())
+        }
+        Some(tracking) => {
+            flags.push(b"on".to_vec());
+            let options = [(tracking.bcast, "bcast"), (tracking.optin, "optin"), (tracking.optout, "optout"), (tracking.noloop, "noloop")];
+            flags.extend(options.into_iter().filter(|(set, _)| *set).map(|(_, flag)| flag.as_bytes().to_vec()));
+            match client.caching {
+                Some(true) => flags.push(b"caching-yes".to_vec()),
+                Some(false) => flags.push(b"caching-no".to_vec()),
+                None => {}
+            }
+            (tracking.redirect.map_or(0, |id| id as i64), tracking.prefixes.clone())
+        }
+    };
+    Response::Array(vec![
+        Response::BulkString(b"flags".to_vec()),
+        Response::string_array(flags),
+        Response::BulkString(b"redirect".to_vec()),
+        Response::Number(redirect),
+        Response::BulkString(b"prefixes".to_vec()),
+        Response::string_array(prefixes),
+    ])
+}
+
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let mut subcommand = cmd.parse_partial_args::<ByteString>()?;
+    subcommand.make_ascii_lowercase();
+    match subcommand.as_slice() {
+        b"id" => {
+            cmd.ensure_empty()?;
+            Ok(Response::Number(db.client_id() as _))
+        }
+        b"tracking" => tracking(db, &mut cmd),
+        b"caching" => caching(db, &mut cmd),
+        b"getredir" => {
+            cmd.ensure_empty()?;
+            let redirect = db.client().tracking.as_ref().map_or(-1, |t| t.redirect.map_or(0, |id| id as i64));
+            Ok(Response::Number(redirect))
+        }
+        b"trackinginfo" => {
+            cmd.ensure_empty()?;
+            Ok(tracking_info(db))
+        }
+        _ => anyhow::bail!("unknown subcommand '{}'", String::from_utf8_lossy(&subcommand)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Database, Response};
+
+    fn exec(db: &mut Database, client: u64, cmd: &str) -> anyhow::Result<Response> {
+        db.select_client(client);
+        crate::test_utils::exec(db, cmd)
+    }
+
+    fn error(db: &mut Database, client: u64, cmd: &str) -> String {
+        crate::error_reply(&exec(db, client, cmd).unwrap_err())
+    }
+
+    /// The keys invalidated since the last call, `None` standing for a flush.
+    fn invalidations(db: &mut Database) -> Vec<Option<Vec<String>>> {
+        db.take_outbox().into_iter().map(|(id, res)| {
+            assert_eq!(id, 1);
+            let Ok(Response::Array(mut message)) = res else { panic!("unexpected {res:?}") };
+            assert_eq!(message[1], Response::BulkString(b"__redis__:invalidate".to_vec()));
+            match message.pop().unwrap() {
+                Response::Array(keys) => Some(keys.into_iter().map(|k| match k {
+                    Response::BulkString(k) => String::from_utf8(k).unwrap(),
+                    k => panic!("unexpected {k:?}"),
+                }).collect()),
+                _ => None,
+            }
+        }).collect()
+    }
+
+    fn setup() -> Database {
+        let mut db = Database::default();
+        for id in 1..=3 {
+            db.accept_client(id, None, false).unwrap();
+        }
+        exec(&mut db, 1, "subscribe __redis__:invalidate").unwrap();
+        db.take_outbox();
+        db
+    }
+
+    #[test]
+    fn test_tracking() {
+        let mut db = setup();
+        assert_eq!(exec(&mut db, 2, "client getredir").unwrap(), Response::Number(-1));
+        exec(&mut db, 2, "client tracking on redirect 1").unwrap();
+        assert_eq!(exec(&mut db, 2, "client getredir").unwrap(), Response::Number(1));
+        exec(&mut db, 3, "set x 1").unwrap();
+        assert!(invalidations(&mut db).is_empty());
+
+        // keys are invalidated once after each read
+        exec(&mut db, 2, "get x").unwrap();
+        exec(&mut db, 2, "mget y z").unwrap();
+        exec(&mut db, 3, "incr x").unwrap();
+        exec(&mut db, 3, "incr x").unwrap();
+        exec(&mut db, 3, "rpush z a").unwrap();
+        assert_eq!(invalidations(&mut db), [Some(vec!["x".to_string()]), Some(vec!["z".to_string()])]);
+
+        // our own writes are invalidated too unless NOLOOP is set
+        exec(&mut db, 2, "get x").unwrap();
+        exec(&mut db, 2, "del x").unwrap();
+        assert_eq!(invalidations(&mut db), [Some(vec!["x".to_string()])]);
+        exec(&mut db, 2, "client tracking on redirect 1 noloop").unwrap();
+        exec(&mut db, 2, "get y").unwrap();
+        exec(&mut db, 2, "set y 1").unwrap();
+        assert!(invalidations(&mut db).is_empty());
+
+        exec(&mut db, 2, "get y").unwrap();
+        exec(&mut db, 3, "flushall").unwrap();
+        assert_eq!(invalidations(&mut db), [None]);
+
+        exec(&mut db, 2, "client tracking off").unwrap();
+        exec(&mut db, 2, "get y").unwrap();
+        exec(&mut db, 3, "set y 2").unwrap();
+        assert!(invalidations(&mut db).is_empty());
+    }
+
+    #[test]
+    fn test_tracking_optin() {
+        let mut db = setup();
+        exec(&mut db, 2, "client tracking on redirect 1 optin").unwrap();
+        exec(&mut db, 2, "get x").unwrap();
+        exec(&mut db, 2, "client caching yes").unwrap();
+        exec(&mut db, 2, "get y").unwrap();
+        exec(&mut db, 3, "mset x 1 y 1").unwrap();
+        assert_eq!(invalidations(&mut db), [Some(vec!["y".to_string()])]);
+        assert_eq!(error(&mut db, 2, "client caching no"), "ERR CLIENT CACHING NO is only valid when tracking is enabled in OPTOUT mode.");
+    }
+
+    #[test]
+    fn test_tracking_bcast() {
+        let mut db = setup();
+        exec(&mut db, 2, "client tracking on redirect 1 bcast prefix user: prefix session:").unwrap();
+        exec(&mut db, 3, "set user:1 a").unwrap();
+        exec(&mut db, 3, "set other a").unwrap();
+        exec(&mut db, 3, "hset session:1 f v").unwrap();
+        assert_eq!(invalidations(&mut db), [Some(vec!["user:1".to_string()]), Some(vec!["session:1".to_string()])]);
+        assert_eq!(exec(&mut db, 2, "client trackinginfo").unwrap(), Response::Array(vec![
+            Response::BulkString(b"flags".to_vec()),
+            Response::string_array([b"on".to_vec(), b"bcast".to_vec()]),
+            Response::BulkString(b"redirect".to_vec()),
+            Response::Number(1),
+            Response::BulkString(b"prefixes".to_vec()),
+            Response::string_array([b"user:".to_vec(), b"session:".to_vec()]),
+        ]));
+
+        assert_eq!(
+            error(&mut db, 2, "client tracking on redirect 1"),
+            "ERR You can't switch BCAST mode on/off before disabling tracking for this client, and then re-enabling it with a different mode.",
+        );
+        assert!(error(&mut db, 2, "client tracking on bcast prefix user:a").contains("overlaps"));
+        assert_eq!(error(&mut db, 3, "client tracking on prefix a"), "ERR PREFIX option requires BCAST mode to be enabled");
+        assert_eq!(error(&mut db, 3, "client tracking on redirect 42"), "ERR The client ID you want redirect to does not exist");
+    }
+
+    #[test]
+    fn test_tracking_sources() {
+        let mut db = setup();
+        exec(&mut db, 2, "client tracking on redirect 1 bcast").unwrap();
+        exec(&mut db, 3, "rpush l 1").unwrap();
+        exec(&mut db, 3, "set w_1 1").unwrap();
+        exec(&mut db, 3, "sadd s1 a").unwrap();
+        invalidations(&mut db);
+        // keys only read by write commands are not invalidated
+        exec(&mut db, 3, "sort l by w_*").unwrap();
+        exec(&mut db, 3, "sunionstore d s1").unwrap();
+        assert_eq!(invalidations(&mut db), [Some(vec!["d".to_string()])]);
+    }
+}
@@ -0,0 +1,291 @@
+use super::{parse_from_bytes, CommandInfo};
+use crate::command::Command;
+use crate::{key_hash_slot, ByteString, Cluster, Database, Node, Response, SlotState};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"cluster",
+    arity: -2,
+    flags: &[
+        b"noscript",
+        b"stale",
+    ],
+    first_key: 0,
+    last_key: 0,
+    step: 0,
+};
+
+fn parse_slot(arg: &[u8]) -> anyhow::Result<u16> {
+    parse_from_bytes::<u16>(arg).ok()
+        .filter(|&slot| (slot as usize) < crate::cluster::SLOTS)
+        .ok_or_else(|| anyhow::anyhow!("Invalid or out of range slot"))
+}
+
+fn parse_slots(cmd: &mut Command) -> anyhow::Result<Vec<u16>> {
+    let slots = cmd.parse_args::<Vec<ByteString>>()?.iter().map(|s| parse_slot(s)).collect::<anyhow::Result<Vec<_>>>()?;
+    anyhow::ensure!(!slots.is_empty(), "wrong number of arguments for 'cluster' command");
+    Ok(slots)
+}
+
+fn parse_slot_ranges(cmd: &mut Command) -> anyhow::Result<Vec<u16>> {
+    let ranges = cmd.parse_args::<Vec<(ByteString, ByteString)>>()?;
+    anyhow::ensure!(!ranges.is_empty(), "wrong number of arguments for 'cluster' command");
+    let mut slots = Vec::new();
+    for (start, end) in ranges {
+        let (start, end) = (parse_slot(&start)?, parse_slot(&end)?);
+        anyhow::ensure!(start <= end, "start slot number {start} is greater than end slot number {end}");
+        slots.extend(start..=end);
+    }
+    Ok(slots)
+}
+
+fn node_id(id: ByteString) -> anyhow::Result<String> {
+    String::from_utf8(id).map_err(|_| anyhow::anyhow!("Invalid node name"))
+}
+
+fn info(cluster: &Cluster) -> String {
+    let assigned = cluster.assigned_slots();
+    let state = if assigned == crate::cluster::SLOTS { "ok" } else { "fail" };
+    let size = cluster.nodes().filter(|n| !cluster.slot_ranges(&n.id).is_empty()).count();
+    let fields = [
+        ("cluster_enabled", "1".to_string()),
+        ("cluster_state", state.to_string()),
+        ("cluster_slots_assigned", assigned.to_string()),
+        ("cluster_slots_ok", assigned.to_string()),
+        ("cluster_slots_pfail", "0".to_string()),
+        ("cluster_slots_fail", "0".to_string()),
+        ("cluster_known_nodes", cluster.nodes().count().to_string()),
+        ("cluster_size", size.to_string()),
+        ("cluster_current_epoch", cluster.current_epoch().to_string()),
+        ("cluster_my_epoch", cluster.myself().config_epoch.to_string()),
+    ];
+    fields.iter().map(|(k, v)| format!("{k}:{v}\r\n")).collect()
+}
+
+fn node_address(node: &Node) -> Response {
+    Response::Array(vec![
+        Response::BulkString(node.ip.as_bytes().to_vec()),
+        Response::Number(node.port as _),
+        Response::BulkString(node.id.as_bytes().to_vec()),
+    ])
+}
+
+fn slots(cluster: &Cluster) -> Response {
+    let mut ranges = cluster.nodes()
+        .flat_map(|node| cluster.slot_ranges(&node.id).into_iter().map(move |range| (range, node)))
+        .collect::<Vec<_>>();
+    ranges.sort_by_key(|(range, _)| *range);
+    Response::Array(ranges.into_iter()
+        .map(|((start, end), node)| Response::Array(vec![
+            Response::Number(start as _),
+            Response::Number(end as _),
+            node_address(node),
+        ]))
+        .collect())
+}
+
+fn shards(cluster: &Cluster, offset: u64) -> Response {
+    let shards = cluster.nodes().map(|node| {
+        let slots = cluster.slot_ranges(&node.id).into_iter().flat_map(|(start, end)| [start, end]);
+        let health = if node.connected { "online" } else { "fail" };
+        let offset = if node.id == cluster.myself().id { offset } else { 0 };
+        let node = Response::Array(vec![
+            Response::BulkString(b"id".to_vec()),
+            Response::BulkString(node.id.as_bytes().to_vec()),
+            Response::BulkString(b"port".to_vec()),
+            Response::Number(node.port as _),
+            Response::BulkString(b"ip".to_vec()),
+            Response::BulkString(node.ip.as_bytes().to_vec()),
+            Response::BulkString(b"endpoint".to_vec()),
+            Response::BulkString(node.ip.as_bytes().to_vec()),
+            Response::BulkString(b"role".to_vec()),
+            Response::BulkString(b"master".to_vec()),
+            Response::BulkString(b"replication-offset".to_vec()),
+            Response::Number(offset as _),
+            Response::BulkString(b"health".to_vec()),
+            Response::BulkString(health.as_bytes().to_vec()),
+        ]);
+        Response::Array(vec![
+            Response::BulkString(b"slots".to_vec()),
+            Response::Array(slots.map(|s| Response::Number(s as _)).collect()),
+            Response::BulkString(b"nodes".to_vec()),
+            Response::Array(vec![node]),
+        ])
+    });
+    Response::Array(shards.collect())
+}
+
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let mut subcommand = cmd.parse_partial_args::<ByteString>()?;
+    subcommand.make_ascii_lowercase();
+    let offset = db.replication().offset();
+    let ok = Response::SimpleString(b"OK".to_vec());
+    let res = match subcommand.as_slice() {
+        b"info" => {
+            cmd.ensure_empty()?;
+            Response::BulkString(info(db.cluster()?).into_bytes())
+        }
+        b"myid" => {
+            cmd.ensure_empty()?;
+            Response::BulkString(db.cluster()?.myself().id.as_bytes().to_vec())
+        }
+        b"nodes" => {
+            cmd.ensure_empty()?;
+            Response::BulkString(db.cluster()?.describe().into_bytes())
+        }
+        b"slots" => {
+            cmd.ensure_empty()?;
+            slots(db.cluster()?)
+        }
+        b"shards" => {
+            cmd.ensure_empty()?;
+            shards(db.cluster()?, offset)
+        }
+        b"keyslot" => {
+            let key = cmd.parse_args::<ByteString>()?;
+            db.cluster()?;
+            Response::Number(key_hash_slot(&key) as _)
+        }
+        b"countkeysinslot" => {
+            let slot = parse_slot(&cmd.parse_args::<ByteString>()?)?;
+            db.cluster()?;
+            Response::Number(db.keys_in_slot(slot).count() as _)
+        }
+        b"getkeysinslot" => {
+            let (slot, count) = cmd.parse_args::<(ByteString, i64)>()?;
+            let slot = parse_slot(&slot)?;
+            anyhow::ensure!(count >= 0, "Invalid number of keys");
+            db.cluster()?;
+            Response::string_array(db.keys_in_slot(slot).take(count as _).map(|k| k.to_vec()).collect::<Vec<_>>())
+        }
+        b"addslots" => {
+            let slots = parse_slots(&mut cmd)?;
+            db.cluster()?.add_slots(&slots)?;
+            ok
+        }
+        b"addslotsrange" => {
+            let slots = parse_slot_ranges(&mut cmd)?;
+            db.cluster()?.add_slots(&slots)?;
+            ok
+        }
+        b"delslots" => {
+            let slots = parse_slots(&mut cmd)?;
+            db.cluster()?.del_slots(&slots)?;
+            ok
+        }
+        b"delslotsrange" => {
+            let slots = parse_slot_ranges(&mut cmd)?;
+            db.cluster()?.del_slots(&slots)?;
+            ok
+        }
+        b"setslot" => {
+            let (slot, mut action) = cmd.parse_partial_args::<(ByteString, ByteString)>()?;
+            let slot = parse_slot(&slot)?;
+            action.make_ascii_lowercase();
+            let state = match action.as_slice() {
+                b"importing" => SlotState::Importing(node_id(cmd.parse_args()?)?),
+                b"migrating" => SlotState::Migrating(node_id(cmd.parse_args()?)?),
+                b"node" => SlotState::Node(node_id(cmd.parse_args()?)?),
+                b"stable" => {
+                    cmd.ensure_empty()?;
+                    SlotState::Stable
+                }
+                _ => anyhow::bail!("Invalid CLUSTER SETSLOT action or number of arguments. Try CLUSTER HELP"),
+            };
+            db.cluster()?.set_slot(slot, state)?;
+            ok
+        }
+        b"meet" => {
+            let (ip, port) = cmd.parse_args::<(ByteString, ByteString)>()?;
+            let ip = String::from_utf8(ip).map_err(|_| anyhow::anyhow!("Invalid node address specified"))?;
+            let port = parse_from_bytes::<u16>(&port).map_err(|_| anyhow::anyhow!("Invalid base port specified: {}", String::from_utf8_lossy(&port)))?;
+            db.cluster()?.meet(&ip, port)?;
+            ok
+        }
+        b"forget" => {
+            let id = node_id(cmd.parse_args()?)?;
+            db.cluster()?.forget(&id)?;
+            ok
+        }
+        _ => anyhow::bail!("unknown subcommand '{}'", String::from_utf8_lossy(&subcommand)),
+    };
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Config, Database, Response};
+    use crate::test_utils::exec;
+
+    fn error(db: &mut Database, cmd: &str) -> String {
+        exec(db, cmd).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_cluster() {
+        assert_eq!(error(&mut Database::default(), "cluster keyslot x"), "This instance has cluster support disabled");
+
+        let path = std::env::temp_dir().join(format!("rudis-cluster-test-{}.conf", std::process::id()));
+        let mut config = Config::default();
+        config.set("cluster-enabled", "yes").unwrap();
+        config.set("cluster-config-file", path.to_str().unwrap()).unwrap();
+        config.set("port", "7000").unwrap();
+        let mut db = Database::from_config(config.clone()).unwrap();
+        let Response::BulkString(id) = exec(&mut db, "cluster myid").unwrap() else { panic!() };
+        let id = String::from_utf8(id).unwrap();
+
+        assert_eq!(exec(&mut db, "cluster keyslot foo").unwrap(), Response::Number(12182));
+        assert_eq!(exec(&mut db, "cluster keyslot {user1}.a").unwrap(), exec(&mut db, "cluster keyslot user1").unwrap());
+        assert_eq!(error(&mut db, "set foo 1"), "CLUSTERDOWN Hash slot not served");
+        exec(&mut db, "cluster addslotsrange 8192 16383").unwrap();
+        assert_eq!(error(&mut db, "cluster addslots 10000"), "Slot 10000 is already busy");
+        assert_eq!(error(&mut db, "cluster addslots 16384"), "Invalid or out of range slot");
+        exec(&mut db, "set x 1").unwrap();
+        exec(&mut db, "set {x}y 2").unwrap();
+        assert_eq!(error(&mut db, "mset x 1 foo 2"), "CROSSSLOT Keys in request don't hash to the same slot");
+        assert_eq!(exec(&mut db, "cluster countkeysinslot 16287").unwrap(), Response::Number(2));
+        assert!(matches!(exec(&mut db, "cluster getkeysinslot 16287 1").unwrap(), Response::Array(keys) if keys.len() == 1));
+        exec(&mut db, "del {x}y").unwrap();
+        exec(&mut db, "set {x}z 1 px 1").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        assert_eq!(exec(&mut db, "cluster countkeysinslot 16287").unwrap(), Response::Number(1));
+        assert_eq!(exec(&mut db, "cluster getkeysinslot 16287 10").unwrap(), Response::Array(vec![Response::BulkString(b"x".to_vec())]));
+        assert!(matches!(exec(&mut db, "info server").unwrap(), Response::BulkString(s) if String::from_utf8_lossy(&s).contains("redis_mode:cluster")));
+        assert_eq!(error(&mut db, "get user1"), "CLUSTERDOWN Hash slot not served");
+
+        exec(&mut db, "cluster meet 127.0.0.1 7001").unwrap();
+        let other = format!("{:040x} 127.0.0.1:7001@7001 myself,master - 0 0 1 connected 0-8191\n", 1);
+        db.cluster().unwrap().gossip("127.0.0.1", 7001, Some(&other)).unwrap();
+        assert_eq!(error(&mut db, "get user1"), "MOVED 8106 127.0.0.1:7001");
+        let info = exec(&mut db, "cluster info").unwrap();
+        assert!(matches!(info, Response::BulkString(s) if String::from_utf8_lossy(&s).contains("cluster_state:ok\r\ncluster_slots_assigned:16384\r\n")));
+        assert_eq!(exec(&mut db, "cluster slots").unwrap(), Response::Array(vec![
+            Response::Array(vec![Response::Number(0), Response::Number(8191), Response::Array(vec![
+                Response::BulkString(b"127.0.0.1".to_vec()), Response::Number(7001), Response::BulkString(format!("{:040x}", 1).into_bytes()),
+            ])]),
+            Response::Array(vec![Response::Number(8192), Response::Number(16383), Response::Array(vec![
+                Response::BulkString(b"127.0.0.1".to_vec()), Response::Number(7000), Response::BulkString(id.as_bytes().to_vec()),
+            ])]),
+        ]));
+
+        let other = format!("{:040x}", 1);
+        exec(&mut db, &format!("cluster setslot 16287 migrating {other}")).unwrap();
+        exec(&mut db, "get x").unwrap();
+        assert_eq!(error(&mut db, "get {x}z"), "ASK 16287 127.0.0.1:7001");
+        assert_eq!(error(&mut db, "mget x {x}z"), "TRYAGAIN Multiple keys request during rehashing of slot");
+        exec(&mut db, &format!("cluster setslot 8106 importing {other}")).unwrap();
+        exec(&mut db, "asking").unwrap();
+        exec(&mut db, "set user1 1").unwrap();
+        assert!(error(&mut db, "get user1").starts_with("MOVED"));
+
+        // the node id and slots survive restarts
+        drop(db);
+        let mut db = Database::from_config(config).unwrap();
+        assert_eq!(exec(&mut db, "cluster myid").unwrap(), Response::BulkString(id.into_bytes()));
+        assert!(matches!(exec(&mut db, "cluster nodes").unwrap(), Response::BulkString(s) if String::from_utf8_lossy(&s).contains("[16287->-")));
+        exec(&mut db, &format!("cluster forget {other}")).unwrap();
+        assert_eq!(error(&mut db, "get user1"), "CLUSTERDOWN Hash slot not served");
+        exec(&mut db, "cluster delslotsrange 8192 16383").unwrap();
+        assert_eq!(exec(&mut db, "cluster slots").unwrap(), Response::Array(vec![]));
+        std::fs::remove_file(path).unwrap();
+    }
+}
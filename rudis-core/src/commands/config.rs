@@ -0,0 +1,118 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{ByteString, Database, Response, Stats};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"config",
+    arity: -2,
+    flags: &[
+        b"admin",
+        b"noscript",
+        b"loading",
+        b"stale",
+    ],
+    first_key: 0,
+    last_key: 0,
+    step: 0,
+};
+
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let mut subcommand = cmd.parse_partial_args::<ByteString>()?;
+    subcommand.make_ascii_lowercase();
+    let res = match subcommand.as_slice() {
+        b"get" => {
+            let patterns = cmd.parse_args::<Vec<ByteString>>()?;
+            anyhow::ensure!(!patterns.is_empty(), "wrong number of arguments for 'config|get' command");
+            let mut params = Vec::new();
+            for pattern in &patterns {
+                for (name, value) in db.config().matching(pattern) {
+                    if !params.iter().any(|(n, _)| *n == name) {
+                        params.push((name, value));
+                    }
+                }
+            }
+            Response::string_array(params.into_iter().flat_map(|(n, v)| [n.as_bytes().to_vec(), v.as_bytes().to_vec()]))
+        }
+        b"set" => {
+            let params = cmd.parse_args::<Vec<(ByteString, ByteString)>>()?;
+            anyhow::ensure!(!params.is_empty(), "wrong number of arguments for 'config|set' command");
+            let params = params.into_iter()
+                .map(|(name, value)| Ok((String::from_utf8(name)?, String::from_utf8(value)?)))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            db.set_config(&params)?;
+            Response::SimpleString(b"OK".to_vec())
+        }
+        b"resetstat" => {
+            cmd.ensure_empty()?;
+            *db.stats() = Stats::default();
+            Response::SimpleString(b"OK".to_vec())
+        }
+        b"rewrite" => {
+            cmd.ensure_empty()?;
+            db.config().rewrite()?;
+            Response::SimpleString(b"OK".to_vec())
+        }
+        _ => anyhow::bail!("unknown subcommand '{}'", String::from_utf8_lossy(&subcommand)),
+    };
+    Ok(res)
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "config get port"                        => ["port", "8888"];
+    "config get tls-*-file"                  => ["tls-ca-cert-file", "", "tls-cert-file", "", "tls-key-file", ""];
+    "config get maxmemory timeout maxmemory" => ["maxmemory", "0", "timeout", "0"];
+    "config set maxmemory 1mb timeout 10"    => "OK";
+    "config get maxmemory timeout"           => ["maxmemory", "1048576", "timeout", "10"];
+    "config get nonexistent"                 => [];
+    "config resetstat"                       => "OK";
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Config, Database, Response};
+    use crate::test_utils::exec;
+
+    #[test]
+    fn test_config_set() {
+        let mut db = Database::default();
+        assert!(exec(&mut db, "config set port 1234").unwrap_err().to_string().contains("can't set immutable config"));
+        assert!(exec(&mut db, "config set timeout 5 maxmemory lots").is_err());
+        let err = exec(&mut db, "config set maxmemory 18446744073709551615gb").unwrap_err();
+        assert!(err.to_string().contains("argument must be a memory value"), "{err}");
+        assert_eq!(db.config().get("timeout"), "0");
+        assert!(exec(&mut db, "config set foo bar").unwrap_err().to_string().starts_with("Unknown option"));
+        assert!(exec(&mut db, "config rewrite").is_err());
+
+        exec(&mut db, "config set requirepass secret").unwrap();
+        assert!(exec(&mut db, "get x").is_err());
+        exec(&mut db, "auth secret").unwrap();
+        assert_eq!(exec(&mut db, "get x").unwrap(), Response::Nil);
+    }
+
+    #[test]
+    fn test_config_file() {
+        let path = std::env::temp_dir().join(format!("rudis-config-test-{}.conf", std::process::id()));
+        std::fs::write(&path, "# a comment\nport 7000\nbind \"127.0.0.1 ::1\"\n\nTIMEOUT 10\ntimeout 20\n").unwrap();
+        let mut db = Database::from_config(Config::load(&path).unwrap()).unwrap();
+        assert_eq!(db.config().get("port"), "7000");
+        assert_eq!(db.config().get("bind"), "127.0.0.1 ::1");
+        assert_eq!(db.config().get("timeout"), "20");
+
+        exec(&mut db, "config set maxmemory 100 timeout 30").unwrap();
+        exec(&mut db, "config rewrite").unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(contents, "# a comment\nport 7000\nbind \"127.0.0.1 ::1\"\n\ntimeout 30\nmaxmemory 100\n");
+        assert_eq!(Config::load(&path).unwrap().get("maxmemory"), "100");
+
+        // unsupported directives of a stock redis.conf are skipped, invalid values are not
+        std::fs::write(&path, "bind 127.0.0.1 -::1\nport 6379\ndaemonize no\nloglevel notice\nsave 3600 1 300 100 60 10000\nappendonly no\nappendfsync everysec\n").unwrap();
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.get("port"), "6379");
+        assert_eq!(config.unsupported().len(), 5);
+        assert!(config.unsupported()[0].ends_with(":3: daemonize"));
+        std::fs::write(&path, "port abc\n").unwrap();
+        assert!(Config::load(&path).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
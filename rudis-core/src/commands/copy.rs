@@ -19,7 +19,11 @@ pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
     let v = match db.get(&src) {
         Some(v) => {
             let copy = v.clone();
-            db.set(dst, copy);
+            db.set(dst.clone(), copy);
+            if let Some(at) = db.expiry(&src) {
+                db.set_expiry(&dst, at);
+            }
+            db.notify('g', "copy_to", &dst);
             1
         }
         None => 0
@@ -0,0 +1,169 @@
+use sha1::{Digest, Sha1};
+
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{rdb, ByteString, Database, Response, Value};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"debug",
+    arity: -2,
+    flags: &[
+        b"admin",
+        b"noscript",
+        b"loading",
+        b"stale",
+    ],
+    first_key: 0,
+    last_key: 0,
+    step: 0,
+};
+
+type Sha = [u8; 20];
+
+/// The SHA1 of `parts`, each prefixed with its length so that they cannot run into each other.
+fn sha(parts: &[&[u8]]) -> Sha {
+    let mut h = Sha1::new();
+    for part in parts {
+        h.update((part.len() as u64).to_le_bytes());
+        h.update(part);
+    }
+    h.finalize().into()
+}
+
+fn xor(digest: &mut Sha, other: Sha) {
+    digest.iter_mut().zip(other).for_each(|(a, b)| *a ^= b);
+}
+
+fn hex(digest: Sha) -> ByteString {
+    digest.iter().map(|b| format!("{b:02x}")).collect::<String>().into_bytes()
+}
+
+/// The digest of a value, which only depends on its content: not on its encoding, nor on the
+/// order of the elements of unordered aggregates, which are combined with XOR.
+fn value_digest(value: &Value) -> Sha {
+    let mut digest = [0; 20];
+    match value {
+        Value::String(s) => return sha(&[b"string", &s.as_bytes()]),
+        Value::Array(v) => {
+            digest = sha(&[b"list"]);
+            for e in v {
+                digest = sha(&[&digest, e]);
+            }
+        }
+        Value::Hash(h) => {
+            for (k, v) in h.iter() {
+                let expiry = h.expiry(k).unwrap_or(-1).to_le_bytes();
+                xor(&mut digest, sha(&[k, v, &expiry]));
+            }
+            digest = sha(&[b"hash", &digest]);
+        }
+        Value::Set(s) => {
+            for m in s.iter() {
+                xor(&mut digest, sha(&[&m]));
+            }
+            digest = sha(&[b"set", &digest]);
+        }
+        Value::ZSet(z) => {
+            digest = sha(&[b"zset"]);
+            for (score, m) in z.iter() {
+                digest = sha(&[&digest, m, &score.to_le_bytes()]);
+            }
+        }
+    }
+    digest
+}
+
+/// The digest of the whole dataset including expires, all zeros when it is empty, so that
+/// replicas can be checked to be consistent with their master.
+fn dataset_digest(db: &Database) -> Sha {
+    let mut digest = [0; 20];
+    for (key, entry) in &db.state {
+        let expiry = db.expires.get(key).copied().unwrap_or(-1).to_le_bytes();
+        xor(&mut digest, sha(&[key, &value_digest(&entry.value), &expiry]));
+    }
+    digest
+}
+
+/// DEBUG SLEEP seconds | OBJECT key | DIGEST | DIGEST-VALUE [key ...]
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let mut subcommand = cmd.parse_partial_args::<ByteString>()?;
+    subcommand.make_ascii_lowercase();
+    let res = match subcommand.as_slice() {
+        b"sleep" => {
+            let seconds = cmd.parse_args::<f64>()?;
+            anyhow::ensure!(seconds >= 0.0 && seconds.is_finite(), "Invalid sleep time");
+            // blocks the whole server, as intended to simulate a slow command
+            std::thread::sleep(std::time::Duration::from_secs_f64(seconds));
+            Response::SimpleString(b"OK".to_vec())
+        }
+        b"object" => {
+            let key = cmd.parse_args::<ByteString>()?;
+            let (value, access) = db.peek(&key).ok_or_else(|| anyhow::anyhow!("no such key"))?;
+            let serialized = rdb::dump_payload(value).len() - 10;
+            let info = format!(
+                "Value at:{:p} refcount:1 encoding:{} serializedlength:{serialized} lru_seconds_idle:{}",
+                value, value.encoding(), access.idle_time(),
+            );
+            Response::SimpleString(info.into_bytes())
+        }
+        b"digest" => {
+            cmd.ensure_empty()?;
+            Response::SimpleString(hex(dataset_digest(db)))
+        }
+        b"digest-value" => {
+            let keys = cmd.parse_args::<Vec<ByteString>>()?;
+            let digests = keys.iter().map(|key| {
+                let digest = db.peek(key).map_or([0; 20], |(value, _)| value_digest(value));
+                Response::BulkString(hex(digest))
+            });
+            Response::Array(digests.collect())
+        }
+        _ => anyhow::bail!("unknown subcommand '{}'", String::from_utf8_lossy(&subcommand)),
+    };
+    Ok(res)
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "debug digest"               => "0000000000000000000000000000000000000000";
+    "debug digest-value x"       => ["0000000000000000000000000000000000000000"];
+    "debug sleep 0"              => "OK";
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Database, Response};
+    use crate::test_utils::exec;
+
+    #[test]
+    fn test_digest() {
+        let (mut a, mut b) = (Database::default(), Database::default());
+        // same content, in a different order and encoding
+        exec(&mut a, "sadd s 1 2 x").unwrap();
+        exec(&mut a, "hset h a 1 b 2").unwrap();
+        exec(&mut a, "set n 10").unwrap();
+        exec(&mut b, "config set hash-max-listpack-entries 0").unwrap();
+        exec(&mut b, "set n 1").unwrap();
+        exec(&mut b, "append n 0").unwrap();
+        exec(&mut b, "hset h b 2 a 1").unwrap();
+        exec(&mut b, "sadd s x 2 1").unwrap();
+        assert_eq!(exec(&mut a, "debug digest").unwrap(), exec(&mut b, "debug digest").unwrap());
+        assert_eq!(exec(&mut a, "debug digest-value h s").unwrap(), exec(&mut b, "debug digest-value h s").unwrap());
+
+        exec(&mut b, "expire n 100").unwrap();
+        assert_ne!(exec(&mut a, "debug digest").unwrap(), exec(&mut b, "debug digest").unwrap());
+        assert_eq!(exec(&mut a, "debug digest-value n").unwrap(), exec(&mut b, "debug digest-value n").unwrap());
+        exec(&mut b, "rpush n2 10").unwrap();
+        assert_ne!(exec(&mut a, "debug digest-value n").unwrap(), exec(&mut b, "debug digest-value n2").unwrap());
+    }
+
+    #[test]
+    fn test_object() {
+        let mut db = Database::default();
+        assert_eq!(exec(&mut db, "debug object x").unwrap_err().to_string(), "no such key");
+        exec(&mut db, "set x 12").unwrap();
+        let Response::SimpleString(info) = exec(&mut db, "debug object x").unwrap() else { panic!() };
+        let info = String::from_utf8(info).unwrap();
+        assert!(info.contains(" refcount:1 encoding:int serializedlength:3 "), "{info}");
+    }
+}
@@ -0,0 +1,53 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::rdb::dump_payload;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"dump",
+    arity: 2,
+    flags: &[
+        b"readonly",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+/// DUMP key
+///
+/// The value in the RDB format followed by the RDB version and a checksum, see RESTORE.
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let key = cmd.parse_args::<ByteString>()?;
+    Ok(db.get(&key).map(|value| Response::BulkString(dump_payload(value))).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Database, Response};
+    use crate::test_utils::exec_args;
+
+    #[test]
+    fn test_dump_restore() {
+        let (mut src, mut dst) = (Database::default(), Database::default());
+        assert_eq!(exec_args(&mut src, &[b"dump", b"x"]).unwrap(), Response::Nil);
+        let setup: &[&[&[u8]]] = &[
+            &[b"set", b"s", b"-300"],
+            &[b"set", b"t", b"hello"],
+            &[b"rpush", b"l", b"a", b"b"],
+            &[b"sadd", b"i", b"1", b"2"],
+            &[b"sadd", b"m", b"x", b"2"],
+            &[b"hset", b"h", b"f", b"v"],
+            &[b"zadd", b"z", b"1.5", b"a", b"-2", b"b"],
+        ];
+        for args in setup {
+            exec_args(&mut src, args).unwrap();
+        }
+        for key in [b"s", b"t", b"l", b"i", b"m", b"h", b"z"] {
+            let Response::BulkString(payload) = exec_args(&mut src, &[b"dump", key]).unwrap() else { panic!() };
+            exec_args(&mut dst, &[b"restore", key, b"0", &payload]).unwrap();
+            assert_eq!(exec_args(&mut dst, &[b"object", b"encoding", key]).unwrap(), exec_args(&mut src, &[b"object", b"encoding", key]).unwrap());
+        }
+        assert_eq!(exec_args(&mut dst, &[b"debug", b"digest"]).unwrap(), exec_args(&mut src, &[b"debug", b"digest"]).unwrap());
+    }
+}
@@ -0,0 +1,115 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"eval",
+    arity: -3,
+    flags: &[
+        b"noscript",
+        b"movablekeys",
+    ],
+    first_key: 0,
+    last_key: 0,
+    step: 0,
+};
+
+/// Splits the `numkeys key... arg...` arguments of EVAL and EVALSHA.
+pub fn parse_keys_and_args(cmd: &mut Command) -> anyhow::Result<(Vec<ByteString>, Vec<ByteString>)> {
+    let (numkeys, mut keys) = cmd.parse_args::<(i64, Vec<ByteString>)>()?;
+    anyhow::ensure!(numkeys >= 0, "Number of keys can't be negative");
+    anyhow::ensure!(numkeys as usize <= keys.len(), "Number of keys can't be greater than number of args");
+    let args = keys.split_off(numkeys as _);
+    Ok((keys, args))
+}
+
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let body = cmd.parse_partial_args::<ByteString>()?;
+    let (keys, args) = parse_keys_and_args(&mut cmd)?;
+    let sha = db.script_load(&body)?;
+    db.eval_script(sha.as_bytes(), keys, args)
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "eval return(1) 0"                                              => 1;
+    "eval return(3.99) 0"                                           => 3;
+    "eval return(redis.call('set',KEYS[1],ARGV[1])) 1 x 10"         => "OK";
+    "eval return(redis.call('incrby',KEYS[1],5)) 1 x"               => 15;
+    "eval return(redis.call('get','nokey')) 0"                      => ();
+    "eval return(redis.call('get','nokey')==false) 0"               => 1;
+    "eval return(redis.status_reply('FINE')) 0"                     => "FINE";
+    "eval return(redis.pcall('rpush','x','a')['err']) 0"            => "ERR expected array value";
+    "eval return(redis.sha1hex('')) 0"                              => "da39a3ee5e6b4b0d3255bfef95601890afd80709";
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Database, Response};
+    use crate::test_utils::exec_args;
+
+    fn error(db: &mut Database, cmd: &[&[u8]]) -> String {
+        crate::error_reply(&exec_args(db, cmd).unwrap_err())
+    }
+
+    #[test]
+    fn test_eval_tables() {
+        let mut db = Database::default();
+        let res = exec_args(&mut db, &[b"eval", b"return {1, 2, nil, 4}", b"0"]).unwrap();
+        assert_eq!(res, Response::Array(vec![Response::Number(1), Response::Number(2)]));
+        let res = exec_args(&mut db, &[b"eval", b"return {KEYS[1], {ARGV[1], ARGV[2]}}", b"1", b"k", b"a", b"b"]).unwrap();
+        assert_eq!(res, Response::Array(vec![
+            Response::BulkString(b"k".to_vec()),
+            Response::Array(vec![Response::BulkString(b"a".to_vec()), Response::BulkString(b"b".to_vec())]),
+        ]));
+        exec_args(&mut db, &[b"rpush", b"l", b"a", b"b"]).unwrap();
+        let res = exec_args(&mut db, &[b"eval", b"local l = redis.call('lrange', 'l', 0, -1) return #l .. l[2]", b"0"]).unwrap();
+        assert_eq!(res, Response::BulkString(b"2b".to_vec()));
+    }
+
+    #[test]
+    fn test_eval_errors() {
+        let mut db = Database::default();
+        exec_args(&mut db, &[b"set", b"x", b"a"]).unwrap();
+        assert_eq!(error(&mut db, &[b"evalsha", b"ffff", b"0"]), "NOSCRIPT No matching script. Please use EVAL.");
+        assert_eq!(error(&mut db, &[b"eval", b"return 1", b"2", b"a"]), "ERR Number of keys can't be greater than number of args");
+        assert_eq!(error(&mut db, &[b"eval", b"return redis.error_reply('MY error')", b"0"]), "MY error");
+        assert!(error(&mut db, &[b"eval", b"return redis.call('incr', 'x')", b"0"]).starts_with("ERR tried to parse number, got non-numeric value script: "));
+        assert!(error(&mut db, &[b"eval", b"return (", b"0"]).starts_with("ERR Error compiling script"));
+        assert!(error(&mut db, &[b"eval", b"x = 1", b"0"]).contains("Script attempted to create global variable 'x'"));
+        assert!(error(&mut db, &[b"eval", b"return y", b"0"]).contains("Script attempted to access nonexistent global variable 'y'"));
+        assert!(error(&mut db, &[b"eval", b"return redis.call('wait', '0', '0')", b"0"]).contains("This Redis command is not allowed from script"));
+        assert!(error(&mut db, &[b"eval", b"return redis.call('nosuchcommand')", b"0"]).contains("Unknown Redis command called from script"));
+        assert!(error(&mut db, &[b"eval", b"return redis.call({})", b"0"]).contains("Lua redis lib command arguments must be strings or integers"));
+        assert!(error(&mut db, &[b"eval", b"return os.time()", b"0"]).contains("nonexistent global variable 'os'"));
+
+        // errors inside a script do not leave it running
+        assert_eq!(error(&mut db, &[b"script", b"kill"]), "NOTBUSY No scripts in execution right now.");
+    }
+
+    #[test]
+    fn test_eval_sandbox() {
+        let mut db = Database::default();
+        // scripts cannot change what the next ones see
+        let attempts: &[&[u8]] = &[
+            b"redis.call = function() return 7 end",
+            b"redis.pcall = nil",
+            b"rawset(redis, 'call', nil)",
+            b"string.rep = nil",
+            b"getmetatable('').__index = {}",
+            b"setmetatable(_G, nil) leak = 5",
+            b"rawset(_G, 'leak', 5)",
+            b"redis = nil",
+            b"setfenv(0, {})",
+        ];
+        for &script in attempts {
+            assert!(exec_args(&mut db, &[b"eval", script, b"0"]).is_err(), "{}", String::from_utf8_lossy(script));
+        }
+        assert!(error(&mut db, &[b"eval", b"redis.call = nil", b"0"]).contains("Attempt to modify a readonly table"));
+        assert!(error(&mut db, &[b"eval", b"return leak", b"0"]).contains("nonexistent global variable 'leak'"));
+        let res = exec_args(&mut db, &[b"eval", b"return redis.call('set', 'x', string.rep('a', 2))", b"0"]).unwrap();
+        assert_eq!(res, Response::SimpleString(b"OK".to_vec()));
+        let res = exec_args(&mut db, &[b"eval", b"local t = {} rawset(t, 1, 'a') return t", b"0"]).unwrap();
+        assert_eq!(res, Response::Array(vec![Response::BulkString(b"a".to_vec())]));
+    }
+}
@@ -0,0 +1,28 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"evalsha",
+    arity: -3,
+    flags: &[
+        b"noscript",
+        b"movablekeys",
+    ],
+    first_key: 0,
+    last_key: 0,
+    step: 0,
+};
+
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let sha = cmd.parse_partial_args::<ByteString>()?;
+    let (keys, args) = super::eval::parse_keys_and_args(&mut cmd)?;
+    db.eval_script(&sha, keys, args)
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "script load return(KEYS[1]..ARGV[1])"                      => "5e38917d6485fabceb0f779b43ede678b098b9a7";
+    "evalsha 5e38917d6485fabceb0f779b43ede678b098b9a7 1 k a"    => "ka";
+    "evalsha 5E38917D6485FABCEB0F779B43EDE678B098B9A7 1 k b"    => "kb";
+}
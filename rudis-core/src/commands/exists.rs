@@ -29,4 +29,5 @@ crate::command_test! {
     "exists z y x" => 1;
     "sadd y a" => 1;
     "exists x y z" => 2;
+    "exists x x y" => 3;
 }
@@ -0,0 +1,85 @@
+use super::{expire_at, CommandInfo};
+use crate::command::Command;
+use crate::expire::now_ms;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"expire",
+    arity: -3,
+    flags: &[
+        b"write",
+        b"fast",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+/// EXPIRE and its variants, `unit` being 1000 for times in seconds and 1 for milliseconds.
+///
+/// EXPIRE key time [NX | XX | GT | LT]
+pub fn expire(db: &mut Database, mut cmd: Command, unit: i64, absolute: bool) -> anyhow::Result<Response> {
+    let name = cmd.cmd().to_string();
+    let (key, time) = cmd.parse_partial_args::<(ByteString, i64)>()?;
+    let (mut nx, mut xx, mut gt, mut lt) = (false, false, false, false);
+    while cmd.has_more() {
+        if cmd.parse_option("nx") {
+            nx = true;
+        } else if cmd.parse_option("xx") {
+            xx = true;
+        } else if cmd.parse_option("gt") {
+            gt = true;
+        } else if cmd.parse_option("lt") {
+            lt = true;
+        } else {
+            anyhow::bail!("Unsupported option {}", String::from_utf8_lossy(cmd.arg(0).unwrap_or_default()));
+        }
+    }
+    anyhow::ensure!(!(nx && (xx || gt || lt)), "NX and XX, GT or LT options at the same time are not compatible");
+    anyhow::ensure!(!(gt && lt), "GT and LT options at the same time are not compatible");
+    let at = expire_at(time, unit, absolute, &name)?;
+    if !db.contains(&key) {
+        return Ok(Response::Number(0));
+    }
+    // keys without an expire have an infinite ttl
+    let current = db.expiry(&key);
+    let allowed = match current {
+        None => !xx && !gt,
+        Some(current) => !nx && (!gt || at > current) && (!lt || at < current),
+    };
+    if !allowed {
+        return Ok(Response::Number(0));
+    }
+    if at <= now_ms() && !db.is_master_client() && db.master().is_none() {
+        db.del(&key);
+        db.notify('g', "del", &key);
+        db.propagate_as(vec![b"del".to_vec(), key]);
+    } else {
+        db.set_expiry(&key, at);
+        db.notify('g', "expire", &key);
+        db.propagate_as(vec![b"pexpireat".to_vec(), key, at.to_string().into_bytes()]);
+    }
+    Ok(Response::Number(1))
+}
+
+pub fn run(db: &mut Database, cmd: Command) -> anyhow::Result<Response> {
+    expire(db, cmd, 1000, false)
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "expire x 100"     => 0;
+    "set x a"          => "OK";
+    "expire x 100 xx"  => 0;
+    "expire x 100 gt"  => 0;
+    "expire x 100 nx"  => 1;
+    "expire x 200 nx"  => 0;
+    "expire x 50 gt"   => 0;
+    "expire x 200 gt"  => 1;
+    "ttl x"            => 200;
+    "expire x 300 lt"  => 0;
+    "expire x 100 lt"  => 1;
+    "ttl x"            => 100;
+    "expire x -1"      => 1;
+    "exists x"         => 0;
+}
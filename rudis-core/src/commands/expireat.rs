@@ -0,0 +1,28 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"expireat",
+    arity: -3,
+    flags: &[
+        b"write",
+        b"fast",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+pub fn run(db: &mut Database, cmd: Command) -> anyhow::Result<Response> {
+    super::expire::expire(db, cmd, 1000, true)
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "set x a"                => "OK";
+    "expireat x 4102444800"  => 1;
+    "expiretime x"           => 4102444800;
+    "expireat x 1"           => 1;
+    "exists x"               => 0;
+}
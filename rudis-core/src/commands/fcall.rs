@@ -0,0 +1,21 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"fcall",
+    arity: -3,
+    flags: &[
+        b"noscript",
+        b"movablekeys",
+    ],
+    first_key: 0,
+    last_key: 0,
+    step: 0,
+};
+
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let name = cmd.parse_partial_args::<ByteString>()?;
+    let (keys, args) = super::eval::parse_keys_and_args(&mut cmd)?;
+    db.fcall(&name, keys, args, false)
+}
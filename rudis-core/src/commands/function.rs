@@ -0,0 +1,190 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::rdb::{dump_functions, restore_functions};
+use crate::{ByteString, Database, Response, RestorePolicy};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"function",
+    arity: -2,
+    flags: &[
+        b"noscript",
+    ],
+    first_key: 0,
+    last_key: 0,
+    step: 0,
+};
+
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let mut subcommand = cmd.parse_partial_args::<ByteString>()?;
+    subcommand.make_ascii_lowercase();
+    let res = match subcommand.as_slice() {
+        b"load" => {
+            let replace = cmd.parse_option("replace");
+            let code = cmd.parse_args::<ByteString>()?;
+            let name = db.function_load(&code, replace)?;
+            let mut args = vec![b"function".to_vec(), b"load".to_vec()];
+            if replace {
+                args.push(b"replace".to_vec());
+            }
+            args.push(code);
+            db.propagate_as(args);
+            Response::BulkString(name.into_bytes())
+        }
+        b"delete" => {
+            let library = cmd.parse_args::<ByteString>()?;
+            db.function_delete(&String::from_utf8_lossy(&library))?;
+            db.propagate_as(vec![b"function".to_vec(), b"delete".to_vec(), library]);
+            Response::SimpleString(b"OK".to_vec())
+        }
+        b"flush" => {
+            // functions are always flushed synchronously
+            if !cmd.parse_option("sync") {
+                cmd.parse_option("async");
+            }
+            cmd.ensure_empty()?;
+            db.function_flush();
+            db.propagate_as(vec![b"function".to_vec(), b"flush".to_vec()]);
+            Response::SimpleString(b"OK".to_vec())
+        }
+        b"list" => {
+            let mut with_code = false;
+            let mut pattern = None;
+            while cmd.has_more() {
+                if cmd.parse_option("withcode") {
+                    with_code = true;
+                } else if let Some(p) = cmd.parse_named_arg("libraryname") {
+                    pattern = Some(p);
+                } else {
+                    anyhow::bail!("Unknown argument {}", String::from_utf8_lossy(cmd.arg(0).unwrap_or_default()));
+                }
+            }
+            db.function_list(pattern.as_deref(), with_code)
+        }
+        b"stats" => {
+            cmd.ensure_empty()?;
+            db.function_stats()
+        }
+        b"dump" => {
+            cmd.ensure_empty()?;
+            Response::BulkString(dump_functions(&db.function_codes()))
+        }
+        b"restore" => {
+            let payload = cmd.parse_partial_args::<ByteString>()?;
+            let policy = match cmd.pop_arg().map(|p| p.to_ascii_lowercase()) {
+                None => RestorePolicy::Append,
+                Some(p) if p == b"append" => RestorePolicy::Append,
+                Some(p) if p == b"replace" => RestorePolicy::Replace,
+                Some(p) if p == b"flush" => RestorePolicy::Flush,
+                Some(_) => anyhow::bail!("Wrong restore policy given, value should be either FLUSH, APPEND or REPLACE."),
+            };
+            cmd.ensure_empty()?;
+            let codes = restore_functions(&payload)?;
+            db.function_restore(&codes, policy)?;
+            let policy = format!("{policy:?}").to_ascii_lowercase().into_bytes();
+            db.propagate_as(vec![b"function".to_vec(), b"restore".to_vec(), payload, policy]);
+            Response::SimpleString(b"OK".to_vec())
+        }
+        b"kill" => {
+            cmd.ensure_empty()?;
+            db.script_kill()?;
+            Response::SimpleString(b"OK".to_vec())
+        }
+        _ => anyhow::bail!("unknown subcommand '{}'", String::from_utf8_lossy(&subcommand)),
+    };
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Database, Response};
+    use crate::test_utils::exec_args;
+
+    fn error(db: &mut Database, args: &[&[u8]]) -> String {
+        crate::error_reply(&exec_args(db, args).unwrap_err())
+    }
+
+    fn simple(s: &str) -> Response {
+        Response::SimpleString(s.as_bytes().to_vec())
+    }
+
+    fn bulk(s: &str) -> Response {
+        Response::BulkString(s.as_bytes().to_vec())
+    }
+
+    const LIB: &[u8] = b"#!lua name=mylib\n\
+        redis.register_function('myset', function(keys, args) return redis.call('set', keys[1], args[1]) end)\n\
+        redis.register_function{function_name='myget', callback=function(keys) return redis.call('get', keys[1]) end, flags={'no-writes'}, description='gets'}";
+
+    #[test]
+    fn test_function() {
+        let mut db = Database::default();
+        assert_eq!(exec_args(&mut db, &[b"function", b"load", LIB]).unwrap(), bulk("mylib"));
+        assert_eq!(error(&mut db, &[b"function", b"load", LIB]), "ERR Library 'mylib' already exists");
+        assert_eq!(exec_args(&mut db, &[b"function", b"load", b"replace", LIB]).unwrap(), bulk("mylib"));
+
+        assert_eq!(exec_args(&mut db, &[b"fcall", b"myset", b"1", b"x", b"10"]).unwrap(), simple("OK"));
+        assert_eq!(exec_args(&mut db, &[b"fcall_ro", b"myget", b"1", b"x"]).unwrap(), simple("10"));
+        assert_eq!(error(&mut db, &[b"fcall_ro", b"myset", b"1", b"x", b"11"]), "ERR Can not execute a script with write flag using *_ro command.");
+        assert_eq!(error(&mut db, &[b"fcall", b"nosuch", b"0"]), "ERR Function not found");
+
+        let list = exec_args(&mut db, &[b"function", b"list", b"libraryname", b"my*"]).unwrap();
+        assert_eq!(list, Response::Array(vec![Response::Array(vec![
+            bulk("library_name"),
+            bulk("mylib"),
+            bulk("engine"),
+            bulk("LUA"),
+            bulk("functions"),
+            Response::Array(vec![
+                Response::Array(vec![bulk("name"), bulk("myget"), bulk("description"), bulk("gets"), bulk("flags"), Response::Array(vec![bulk("no-writes")])]),
+                Response::Array(vec![bulk("name"), bulk("myset"), bulk("description"), Response::Nil, bulk("flags"), Response::Array(vec![])]),
+            ]),
+        ])]));
+        assert_eq!(exec_args(&mut db, &[b"function", b"list", b"libraryname", b"other*"]).unwrap(), Response::Array(vec![]));
+
+        let Response::BulkString(payload) = exec_args(&mut db, &[b"function", b"dump"]).unwrap() else {
+            panic!("expected a payload");
+        };
+        assert_eq!(error(&mut db, &[b"function", b"restore", &payload]), "ERR Library 'mylib' already exists");
+        exec_args(&mut db, &[b"function", b"flush"]).unwrap();
+        assert_eq!(error(&mut db, &[b"function", b"delete", b"mylib"]), "ERR Library not found");
+        // like DUMP payloads, a zero checksum is not skipped
+        let unchecked = [&payload[..payload.len() - 8], &[0; 8]].concat();
+        assert_eq!(error(&mut db, &[b"function", b"restore", &unchecked]), "ERR payload version or checksum are wrong");
+        exec_args(&mut db, &[b"function", b"restore", &payload]).unwrap();
+        assert_eq!(exec_args(&mut db, &[b"fcall", b"myget", b"1", b"x"]).unwrap(), simple("10"));
+
+        // the libraries are saved along with the keyspace
+        let rdb = db.save_rdb();
+        let mut other = Database::default();
+        other.load_rdb(&rdb).unwrap();
+        assert_eq!(exec_args(&mut other, &[b"fcall", b"myget", b"1", b"x"]).unwrap(), simple("10"));
+
+        exec_args(&mut db, &[b"function", b"delete", b"mylib"]).unwrap();
+        assert_eq!(error(&mut db, &[b"fcall", b"myget", b"1", b"x"]), "ERR Function not found");
+    }
+
+    #[test]
+    fn test_function_load_errors() {
+        let mut db = Database::default();
+        assert_eq!(error(&mut db, &[b"function", b"load", b"return 1"]), "ERR Missing library metadata");
+        assert_eq!(error(&mut db, &[b"function", b"load", b"#!js name=lib\n"]), "ERR Engine 'js' not found");
+        assert_eq!(error(&mut db, &[b"function", b"load", b"#!lua name=lib\nreturn 1"]), "ERR No functions registered");
+        assert!(error(&mut db, &[b"function", b"load", b"#!lua name=lib\nredis.register_function{function_name='f', callback=function() end, flags={'bad'}}"]).contains("unknown flag given"));
+
+        // a library loading an existing function is rejected as a whole
+        exec_args(&mut db, &[b"function", b"load", LIB]).unwrap();
+        let clash = b"#!lua name=other\nredis.register_function('myget', function() return 1 end)";
+        assert_eq!(error(&mut db, &[b"function", b"load", clash]), "ERR Function myget already exists");
+        assert_eq!(exec_args(&mut db, &[b"function", b"stats"]).unwrap(), Response::Array(vec![
+            bulk("running_script"),
+            Response::Nil,
+            bulk("engines"),
+            Response::Array(vec![bulk("LUA"), Response::Array(vec![bulk("libraries_count"), Response::Number(1), bulk("functions_count"), Response::Number(2)])]),
+        ]));
+
+        // redis.register_function is only available while loading
+        let late = b"#!lua name=late\nredis.register_function('late', function() redis.register_function('x', function() end) end)";
+        exec_args(&mut db, &[b"function", b"load", late]).unwrap();
+        assert!(exec_args(&mut db, &[b"fcall", b"late", b"0"]).is_err());
+    }
+}
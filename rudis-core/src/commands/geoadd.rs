@@ -0,0 +1,95 @@
+use ordered_float::NotNan;
+
+use super::CommandInfo;
+use crate::command::Command;
+use crate::geo;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"geoadd",
+    arity: -5,
+    flags: &[
+        b"write",
+        b"denyoom",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+/// GEOADD key [NX | XX] [CH] longitude latitude member [longitude latitude member ...]
+///
+/// Members are stored in a sorted set, scored by the geohash of their position.
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let key = cmd.parse_partial_args::<ByteString>()?;
+    let (mut nx, mut xx, mut ch) = (false, false, false);
+    loop {
+        if cmd.parse_option("nx") {
+            nx = true;
+        } else if cmd.parse_option("xx") {
+            xx = true;
+        } else if cmd.parse_option("ch") {
+            ch = true;
+        } else {
+            break;
+        }
+    }
+    anyhow::ensure!(!(nx && xx), "XX and NX options at the same time are not compatible");
+    anyhow::ensure!(
+        cmd.has_more() && cmd.arg_count().is_multiple_of(3),
+        "syntax error. Try GEOADD key [x1] [y1] [name1] [x2] [y2] [name2] ... ",
+    );
+    let mut members = Vec::with_capacity(cmd.arg_count() / 3);
+    while cmd.has_more() {
+        let (lon, lat, member) = cmd.parse_partial_args::<(f64, f64, ByteString)>()?;
+        geo::validate(lon, lat)?;
+        members.push((geo::encode(lon, lat) as f64, member));
+    }
+    let z = db.get_or_insert_zset(key.clone())?;
+    let (mut added, mut changed) = (0, 0);
+    for (score, member) in members {
+        let score = NotNan::new(score)?;
+        match z.get_score(&member) {
+            None if !xx => {
+                z.insert(score, member);
+                added += 1;
+            }
+            Some(old) if !nx && old != *score => {
+                z.insert(score, member);
+                changed += 1;
+            }
+            _ => {}
+        }
+    }
+    if added + changed > 0 {
+        db.notify('z', "zadd", &key);
+    }
+    Ok(Response::Number(if ch { added + changed } else { added }))
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "geoadd s 13.361389 38.115556 Palermo 15.087269 37.502669 Catania" => 2;
+    "zscore s Palermo"                                                 => "3479099956230698";
+    "geoadd s nx 13.361389 38.115556 Palermo 13.5 38.1 Trabia"         => 1;
+    "geoadd s xx ch 13.4 38.1 Palermo 14 38 Nowhere"                   => 1;
+    "zcard s"                                                          => 3;
+    "geoadd q xx 13.4 38.1 Palermo"                                    => 0;
+    "exists q"                                                         => 0;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Database, Response};
+    use crate::test_utils::exec;
+
+    #[test]
+    fn test_geoadd_errors() {
+        let mut db = Database::default();
+        assert_eq!(exec(&mut db, "geoadd s 200 10 a").unwrap_err().to_string(), "invalid longitude,latitude pair 200.000000,10.000000");
+        assert_eq!(exec(&mut db, "geoadd s 10 86 a").unwrap_err().to_string(), "invalid longitude,latitude pair 10.000000,86.000000");
+        assert_eq!(exec(&mut db, "geoadd s nx xx 10 10 a").unwrap_err().to_string(), "XX and NX options at the same time are not compatible");
+        assert!(exec(&mut db, "geoadd s 10 10 a 20").unwrap_err().to_string().starts_with("syntax error"));
+        assert_eq!(exec(&mut db, "exists s").unwrap(), Response::Number(0));
+    }
+}
@@ -0,0 +1,37 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::geo;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"geodist",
+    arity: -4,
+    flags: &[
+        b"readonly",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+/// GEODIST key member1 member2 [M | KM | FT | MI]
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let (key, a, b) = cmd.parse_partial_args::<(ByteString, ByteString, ByteString)>()?;
+    let unit = cmd.parse_partial_args::<Option<ByteString>>()?;
+    cmd.ensure_empty().map_err(|_| anyhow::anyhow!("syntax error"))?;
+    let unit = unit.map_or(Ok(1.0), |unit| geo::parse_unit(&unit))?;
+    let Some(z) = db.get_zset(&key)? else { return Ok(Response::Nil) };
+    let (Some(a), Some(b)) = (z.get_score(&a), z.get_score(&b)) else { return Ok(Response::Nil) };
+    let distance = geo::distance(geo::decode(a as u64), geo::decode(b as u64)) / unit;
+    Ok(Response::BulkString(format!("{distance:.4}").into_bytes()))
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "geoadd s 13.361389 38.115556 Palermo 15.087269 37.502669 Catania" => 2;
+    "geodist s Palermo Catania"                                        => "166274.1516";
+    "geodist s Palermo Catania km"                                     => "166.2742";
+    "geodist s Palermo Catania MI"                                     => "103.3182";
+    "geodist s Palermo q"                                              => ();
+    "geodist q Palermo Catania"                                        => ();
+}
@@ -0,0 +1,32 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::geo;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"geohash",
+    arity: -2,
+    flags: &[
+        b"readonly",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+/// GEOHASH key [member ...]
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let (key, members) = cmd.parse_args::<(ByteString, Vec<ByteString>)>()?;
+    let z = db.get_zset(&key)?;
+    let res = members.iter().map(|member| match z.as_ref().and_then(|z| z.get_score(member)) {
+        Some(score) => Response::BulkString(geo::hash_string(score as u64).into_bytes()),
+        None => Response::Nil,
+    });
+    Ok(Response::Array(res.collect()))
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "geoadd s 13.361389 38.115556 Palermo 15.087269 37.502669 Catania" => 2;
+    "geohash s Palermo Catania q"                                      => ["sqc8b49rny0", "sqdtr74hyu0", ()];
+}
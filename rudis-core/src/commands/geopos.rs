@@ -0,0 +1,39 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::geo;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"geopos",
+    arity: -2,
+    flags: &[
+        b"readonly",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+/// GEOPOS key [member ...]
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let (key, members) = cmd.parse_args::<(ByteString, Vec<ByteString>)>()?;
+    let z = db.get_zset(&key)?;
+    let res = members.iter().map(|member| match z.as_ref().and_then(|z| z.get_score(member)) {
+        Some(score) => {
+            let (lon, lat) = geo::decode(score as u64);
+            Response::Array(vec![Response::float(lon), Response::float(lat)])
+        }
+        None => Response::Nil,
+    });
+    Ok(Response::Array(res.collect()))
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "geoadd s 13.361389 38.115556 Palermo"   => 1;
+    "geopos s Palermo q"                     => [
+        Response::Array(vec![Response::float(13.361389338970184), Response::float(38.1155563954963)]),
+        (),
+    ];
+    "geopos q Palermo"                       => [()];
+}
@@ -0,0 +1,55 @@
+use super::geosearch::{search, Kind};
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"georadius",
+    arity: -6,
+    flags: &[
+        b"write",
+        b"denyoom",
+        b"movablekeys",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+/// GEORADIUS key longitude latitude radius M | KM | FT | MI [WITHCOORD] [WITHDIST] [WITHHASH]
+/// [COUNT count [ANY]] [ASC | DESC] [STORE key | STOREDIST key]
+pub fn run(db: &mut Database, cmd: Command) -> anyhow::Result<Response> {
+    search(db, cmd, Kind::Radius { readonly: false })
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "geoadd s 13.361389 38.115556 Palermo 15.087269 37.502669 Catania" => 2;
+    "georadius s 15 37 200 km asc"                                     => ["Catania", "Palermo"];
+    "georadius s 15 37 200 km withdist desc"                           => [
+        Response::Array(vec![Response::BulkString(b"Palermo".to_vec()), Response::BulkString(b"190.4424".to_vec())]),
+        Response::Array(vec![Response::BulkString(b"Catania".to_vec()), Response::BulkString(b"56.4413".to_vec())]),
+    ];
+    "georadius s 15 37 200 km store d"                                 => 2;
+    "zcard d"                                                          => 2;
+    "georadius_ro s 15 37 100 km"                                      => ["Catania"];
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Database;
+    use crate::test_utils::exec;
+
+    fn err(db: &mut Database, cmd: &str) -> String {
+        exec(db, cmd).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_georadius_errors() {
+        let mut db = Database::default();
+        assert_eq!(err(&mut db, "georadius_ro s 15 37 200 km store d"), "syntax error");
+        assert_eq!(err(&mut db, "georadius s 15 37 200 km withdist store d"), "STORE option in GEORADIUS is not compatible with WITHDIST, WITHHASH and WITHCOORD options");
+        assert_eq!(err(&mut db, "georadius s 15 37 200 km frommember x"), "syntax error");
+        assert_eq!(err(&mut db, "georadius s 15 37 -1 km"), "radius cannot be negative");
+    }
+}
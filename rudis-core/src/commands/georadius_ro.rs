@@ -0,0 +1,21 @@
+use super::geosearch::{search, Kind};
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"georadius_ro",
+    arity: -6,
+    flags: &[
+        b"readonly",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+/// GEORADIUS_RO key longitude latitude radius M | KM | FT | MI [WITHCOORD] [WITHDIST] [WITHHASH]
+/// [COUNT count [ANY]] [ASC | DESC]
+pub fn run(db: &mut Database, cmd: Command) -> anyhow::Result<Response> {
+    search(db, cmd, Kind::Radius { readonly: true })
+}
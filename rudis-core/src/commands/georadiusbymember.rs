@@ -0,0 +1,33 @@
+use super::geosearch::{search, Kind};
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"georadiusbymember",
+    arity: -5,
+    flags: &[
+        b"write",
+        b"denyoom",
+        b"movablekeys",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+/// GEORADIUSBYMEMBER key member radius M | KM | FT | MI [WITHCOORD] [WITHDIST] [WITHHASH]
+/// [COUNT count [ANY]] [ASC | DESC] [STORE key | STOREDIST key]
+pub fn run(db: &mut Database, cmd: Command) -> anyhow::Result<Response> {
+    search(db, cmd, Kind::RadiusByMember { readonly: false })
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "geoadd s 13.583333 37.316667 Agrigento"                               => 1;
+    "geoadd s 13.361389 38.115556 Palermo 15.087269 37.502669 Catania"     => 2;
+    "georadiusbymember s Agrigento 100 km"                                 => ["Agrigento", "Palermo"] ignore_order;
+    "georadiusbymember_ro s Agrigento 100 km count 1"                      => ["Agrigento"];
+    "georadiusbymember s Agrigento 200 km storedist d"                     => 3;
+    "zscore d Agrigento"                                                   => "0";
+}
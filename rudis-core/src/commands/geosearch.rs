@@ -0,0 +1,303 @@
+use ordered_float::NotNan;
+
+use super::CommandInfo;
+use crate::command::Command;
+use crate::geo::{self, Shape};
+use crate::sorted_set::SortedSet;
+use crate::{ByteString, Database, Response, Value};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"geosearch",
+    arity: -7,
+    flags: &[
+        b"readonly",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+/// The commands searching geo sets, differing by their arguments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Search,
+    SearchStore,
+    /// GEORADIUS and GEORADIUS_RO, their center and radius given first.
+    Radius { readonly: bool },
+    /// GEORADIUSBYMEMBER and GEORADIUSBYMEMBER_RO.
+    RadiusByMember { readonly: bool },
+}
+
+#[derive(Default)]
+struct Query {
+    from_member: Option<ByteString>,
+    from_lonlat: Option<(f64, f64)>,
+    /// The shape in meters and the unit it was given in.
+    shape: Option<(Shape, f64)>,
+    ascending: Option<bool>,
+    count: Option<(usize, bool)>,
+    with_coord: bool,
+    with_dist: bool,
+    with_hash: bool,
+    store: Option<ByteString>,
+    store_dist: bool,
+}
+
+struct Match {
+    member: ByteString,
+    score: f64,
+    distance: f64,
+}
+
+fn parse_lonlat(cmd: &mut Command) -> anyhow::Result<(f64, f64)> {
+    let (lon, lat) = cmd.parse_partial_args::<(f64, f64)>()?;
+    geo::validate(lon, lat)?;
+    Ok((lon, lat))
+}
+
+fn parse_radius(cmd: &mut Command) -> anyhow::Result<(Shape, f64)> {
+    let (radius, unit) = cmd.parse_partial_args::<(f64, ByteString)>()?;
+    anyhow::ensure!(radius >= 0.0, "radius cannot be negative");
+    let unit = geo::parse_unit(&unit)?;
+    Ok((Shape::Radius(radius * unit), unit))
+}
+
+fn parse_box(cmd: &mut Command) -> anyhow::Result<(Shape, f64)> {
+    let (width, height, unit) = cmd.parse_partial_args::<(f64, f64, ByteString)>()?;
+    anyhow::ensure!(width >= 0.0 && height >= 0.0, "height or width cannot be negative");
+    let unit = geo::parse_unit(&unit)?;
+    Ok((Shape::Box { width: width * unit, height: height * unit }, unit))
+}
+
+fn parse_query(cmd: &mut Command, kind: Kind) -> anyhow::Result<Query> {
+    let name = cmd.cmd().to_uppercase();
+    let mut q = Query::default();
+    let (legacy, readonly) = match kind {
+        Kind::Radius { readonly } => {
+            q.from_lonlat = Some(parse_lonlat(cmd)?);
+            q.shape = Some(parse_radius(cmd)?);
+            (true, readonly)
+        }
+        Kind::RadiusByMember { readonly } => {
+            q.from_member = Some(cmd.parse_partial_args::<ByteString>()?);
+            q.shape = Some(parse_radius(cmd)?);
+            (true, readonly)
+        }
+        Kind::Search => (false, true),
+        Kind::SearchStore => (false, false),
+    };
+    let from_error = || anyhow::anyhow!("exactly one of FROMMEMBER or FROMLONLAT can be specified for {name}");
+    let by_error = || anyhow::anyhow!("exactly one of BYRADIUS and BYBOX can be specified for {name}");
+    while let Some(arg) = cmd.pop_arg() {
+        match arg.to_ascii_lowercase().as_slice() {
+            b"withcoord" if kind != Kind::SearchStore => q.with_coord = true,
+            b"withdist" if kind != Kind::SearchStore => q.with_dist = true,
+            b"withhash" if kind != Kind::SearchStore => q.with_hash = true,
+            b"asc" => q.ascending = Some(true),
+            b"desc" => q.ascending = Some(false),
+            b"count" => {
+                let count = cmd.parse_partial_args::<i64>()?;
+                anyhow::ensure!(count > 0, "COUNT must be > 0");
+                q.count = Some((count as usize, cmd.parse_option("any")));
+            }
+            b"frommember" if !legacy => {
+                anyhow::ensure!(q.from_member.is_none() && q.from_lonlat.is_none(), from_error());
+                q.from_member = Some(cmd.parse_partial_args::<ByteString>()?);
+            }
+            b"fromlonlat" if !legacy => {
+                anyhow::ensure!(q.from_member.is_none() && q.from_lonlat.is_none(), from_error());
+                q.from_lonlat = Some(parse_lonlat(cmd)?);
+            }
+            b"byradius" if !legacy => {
+                anyhow::ensure!(q.shape.is_none(), by_error());
+                q.shape = Some(parse_radius(cmd)?);
+            }
+            b"bybox" if !legacy => {
+                anyhow::ensure!(q.shape.is_none(), by_error());
+                q.shape = Some(parse_box(cmd)?);
+            }
+            b"store" if legacy && !readonly => {
+                q.store = Some(cmd.parse_partial_args::<ByteString>()?);
+                q.store_dist = false;
+            }
+            b"storedist" if legacy && !readonly => {
+                q.store = Some(cmd.parse_partial_args::<ByteString>()?);
+                q.store_dist = true;
+            }
+            b"storedist" if kind == Kind::SearchStore => q.store_dist = true,
+            _ => anyhow::bail!("syntax error"),
+        }
+    }
+    anyhow::ensure!(q.from_member.is_some() || q.from_lonlat.is_some(), from_error());
+    anyhow::ensure!(q.shape.is_some(), by_error());
+    anyhow::ensure!(
+        q.store.is_none() || !(q.with_coord || q.with_dist || q.with_hash),
+        "STORE option in {name} is not compatible with WITHDIST, WITHHASH and WITHCOORD options",
+    );
+    Ok(q)
+}
+
+/// The members within the shape, stopping after `limit` of them.
+fn find(z: &SortedSet, center: (f64, f64), shape: Shape, limit: Option<usize>) -> Vec<Match> {
+    let mut found = Vec::new();
+    for (min, max) in shape.score_ranges(center) {
+        let (min, max) = (NotNan::new(min).unwrap(), NotNan::new(max).unwrap());
+        for (score, member) in z.range(min, max) {
+            if limit.is_some_and(|limit| found.len() >= limit) {
+                return found;
+            }
+            if let Some(distance) = shape.distance_within(center, geo::decode(score as u64)) {
+                found.push(Match { member: member.to_vec(), score, distance });
+            }
+        }
+    }
+    found
+}
+
+/// The key and the STORE or STOREDIST destination of GEORADIUS and GEORADIUSBYMEMBER, whose
+/// options start at argument `options`.
+pub fn radius_keys(cmd: &Command, options: usize) -> Vec<&[u8]> {
+    let store = (options..cmd.arg_count())
+        .rfind(|&i| cmd.arg(i).is_some_and(|a| a.eq_ignore_ascii_case(b"store") || a.eq_ignore_ascii_case(b"storedist")))
+        .and_then(|i| cmd.arg(i + 1));
+    cmd.arg(0).into_iter().chain(store).collect()
+}
+
+/// Runs a geo search, `cmd` starting after the keys.
+pub fn search(db: &mut Database, mut cmd: Command, kind: Kind) -> anyhow::Result<Response> {
+    let dest = match kind {
+        Kind::SearchStore => Some(cmd.parse_partial_args::<ByteString>()?),
+        _ => None,
+    };
+    let key = cmd.parse_partial_args::<ByteString>()?;
+    let mut q = parse_query(&mut cmd, kind)?;
+    q.store = q.store.or(dest);
+    let (shape, unit) = q.shape.unwrap();
+    let mut found = match db.get_zset(&key)? {
+        Some(z) => {
+            let center = match &q.from_member {
+                Some(member) => {
+                    let score = z.get_score(member).ok_or_else(|| anyhow::anyhow!("could not decode requested zset member"))?;
+                    geo::decode(score as u64)
+                }
+                None => q.from_lonlat.unwrap(),
+            };
+            find(z, center, shape, q.count.filter(|&(_, any)| any).map(|(count, _)| count))
+        }
+        None => Vec::new(),
+    };
+    // a COUNT without ANY returns the closest members
+    let ascending = q.ascending.or(q.count.filter(|&(_, any)| !any).map(|_| true));
+    match ascending {
+        Some(true) => found.sort_by(|a, b| a.distance.total_cmp(&b.distance)),
+        Some(false) => found.sort_by(|a, b| b.distance.total_cmp(&a.distance)),
+        None => {}
+    }
+    if let Some((count, _)) = q.count {
+        found.truncate(count);
+    }
+
+    if let Some(dest) = q.store {
+        if found.is_empty() {
+            if db.del(&dest).is_some() {
+                db.notify('g', "del", &dest);
+            }
+            return Ok(Response::Number(0));
+        }
+        let mut z = SortedSet::new();
+        for m in found {
+            let score = if q.store_dist { m.distance / unit } else { m.score };
+            z.insert(NotNan::new(score)?, m.member);
+        }
+        let len = z.len();
+        db.set(dest.clone(), Value::ZSet(z));
+        let event = if kind == Kind::SearchStore { "geosearchstore" } else { "georadiusstore" };
+        db.notify('z', event, &dest);
+        return Ok(Response::Number(len as _));
+    }
+
+    if !(q.with_coord || q.with_dist || q.with_hash) {
+        return Ok(Response::string_array(found.into_iter().map(|m| m.member)));
+    }
+    let res = found.into_iter().map(|m| {
+        let mut item = vec![Response::BulkString(m.member)];
+        if q.with_dist {
+            item.push(Response::BulkString(format!("{:.4}", m.distance / unit).into_bytes()));
+        }
+        if q.with_hash {
+            item.push(Response::Number(m.score as _));
+        }
+        if q.with_coord {
+            let (lon, lat) = geo::decode(m.score as u64);
+            item.push(Response::Array(vec![Response::float(lon), Response::float(lat)]));
+        }
+        Response::Array(item)
+    });
+    Ok(Response::Array(res.collect()))
+}
+
+/// GEOSEARCH key FROMMEMBER member | FROMLONLAT longitude latitude
+/// BYRADIUS radius M | KM | FT | MI | BYBOX width height M | KM | FT | MI
+/// [ASC | DESC] [COUNT count [ANY]] [WITHCOORD] [WITHDIST] [WITHHASH]
+pub fn run(db: &mut Database, cmd: Command) -> anyhow::Result<Response> {
+    search(db, cmd, Kind::Search)
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "geoadd s 13.361389 38.115556 Palermo 15.087269 37.502669 Catania"        => 2;
+    "geoadd s 12.758489 38.788135 edge1 17.241510 38.788135 edge2"            => 2;
+    "geosearch s fromlonlat 15 37 byradius 200 km asc"                        => ["Catania", "Palermo"];
+    "geosearch s fromlonlat 15 37 byradius 100 km"                            => ["Catania"];
+    "geosearch s fromlonlat 15 37 bybox 400 400 km desc"                      => ["edge1", "edge2", "Palermo", "Catania"];
+    "geosearch s frommember Palermo byradius 200 km count 1"                  => ["Palermo"];
+    "geosearch s frommember Palermo byradius 200 km desc count 1"             => ["Catania"];
+    "geosearch s fromlonlat 15 37 byradius 200 km asc withdist withhash"      => [
+        Response::Array(vec![Response::BulkString(b"Catania".to_vec()), Response::BulkString(b"56.4413".to_vec()), Response::Number(3479447370796909)]),
+        Response::Array(vec![Response::BulkString(b"Palermo".to_vec()), Response::BulkString(b"190.4424".to_vec()), Response::Number(3479099956230698)]),
+    ];
+    "geosearch q fromlonlat 15 37 byradius 200 km"                            => Response::Array(vec![]);
+    "geosearch q frommember x byradius 200 km"                                => Response::Array(vec![]);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Database, Response};
+    use crate::test_utils::exec;
+
+    fn err(db: &mut Database, cmd: &str) -> String {
+        exec(db, cmd).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_geosearch_errors() {
+        let mut db = Database::default();
+        exec(&mut db, "geoadd s 13.361389 38.115556 Palermo").unwrap();
+        let from = "exactly one of FROMMEMBER or FROMLONLAT can be specified for GEOSEARCH";
+        assert_eq!(err(&mut db, "geosearch s byradius 10 km"), from);
+        assert_eq!(err(&mut db, "geosearch s frommember Palermo fromlonlat 1 1 byradius 10 km"), from);
+        let by = "exactly one of BYRADIUS and BYBOX can be specified for GEOSEARCH";
+        assert_eq!(err(&mut db, "geosearch s frommember Palermo"), by);
+        assert_eq!(err(&mut db, "geosearch s frommember Palermo byradius 10 km bybox 1 1 km"), by);
+        assert_eq!(err(&mut db, "geosearch s frommember Palermo byradius 10 parsec"), "unsupported unit provided. please use M, KM, FT, MI");
+        assert_eq!(err(&mut db, "geosearch s frommember Palermo byradius 10 km count 0"), "COUNT must be > 0");
+        assert_eq!(err(&mut db, "geosearch s frommember Rome byradius 10 km"), "could not decode requested zset member");
+        assert_eq!(err(&mut db, "geosearch s frommember Palermo byradius 10 km store x"), "syntax error");
+    }
+
+    #[test]
+    fn test_search_covers_radius() {
+        // members all around the center, including across the antimeridian
+        let mut db = Database::default();
+        let mut expected = 0;
+        for i in 0..360 {
+            let (lon, lat) = ((179.9 + i as f64 * 0.001 + 180.0).rem_euclid(360.0) - 180.0, 40.0 + (i % 20) as f64 * 0.0005);
+            exec(&mut db, &format!("geoadd s {lon} {lat} m{i}")).unwrap();
+            let distance = crate::geo::distance((179.95, 40.005), crate::geo::decode(crate::geo::encode(lon, lat)));
+            expected += (distance <= 20000.0) as usize;
+        }
+        let Response::Array(found) = exec(&mut db, "geosearch s fromlonlat 179.95 40.005 byradius 20 km").unwrap() else { panic!() };
+        assert_eq!(found.len(), expected);
+        assert!(expected > 0 && expected < 360);
+    }
+}
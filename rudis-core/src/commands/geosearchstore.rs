@@ -0,0 +1,34 @@
+use super::geosearch::{search, Kind};
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"geosearchstore",
+    arity: -8,
+    flags: &[
+        b"write",
+        b"denyoom",
+    ],
+    first_key: 1,
+    last_key: 2,
+    step: 1,
+};
+
+/// GEOSEARCHSTORE destination source FROMMEMBER member | FROMLONLAT longitude latitude
+/// BYRADIUS radius M | KM | FT | MI | BYBOX width height M | KM | FT | MI
+/// [ASC | DESC] [COUNT count [ANY]] [STOREDIST]
+pub fn run(db: &mut Database, cmd: Command) -> anyhow::Result<Response> {
+    search(db, cmd, Kind::SearchStore)
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "geoadd s 13.361389 38.115556 Palermo 15.087269 37.502669 Catania"      => 2;
+    "geosearchstore d s fromlonlat 15 37 byradius 200 km"                   => 2;
+    "zscore d Palermo"                                                      => "3479099956230698";
+    "geosearchstore d s fromlonlat 15 37 byradius 200 km count 1 storedist" => 1;
+    "zscore d Catania"                                                      => "56.4412578701582";
+    "geosearchstore d s fromlonlat 15 37 byradius 1 km"                     => 0;
+    "exists d"                                                              => 0;
+}
@@ -21,7 +21,7 @@ pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
         .and_then(|s| {
             let i = (offset >> 3) as usize;
             let j = 7 - (offset & 0x7) as usize;
-            s.get(i).map(|x| (x >> j) & 1)
+            s.as_bytes().get(i).map(|x| (x >> j) & 1)
         })
         .unwrap_or(0);
     Ok(Response::Number(bit as _))
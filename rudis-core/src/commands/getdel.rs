@@ -16,8 +16,9 @@ pub static INFO: CommandInfo = CommandInfo {
 
 pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
     let key = cmd.parse_args::<ByteString>()?;
-    let res = db.get_str(&key)?.cloned().map(|s| {
+    let res = db.get_str(&key)?.map(|s| s.to_vec()).map(|s| {
         db.del(&key);
+        db.notify('g', "del", &key);
         Response::SimpleString(s)
     }).unwrap_or_default();
     Ok(res)
@@ -0,0 +1,47 @@
+use super::{parse_expire_option, CommandInfo};
+use crate::command::Command;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"getex",
+    arity: -2,
+    flags: &[
+        b"write",
+        b"fast",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+/// GETEX key [EX seconds | PX milliseconds | EXAT unix-time-seconds | PXAT unix-time-milliseconds | PERSIST]
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let key = cmd.parse_partial_args::<ByteString>()?;
+    let persist = cmd.parse_option("persist");
+    let expiry = if persist { None } else { parse_expire_option(&mut cmd, "getex")? };
+    cmd.ensure_empty().map_err(|_| anyhow::anyhow!("syntax error"))?;
+    let Some(value) = db.get_str(&key)?.map(|s| s.to_vec()) else { return Ok(Response::Nil) };
+    if let Some(at) = expiry {
+        db.set_expiry(&key, at);
+        db.notify('g', "expire", &key);
+        db.propagate_as(vec![b"pexpireat".to_vec(), key, at.to_string().into_bytes()]);
+    } else if persist && db.persist(&key) {
+        db.notify('g', "persist", &key);
+        db.propagate_as(vec![b"persist".to_vec(), key]);
+    }
+    Ok(Response::SimpleString(value))
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "getex x"              => ();
+    "set x a"              => "OK";
+    "getex x ex 100"       => "a";
+    "ttl x"                => 100;
+    "getex x"              => "a";
+    "ttl x"                => 100;
+    "getex x persist"      => "a";
+    "ttl x"                => -1;
+    "getex x pxat 1"       => "a";
+    "get x"                => ();
+}
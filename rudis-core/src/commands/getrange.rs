@@ -16,6 +16,7 @@ pub static INFO: CommandInfo = CommandInfo {
 pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
     let (key, start, stop) = cmd.parse_args::<(ByteString, i64, i64)>()?;
     let range = db.get_str(&key)?.map(|s| {
+        let s = s.as_bytes();
         let (start, stop) = clamp_range(s.len(), start, stop);
         s[start..=stop].to_vec()
     }).unwrap_or_default();
@@ -17,16 +17,19 @@ pub static INFO: CommandInfo = CommandInfo {
 
 pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
     let (key, value) = cmd.parse_args::<(ByteString, ByteString)>()?;
-    Ok(match db.get_str(&key)? {
+    let res = match db.get_str_mut(&key)? {
         Some(s) => {
-            let prev = std::mem::replace(s, value);
-            Response::SimpleString(prev)
+            let prev = std::mem::replace(s, value.into());
+            db.persist(&key);
+            Response::SimpleString(prev.into_bytes())
         },
         None => {
-            db.set(key, Value::String(value));
+            db.set(key.clone(), Value::String(value.into()));
             Response::Nil
         },
-    })
+    };
+    db.notify('$', "set", &key);
+    Ok(res)
 }
 
 #[cfg(test)]
@@ -17,9 +17,12 @@ pub static INFO: CommandInfo = CommandInfo {
 pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
     let (key, fields) = cmd.parse_args::<(ByteString, Vec<ByteString>)>()?;
     anyhow::ensure!(!fields.is_empty(), "expected HDEL key field [field ..]");
-    let deleted = db.get_hash(&key)?
+    let deleted = db.get_hash_mut(&key)?
         .map(|h| fields.iter().filter_map(|f| h.remove(f)).count())
         .unwrap_or(0);
+    if deleted > 0 {
+        db.notify('h', "hdel", &key);
+    }
     Ok(Response::Number(deleted as _))
 }
 
@@ -30,5 +33,6 @@ crate::command_test! {
     "hlen x"         => 1;
     "hdel x x"       => 1;
     "hlen x"         => 0;
+    "exists x"       => 0;
     "hdel q a b c"   => 0;
 }
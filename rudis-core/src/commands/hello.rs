@@ -0,0 +1,63 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"hello",
+    arity: -1,
+    flags: &[
+        b"noscript",
+        b"loading",
+        b"stale",
+        b"fast",
+        b"no_auth",
+        b"allow_busy",
+    ],
+    first_key: 0,
+    last_key: 0,
+    step: 0,
+};
+
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    if let Some(protover) = cmd.parse_partial_args::<Option<i64>>()? {
+        anyhow::ensure!(protover == 2, "NOPROTO unsupported protocol version");
+    }
+    while cmd.has_more() {
+        if let Some(username) = cmd.parse_named_arg("AUTH") {
+            let password = cmd.parse_partial_args::<ByteString>()?;
+            super::auth::authenticate(db, Some(&username), &password)?;
+        } else if cmd.parse_named_arg("SETNAME").is_none() {
+            anyhow::bail!("syntax error in HELLO option");
+        }
+    }
+    let fields = [
+        (b"server".to_vec(), Response::BulkString(b"redis".to_vec())),
+        (b"version".to_vec(), Response::BulkString(env!("CARGO_PKG_VERSION").as_bytes().to_vec())),
+        (b"proto".to_vec(), Response::Number(2)),
+        (b"id".to_vec(), Response::Number(db.client_id() as _)),
+        (b"mode".to_vec(), Response::BulkString(b"standalone".to_vec())),
+        (b"role".to_vec(), Response::BulkString(b"master".to_vec())),
+        (b"modules".to_vec(), Response::Array(Vec::new())),
+    ];
+    Ok(Response::Array(fields.into_iter().flat_map(|(k, v)| [Response::BulkString(k), v]).collect()))
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "hello 2" => Response::Array(vec![
+        Response::BulkString(b"server".to_vec()),
+        Response::BulkString(b"redis".to_vec()),
+        Response::BulkString(b"version".to_vec()),
+        Response::BulkString(env!("CARGO_PKG_VERSION").as_bytes().to_vec()),
+        Response::BulkString(b"proto".to_vec()),
+        Response::Number(2),
+        Response::BulkString(b"id".to_vec()),
+        Response::Number(0),
+        Response::BulkString(b"mode".to_vec()),
+        Response::BulkString(b"standalone".to_vec()),
+        Response::BulkString(b"role".to_vec()),
+        Response::BulkString(b"master".to_vec()),
+        Response::BulkString(b"modules".to_vec()),
+        Response::Array(Vec::new()),
+    ]);
+}
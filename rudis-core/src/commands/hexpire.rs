@@ -0,0 +1,146 @@
+use super::{expire_at, CommandInfo};
+use crate::command::Command;
+use crate::expire::now_ms;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"hexpire",
+    arity: -6,
+    flags: &[
+        b"write",
+        b"denyoom",
+        b"fast",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+/// The largest unix time in milliseconds fields can expire at.
+const MAX_EXPIRE: i64 = 1 << 48;
+
+/// Parses the trailing `FIELDS numfields field [field ...]` of the field expire commands,
+/// `per_field` being the number of arguments following each field name.
+pub fn parse_fields(cmd: &mut Command, per_field: usize) -> anyhow::Result<Vec<ByteString>> {
+    anyhow::ensure!(cmd.parse_option("fields"), "Mandatory argument FIELDS is missing or not at the right position");
+    let count = cmd.parse_partial_args::<i64>().ok().filter(|&n| n > 0);
+    let count = count.ok_or_else(|| anyhow::anyhow!("Number of fields must be a positive integer"))? as usize;
+    anyhow::ensure!(cmd.arg_count() == count * (1 + per_field), "The `numfields` parameter must match the number of arguments");
+    cmd.parse_args()
+}
+
+/// HEXPIRE and its variants, `unit` being 1000 for times in seconds and 1 for milliseconds.
+/// Replies per field with -2 if it does not exist, 0 if the condition is not met, 1 if the
+/// expire was set and 2 if the field was deleted, the time being in the past.
+///
+/// HEXPIRE key time [NX | XX | GT | LT] FIELDS numfields field [field ...]
+pub fn hexpire(db: &mut Database, mut cmd: Command, unit: i64, absolute: bool) -> anyhow::Result<Response> {
+    let name = cmd.cmd().to_string();
+    let (key, time) = cmd.parse_partial_args::<(ByteString, i64)>()?;
+    let condition = ["nx", "xx", "gt", "lt"].into_iter().find(|c| cmd.parse_option(c));
+    let fields = parse_fields(&mut cmd, 0)?;
+    let at = expire_at(time, unit, absolute, &name).ok().filter(|&at| time >= 0 && at <= MAX_EXPIRE);
+    let at = at.ok_or_else(|| anyhow::anyhow!("invalid expire time, must be >= 0 && <= 2^48"))?;
+    let delete = at <= now_ms() && !db.is_master_client() && db.master().is_none();
+    let Some(h) = db.get_hash_mut(&key)? else {
+        return Ok(Response::Array(vec![Response::Number(-2); fields.len()]));
+    };
+    let mut replies = Vec::with_capacity(fields.len());
+    let mut deleted = Vec::new();
+    for field in &fields {
+        if !h.contains_key(field) {
+            replies.push(Response::Number(-2));
+            continue;
+        }
+        // fields without an expire have an infinite ttl
+        let allowed = match (h.expiry(field), condition) {
+            (None, Some("xx" | "gt")) | (Some(_), Some("nx")) => false,
+            (Some(current), Some("gt")) => at > current,
+            (Some(current), Some("lt")) => at < current,
+            _ => true,
+        };
+        if !allowed {
+            replies.push(Response::Number(0));
+        } else if delete {
+            h.remove(field);
+            deleted.push(field.clone());
+            replies.push(Response::Number(2));
+        } else {
+            h.set_expiry(field, at);
+            replies.push(Response::Number(1));
+        }
+    }
+    if !deleted.is_empty() {
+        db.notify('h', "hexpired", &key);
+        db.propagate_as([b"hdel".to_vec(), key].into_iter().chain(deleted).collect());
+    } else {
+        if replies.contains(&Response::Number(1)) {
+            db.track_hash_expires(&key);
+            db.notify('h', "hexpire", &key);
+        }
+        let mut args = vec![b"hpexpireat".to_vec(), key, at.to_string().into_bytes()];
+        args.extend(condition.map(|c| c.as_bytes().to_vec()));
+        args.extend([b"fields".to_vec(), fields.len().to_string().into_bytes()]);
+        db.propagate_as(args.into_iter().chain(fields).collect());
+    }
+    Ok(Response::Array(replies))
+}
+
+pub fn run(db: &mut Database, cmd: Command) -> anyhow::Result<Response> {
+    hexpire(db, cmd, 1000, false)
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "hexpire x 100 fields 1 a"         => [-2];
+    "hset x a 1 b 2"                   => 2;
+    "hexpire x 100 xx fields 2 a c"    => [0, -2];
+    "hexpire x 100 nx fields 1 a"      => [1];
+    "hexpire x 200 nx fields 2 a b"    => [0, 1];
+    "hexpire x 50 gt fields 1 a"       => [0];
+    "hexpire x 200 gt fields 1 a"      => [1];
+    "httl x fields 2 a b"              => [200, 200];
+    "hexpire x 300 lt fields 1 a"      => [0];
+    "hexpire x 0 fields 1 a"           => [2];
+    "hgetall x"                        => ["b", "2"];
+    "hexpire x 0 fields 1 b"           => [2];
+    "exists x"                         => 0;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Database, Response};
+    use crate::test_utils::exec;
+
+    #[test]
+    fn test_invalid_arguments() {
+        let mut db = Database::default();
+        exec(&mut db, "hset x a 1").unwrap();
+        let err = |db: &mut Database, cmd| exec(db, cmd).unwrap_err().to_string();
+        assert_eq!(err(&mut db, "hexpire x 100 a"), "Mandatory argument FIELDS is missing or not at the right position");
+        assert_eq!(err(&mut db, "hexpire x 100 fields 0 a"), "Number of fields must be a positive integer");
+        assert_eq!(err(&mut db, "hexpire x 100 fields 2 a"), "The `numfields` parameter must match the number of arguments");
+        assert_eq!(err(&mut db, "hexpire x -1 fields 1 a"), "invalid expire time, must be >= 0 && <= 2^48");
+    }
+
+    #[test]
+    fn test_expired_fields() {
+        let mut db = Database::default();
+        exec(&mut db, "hset x a 1 b 2").unwrap();
+        // set on the hash directly, as HPEXPIREAT deletes fields expiring in the past
+        let Some(h) = db.get_hash_mut(b"x").unwrap() else { panic!() };
+        h.set_expiry(b"a", 1);
+        h.set_expiry(b"b", 1);
+        db.track_hash_expires(b"x");
+        assert_eq!(exec(&mut db, "hlen x").unwrap(), Response::Number(0));
+        assert_eq!(exec(&mut db, "exists x").unwrap(), Response::Number(0));
+
+        exec(&mut db, "hset y a 1 b 2").unwrap();
+        let Some(h) = db.get_hash_mut(b"y").unwrap() else { panic!() };
+        h.set_expiry(b"a", 1);
+        db.track_hash_expires(b"y");
+        db.active_expire_cycle();
+        assert_eq!(exec(&mut db, "hgetall y").unwrap(), Response::string_array([b"b".to_vec(), b"2".to_vec()]));
+        assert_eq!(exec(&mut db, "httl y fields 1 b").unwrap(), Response::Array(vec![Response::Number(-1)]));
+    }
+}
@@ -0,0 +1,20 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"hexpireat",
+    arity: -6,
+    flags: &[
+        b"write",
+        b"denyoom",
+        b"fast",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+pub fn run(db: &mut Database, cmd: Command) -> anyhow::Result<Response> {
+    super::hexpire::hexpire(db, cmd, 1000, true)
+}
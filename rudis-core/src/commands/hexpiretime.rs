@@ -0,0 +1,27 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"hexpiretime",
+    arity: -5,
+    flags: &[
+        b"readonly",
+        b"fast",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+pub fn run(db: &mut Database, cmd: Command) -> anyhow::Result<Response> {
+    super::httl::field_ttl(db, cmd, |at| at / 1000)
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "hexpiretime x fields 1 a"                  => [-2];
+    "hset x a 1 b 2"                            => 2;
+    "hexpireat x 4102444800 fields 1 a"         => [1];
+    "hexpiretime x fields 3 a b c"              => [4102444800, -1, -2];
+}
@@ -0,0 +1,64 @@
+use super::{parse_expire_option, CommandInfo};
+use crate::command::Command;
+use crate::expire::now_ms;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"hgetex",
+    arity: -5,
+    flags: &[
+        b"write",
+        b"fast",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+/// Gets fields, setting or removing their expire. Like HEXPIRE, a time in the past deletes them.
+///
+/// HGETEX key [EX seconds | PX milliseconds | EXAT unix-time-seconds | PXAT unix-time-milliseconds | PERSIST]
+///     FIELDS numfields field [field ...]
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let key = cmd.parse_partial_args::<ByteString>()?;
+    let persist = cmd.parse_option("persist");
+    let expiry = if persist { None } else { parse_expire_option(&mut cmd, "hgetex")? };
+    let fields = super::hexpire::parse_fields(&mut cmd, 0)?;
+    let delete = expiry.is_some_and(|at| at <= now_ms()) && !db.is_master_client() && db.master().is_none();
+    let Some(h) = db.get_hash_mut(&key)? else {
+        return Ok(Response::Array(vec![Response::Nil; fields.len()]));
+    };
+    let values = fields.iter().map(|f| h.get(f).map(|v| v.to_vec())).collect::<Vec<_>>();
+    let present = fields.into_iter().filter(|f| h.contains_key(f)).collect::<Vec<_>>();
+    if let Some(at) = expiry.filter(|_| !present.is_empty()) {
+        if delete {
+            present.iter().for_each(|f| { h.remove(f); });
+            db.notify('h', "hexpired", &key);
+            db.propagate_as([b"hdel".to_vec(), key].into_iter().chain(present).collect());
+        } else {
+            present.iter().for_each(|f| h.set_expiry(f, at));
+            db.track_hash_expires(&key);
+            db.notify('h', "hexpire", &key);
+            let args = [b"hpexpireat".to_vec(), key, at.to_string().into_bytes(), b"fields".to_vec(), present.len().to_string().into_bytes()];
+            db.propagate_as(args.into_iter().chain(present).collect());
+        }
+    } else if persist {
+        let persisted = present.into_iter().filter(|f| h.persist(f)).collect::<Vec<_>>();
+        if !persisted.is_empty() {
+            db.notify('h', "hpersist", &key);
+        }
+    }
+    Ok(Response::Array(values.into_iter().map(|v| v.map(Response::BulkString).unwrap_or_default()).collect()))
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "hgetex x fields 1 a"                => [()];
+    "hset x a 1 b 2"                     => 2;
+    "hgetex x ex 100 fields 2 a c"       => ["1", ()];
+    "httl x fields 2 a b"                => [100, -1];
+    "hgetex x persist fields 1 a"        => ["1"];
+    "httl x fields 1 a"                  => [-1];
+    "hgetex x pxat 1 fields 1 a"         => ["1"];
+    "hgetall x"                          => ["b", "2"];
+}
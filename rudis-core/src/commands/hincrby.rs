@@ -17,9 +17,15 @@ pub static INFO: CommandInfo = CommandInfo {
 
 pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
     let (key, field, increment) = cmd.parse_args::<(ByteString, ByteString, i64)>()?;
-    let h = db.get_or_insert_hash(key)?;
-    let n = h.get(&field).map_or(Ok(0), |v| parse_from_bytes(v))? + increment;
-    h.insert(field, n.to_string().into_bytes());
+    let h = db.get_or_insert_hash(key.clone())?;
+    let current = match h.get(&field) {
+        Some(v) => parse_from_bytes::<i64>(v).map_err(|_| anyhow::anyhow!("hash value is not an integer"))?,
+        None => 0,
+    };
+    let n = current.checked_add(increment).ok_or_else(|| anyhow::anyhow!("increment or decrement would overflow"))?;
+    // like HSET would not, the field keeps its expire
+    h.insert_keep_ttl(field, n.to_string().into_bytes());
+    db.notify('h', "hincrby", &key);
     Ok(Response::Number(n))
 }
 
@@ -31,3 +37,18 @@ crate::command_test! {
     "hincrby x a 20" => 25;
     "hincrby x y -3" => -3;
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{Database, Response};
+    use crate::test_utils::exec;
+
+    #[test]
+    fn test_invalid_increments() {
+        let mut db = Database::default();
+        exec(&mut db, "hset x a 5 s abc").unwrap();
+        assert_eq!(exec(&mut db, "hincrby x s 1").unwrap_err().to_string(), "hash value is not an integer");
+        assert_eq!(exec(&mut db, "hincrby x a 9223372036854775807").unwrap_err().to_string(), "increment or decrement would overflow");
+        assert_eq!(exec(&mut db, "hget x a").unwrap(), Response::SimpleString(b"5".to_vec()));
+    }
+}
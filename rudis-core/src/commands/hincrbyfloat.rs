@@ -17,10 +17,16 @@ pub static INFO: CommandInfo = CommandInfo {
 
 pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
     let (key, field, increment) = cmd.parse_args::<(ByteString, ByteString, f64)>()?;
-    let h = db.get_or_insert_hash(key)?;
-    let n = h.get(&field).map_or(Ok(0.0), |v| parse_from_bytes(v))? + increment;
+    let h = db.get_or_insert_hash(key.clone())?;
+    let current = match h.get(&field) {
+        Some(v) => parse_from_bytes::<f64>(v).map_err(|_| anyhow::anyhow!("hash value is not a float"))?,
+        None => 0.0,
+    };
+    let n = current + increment;
+    anyhow::ensure!(n.is_finite(), "increment would produce NaN or Infinity");
     let val = n.to_string().into_bytes();
-    h.insert(field, val.clone());
+    h.insert_keep_ttl(field, val.clone());
+    db.notify('h', "hincrbyfloat", &key);
     Ok(Response::BulkString(val))
 }
 
@@ -33,3 +39,18 @@ crate::command_test! {
     "hincrbyfloat x y -3.7" => "-3.7";
     "hincrbyfloat x y 3.7"  => "0";
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{Database, Response};
+    use crate::test_utils::exec;
+
+    #[test]
+    fn test_invalid_increments() {
+        let mut db = Database::default();
+        exec(&mut db, "hset x a 5 s abc").unwrap();
+        assert_eq!(exec(&mut db, "hincrbyfloat x s 1").unwrap_err().to_string(), "hash value is not a float");
+        assert_eq!(exec(&mut db, "hincrbyfloat x a inf").unwrap_err().to_string(), "increment would produce NaN or Infinity");
+        assert_eq!(exec(&mut db, "hget x a").unwrap(), Response::SimpleString(b"5".to_vec()));
+    }
+}
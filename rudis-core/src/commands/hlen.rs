@@ -24,7 +24,7 @@ pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
 crate::command_test! {
     "hset x a b x y" => 2;
     "hlen x"         => 2;
-    "hset x a c z 1" => 2;
+    "hset x a c z 1" => 1;
     "hlen x"         => 3;
     "hdel x a"       => 1;
     "hlen x"         => 2;
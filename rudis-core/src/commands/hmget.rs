@@ -20,9 +20,8 @@ pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
     let hash = db.get_hash(&key)?;
     let res = fields.iter().map(|f| {
         hash.as_ref()
-            .map(|h| h.get(f))
-            .flatten()
-            .map(|v| Response::BulkString(v.clone()))
+            .and_then(|h| h.get(f))
+            .map(|v| Response::BulkString(v.to_vec()))
             .unwrap_or_default()
     }).collect();
     Ok(Response::Array(res))
@@ -0,0 +1,35 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"hmset",
+    arity: -4,
+    flags: &[
+        b"write",
+        b"denyoom",
+        b"fast",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+/// The deprecated form of HSET, replying OK.
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let (key, fields) = cmd.parse_args::<(ByteString, Vec<(ByteString, ByteString)>)>()?;
+    anyhow::ensure!(!fields.is_empty(), "expected HMSET key field value [field value ..]");
+    let h = db.get_or_insert_hash(key.clone())?;
+    for (k, v) in fields {
+        h.insert(k, v);
+    }
+    db.notify('h', "hset", &key);
+    Ok(Response::SimpleString(b"OK".to_vec()))
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "hmset x a 1 b 2" => "OK";
+    "hmset x a 3"     => "OK";
+    "hmget x a b"     => ["3", "2"];
+}
@@ -0,0 +1,45 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"hpersist",
+    arity: -5,
+    flags: &[
+        b"write",
+        b"fast",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+/// Replies per field with -2 if it does not exist, -1 if it has no expire and 1 if its expire
+/// was removed.
+///
+/// HPERSIST key FIELDS numfields field [field ...]
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let key = cmd.parse_partial_args::<ByteString>()?;
+    let fields = super::hexpire::parse_fields(&mut cmd, 0)?;
+    let Some(h) = db.get_hash_mut(&key)? else {
+        return Ok(Response::Array(vec![Response::Number(-2); fields.len()]));
+    };
+    let replies = fields.iter().map(|f| match h.contains_key(f) {
+        false => Response::Number(-2),
+        true if h.persist(f) => Response::Number(1),
+        true => Response::Number(-1),
+    }).collect::<Vec<_>>();
+    if replies.contains(&Response::Number(1)) {
+        db.notify('h', "hpersist", &key);
+    }
+    Ok(Response::Array(replies))
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "hpersist x fields 1 a"           => [-2];
+    "hset x a 1 b 2"                  => 2;
+    "hexpire x 100 fields 1 a"        => [1];
+    "hpersist x fields 3 a b c"       => [1, -1, -2];
+    "httl x fields 1 a"               => [-1];
+}
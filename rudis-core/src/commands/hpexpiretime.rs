@@ -0,0 +1,26 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"hpexpiretime",
+    arity: -5,
+    flags: &[
+        b"readonly",
+        b"fast",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+pub fn run(db: &mut Database, cmd: Command) -> anyhow::Result<Response> {
+    super::httl::field_ttl(db, cmd, |at| at)
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "hset x a 1"                                => 1;
+    "hexpireat x 4102444800 fields 1 a"         => [1];
+    "hpexpiretime x fields 1 a"                 => [4102444800000];
+}
@@ -0,0 +1,28 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::expire::now_ms;
+use crate::{Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"hpttl",
+    arity: -5,
+    flags: &[
+        b"readonly",
+        b"fast",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+pub fn run(db: &mut Database, cmd: Command) -> anyhow::Result<Response> {
+    super::httl::field_ttl(db, cmd, |at| (at - now_ms()).max(0))
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "hpttl x fields 1 a"              => [-2];
+    "hset x a 1 b 2"                  => 2;
+    "hpexpire x 100000 fields 1 a"    => [1];
+    "hpttl x fields 2 b c"            => [-1, -2];
+}
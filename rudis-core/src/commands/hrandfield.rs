@@ -0,0 +1,65 @@
+use super::{random_indices, CommandInfo};
+use crate::command::Command;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"hrandfield",
+    arity: -2,
+    flags: &[
+        b"readonly",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+/// Random fields, distinct for a positive count and possibly repeated for a negative one.
+///
+/// HRANDFIELD key [count [WITHVALUES]]
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let (key, count) = cmd.parse_partial_args::<(ByteString, Option<i64>)>()?;
+    let with_values = count.is_some() && cmd.parse_option("withvalues");
+    cmd.ensure_empty().map_err(|_| anyhow::anyhow!("syntax error"))?;
+    let len = db.get_hash(&key)?.map_or(0, |h| h.len());
+    let indices = random_indices(db.rng(), len, count)?;
+    if len == 0 {
+        return Ok(count.map_or(Response::Nil, |_| Response::Array(vec![])));
+    }
+    let h = db.get_hash(&key)?.expect("hash exists");
+    let picked = indices.into_iter().filter_map(|i| h.get_index(i));
+    if count.is_none() {
+        return Ok(picked.map(|(k, _)| Response::BulkString(k.to_vec())).next().unwrap_or_default());
+    }
+    let replies = picked.flat_map(|(k, v)| std::iter::once(k).chain(with_values.then_some(v)));
+    Ok(Response::string_array(replies.map(|e| e.to_vec())))
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "hrandfield x"                => ();
+    "hrandfield x 2"              => [];
+    "hset x a 1 b 2"              => 2;
+    "hrandfield x 5"              => ["a", "b"] ignore_order;
+    "hrandfield x 2 withvalues"   => ["1", "2", "a", "b"] ignore_order;
+    "hset y a 1"                  => 1;
+    "hrandfield y"                => "a";
+    "hrandfield y -3 withvalues"  => ["a", "1", "a", "1", "a", "1"];
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Database, Response};
+    use crate::test_utils::exec;
+
+    #[test]
+    fn test_count_out_of_range() {
+        let mut db = Database::default();
+        assert_eq!(exec(&mut db, "hrandfield x -9223372036854775808").unwrap_err().to_string(), "value is out of range");
+        exec(&mut db, "hset x a 1").unwrap();
+        assert_eq!(exec(&mut db, "hrandfield x -9223372036854775808 withvalues").unwrap_err().to_string(), "value is out of range");
+        assert_eq!(exec(&mut db, "hrandfield x 4611686018427387904").unwrap_err().to_string(), "value is out of range");
+        assert_eq!(exec(&mut db, "hrandfield x -4611686018427387904").unwrap_err().to_string(), "value is out of range");
+        assert_eq!(exec(&mut db, "hrandfield x 4611686018427387903").unwrap(), Response::string_array([b"a".to_vec()]));
+        assert_eq!(exec(&mut db, "hrandfield x -4611686018427387903 withvalues").unwrap_err().to_string(), "value is out of range");
+    }
+}
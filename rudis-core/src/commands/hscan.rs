@@ -0,0 +1,86 @@
+use super::CommandInfo;
+use crate::acl::glob_matches;
+use crate::command::Command;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"hscan",
+    arity: -3,
+    flags: &[
+        b"readonly",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+/// HSCAN key cursor [MATCH pattern] [COUNT count] [NOVALUES]
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let (key, cursor) = cmd.parse_partial_args::<(ByteString, ByteString)>()?;
+    let cursor = std::str::from_utf8(&cursor).ok().and_then(|c| c.parse::<u64>().ok());
+    let cursor = cursor.ok_or_else(|| anyhow::anyhow!("invalid cursor"))?;
+    let (mut pattern, mut count, mut no_values) = (None, 10, false);
+    while cmd.has_more() {
+        if let Some(p) = cmd.parse_named_arg("match") {
+            pattern = Some(p);
+        } else if cmd.parse_option("count") {
+            count = cmd.parse_partial_args::<i64>()?;
+            anyhow::ensure!(count > 0, "syntax error");
+        } else if cmd.parse_option("novalues") {
+            no_values = true;
+        } else {
+            anyhow::bail!("syntax error");
+        }
+    }
+    let Some(h) = db.get_hash(&key)? else {
+        return Ok(Response::Array(vec![Response::BulkString(b"0".to_vec()), Response::Array(vec![])]));
+    };
+    let (next, pairs) = h.scan(cursor, count as usize);
+    // like Redis, MATCH filters the elements of the step, which may leave none
+    let pairs = pairs.into_iter().filter(|(k, _)| pattern.as_ref().is_none_or(|p| glob_matches(p, k)));
+    let replies = pairs.flat_map(|(k, v)| std::iter::once(k).chain((!no_values).then_some(v)));
+    Ok(Response::Array(vec![
+        Response::BulkString(next.to_string().into_bytes()),
+        Response::string_array(replies.map(|e| e.to_vec())),
+    ]))
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "hscan x 0"                          => [Response::BulkString(b"0".to_vec()), Response::Array(vec![])];
+    "hset x a 1 b 2 ab 3"                => 3;
+    "hscan x 0 match a? novalues"        => [Response::BulkString(b"0".to_vec()), Response::string_array([b"ab".to_vec()])];
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Database, Response};
+    use crate::test_utils::exec;
+
+    #[test]
+    fn test_cursor() {
+        let mut db = Database::default();
+        // listpacks are returned whole
+        exec(&mut db, "config set hash-max-listpack-entries 1").unwrap();
+        for i in 0..25 {
+            exec(&mut db, &format!("hset x f{i} {i}")).unwrap();
+        }
+        let (mut cursor, mut seen) = ("0".to_string(), Vec::new());
+        loop {
+            let Response::Array(res) = exec(&mut db, &format!("hscan x {cursor} count 4")).unwrap() else { panic!() };
+            let [Response::BulkString(next), Response::Array(pairs)] = res.as_slice() else { panic!() };
+            assert!(pairs.len() <= 8);
+            seen.extend(pairs.chunks(2).map(|p| p[0].clone()));
+            // fields added during the iteration do not disturb the others
+            exec(&mut db, &format!("hset x new{cursor} 1")).unwrap();
+            cursor = String::from_utf8(next.clone()).unwrap();
+            if cursor == "0" {
+                break;
+            }
+        }
+        for i in 0..25 {
+            assert!(seen.contains(&Response::BulkString(format!("f{i}").into_bytes())));
+        }
+        assert!(exec(&mut db, "hscan x abc").is_err());
+    }
+}
@@ -18,12 +18,10 @@ pub static INFO: CommandInfo = CommandInfo {
 pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
     let (key, fields) = cmd.parse_args::<(ByteString, Vec<(ByteString, ByteString)>)>()?;
     anyhow::ensure!(!fields.is_empty(), "expected HSET key field value [field value ..]");
-    let len = fields.len();
-    let h = db.get_or_insert_hash(key)?;
-    for (k, v) in fields {
-        h.insert(k, v);
-    }
-    Ok(Response::Number(len as _))
+    let h = db.get_or_insert_hash(key.clone())?;
+    let added = fields.into_iter().filter(|(k, v)| h.insert(k.clone(), v.clone()).is_none()).count();
+    db.notify('h', "hset", &key);
+    Ok(Response::Number(added as _))
 }
 
 #[cfg(test)]
@@ -31,4 +29,6 @@ crate::command_test! {
     "hset x a b x y" => 2;
     "hget x a" => "b";
     "hget x x" => "y";
+    "hset x a c z z" => 1;
+    "hget x a" => "c";
 }
@@ -0,0 +1,98 @@
+use super::{parse_expire_option, CommandInfo};
+use crate::command::Command;
+use crate::expire::now_ms;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"hsetex",
+    arity: -6,
+    flags: &[
+        b"write",
+        b"denyoom",
+        b"fast",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+/// Sets fields and their expire, replying 1 or 0 when FNX or FXX prevented setting them: FNX
+/// only sets fields when none exist, FXX when all exist. Fields lose their expire unless
+/// KEEPTTL is given, and like HEXPIRE, a time in the past deletes them.
+///
+/// HSETEX key [FNX | FXX] [EX seconds | PX milliseconds | EXAT unix-time-seconds | PXAT unix-time-milliseconds | KEEPTTL]
+///     FIELDS numfields field value [field value ...]
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let key = cmd.parse_partial_args::<ByteString>()?;
+    let (mut fnx, mut fxx, mut keep_ttl, mut expiry) = (false, false, false, None);
+    loop {
+        if cmd.parse_option("fnx") {
+            fnx = true;
+        } else if cmd.parse_option("fxx") {
+            fxx = true;
+        } else if cmd.parse_option("keepttl") {
+            keep_ttl = true;
+        } else if let Some(at) = parse_expire_option(&mut cmd, "hsetex")? {
+            anyhow::ensure!(expiry.is_none(), "syntax error");
+            expiry = Some(at);
+        } else {
+            break;
+        }
+    }
+    anyhow::ensure!(!(fnx && fxx || keep_ttl && expiry.is_some()), "syntax error");
+    let pairs = super::hexpire::parse_fields(&mut cmd, 1)?;
+    let fields = pairs.chunks(2).map(|p| (p[0].clone(), p[1].clone())).collect::<Vec<_>>();
+    let delete = expiry.is_some_and(|at| at <= now_ms()) && !db.is_master_client() && db.master().is_none();
+    let allowed = match db.get_hash(&key)? {
+        Some(h) if fnx => fields.iter().all(|(f, _)| !h.contains_key(f)),
+        Some(h) if fxx => fields.iter().all(|(f, _)| h.contains_key(f)),
+        None if fxx => false,
+        _ => true,
+    };
+    if !allowed {
+        return Ok(Response::Number(0));
+    }
+    let h = db.get_or_insert_hash(key.clone())?;
+    for (field, value) in fields.iter().cloned() {
+        match expiry {
+            _ if delete => {
+                h.remove(&field);
+            }
+            Some(at) => {
+                h.insert(field.clone(), value);
+                h.set_expiry(&field, at);
+            }
+            None if keep_ttl => {
+                h.insert_keep_ttl(field, value);
+            }
+            None => {
+                h.insert(field, value);
+            }
+        }
+    }
+    db.notify('h', "hset", &key);
+    if delete {
+        db.notify('h', "hexpired", &key);
+        db.propagate_as([b"hdel".to_vec(), key].into_iter().chain(fields.into_iter().map(|(f, _)| f)).collect());
+    } else if let Some(at) = expiry {
+        db.track_hash_expires(&key);
+        db.notify('h', "hexpire", &key);
+        let args = [b"hsetex".to_vec(), key, b"pxat".to_vec(), at.to_string().into_bytes(), b"fields".to_vec(), fields.len().to_string().into_bytes()];
+        db.propagate_as(args.into_iter().chain(pairs).collect());
+    }
+    Ok(Response::Number(1))
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "hsetex x fxx fields 1 a 1"             => 0;
+    "hsetex x fnx ex 100 fields 2 a 1 b 2"  => 1;
+    "httl x fields 2 a b"                   => [100, 100];
+    "hsetex x fnx fields 2 a 3 c 4"         => 0;
+    "hsetex x fxx keepttl fields 1 a 3"     => 1;
+    "httl x fields 1 a"                     => [100];
+    "hsetex x fields 1 a 4"                 => 1;
+    "httl x fields 2 a b"                   => [-1, 100];
+    "hsetex x pxat 1 fields 1 b 5"          => 1;
+    "hgetall x"                             => ["a", "4"];
+}
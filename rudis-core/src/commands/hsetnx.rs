@@ -0,0 +1,34 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"hsetnx",
+    arity: 4,
+    flags: &[
+        b"write",
+        b"denyoom",
+        b"fast",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let (key, field, value) = cmd.parse_args::<(ByteString, ByteString, ByteString)>()?;
+    let h = db.get_or_insert_hash(key.clone())?;
+    if h.contains_key(&field) {
+        return Ok(Response::Number(0));
+    }
+    h.insert(field, value);
+    db.notify('h', "hset", &key);
+    Ok(Response::Number(1))
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "hsetnx x a 1" => 1;
+    "hsetnx x a 2" => 0;
+    "hget x a"     => "1";
+}
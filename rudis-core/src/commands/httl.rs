@@ -0,0 +1,48 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::expire::now_ms;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"httl",
+    arity: -5,
+    flags: &[
+        b"readonly",
+        b"fast",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+/// HTTL and its variants, replying per field with `ttl` of the unix time in milliseconds at
+/// which it expires, -2 if it does not exist and -1 if it has no expire.
+///
+/// HTTL key FIELDS numfields field [field ...]
+pub fn field_ttl(db: &mut Database, mut cmd: Command, ttl: fn(i64) -> i64) -> anyhow::Result<Response> {
+    let key = cmd.parse_partial_args::<ByteString>()?;
+    let fields = super::hexpire::parse_fields(&mut cmd, 0)?;
+    let Some(h) = db.get_hash(&key)? else {
+        return Ok(Response::Array(vec![Response::Number(-2); fields.len()]));
+    };
+    let replies = fields.iter().map(|f| match h.expiry(f) {
+        _ if !h.contains_key(f) => Response::Number(-2),
+        Some(at) => Response::Number(ttl(at)),
+        None => Response::Number(-1),
+    });
+    Ok(Response::Array(replies.collect()))
+}
+
+pub fn run(db: &mut Database, cmd: Command) -> anyhow::Result<Response> {
+    field_ttl(db, cmd, |at| ((at - now_ms()).max(0) + 500) / 1000)
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "httl x fields 1 a"               => [-2];
+    "hset x a 1 b 2"                  => 2;
+    "hexpire x 100 fields 1 a"        => [1];
+    "httl x fields 3 a b c"           => [100, -1, -2];
+    "hset x a 3"                      => 0;
+    "httl x fields 1 a"               => [-1];
+}
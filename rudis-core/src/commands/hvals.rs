@@ -16,7 +16,7 @@ pub static INFO: CommandInfo = CommandInfo {
 pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
     let key = cmd.parse_args::<ByteString>()?;
     let mut keys = db.get_hash(&key)?
-        .map(|h| h.values().cloned().collect::<Vec<_>>())
+        .map(|h| h.values().map(|v| v.to_vec()).collect::<Vec<_>>())
         .unwrap_or_default();
     keys.sort();
     Ok(Response::string_array(keys))
@@ -27,4 +27,24 @@ crate::command_test! {
     "set x 234"     => "OK";
     "incrby x 1000" => 1234;
     "get x"         => "1234";
+    "set x -15"      => "OK";
+    "strlen x"       => 3;
+    "getrange x 0 0" => "-";
+    "setbit x 6 1"   => 0;
+    "get x"          => "/15";
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Database;
+    use crate::test_utils::exec;
+
+    #[test]
+    fn test_overflow() {
+        let mut db = Database::default();
+        exec(&mut db, "set x 9223372036854775807").unwrap();
+        assert_eq!(exec(&mut db, "incrby x 1").unwrap_err().to_string(), "increment or decrement would overflow");
+        exec(&mut db, "set x -9223372036854775808").unwrap();
+        assert_eq!(exec(&mut db, "incrby x -1").unwrap_err().to_string(), "increment or decrement would overflow");
+    }
 }
@@ -19,11 +19,12 @@ pub static INFO: CommandInfo = CommandInfo {
 pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
     let (key, step) = cmd.parse_args::<(ByteString, f64)>()?;
     let val = step + match db.get_str(&key)? {
-        Some(v) => parse_from_bytes(v)?,
+        Some(v) => parse_from_bytes(&v.as_bytes())?,
         None => 0.0,
     };
     let val = val.to_string().into_bytes();
-    db.set(key, Value::String(val.clone()));
+    db.set_keep_ttl(key.clone(), Value::String(val.clone().into()));
+    db.notify('$', "incrbyfloat", &key);
     Ok(Response::BulkString(val))
 }
 
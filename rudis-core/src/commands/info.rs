@@ -0,0 +1,107 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{ByteString, Database, LinkState, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"info",
+    arity: -1,
+    flags: &[
+        b"loading",
+        b"stale",
+    ],
+    first_key: 0,
+    last_key: 0,
+    step: 0,
+};
+
+type Section = (&'static str, Vec<(String, String)>);
+
+fn fields(fields: Vec<(&str, String)>) -> Vec<(String, String)> {
+    fields.into_iter().map(|(k, v)| (k.to_string(), v)).collect()
+}
+
+fn sections(db: &mut Database) -> Vec<Section> {
+    let config = db.config();
+    let server = vec![
+        ("redis_version", env!("CARGO_PKG_VERSION").to_string()),
+        ("redis_mode", if db.is_cluster_enabled() { "cluster" } else { "standalone" }.to_string()),
+        ("os", std::env::consts::OS.to_string()),
+        ("arch_bits", (usize::BITS).to_string()),
+        ("process_id", std::process::id().to_string()),
+        ("tcp_port", config.get("port").to_string()),
+        ("config_file", config.file().map(|f| f.display().to_string()).unwrap_or_default()),
+    ];
+    let clients = vec![
+        ("connected_clients", db.client_count().to_string()),
+    ];
+    let stats = db.stats();
+    let stats = vec![
+        ("total_connections_received", stats.total_connections_received.to_string()),
+        ("total_commands_processed", stats.total_commands_processed.to_string()),
+        ("rejected_connections", stats.rejected_connections.to_string()),
+        ("expired_keys", stats.expired_keys.to_string()),
+        ("evicted_keys", stats.evicted_keys.to_string()),
+    ];
+    let memory = vec![
+        ("used_memory", db.used_memory().to_string()),
+        ("maxmemory", db.config().get("maxmemory").to_string()),
+        ("maxmemory_policy", db.config().get("maxmemory-policy").to_string()),
+    ];
+    let replication = db.replication();
+    let mut repl = fields(vec![("role", if db.master().is_some() { "slave" } else { "master" }.to_string())]);
+    if let Some((host, port)) = db.master() {
+        let up = replication.link_state() == LinkState::Connected;
+        repl.extend(fields(vec![
+            ("master_host", host),
+            ("master_port", port.to_string()),
+            ("master_link_status", if up { "up" } else { "down" }.to_string()),
+            ("slave_repl_offset", replication.offset().to_string()),
+            ("slave_read_only", (db.config().get_bool("replica-read-only") as u8).to_string()),
+        ]));
+    }
+    repl.push(("connected_slaves".to_string(), replication.replicas().count().to_string()));
+    for (i, r) in replication.replicas().enumerate() {
+        let lag = r.last_ack.elapsed().as_secs();
+        repl.push((format!("slave{i}"), format!("ip={},port={},state=online,offset={},lag={lag}", r.ip, r.port, r.ack_offset)));
+    }
+    repl.extend(fields(vec![
+        ("master_replid", replication.replid().to_string()),
+        ("master_repl_offset", replication.offset().to_string()),
+        ("repl_backlog_active", "1".to_string()),
+        ("repl_backlog_size", db.config().get("repl-backlog-size").to_string()),
+        ("repl_backlog_first_byte_offset", replication.backlog_first_offset().to_string()),
+        ("repl_backlog_histlen", replication.backlog_len().to_string()),
+    ]));
+    vec![
+        ("Server", fields(server)),
+        ("Clients", fields(clients)),
+        ("Memory", fields(memory)),
+        ("Replication", repl),
+        ("Stats", fields(stats)),
+    ]
+}
+
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let wanted = cmd.parse_args::<Vec<ByteString>>()?;
+    let include = |name: &str| wanted.is_empty() || wanted.iter().any(|w| {
+        w.eq_ignore_ascii_case(name.as_bytes()) || w.eq_ignore_ascii_case(b"all") || w.eq_ignore_ascii_case(b"everything")
+    });
+    let mut res = String::new();
+    for (name, fields) in sections(db).into_iter().filter(|(name, _)| include(name)) {
+        if !res.is_empty() {
+            res += "\r\n";
+        }
+        res += &format!("# {name}\r\n");
+        for (k, v) in fields {
+            res += &format!("{k}:{v}\r\n");
+        }
+    }
+    Ok(Response::BulkString(res.into_bytes()))
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "info stats"  => "# Stats\r\ntotal_connections_received:0\r\ntotal_commands_processed:1\r\nrejected_connections:0\r\nexpired_keys:0\r\nevicted_keys:0\r\n";
+    "info STATS"  => "# Stats\r\ntotal_connections_received:0\r\ntotal_commands_processed:2\r\nrejected_connections:0\r\nexpired_keys:0\r\nevicted_keys:0\r\n";
+    "info nope"   => "";
+}
@@ -16,8 +16,8 @@ pub static INFO: CommandInfo = CommandInfo {
 
 pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
     let (key, count) = cmd.parse_args::<(ByteString, Option<i64>)>()?;
-    let Some(a) = db.get_array(&key)? else { return Ok(Response::Nil) };
-    Ok(match count {
+    let Some(a) = db.get_array_mut(&key)? else { return Ok(Response::Nil) };
+    let res = match count {
         Some(n) if n < 0 => anyhow::bail!("value is out of range, must be positive"),
         Some(n) => {
             let n = a.len().min(n as _);
@@ -26,7 +26,11 @@ pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
             Response::string_array(x)
         }
         None => if a.is_empty() { Response::Nil } else { Response::SimpleString(a.remove(0)) },
-    })
+    };
+    if res != Response::Nil && res != Response::Array(Vec::new()) {
+        db.notify('l', "lpop", &key);
+    }
+    Ok(res)
 }
 
 #[cfg(test)]
@@ -37,4 +41,9 @@ crate::command_test! {
     "lrange x 0 -1"     => ["2", "3", "4", "5"];
     "lpop x 3"          => ["2", "3", "4"];
     "lrange x 0 -1"     => ["5"];
+    "lpop x"            => "5";
+    "exists x"          => 0;
+    "rpush x 1"         => 1;
+    "lpop x 2"          => ["1"];
+    "type x"            => "none";
 }
@@ -18,11 +18,13 @@ pub static INFO: CommandInfo = CommandInfo {
 pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
     let (key, elements) = cmd.parse_args::<(ByteString, Vec<ByteString>)>()?;
     anyhow::ensure!(!elements.is_empty(), "expected LPUSH key element [element ...]");
-    let a = db.get_or_insert_array(key)?;
+    let a = db.get_or_insert_array(key.clone())?;
     for e in elements {
         a.insert(0, e);
     }
-    Ok(Response::Number(a.len() as _))
+    let len = a.len();
+    db.notify('l', "lpush", &key);
+    Ok(Response::Number(len as _))
 }
 
 #[cfg(test)]
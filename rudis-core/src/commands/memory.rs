@@ -0,0 +1,102 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"memory",
+    arity: -2,
+    flags: &[
+        b"readonly",
+    ],
+    first_key: 2,
+    last_key: 2,
+    step: 1,
+};
+
+/// Elements sampled by MEMORY USAGE when estimating the size of aggregates, like Redis does.
+const DEFAULT_SAMPLES: usize = 5;
+
+/// Below this many bytes, MEMORY DOCTOR has nothing meaningful to say.
+const DOCTOR_MIN_MEMORY: usize = 5 << 20;
+
+fn doctor(db: &Database) -> String {
+    let used = db.used_memory();
+    if used < DOCTOR_MIN_MEMORY {
+        return "Hi Sam, this instance is empty or is using very little memory, my issues detector can't be used in these conditions. Please, leave for your mission on Earth and fill it with some data. The new Sam and I will be back to our programming as soon as I finished rebooting.".to_string();
+    }
+    let maxmemory = db.memory_config().maxmemory;
+    if maxmemory != 0 && used > maxmemory / 10 * 9 {
+        return format!("Sam, I detected a few issues in this instance memory implementation:\n\n * High memory usage: {used} bytes are used out of the {maxmemory} bytes of maxmemory. Keys will soon be evicted or writes rejected depending on the maxmemory-policy.\n\nI'm here to keep you safe, Sam. I want to help you.");
+    }
+    "Hi Sam, I can't find any memory issue in your instance. I can only account for what occurs on this base.".to_string()
+}
+
+/// MEMORY USAGE key [SAMPLES count] | STATS | DOCTOR
+///
+/// Memory is the approximation used for maxmemory, see `Value::memory_usage`.
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let mut subcommand = cmd.parse_partial_args::<ByteString>()?;
+    subcommand.make_ascii_lowercase();
+    let res = match subcommand.as_slice() {
+        b"usage" => {
+            let key = cmd.parse_partial_args::<ByteString>()?;
+            let samples = match cmd.parse_option("samples") {
+                true => cmd.parse_partial_args::<i64>()?,
+                false => DEFAULT_SAMPLES as _,
+            };
+            anyhow::ensure!(samples >= 0 && !cmd.has_more(), "syntax error");
+            // zero samples all elements
+            let samples = if samples == 0 { usize::MAX } else { samples as usize };
+            match db.peek(&key) {
+                Some((value, _)) => Response::Number((key.len() + value.memory_usage(samples)) as _),
+                None => Response::Nil,
+            }
+        }
+        b"stats" => {
+            cmd.ensure_empty()?;
+            let keys = db.state.len();
+            let used = db.used_memory();
+            let stats = [
+                ("total.allocated", used),
+                ("keys.count", keys),
+                ("expires.count", db.expires.len()),
+                ("keys.bytes-per-key", used.checked_div(keys).unwrap_or(0)),
+                ("dataset.bytes", used),
+            ];
+            let stats = stats.into_iter()
+                .flat_map(|(name, n)| [Response::BulkString(name.as_bytes().to_vec()), Response::Number(n as _)]);
+            Response::Array(stats.collect())
+        }
+        b"doctor" => {
+            cmd.ensure_empty()?;
+            Response::BulkString(doctor(db).into_bytes())
+        }
+        _ => anyhow::bail!("unknown subcommand '{}'", String::from_utf8_lossy(&subcommand)),
+    };
+    Ok(res)
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "memory usage x"                => ();
+    "set x abc"                     => "OK";
+    "memory usage x"                => 20;
+    "rpush l a b c"                 => 3;
+    "memory usage l samples 0"      => 68;
+    "memory stats"                  => ["total.allocated", 88, "keys.count", 2, "expires.count", 0, "keys.bytes-per-key", 44, "dataset.bytes", 88];
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Database, Response};
+    use crate::test_utils::exec;
+
+    #[test]
+    fn test_doctor() {
+        let mut db = Database::default();
+        let Response::BulkString(report) = exec(&mut db, "memory doctor").unwrap() else { panic!() };
+        assert!(String::from_utf8(report).unwrap().contains("empty or is using very little memory"));
+        assert_eq!(exec(&mut db, "memory usage x samples -1").unwrap_err().to_string(), "syntax error");
+        assert_eq!(exec(&mut db, "memory usage x foo").unwrap_err().to_string(), "syntax error");
+    }
+}
@@ -18,7 +18,7 @@ pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
     let keys = cmd.parse_args::<Vec<ByteString>>()?;
     let mut values = Vec::new();
     for k in keys {
-        let res = db.get_str(&k)?.map(|s| Response::BulkString(s.clone())).unwrap_or_default();
+        let res = db.get_str(&k)?.map(|s| Response::BulkString(s.to_vec())).unwrap_or_default();
         values.push(res);
     }
     Ok(Response::Array(values))
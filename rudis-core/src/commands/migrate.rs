@@ -0,0 +1,154 @@
+use std::time::Duration;
+
+use super::{parse_from_bytes, CommandInfo};
+use crate::command::Command;
+use crate::expire::now_ms;
+use crate::rdb::dump_payload;
+use crate::{ByteString, Database, Migration, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"migrate",
+    arity: -6,
+    flags: &[
+        b"write",
+        b"noscript",
+        b"movablekeys",
+    ],
+    first_key: 3,
+    last_key: 3,
+    step: 1,
+};
+
+/// The key argument, or the keys after KEYS when it is empty, skipping the AUTH passwords.
+pub fn keys(cmd: &Command) -> Vec<&[u8]> {
+    let Some(key) = cmd.arg(2) else { return Vec::new() };
+    let mut i = 5;
+    while let Some(arg) = cmd.arg(i) {
+        i += match arg.to_ascii_lowercase().as_slice() {
+            b"auth" => 2,
+            b"auth2" => 3,
+            b"keys" if key.is_empty() => return (i + 1..cmd.arg_count()).filter_map(|i| cmd.arg(i)).collect(),
+            _ => 1,
+        };
+    }
+    if key.is_empty() { Vec::new() } else { vec![key] }
+}
+
+/// MIGRATE host port key|"" destination-db timeout [COPY] [REPLACE] [AUTH password]
+/// [AUTH2 username password] [KEYS key ...]
+///
+/// The keys are sent by the server with RESTORE, the client is blocked until it is done and
+/// writes to the keys from other clients wait for it.
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let (host, port, key) = cmd.parse_partial_args::<(ByteString, ByteString, ByteString)>()?;
+    let (dest_db, timeout) = cmd.parse_partial_args::<(i64, i64)>()?;
+    let host = String::from_utf8(host).map_err(|_| anyhow::anyhow!("Invalid target host"))?;
+    let port = parse_from_bytes::<u16>(&port).map_err(|_| anyhow::anyhow!("Invalid target port"))?;
+    anyhow::ensure!(dest_db == 0, "DB index is out of range");
+    let (mut copy, mut replace, mut auth, mut keys) = (false, false, None, vec![key]);
+    while cmd.has_more() {
+        if cmd.parse_option("copy") {
+            copy = true;
+        } else if cmd.parse_option("replace") {
+            replace = true;
+        } else if cmd.parse_option("auth") {
+            auth = Some(vec![b"AUTH".to_vec(), cmd.parse_partial_args::<ByteString>()?]);
+        } else if cmd.parse_option("auth2") {
+            let (user, password) = cmd.parse_partial_args::<(ByteString, ByteString)>()?;
+            auth = Some(vec![b"AUTH".to_vec(), user, password]);
+        } else if cmd.parse_option("keys") {
+            anyhow::ensure!(keys[0].is_empty(), "When using MIGRATE KEYS option, the key argument must be set to the empty string");
+            keys = cmd.parse_args::<Vec<ByteString>>()?;
+        } else {
+            anyhow::bail!("syntax error");
+        }
+    }
+
+    let restore = if db.is_cluster_enabled() { b"RESTORE-ASKING".as_slice() } else { b"RESTORE".as_slice() };
+    let mut commands = Vec::from_iter(auth);
+    let mut migrated = Vec::new();
+    for key in keys {
+        let Some((value, _)) = db.peek(&key) else { continue };
+        let ttl = db.expiry(&key).map_or(0, |at| (at - now_ms()).max(1));
+        let mut restore = vec![restore.to_vec(), key.clone(), ttl.to_string().into_bytes(), dump_payload(value)];
+        if replace {
+            restore.push(b"REPLACE".to_vec());
+        }
+        commands.push(restore);
+        migrated.push(key);
+    }
+    if migrated.is_empty() {
+        return Ok(Response::SimpleString(b"NOKEY".to_vec()));
+    }
+    let timeout = Duration::from_millis(if timeout <= 0 { 1000 } else { timeout as _ });
+    let migration = Migration { client: db.client_id(), host, port, timeout, commands };
+    db.migrate(migration, migrated, copy);
+    Ok(Response::NoReply)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Database, Response};
+    use crate::test_utils::exec;
+
+    #[test]
+    fn test_migrate() {
+        let mut db = Database::default();
+        db.accept_client(1, None, false).unwrap();
+        db.select_client(1);
+        assert_eq!(exec(&mut db, "migrate localhost 7000 x 0 100").unwrap(), Response::SimpleString(b"NOKEY".to_vec()));
+        assert!(exec(&mut db, "migrate localhost 7000 x 0 100 keys y").is_err());
+        exec(&mut db, "set x 1").unwrap();
+        exec(&mut db, "set y 2").unwrap();
+        assert_eq!(exec(&mut db, "migrate localhost 7000  0 100 replace auth pw keys x y z").unwrap(), Response::NoReply);
+        let migrations = db.take_migrations();
+        assert_eq!(migrations.len(), 1);
+        assert_eq!(migrations[0].commands.len(), 3);
+        assert_eq!(migrations[0].commands[0], vec![b"AUTH".to_vec(), b"pw".to_vec()]);
+        assert_eq!(migrations[0].commands[1][..3], [b"RESTORE".to_vec(), b"x".to_vec(), b"0".to_vec()]);
+        assert_eq!(migrations[0].commands[2][4], b"REPLACE");
+
+        // commands wait for the migration to finish
+        assert_eq!(exec(&mut db, "get x").unwrap(), Response::NoReply);
+        db.migrated(1, Ok(()));
+        assert_eq!(db.take_outbox().into_iter().map(|(_, r)| r.unwrap()).collect::<Vec<_>>(), vec![
+            Response::SimpleString(b"OK".to_vec()),
+            Response::Nil,
+        ]);
+        assert!(!db.contains(b"x") && !db.contains(b"y"));
+
+        // other clients can read the keys being migrated, but their writes wait for the migration
+        exec(&mut db, "set x 1").unwrap();
+        exec(&mut db, "migrate localhost 7000 x 0 100").unwrap();
+        db.accept_client(2, None, false).unwrap();
+        db.select_client(2);
+        assert_eq!(exec(&mut db, "get x").unwrap(), Response::SimpleString(b"1".to_vec()));
+        assert_eq!(exec(&mut db, "set x 2").unwrap(), Response::NoReply);
+        assert_eq!(exec(&mut db, "get x").unwrap(), Response::NoReply);
+        db.accept_client(3, None, false).unwrap();
+        db.select_client(3);
+        assert_eq!(exec(&mut db, "set other 1").unwrap(), Response::SimpleString(b"OK".to_vec()));
+        db.migrated(1, Ok(()));
+        assert_eq!(db.take_outbox().into_iter().map(|(_, r)| r.unwrap()).collect::<Vec<_>>(), vec![
+            Response::SimpleString(b"OK".to_vec()),
+            Response::SimpleString(b"OK".to_vec()),
+            Response::SimpleString(b"2".to_vec()),
+        ]);
+        db.select_client(1);
+
+        exec(&mut db, "set x 1").unwrap();
+        exec(&mut db, "migrate localhost 7000 x 0 100 copy").unwrap();
+        db.migrated(1, Err(anyhow::anyhow!("IOERR error or timeout connecting to the client")));
+        assert!(db.take_outbox()[0].1.is_err());
+        assert!(db.contains(b"x"));
+    }
+    #[test]
+    fn test_migrate_keys() {
+        let mut db = Database::default();
+        exec(&mut db, "acl setuser alice on nopass ~a:* +@all").unwrap();
+        exec(&mut db, "auth alice x").unwrap();
+        assert_eq!(exec(&mut db, "migrate localhost 7000 b 0 100").unwrap_err().to_string(), "NOPERM No permissions to access a key");
+        assert_eq!(exec(&mut db, "migrate localhost 7000  0 100 auth keys keys a:1 b").unwrap_err().to_string(), "NOPERM No permissions to access a key");
+        assert_eq!(exec(&mut db, "migrate localhost 7000  0 100 auth2 keys keys keys a:1 a:2").unwrap(), Response::SimpleString(b"NOKEY".to_vec()));
+    }
+}
@@ -1,7 +1,12 @@
 use std::{collections::HashMap, str::FromStr};
+use std::ops::Range;
 use std::sync::LazyLock;
 
-use crate::{Command, Database, Response, ByteString, Value};
+use rand::Rng;
+use rand::seq::index;
+
+use crate::expire::now_ms;
+use crate::{Command, Database, Response, ByteString, Str, Value};
 
 pub fn parse_from_bytes<T: FromStr>(bytes: &[u8]) -> anyhow::Result<T> {
     std::str::from_utf8(bytes)
@@ -11,14 +16,92 @@ pub fn parse_from_bytes<T: FromStr>(bytes: &[u8]) -> anyhow::Result<T> {
 }
 
 pub fn incr_by(db: &mut Database, key: ByteString, step: i64) -> anyhow::Result<Response> {
-    let val = step + match db.get_str(&key)? {
-        Some(v) => parse_from_bytes(v)?,
-        None => 0,
+    let overflow = || anyhow::anyhow!("increment or decrement would overflow");
+    let val = match db.get_str_mut(&key)? {
+        Some(s) => {
+            let current = match s.as_int() {
+                Some(n) => n,
+                None => parse_from_bytes(&s.as_bytes())?,
+            };
+            let val = current.checked_add(step).ok_or_else(overflow)?;
+            // int encoded counters are updated in place without allocating
+            *s = Str::from(val);
+            val
+        }
+        None => {
+            db.set_keep_ttl(key.clone(), Value::String(Str::from(step)));
+            step
+        }
     };
-    db.set(key, Value::String(val.to_string().into_bytes()));
+    db.notify('$', "incrby", &key);
     Ok(Response::Number(val))
 }
 
+/// Converts a time in seconds (`unit` 1000) or milliseconds (`unit` 1), relative to now unless
+/// `absolute`, to the unix time in milliseconds expires are stored as.
+pub fn expire_at(time: i64, unit: i64, absolute: bool, command: &str) -> anyhow::Result<i64> {
+    let invalid = || anyhow::anyhow!("invalid expire time in '{command}' command");
+    let ms = time.checked_mul(unit).ok_or_else(invalid)?;
+    if absolute {
+        Ok(ms)
+    } else {
+        now_ms().checked_add(ms).ok_or_else(invalid)
+    }
+}
+
+/// Parses the EX, PX, EXAT or PXAT option of SET and GETEX when it comes next.
+pub fn parse_expire_option(cmd: &mut Command, command: &str) -> anyhow::Result<Option<i64>> {
+    let (unit, absolute) = if cmd.parse_option("ex") {
+        (1000, false)
+    } else if cmd.parse_option("px") {
+        (1, false)
+    } else if cmd.parse_option("exat") {
+        (1000, true)
+    } else if cmd.parse_option("pxat") {
+        (1, true)
+    } else {
+        return Ok(None);
+    };
+    let time = cmd.parse_partial_args::<i64>()?;
+    anyhow::ensure!(time > 0, "invalid expire time in '{command}' command");
+    expire_at(time, unit, absolute, command).map(Some)
+}
+
+/// The most repeated picks a reply can hold: unlike Redis, which streams them, replies are
+/// built in memory.
+const MAX_REPEATED_PICKS: u64 = 1 << 20;
+
+/// Random positions among `len` elements as picked by SRANDMEMBER and alike: distinct ones
+/// for a positive count, possibly repeated ones for a negative count, a single one without.
+/// Counts are limited to half the range of an i64 like Redis does, and to
+/// `MAX_REPEATED_PICKS` with repetitions.
+pub fn random_indices(rng: &mut impl Rng, len: usize, count: Option<i64>) -> anyhow::Result<Vec<usize>> {
+    anyhow::ensure!(count.is_none_or(|c| (-i64::MAX / 2..=i64::MAX / 2).contains(&c)), "value is out of range");
+    anyhow::ensure!(count.is_none_or(|c| c >= 0 || len == 0 || c.unsigned_abs() <= MAX_REPEATED_PICKS), "value is out of range");
+    let indices = match count {
+        _ if len == 0 => Vec::new(),
+        None => vec![rng.random_range(0..len)],
+        Some(count) if count >= 0 => index::sample(rng, len, len.min(count as usize)).into_vec(),
+        Some(count) => (0..count.unsigned_abs()).map(|_| rng.random_range(0..len)).collect(),
+    };
+    Ok(indices)
+}
+
+/// One step of a SCAN-like iteration over `len` indexed elements, from the last to the first:
+/// returns the next cursor, 0 once the iteration is complete, and the indices to visit. The
+/// cursor counts the positions left to visit, which stays valid while elements are added or
+/// removed as long as removing one only moves the last, already visited, in its place: those
+/// present during the whole iteration are returned at least once.
+pub fn scan_step(len: usize, cursor: u64, count: usize) -> (u64, Range<usize>) {
+    // 0 is the cursor of a complete iteration, and of its start
+    let end = match cursor {
+        0 => len,
+        cursor => len.min(usize::try_from(cursor).unwrap_or(usize::MAX)),
+    };
+    let start = end.saturating_sub(count);
+    (start as u64, start..end)
+}
+
 pub fn clamp_range(max: usize, start: i64, stop: i64) -> (usize, usize) {
     fn clamp_index(max: usize, i: i64) -> usize {
         let x = if i < 0 { max as i64 + i } else { i };
@@ -48,6 +131,58 @@ impl CommandInfo {
             Response::Number(self.step),
         ])
     }
+
+    /// The keys of an unparsed command, located with `first_key`, `last_key` and `step`, or
+    /// by the command itself for those with `movablekeys`.
+    pub fn keys<'a>(&self, cmd: &'a Command) -> Vec<&'a [u8]> {
+        if self.flags.contains(&b"movablekeys".as_slice()) {
+            return movable_keys(self.name, cmd).expect("commands with movablekeys locate their keys");
+        }
+        if self.first_key <= 0 {
+            return Vec::new();
+        }
+        let argc = cmd.arg_count() as i64 + 1;
+        let last_key = if self.last_key < 0 { argc + self.last_key } else { self.last_key };
+        (self.first_key..=last_key)
+            .step_by(self.step.max(1) as _)
+            .filter_map(|i| cmd.arg(i as usize - 1))
+            .collect()
+    }
+
+    /// The keys of an unparsed command with whether it may write them, like the key specs of
+    /// Redis: write commands storing a result only read its sources.
+    pub fn key_accesses<'a>(&self, cmd: &'a Command) -> Vec<(&'a [u8], bool)> {
+        let write = self.flags.contains(&b"write".as_slice());
+        let written = |i: usize| write && match self.name {
+            b"sunionstore" | b"sinterstore" | b"sdiffstore" | b"bitop" | b"pfmerge" | b"geosearchstore" => i == 0,
+            b"copy" => i == 1,
+            // the source, then the STORE destination if any
+            b"sort" | b"georadius" | b"georadiusbymember" => i > 0,
+            _ => true,
+        };
+        self.keys(cmd).into_iter().enumerate().map(|(i, key)| (key, written(i))).collect()
+    }
+}
+
+/// The keys of commands whose keys depend on their arguments, like the getkeys procs of Redis.
+fn movable_keys<'a>(name: &[u8], cmd: &'a Command) -> Option<Vec<&'a [u8]>> {
+    let keys = match name {
+        b"eval" | b"evalsha" | b"fcall" | b"fcall_ro" => numkeys_keys(cmd, 1),
+        b"sintercard" => numkeys_keys(cmd, 0),
+        b"sort" => sort::keys(cmd),
+        b"georadius" => geosearch::radius_keys(cmd, 5),
+        b"georadiusbymember" => geosearch::radius_keys(cmd, 4),
+        b"migrate" => migrate::keys(cmd),
+        _ => return None,
+    };
+    Some(keys)
+}
+
+/// The keys following the `numkeys` argument at `i`, none when it is invalid as the command
+/// then fails anyway.
+fn numkeys_keys(cmd: &Command, i: usize) -> Vec<&[u8]> {
+    let numkeys = cmd.arg(i).and_then(|n| parse_from_bytes::<usize>(n).ok()).unwrap_or(0);
+    (i + 1..i + 1 + numkeys.min(cmd.arg_count())).filter_map(|i| cmd.arg(i)).collect()
 }
 
 type CommandFn = fn(&mut Database, Command) -> anyhow::Result<Response>;
@@ -68,37 +203,86 @@ macro_rules! register_commands {
     };
 }
 register_commands! {
+    acl,
     append,
+    asking,
+    auth,
+    bitcount,
+    bitfield,
+    bitfield_ro,
+    bitop,
+    bitpos,
+    client,
+    cluster,
     command,
+    config,
     copy,
     dbsize,
+    debug,
     decr,
     decrby,
     del,
+    dump,
     echo,
+    eval,
+    evalsha,
     exists,
+    expire,
+    expireat,
+    expiretime,
+    fcall,
+    fcall_ro,
     flushall,
     flushdb,
+    function,
+    geoadd,
+    geodist,
+    geohash,
+    geopos,
+    georadius,
+    georadius_ro,
+    georadiusbymember,
+    georadiusbymember_ro,
+    geosearch,
+    geosearchstore,
     get,
     getbit,
     getdel,
+    getex,
     getrange,
     getset,
     hdel,
+    hello,
     hexists,
+    hexpire,
+    hexpireat,
+    hexpiretime,
     hget,
     hgetall,
+    hgetex,
     hincrby,
     hincrbyfloat,
     hkeys,
     hlen,
     hmget,
+    hmset,
+    hpersist,
+    hpexpire,
+    hpexpireat,
+    hpexpiretime,
+    hpttl,
+    hrandfield,
+    hscan,
     hset,
+    hsetex,
+    hsetnx,
     hstrlen,
+    httl,
     hvals,
     incr,
     incrby,
     incrbyfloat,
+    info,
     keys,
     lindex,
     llen,
@@ -106,36 +290,74 @@ register_commands! {
     lpop,
     lpush,
     lrange,
+    memory,
     mget,
+    migrate,
     mset,
+    msetnx,
+    object,
+    persist,
+    pexpire,
+    pexpireat,
+    pexpiretime,
+    pfadd,
+    pfcount,
+    pfdebug,
+    pfmerge,
+    pfselftest,
     ping,
+    psetex,
+    psubscribe,
+    psync,
+    pttl,
+    publish,
+    punsubscribe,
     quit,
+    r#type,
+    randomkey,
     rename,
     renamenx,
+    replconf,
+    replicaof,
+    restore,
+    restore_asking,
     role,
     rpop,
     rpush,
     sadd,
     scard,
+    script,
     sdiff,
     sdiffstore,
     set,
     setbit,
+    setex,
+    setnx,
+    setrange,
     sinter,
     sintercard,
     sinterstore,
     sismember,
     smembers,
+    smismember,
     smove,
+    sort,
+    sort_ro,
     spop,
+    srandmember,
     srem,
+    sscan,
     strlen,
+    subscribe,
     substr,
     sunion,
     sunionstore,
     time,
-    r#type,
+    touch,
+    ttl,
     unlink,
+    unsubscribe,
+    wait,
     zadd,
     zcard,
     zcount,
@@ -146,3 +368,31 @@ register_commands! {
     zrem,
     zscore,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(args: &str) -> Vec<String> {
+        let cmd = Command::new(args.split(' ').map(|w| w.as_bytes().to_vec()).collect()).unwrap();
+        let (_, info) = COMMANDS[cmd.cmd().as_bytes()];
+        info.keys(&cmd).into_iter().map(|k| String::from_utf8_lossy(k).into_owned()).collect()
+    }
+
+    #[test]
+    fn test_movable_keys() {
+        for (_, info) in COMMAND_LIST.iter().filter(|(_, info)| info.flags.contains(&b"movablekeys".as_slice())) {
+            assert!(movable_keys(info.name, &Command::new(vec![info.name.to_vec()]).unwrap()).is_some(), "{:?}", info.name);
+        }
+        assert_eq!(keys("eval return 2 a b c"), ["a", "b"]);
+        assert_eq!(keys("fcall f 5 a"), ["a"]);
+        assert_eq!(keys("evalsha sha -1 a"), Vec::<String>::new());
+        assert_eq!(keys("sintercard 2 a b"), ["a", "b"]);
+        assert_eq!(keys("sort a by store get store limit 0 1 store d"), ["a", "d"]);
+        assert_eq!(keys("sort a"), ["a"]);
+        assert_eq!(keys("georadius a 0 0 1 km count 1 storedist d"), ["a", "d"]);
+        assert_eq!(keys("georadiusbymember a m 1 km STORE d"), ["a", "d"]);
+        assert_eq!(keys("migrate h 1 a 0 0 replace auth keys"), ["a"]);
+        assert_eq!(keys("migrate h 1  0 0 auth2 u p keys a b"), ["a", "b"]);
+    }
+}
@@ -17,7 +17,8 @@ pub static INFO: CommandInfo = CommandInfo {
 pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
     let ops = cmd.parse_args::<Vec<(ByteString, ByteString)>>()?;
     for (key, value) in ops {
-        db.set(key, Value::String(value));
+        db.set(key.clone(), Value::String(value.into()));
+        db.notify('$', "set", &key);
     }
     Ok(Response::SimpleString(b"OK".to_vec()))
 }
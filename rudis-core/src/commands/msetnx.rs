@@ -0,0 +1,34 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"msetnx",
+    arity: -3,
+    flags: &[
+        b"write",
+        b"denyoom",
+    ],
+    first_key: 1,
+    last_key: -1,
+    step: 2,
+};
+
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let ops = cmd.parse_args::<Vec<(ByteString, ByteString)>>()?;
+    anyhow::ensure!(!ops.is_empty(), "wrong number of arguments for 'msetnx' command");
+    if ops.iter().any(|(key, _)| db.contains(key)) {
+        return Ok(Response::Number(0));
+    }
+    for (key, value) in ops {
+        super::set::set_string(db, key, value, None, false);
+    }
+    Ok(Response::Number(1))
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "msetnx a 1 b 2" => 1;
+    "msetnx b 3 c 4" => 0;
+    "mget a b c"     => ["1", "2", ()];
+}
@@ -0,0 +1,149 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"object",
+    arity: -2,
+    flags: &[
+        b"readonly",
+    ],
+    first_key: 2,
+    last_key: 2,
+    step: 1,
+};
+
+const HELP: &[&str] = &[
+    "OBJECT <subcommand> [<arg> [value] [opt] ...]. Subcommands are:",
+    "ENCODING <key>",
+    "    Return the kind of internal representation used in order to store the value",
+    "    associated with a <key>.",
+    "FREQ <key>",
+    "    Return the access frequency index of the <key>. The returned integer is",
+    "    proportional to the logarithm of the recent access frequency of the key.",
+    "IDLETIME <key>",
+    "    Return the idle time of the <key>, that is the approximated number of",
+    "    seconds elapsed since the last access to the key.",
+    "REFCOUNT <key>",
+    "    Return the number of references of the value associated with the specified",
+    "    <key>.",
+    "HELP",
+    "    Print this help.",
+];
+
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let mut subcommand = cmd.parse_partial_args::<ByteString>()?;
+    subcommand.make_ascii_lowercase();
+    if subcommand == b"help" {
+        cmd.ensure_empty()?;
+        return Ok(Response::Array(HELP.iter().map(|l| Response::SimpleString(l.as_bytes().to_vec())).collect()));
+    }
+    let known = [b"encoding".as_slice(), b"refcount", b"freq", b"idletime"].contains(&subcommand.as_slice());
+    anyhow::ensure!(known, "unknown subcommand '{}'", String::from_utf8_lossy(&subcommand));
+    let key = cmd.parse_args::<ByteString>()?;
+    let lfu = db.memory_config().policy.is_lfu();
+    let config = *db.memory_config();
+    let Some((value, access)) = db.peek(&key) else {
+        return Ok(Response::Nil);
+    };
+    let res = match subcommand.as_slice() {
+        b"encoding" => Response::BulkString(value.encoding().as_bytes().to_vec()),
+        // values are never shared between keys
+        b"refcount" => Response::Number(1),
+        b"freq" => {
+            anyhow::ensure!(lfu, "An LFU maxmemory policy is not selected, access frequency not tracked. Please note that when switching between policies at runtime LRU and LFU data will take some time to adjust.");
+            Response::Number(access.frequency(&config) as _)
+        }
+        b"idletime" => {
+            anyhow::ensure!(!lfu, "An LFU maxmemory policy is selected, idle time not tracked. Please note that when switching between policies at runtime LRU and LFU data will take some time to adjust.");
+            Response::Number(access.idle_time() as _)
+        }
+        _ => unreachable!(),
+    };
+    Ok(res)
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "object idletime x"                         => ();
+    "object refcount x"                         => ();
+    "set x 1"                                   => "OK";
+    "object idletime x"                         => 0;
+    "config set maxmemory-policy allkeys-lfu"   => "OK";
+    "object freq x"                             => 5;
+    "get x"                                     => "1";
+    "object freq x"                             => 6;
+    "hset h a 1"                                => 1;
+    "object encoding h"                         => "listpack";
+    "config set hash-max-listpack-entries 1"    => "OK";
+    "hset h b 2"                                => 1;
+    "object encoding h"                         => "hashtable";
+    "sadd s 1 2"                                => 2;
+    "object encoding s"                         => "intset";
+    "sadd s a"                                  => 1;
+    "object encoding s"                         => "listpack";
+    "zadd z 1 a"                                => 1;
+    "object encoding z"                         => "listpack";
+    "config set zset-max-listpack-value 1"      => "OK";
+    "zadd z 2 bb"                               => 1;
+    "object encoding z"                         => "skiplist";
+    "zrank z bb"                                => 1;
+    "object encoding x"                         => "int";
+    "incrby x 41"                               => 42;
+    "object encoding x"                         => "int";
+    "set y 042"                                 => "OK";
+    "object encoding y"                         => "embstr";
+    "append x 0"                                => 3;
+    "object encoding x"                         => "raw";
+    "incr x"                                    => 421;
+    "object encoding x"                         => "int";
+    "object refcount x"                         => 1;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Database, Response};
+    use crate::test_utils::exec;
+
+    #[test]
+    fn test_help() {
+        let mut db = Database::default();
+        let Response::Array(lines) = exec(&mut db, "object help").unwrap() else { panic!() };
+        assert_eq!(lines.len(), super::HELP.len());
+        assert!(exec(&mut db, "object encoding").is_err());
+        assert_eq!(exec(&mut db, "object foo x").unwrap_err().to_string(), "unknown subcommand 'foo'");
+    }
+
+    #[test]
+    fn test_eviction() {
+        let mut db = Database::default();
+        exec(&mut db, "set a 1").unwrap();
+        assert!(exec(&mut db, "object freq a").is_err());
+        exec(&mut db, "config set maxmemory 1").unwrap();
+        assert_eq!(exec(&mut db, "set b 1").unwrap_err().to_string(), "OOM command not allowed when used memory > 'maxmemory'.");
+        assert_eq!(exec(&mut db, "get a").unwrap(), Response::SimpleString(b"1".to_vec()));
+
+        exec(&mut db, "config set maxmemory 0 maxmemory-policy allkeys-lru maxmemory-samples 64").unwrap();
+        exec(&mut db, "set b 1").unwrap();
+        exec(&mut db, "rpush c 1 2 3").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        exec(&mut db, "get a").unwrap();
+        exec(&mut db, "lrange c 0 -1").unwrap();
+        exec(&mut db, "config set maxmemory 90").unwrap();
+        exec(&mut db, "set d 1").unwrap();
+        assert!(!db.contains(b"b"));
+        assert!(db.contains(b"a") && db.contains(b"c") && db.contains(b"d"));
+        assert!(db.used_memory() <= 110);
+
+        exec(&mut db, "config set maxmemory 0 maxmemory-policy volatile-lru").unwrap();
+        exec(&mut db, "set e 1 px 100000").unwrap();
+        exec(&mut db, "set f 1 px 100000").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        exec(&mut db, "get e").unwrap();
+        let maxmemory = db.used_memory() - 1;
+        exec(&mut db, &format!("config set maxmemory {maxmemory}")).unwrap();
+        exec(&mut db, "set g 1").unwrap();
+        assert!(!db.contains(b"f"));
+        assert!(db.contains(b"a") && db.contains(b"e") && db.contains(b"g"));
+    }
+}
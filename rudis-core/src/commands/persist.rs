@@ -0,0 +1,34 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"persist",
+    arity: 2,
+    flags: &[
+        b"write",
+        b"fast",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let key = cmd.parse_args::<ByteString>()?;
+    if !db.contains(&key) || !db.persist(&key) {
+        return Ok(Response::Number(0));
+    }
+    db.notify('g', "persist", &key);
+    Ok(Response::Number(1))
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "persist x"        => 0;
+    "set x a"          => "OK";
+    "persist x"        => 0;
+    "expire x 100"     => 1;
+    "persist x"        => 1;
+    "ttl x"            => -1;
+}
@@ -0,0 +1,27 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"pexpire",
+    arity: -3,
+    flags: &[
+        b"write",
+        b"fast",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+pub fn run(db: &mut Database, cmd: Command) -> anyhow::Result<Response> {
+    super::expire::expire(db, cmd, 1, false)
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "set x a"                => "OK";
+    "pexpire x 100000"       => 1;
+    "ttl x"                  => 100;
+    "pexpire y 100000"       => 0;
+}
@@ -0,0 +1,33 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"pexpiretime",
+    arity: 2,
+    flags: &[
+        b"readonly",
+        b"fast",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+/// The unix time at which the key expires, -2 for missing keys, -1 for keys without an expire.
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let key = cmd.parse_args::<ByteString>()?;
+    if !db.contains(&key) {
+        return Ok(Response::Number(-2));
+    }
+    Ok(Response::Number(db.expiry(&key).map_or(-1, |at| at)))
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "pexpiretime x"               => -2;
+    "set x a"                     => "OK";
+    "pexpiretime x"               => -1;
+    "pexpireat x 4102444800123"   => 1;
+    "pexpiretime x"               => 4102444800123;
+}
@@ -0,0 +1,47 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::hyperloglog;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"pfadd",
+    arity: -2,
+    flags: &[
+        b"write",
+        b"denyoom",
+        b"fast",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+/// PFADD key [element ...]
+///
+/// Returns 1 when the estimated cardinality may have changed, or the key was created.
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let (key, elements) = cmd.parse_args::<(ByteString, Vec<ByteString>)>()?;
+    let sparse_max = db.config().get_parsed("hll-sparse-max-bytes");
+    let created = !db.contains(&key);
+    let s = db.get_or_insert_str(key.clone())?.to_mut();
+    if created {
+        *s = hyperloglog::new();
+    }
+    let changed = hyperloglog::add(s, &elements, sparse_max)? || created;
+    if changed {
+        db.notify('$', "pfadd", &key);
+    }
+    Ok(Response::Number(changed as _))
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "pfadd h a b c d e f g" => 1;
+    "pfcount h"             => 7;
+    "pfadd h a b"           => 0;
+    "pfadd h z"             => 1;
+    "pfcount h"             => 8;
+    "pfadd e"               => 1;
+    "pfadd e"               => 0;
+    "pfcount e"             => 0;
+}
@@ -0,0 +1,113 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::hyperloglog;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"pfcount",
+    arity: -2,
+    flags: &[
+        b"readonly",
+    ],
+    first_key: 1,
+    last_key: -1,
+    step: 1,
+};
+
+/// PFCOUNT key [key ...]
+///
+/// The cardinality of the union of several HyperLogLogs is estimated by merging them on the
+/// fly, only the cardinality of a single one is cached.
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let keys = cmd.parse_args::<Vec<ByteString>>()?;
+    if let [key] = keys.as_slice() {
+        let count = match db.get_str_mut(key)? {
+            Some(s) => hyperloglog::count(s.to_mut())?,
+            None => 0,
+        };
+        return Ok(Response::Number(count as _));
+    }
+    let mut registers = vec![0; hyperloglog::REGISTERS];
+    for key in &keys {
+        if let Some(s) = db.get_str(key)? {
+            hyperloglog::merge(&mut registers, &s.as_bytes())?;
+        }
+    }
+    Ok(Response::Number(hyperloglog::estimate(&registers) as _))
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "pfcount a"       => 0;
+    "pfadd a 1 2 3 4" => 1;
+    "pfadd b 3 4 5 6" => 1;
+    "pfcount a b c"   => 6;
+    "pfcount a"       => 4;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Database, Response};
+    use crate::test_utils::{exec, exec_args};
+
+    fn pfadd(db: &mut Database, key: &str, elements: impl Iterator<Item = String>) {
+        exec(db, &format!("pfadd {key} {}", elements.collect::<Vec<_>>().join(" "))).unwrap();
+    }
+
+    fn count(db: &mut Database, cmd: &str) -> i64 {
+        match exec(db, cmd).unwrap() {
+            Response::Number(n) => n,
+            res => panic!("unexpected reply {res:?}"),
+        }
+    }
+
+    #[test]
+    fn test_error_bounds() {
+        let mut db = Database::default();
+        // the standard error is 0.81%, allow 5 of them for both encodings
+        for batch in 0..100 {
+            pfadd(&mut db, "h", (batch * 1000..(batch + 1) * 1000).map(|i| format!("element:{i}")));
+            let (card, expected) = (count(&mut db, "pfcount h"), (batch + 1) * 1000);
+            let error = (card - expected).abs() as f64 / expected as f64;
+            assert!(error < 0.0405, "{card} for {expected} elements");
+        }
+        assert_eq!(exec(&mut db, "pfdebug encoding h").unwrap(), Response::SimpleString(b"dense".to_vec()));
+    }
+
+    #[test]
+    fn test_union() {
+        let mut db = Database::default();
+        pfadd(&mut db, "a", (0..20000).map(|i| i.to_string()));
+        pfadd(&mut db, "b", (10000..30000).map(|i| i.to_string()));
+        let card = count(&mut db, "pfcount a b");
+        assert!((card - 30000).abs() < 30000 * 4 / 100, "{card}");
+        exec(&mut db, "pfmerge c a b").unwrap();
+        assert_eq!(count(&mut db, "pfcount c"), card);
+    }
+
+    #[test]
+    fn test_string_round_trip() {
+        let mut db = Database::default();
+        pfadd(&mut db, "h", (0..100).map(|i| i.to_string()));
+        let Response::SimpleString(value) = exec(&mut db, "get h").unwrap() else { panic!() };
+        assert!(value.starts_with(b"HYLL"));
+        exec_args(&mut db, &[b"set", b"copy", &value]).unwrap();
+        assert_eq!(count(&mut db, "pfcount copy"), count(&mut db, "pfcount h"));
+        // a stale cache is recomputed
+        let Response::SimpleString(mut value) = exec(&mut db, "get h").unwrap() else { panic!() };
+        value[15] |= 0x80;
+        exec_args(&mut db, &[b"set", b"stale", &value]).unwrap();
+        assert_eq!(count(&mut db, "pfcount stale"), count(&mut db, "pfcount h"));
+    }
+
+    #[test]
+    fn test_invalid() {
+        let mut db = Database::default();
+        exec(&mut db, "set s foo").unwrap();
+        let err = exec(&mut db, "pfcount s").unwrap_err().to_string();
+        assert_eq!(err, "WRONGTYPE Key is not a valid HyperLogLog string value.");
+        assert_eq!(exec(&mut db, "pfadd s a").unwrap_err().to_string(), err);
+        exec(&mut db, "set s HYLL\x01\0\0\0\0\0\0\0\0\0\0\0\x7f").unwrap();
+        assert_eq!(exec(&mut db, "pfcount s a").unwrap_err().to_string(), "INVALIDOBJ Corrupted HLL object detected");
+    }
+}
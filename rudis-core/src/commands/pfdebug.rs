@@ -0,0 +1,51 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::hyperloglog;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"pfdebug",
+    arity: 3,
+    flags: &[
+        b"write",
+        b"denyoom",
+        b"admin",
+    ],
+    first_key: 2,
+    last_key: 2,
+    step: 1,
+};
+
+/// PFDEBUG GETREG | DECODE | ENCODING | TODENSE key
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let (subcommand, key) = cmd.parse_args::<(ByteString, ByteString)>()?;
+    let s = db.get_str_mut(&key)?.ok_or_else(|| anyhow::anyhow!("The specified key does not exist"))?.to_mut();
+    let res = match subcommand.to_ascii_lowercase().as_slice() {
+        b"getreg" => {
+            hyperloglog::to_dense(s)?;
+            let registers = hyperloglog::registers(s)?;
+            Response::Array(registers.into_iter().map(|r| Response::Number(r as _)).collect())
+        }
+        b"decode" => Response::SimpleString(hyperloglog::decode_sparse(s)?.into_bytes()),
+        b"encoding" => {
+            let encoding = if hyperloglog::is_sparse(s)? { "sparse" } else { "dense" };
+            Response::SimpleString(encoding.as_bytes().to_vec())
+        }
+        b"todense" => Response::Number(hyperloglog::to_dense(s)? as _),
+        _ => anyhow::bail!("Unknown PFDEBUG subcommand '{}'", String::from_utf8_lossy(&subcommand)),
+    };
+    Ok(res)
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "pfadd h"            => 1;
+    "pfdebug decode h"   => "Z:16384";
+    "pfadd h a"          => 1;
+    "pfdebug decode h"   => "Z:12711 v:2,1 Z:3672";
+    "pfdebug encoding h" => "sparse";
+    "pfdebug todense h"  => 1;
+    "pfdebug todense h"  => 0;
+    "pfdebug encoding h" => "dense";
+    "pfcount h"          => 1;
+}
@@ -0,0 +1,59 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::hyperloglog;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"pfmerge",
+    arity: -2,
+    flags: &[
+        b"write",
+        b"denyoom",
+    ],
+    first_key: 1,
+    last_key: -1,
+    step: 1,
+};
+
+/// PFMERGE destkey [sourcekey ...]
+///
+/// The destination is part of the union when it exists, and ends up dense if any of the
+/// merged HyperLogLogs is.
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let (dest, sources) = cmd.parse_args::<(ByteString, Vec<ByteString>)>()?;
+    let mut registers = vec![0; hyperloglog::REGISTERS];
+    let mut dense = false;
+    for key in std::iter::once(&dest).chain(&sources) {
+        if let Some(s) = db.get_str(key)?.map(|s| s.as_bytes()) {
+            dense |= !hyperloglog::is_sparse(&s)?;
+            hyperloglog::merge(&mut registers, &s)?;
+        }
+    }
+    let sparse_max = db.config().get_parsed("hll-sparse-max-bytes");
+    let created = !db.contains(&dest);
+    let s = db.get_or_insert_str(dest.clone())?.to_mut();
+    if created {
+        *s = hyperloglog::new();
+    }
+    if dense {
+        hyperloglog::to_dense(s)?;
+    }
+    hyperloglog::store(s, &registers, sparse_max)?;
+    db.notify('$', "pfadd", &dest);
+    Ok(Response::SimpleString(b"OK".to_vec()))
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "pfadd a 1 2 3"      => 1;
+    "pfadd b 3 4 5"      => 1;
+    "pfmerge c a b"      => "OK";
+    "pfcount c"          => 5;
+    "pfdebug encoding c" => "sparse";
+    "pfdebug todense b"  => 1;
+    "pfmerge a b"        => "OK";
+    "pfcount a"          => 5;
+    "pfdebug encoding a" => "dense";
+    "pfmerge d"          => "OK";
+    "pfcount d"          => 0;
+}
@@ -0,0 +1,21 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::hyperloglog;
+use crate::{Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"pfselftest",
+    arity: 1,
+    flags: &[
+        b"admin",
+    ],
+    first_key: 0,
+    last_key: 0,
+    step: 0,
+};
+
+pub fn run(_: &mut Database, cmd: Command) -> anyhow::Result<Response> {
+    cmd.ensure_empty()?;
+    hyperloglog::self_test(&mut rand::rng())?;
+    Ok(Response::SimpleString(b"OK".to_vec()))
+}
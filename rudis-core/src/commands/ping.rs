@@ -13,8 +13,12 @@ pub static INFO: CommandInfo = CommandInfo {
     step: 0,
 };
 
-pub fn run(_: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
     let message = cmd.parse_args::<Option<ByteString>>()?;
+    // subscribed clients can't tell replies from messages otherwise
+    if db.is_subscribed() {
+        return Ok(Response::string_array([b"pong".to_vec(), message.unwrap_or_default()]));
+    }
     Ok(Response::SimpleString(message.unwrap_or_else(|| b"PONG".to_vec())))
 }
 
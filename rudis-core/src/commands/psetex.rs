@@ -0,0 +1,31 @@
+use super::{expire_at, CommandInfo};
+use crate::command::Command;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"psetex",
+    arity: 4,
+    flags: &[
+        b"write",
+        b"denyoom",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let (key, time, value) = cmd.parse_args::<(ByteString, i64, ByteString)>()?;
+    anyhow::ensure!(time > 0, "invalid expire time in 'psetex' command");
+    let at = expire_at(time, 1, false, "psetex")?;
+    db.propagate_as(vec![b"set".to_vec(), key.clone(), value.clone(), b"pxat".to_vec(), at.to_string().into_bytes()]);
+    super::set::set_string(db, key, value, Some(at), false);
+    Ok(Response::SimpleString(b"OK".to_vec()))
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "psetex x 100000 a" => "OK";
+    "get x"             => "a";
+    "ttl x"             => 100;
+}
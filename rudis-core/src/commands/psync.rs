@@ -0,0 +1,97 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"psync",
+    arity: 3,
+    flags: &[
+        b"admin",
+        b"noscript",
+    ],
+    first_key: 0,
+    last_key: 0,
+    step: 0,
+};
+
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let (replid, offset) = cmd.parse_args::<(ByteString, i64)>()?;
+    db.psync(&replid, offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Database, Response};
+    use crate::test_utils::exec;
+
+    fn raw(res: Response) -> Vec<u8> {
+        let Response::Raw(raw) = res else { panic!("expected a raw reply, got {res:?}") };
+        raw
+    }
+
+    #[test]
+    fn test_psync() {
+        let mut master = Database::default();
+        exec(&mut master, "set s 1").unwrap();
+        exec(&mut master, "rpush l a b").unwrap();
+        exec(&mut master, "hset h f v").unwrap();
+        exec(&mut master, "sadd set x").unwrap();
+        exec(&mut master, "zadd z 1.5 m").unwrap();
+        let replid = master.replication().replid().to_string();
+        let offset = master.replication().offset();
+
+        master.select_client(2);
+        exec(&mut master, "replconf listening-port 7000").unwrap();
+        let reply = raw(exec(&mut master, "psync ? -1").unwrap());
+        let header = format!("+FULLRESYNC {replid} {offset}\r\n");
+        assert!(reply.starts_with(header.as_bytes()));
+        let rdb = &reply[header.len()..];
+        let rdb = &rdb[rdb.iter().position(|&b| b == b'\n').unwrap() + 1..];
+
+        let mut replica = Database::default();
+        replica.load_rdb(rdb).unwrap();
+        for key in ["s", "l", "h", "set", "z"] {
+            assert_eq!(replica.peek(key.as_bytes()).unwrap().0, master.peek(key.as_bytes()).unwrap().0);
+        }
+        assert!(replica.load_rdb(&rdb[..rdb.len() - 1]).is_err());
+        assert_eq!(crate::rdb::crc64(b"123456789"), 0xe9c6d914c4b8d9ca);
+
+        master.select_client(1);
+        exec(&mut master, "get s").unwrap();
+        exec(&mut master, "del s").unwrap();
+        let outbox = master.take_outbox();
+        assert_eq!(outbox.len(), 1);
+        assert_eq!(raw(outbox.into_iter().next().unwrap().1.unwrap()), b"*2\r\n$3\r\ndel\r\n$1\r\ns\r\n");
+        assert_eq!(exec(&mut master, "role").unwrap(), Response::Array(vec![
+            Response::BulkString(b"master".to_vec()),
+            Response::Number(offset as i64 + 20),
+            Response::Array(vec![Response::string_array([b"127.0.0.1".to_vec(), b"7000".to_vec(), b"0".to_vec()])]),
+        ]));
+
+        master.select_client(3);
+        let reply = raw(exec(&mut master, &format!("psync {replid} {}", offset + 1)).unwrap());
+        assert_eq!(reply, format!("+CONTINUE {replid}\r\n*2\r\n$3\r\ndel\r\n$1\r\ns\r\n").into_bytes());
+        assert!(raw(exec(&mut master, "psync 0123 1").unwrap()).starts_with(b"+FULLRESYNC"));
+    }
+
+    #[test]
+    fn test_wait() {
+        let mut db = Database::default();
+        assert_eq!(exec(&mut db, "wait 0 0").unwrap(), Response::Number(0));
+        db.select_client(2);
+        raw(exec(&mut db, "psync ? -1").unwrap());
+
+        db.select_client(1);
+        exec(&mut db, "set x 1").unwrap();
+        assert_eq!(exec(&mut db, "wait 1 0").unwrap(), Response::NoReply);
+        assert_eq!(exec(&mut db, "get x").unwrap(), Response::NoReply);
+        let stream = db.take_outbox();
+        assert!(matches!(&stream[..], [(2, Ok(Response::Raw(_))), (2, Ok(Response::Raw(_)))]));
+
+        db.select_client(2);
+        let offset = db.replication().offset();
+        assert_eq!(exec(&mut db, &format!("replconf ack {offset}")).unwrap(), Response::NoReply);
+        let replies = db.take_outbox().into_iter().map(|(id, res)| (id, res.unwrap())).collect::<Vec<_>>();
+        assert_eq!(replies, vec![(1, Response::Number(1)), (1, Response::SimpleString(b"1".to_vec()))]);
+    }
+}
@@ -0,0 +1,35 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::expire::now_ms;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"pttl",
+    arity: 2,
+    flags: &[
+        b"readonly",
+        b"fast",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+/// -2 for missing keys, -1 for keys without an expire.
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let key = cmd.parse_args::<ByteString>()?;
+    if !db.contains(&key) {
+        return Ok(Response::Number(-2));
+    }
+    let ttl = db.expiry(&key).map_or(-1, |at| (at - now_ms()).max(0));
+    Ok(Response::Number(ttl))
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "pttl x"                 => -2;
+    "set x a"                => "OK";
+    "pttl x"                 => -1;
+    "pexpireat x 1"          => 1;
+    "pttl x"                 => -2;
+}
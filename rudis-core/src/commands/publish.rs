@@ -0,0 +1,78 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"publish",
+    arity: 3,
+    flags: &[
+        b"pubsub",
+        b"loading",
+        b"stale",
+        b"fast",
+    ],
+    first_key: 0,
+    last_key: 0,
+    step: 0,
+};
+
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let (channel, message) = cmd.parse_args::<(ByteString, ByteString)>()?;
+    Ok(Response::Number(db.publish(&channel, &message) as _))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Database, Response};
+    use crate::test_utils::exec;
+
+    /// The messages received by client 1 since the last call.
+    fn messages(db: &mut Database) -> Vec<Vec<String>> {
+        db.take_outbox().into_iter().map(|(_, res)| match res.unwrap() {
+            Response::Array(parts) => parts.into_iter().map(|p| match p {
+                Response::BulkString(s) => String::from_utf8(s).unwrap(),
+                p => panic!("unexpected {p:?}"),
+            }).collect(),
+            res => panic!("unexpected {res:?}"),
+        }).collect()
+    }
+
+    #[test]
+    fn test_keyspace_notifications() {
+        let mut db = Database::default();
+        db.accept_client(1, None, false).unwrap();
+        db.select_client(1);
+        exec(&mut db, "psubscribe __key*__:*").unwrap();
+        db.select_client(2);
+
+        // disabled by default
+        exec(&mut db, "set x 1").unwrap();
+        assert!(messages(&mut db).is_empty());
+
+        exec(&mut db, "config set notify-keyspace-events KEA").unwrap();
+        exec(&mut db, "set x 1").unwrap();
+        assert_eq!(messages(&mut db), [
+            ["pmessage", "__key*__:*", "__keyspace@0__:x", "set"],
+            ["pmessage", "__key*__:*", "__keyevent@0__:set", "x"],
+        ]);
+
+        exec(&mut db, "config set notify-keyspace-events Egl").unwrap();
+        exec(&mut db, "incr x").unwrap();
+        exec(&mut db, "rpush l a").unwrap();
+        exec(&mut db, "rename l m").unwrap();
+        exec(&mut db, "lpop nolist").unwrap();
+        exec(&mut db, "del x m nokey").unwrap();
+        let events = messages(&mut db).into_iter().map(|m| (m[2].clone(), m[3].clone())).collect::<Vec<_>>();
+        let events = events.iter().map(|(c, k)| (c.as_str(), k.as_str())).collect::<Vec<_>>();
+        assert_eq!(events, [
+            ("__keyevent@0__:rpush", "l"),
+            ("__keyevent@0__:rename_from", "l"),
+            ("__keyevent@0__:rename_to", "m"),
+            ("__keyevent@0__:del", "x"),
+            ("__keyevent@0__:del", "m"),
+        ]);
+
+        let err = exec(&mut db, "config set notify-keyspace-events KEQ").unwrap_err();
+        assert!(err.to_string().contains("Invalid event class character"));
+    }
+}
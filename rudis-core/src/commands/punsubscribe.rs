@@ -0,0 +1,22 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"punsubscribe",
+    arity: -1,
+    flags: &[
+        b"pubsub",
+        b"noscript",
+        b"loading",
+        b"stale",
+    ],
+    first_key: 0,
+    last_key: 0,
+    step: 0,
+};
+
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let patterns = cmd.parse_args::<Vec<ByteString>>()?;
+    Ok(db.punsubscribe(patterns))
+}
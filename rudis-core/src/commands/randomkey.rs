@@ -0,0 +1,54 @@
+use super::{random_indices, CommandInfo};
+use crate::command::Command;
+use crate::{Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"randomkey",
+    arity: 1,
+    flags: &[
+        b"readonly",
+    ],
+    first_key: 0,
+    last_key: 0,
+    step: 0,
+};
+
+/// Keys sampled before giving up when they all turn out to be expired.
+const MAX_ATTEMPTS: usize = 100;
+
+pub fn run(db: &mut Database, _cmd: Command) -> anyhow::Result<Response> {
+    for _ in 0..MAX_ATTEMPTS {
+        let len = db.state.len();
+        let Some(&i) = random_indices(db.rng(), len, None)?.first() else { break };
+        let key = db.state.get_index(i).map(|(k, _)| k.clone()).expect("the index is in range");
+        // expired keys are deleted, except on replicas
+        if db.contains(&key) {
+            return Ok(Response::BulkString(key));
+        }
+    }
+    Ok(Response::Nil)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::{Database, Response};
+    use crate::test_utils::exec;
+
+    #[test]
+    fn test_randomkey() {
+        let mut db = Database::default();
+        db.seed_rng(1);
+        assert_eq!(exec(&mut db, "randomkey").unwrap(), Response::Nil);
+        exec(&mut db, "mset a 1 b 2 c 3").unwrap();
+        let keys = (0..100).map(|_| format!("{:?}", exec(&mut db, "randomkey").unwrap())).collect::<HashSet<_>>();
+        assert_eq!(keys.len(), 3);
+
+        exec(&mut db, "flushall").unwrap();
+        exec(&mut db, "set a 1").unwrap();
+        db.set_expiry(b"a", 1);
+        assert_eq!(exec(&mut db, "randomkey").unwrap(), Response::Nil);
+        assert_eq!(exec(&mut db, "dbsize").unwrap(), Response::Number(0));
+    }
+}
@@ -15,8 +15,14 @@ pub static INFO: CommandInfo = CommandInfo {
 
 pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
     let (key, newkey) = cmd.parse_args::<(ByteString, ByteString)>()?;
+    let expiry = db.expiry(&key);
     let val = db.del(&key).ok_or(anyhow::anyhow!("key does not exist"))?;
-    db.set(newkey, val);
+    db.set(newkey.clone(), val);
+    if let Some(at) = expiry {
+        db.set_expiry(&newkey, at);
+    }
+    db.notify('g', "rename_from", &key);
+    db.notify('g', "rename_to", &newkey);
     Ok(Response::SimpleString(b"OK".to_vec()))
 }
 
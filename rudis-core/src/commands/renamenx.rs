@@ -19,8 +19,14 @@ pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
     let n = if db.contains(&newkey) {
         0
     } else {
+        let expiry = db.expiry(&key);
         let val = db.del(&key).ok_or(anyhow::anyhow!("key does not exist"))?;
-        db.set(newkey, val);
+        db.set(newkey.clone(), val);
+        if let Some(at) = expiry {
+            db.set_expiry(&newkey, at);
+        }
+        db.notify('g', "rename_from", &key);
+        db.notify('g', "rename_to", &newkey);
         1
     };
     Ok(Response::Number(n))
@@ -0,0 +1,49 @@
+use super::{parse_from_bytes, CommandInfo};
+use crate::command::Command;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"replconf",
+    arity: -1,
+    flags: &[
+        b"admin",
+        b"noscript",
+        b"loading",
+        b"stale",
+        b"allow_busy",
+    ],
+    first_key: 0,
+    last_key: 0,
+    step: 0,
+};
+
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let mut res = Response::SimpleString(b"OK".to_vec());
+    while cmd.has_more() {
+        let (mut option, value) = cmd.parse_partial_args::<(ByteString, ByteString)>()?;
+        option.make_ascii_lowercase();
+        match option.as_slice() {
+            b"listening-port" => db.add_replica(parse_from_bytes(&value)?),
+            b"capa" | b"ip-address" => {}
+            // acknowledgements from replicas are never answered
+            b"ack" => {
+                db.replica_ack(parse_from_bytes(&value)?);
+                res = Response::NoReply;
+            }
+            b"getack" if db.is_master_client() => {
+                let offset = db.replication().offset().to_string().into_bytes();
+                res = Response::string_array([b"REPLCONF".to_vec(), b"ACK".to_vec(), offset]);
+            }
+            b"getack" => res = Response::NoReply,
+            _ => anyhow::bail!("Unrecognized REPLCONF option: {}", String::from_utf8_lossy(&option)),
+        }
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "replconf listening-port 7000 capa psync2" => "OK";
+    "replconf ack 10"                           => Response::NoReply;
+    "role"                                      => ["master", 0, Response::Array(vec![])];
+}
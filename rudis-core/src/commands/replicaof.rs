@@ -0,0 +1,42 @@
+use super::{parse_from_bytes, CommandInfo};
+use crate::command::Command;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"replicaof",
+    arity: 3,
+    flags: &[
+        b"admin",
+        b"noscript",
+        b"stale",
+    ],
+    first_key: 0,
+    last_key: 0,
+    step: 0,
+};
+
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let (host, port) = cmd.parse_args::<(ByteString, ByteString)>()?;
+    if host.eq_ignore_ascii_case(b"no") && port.eq_ignore_ascii_case(b"one") {
+        db.replica_of(None);
+        return Ok(Response::SimpleString(b"OK".to_vec()));
+    }
+    let host = String::from_utf8(host).map_err(|_| anyhow::anyhow!("Invalid master host"))?;
+    let port = parse_from_bytes::<u16>(&port).map_err(|_| anyhow::anyhow!("Invalid master port"))?;
+    let master = Some((host, port));
+    if db.master() == master {
+        return Ok(Response::SimpleString(b"OK Already connected to specified master".to_vec()));
+    }
+    db.replica_of(master);
+    Ok(Response::SimpleString(b"OK".to_vec()))
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "replicaof localhost 6379"    => "OK";
+    "replicaof localhost 6379"    => "OK Already connected to specified master";
+    "config get replicaof"        => ["replicaof", "localhost 6379"];
+    "role"                        => ["slave", "localhost", 6379, "connect", 0];
+    "replicaof no one"            => "OK";
+    "set x 1"                     => "OK";
+}
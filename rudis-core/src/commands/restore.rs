@@ -0,0 +1,225 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::expire::now_ms;
+use crate::rdb::restore_payload;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"restore",
+    arity: -4,
+    flags: &[
+        b"write",
+        b"denyoom",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+/// RESTORE key ttl serialized-value [REPLACE] [ABSTTL] [IDLETIME seconds] [FREQ frequency]
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let (key, ttl, payload) = cmd.parse_partial_args::<(ByteString, i64, ByteString)>()?;
+    let (mut replace, mut absolute, mut idle_time, mut frequency) = (false, false, None, None);
+    while cmd.has_more() {
+        if cmd.parse_option("replace") {
+            replace = true;
+        } else if cmd.parse_option("absttl") {
+            absolute = true;
+        } else if cmd.parse_option("idletime") && frequency.is_none() {
+            let seconds = cmd.parse_partial_args::<i64>()?;
+            anyhow::ensure!(seconds >= 0, "Invalid IDLETIME value, must be >= 0");
+            idle_time = Some(seconds as u64);
+        } else if cmd.parse_option("freq") && idle_time.is_none() {
+            let counter = cmd.parse_partial_args::<i64>()?;
+            let counter = u8::try_from(counter).map_err(|_| anyhow::anyhow!("Invalid FREQ value, must be >= 0 and <= 255"))?;
+            frequency = Some(counter);
+        } else {
+            anyhow::bail!("syntax error");
+        }
+    }
+    anyhow::ensure!(ttl >= 0, "Invalid TTL value, must be >= 0");
+    anyhow::ensure!(replace || !db.contains(&key), "BUSYKEY Target key name already exists.");
+    let value = restore_payload(&payload)?;
+    let at = match (ttl, absolute) {
+        (0, _) => None,
+        (ttl, true) => Some(ttl),
+        (ttl, false) => Some(now_ms().saturating_add(ttl)),
+    };
+    if at.is_some_and(|at| at <= now_ms()) {
+        // already expired, only deleting the key it replaces
+        if replace && db.del(&key).is_some() {
+            db.notify('g', "del", &key);
+            db.propagate_as(vec![b"del".to_vec(), key]);
+        }
+        return Ok(Response::SimpleString(b"OK".to_vec()));
+    }
+    db.set(key.clone(), value);
+    if let Some(at) = at {
+        db.set_expiry(&key, at);
+        // relative expires would drift on replicas
+        let mut args = vec![b"restore".to_vec(), key.clone(), at.to_string().into_bytes(), payload, b"absttl".to_vec()];
+        args.extend(replace.then(|| b"replace".to_vec()));
+        args.extend(idle_time.map(|s| [b"idletime".to_vec(), s.to_string().into_bytes()]).into_iter().flatten());
+        args.extend(frequency.map(|f| [b"freq".to_vec(), f.to_string().into_bytes()]).into_iter().flatten());
+        db.propagate_as(args);
+    }
+    let entry = db.state.get_mut(&key).expect("the key was just set");
+    if let Some(seconds) = idle_time {
+        entry.access.set_idle_time(seconds);
+    }
+    if let Some(counter) = frequency {
+        entry.access.set_frequency(counter);
+    }
+    db.notify('g', "restore", &key);
+    Ok(Response::SimpleString(b"OK".to_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rdb::dump_payload;
+    use crate::{Database, Hash, Response, Value};
+    use crate::test_utils::exec_args;
+
+    #[test]
+    fn test_restore() {
+        let mut db = Database::default();
+        let payload = dump_payload(&Value::Array(vec![b"a".to_vec(), b"b".to_vec()]));
+        exec_args(&mut db, &[b"restore", b"x", b"0", &payload]).unwrap();
+        let range = exec_args(&mut db, &[b"lrange", b"x", b"0", b"-1"]).unwrap();
+        assert_eq!(range, Response::Array(vec![Response::BulkString(b"a".to_vec()), Response::BulkString(b"b".to_vec())]));
+        let err = exec_args(&mut db, &[b"restore", b"x", b"0", &payload]).unwrap_err();
+        assert_eq!(err.to_string(), "BUSYKEY Target key name already exists.");
+
+        let payload = dump_payload(&Value::String(b"1".to_vec().into()));
+        exec_args(&mut db, &[b"restore", b"x", b"0", &payload, b"REPLACE"]).unwrap();
+        assert_eq!(exec_args(&mut db, &[b"get", b"x"]).unwrap(), Response::SimpleString(b"1".to_vec()));
+
+        let mut corrupted = payload.clone();
+        corrupted[1] ^= 1;
+        let err = exec_args(&mut db, &[b"restore", b"y", b"0", &corrupted]).unwrap_err();
+        assert_eq!(err.to_string(), "DUMP payload version or checksum are wrong");
+        assert!(exec_args(&mut db, &[b"restore", b"y", b"0", b"junk"]).is_err());
+
+        exec_args(&mut db, &[b"restore", b"z", b"5000", &payload]).unwrap();
+        let Response::Number(ttl) = exec_args(&mut db, &[b"pttl", b"z"]).unwrap() else { panic!("expected a ttl") };
+        assert!(ttl > 4000 && ttl <= 5000);
+    }
+
+    #[test]
+    fn test_restore_hash_field_expires() {
+        let mut db = Database::default();
+        let mut h = [(b"a".to_vec(), b"1".to_vec()), (b"b".to_vec(), b"2".to_vec()), (b"c".to_vec(), b"3".to_vec())].into_iter().collect::<Hash>();
+        h.set_expiry(b"a", 4102444800000);
+        h.set_expiry(b"b", 4102444801000);
+        let payload = dump_payload(&Value::Hash(h));
+        assert_eq!(payload[payload.len() - 10], 12);
+        exec_args(&mut db, &[b"restore", b"x", b"0", &payload]).unwrap();
+        let times = exec_args(&mut db, &[b"hpexpiretime", b"x", b"fields", b"3", b"a", b"b", b"c"]).unwrap();
+        assert_eq!(times, Response::Array(vec![Response::Number(4102444800000), Response::Number(4102444801000), Response::Number(-1)]));
+        assert_eq!(exec_args(&mut db, &[b"hget", b"x", b"c"]).unwrap(), Response::SimpleString(b"3".to_vec()));
+    }
+
+    #[test]
+    fn test_restore_redis_encodings() {
+        // compact encodings written by Redis, with the RDB version and checksum appended
+        let payload = |data: &[u8]| {
+            let mut payload = [data, &11u16.to_le_bytes()].concat();
+            payload.extend_from_slice(&crate::rdb::crc64(&payload).to_le_bytes());
+            payload
+        };
+        let bulk = |s: &[u8]| Response::SimpleString(s.to_vec());
+        let array = |items: &[&[u8]]| Response::string_array(items.iter().map(|i| i.to_vec()));
+        let values: Vec<(&[u8], &str, Response)> = vec![
+            // LZF compressed string
+            (b"\x00\xc3\x05\x0a\x00\x61\xe0\x00\x00", "get x", bulk(b"aaaaaaaaaa")),
+            (b"\x00\xc1\xd4\xfe", "get x", bulk(b"-300")),
+            (b"\x0b\x0c\x02\x00\x00\x00\x02\x00\x00\x00\x01\x00\x02\x00", "sismember x 2", Response::Number(1)),
+            (b"\x14\x0c\x0c\x00\x00\x00\x02\x00\x81\x78\x02\x02\x01\xff", "sismember x x", Response::Number(1)),
+            (b"\x10\x0d\x0d\x00\x00\x00\x02\x00\x81\x66\x02\x81\x76\x02\xff", "hgetall x", array(&[b"f", b"v"])),
+            (b"\x12\x01\x02\x0f\x0f\x00\x00\x00\x03\x00\x81\x61\x02\x03\x01\xde\xd4\x02\xff", "lrange x 0 -1", array(&[b"a", b"3", b"-300"])),
+            (b"\x11\x15\x15\x00\x00\x00\x04\x00\x81\x62\x02\xdf\xfe\x02\x81\x61\x02\x83\x31\x2e\x35\x04\xff", "zscore x a", Response::float(1.5)),
+        ];
+        let mut db = Database::default();
+        for (data, cmd, expected) in values {
+            exec_args(&mut db, &[b"restore", b"x", b"0", &payload(data), b"replace"]).unwrap();
+            let args = cmd.split(' ').map(|a| a.as_bytes()).collect::<Vec<_>>();
+            assert_eq!(exec_args(&mut db, &args).unwrap(), expected, "{cmd}");
+        }
+        let err = exec_args(&mut db, &[b"restore", b"y", b"0", &payload(b"\x11\x15\x15\x00\x00\x00\x04\x00\x81\x62\x02\xdf")]).unwrap_err();
+        assert_eq!(err.to_string(), "Bad data format");
+        let err = exec_args(&mut db, &[b"restore", b"y", b"0", &payload(b"\x02\x00")]).unwrap_err();
+        assert_eq!(err.to_string(), "Bad data format");
+    }
+
+    #[test]
+    fn test_restore_malformed_payloads() {
+        let payload = |data: &[u8]| {
+            let mut payload = [data, &11u16.to_le_bytes()].concat();
+            payload.extend_from_slice(&crate::rdb::crc64(&payload).to_le_bytes());
+            payload
+        };
+        let mut db = Database::default();
+        // an LZF string declaring a length of 2^62 bytes
+        let oversized = payload(b"\x00\xc3\x01\x81\x40\x00\x00\x00\x00\x00\x00\x00\x00a");
+        assert_eq!(exec_args(&mut db, &[b"restore", b"x", b"0", &oversized]).unwrap_err().to_string(), "Bad data format");
+        // a hash field expiring past the largest timestamp
+        let overflowing = payload(&[b"\x18".as_slice(), &i64::MAX.to_le_bytes(), b"\x01\x02\x01a\x011"].concat());
+        assert_eq!(exec_args(&mut db, &[b"restore", b"x", b"0", &overflowing]).unwrap_err().to_string(), "Bad data format");
+
+        let mut h = [(b"a".to_vec(), b"1".to_vec()), (b"b".to_vec(), b"2".to_vec())].into_iter().collect::<Hash>();
+        h.set_expiry(b"a", 4102444800000);
+        let values = [
+            Value::String(b"abc".to_vec().into()),
+            Value::String(b"12".to_vec().into()),
+            Value::Array(vec![b"a".to_vec(), b"bc".to_vec()]),
+            Value::Hash(h),
+        ];
+        let mut dumps = values.iter().map(|v| {
+            let payload = dump_payload(v);
+            payload[..payload.len() - 10].to_vec()
+        }).collect::<Vec<_>>();
+        dumps.extend([
+            b"\x00\xc3\x05\x0a\x00\x61\xe0\x00\x00".to_vec(),
+            b"\x0b\x0c\x02\x00\x00\x00\x02\x00\x00\x00\x01\x00\x02\x00".to_vec(),
+            b"\x10\x0d\x0d\x00\x00\x00\x02\x00\x81\x66\x02\x81\x76\x02\xff".to_vec(),
+            b"\x12\x01\x02\x0f\x0f\x00\x00\x00\x03\x00\x81\x61\x02\x03\x01\xde\xd4\x02\xff".to_vec(),
+            b"\x11\x15\x15\x00\x00\x00\x04\x00\x81\x62\x02\xdf\xfe\x02\x81\x61\x02\x83\x31\x2e\x35\x04\xff".to_vec(),
+        ]);
+        // truncated payloads, and payloads with any byte set to a few interesting values,
+        // are either rejected or restored but never crash the server
+        for data in dumps {
+            for len in 0..data.len() {
+                assert!(exec_args(&mut db, &[b"restore", b"x", b"0", &payload(&data[..len]), b"replace"]).is_err());
+            }
+            for i in 0..data.len() {
+                for b in [0x00, 0x01, 0x3f, 0x40, 0x7f, 0x80, 0x81, 0xc0, 0xc3, 0xfe, 0xff] {
+                    let mut data = data.clone();
+                    data[i] = b;
+                    let _ = exec_args(&mut db, &[b"restore", b"x", b"0", &payload(&data), b"replace"]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_restore_options() {
+        let mut db = Database::default();
+        let payload = dump_payload(&Value::String(b"1".to_vec().into()));
+        let err = |db: &mut Database, args: &[&[u8]]| exec_args(db, args).unwrap_err().to_string();
+        assert_eq!(err(&mut db, &[b"restore", b"x", b"0", &payload, b"idletime", b"-1"]), "Invalid IDLETIME value, must be >= 0");
+        assert_eq!(err(&mut db, &[b"restore", b"x", b"0", &payload, b"freq", b"256"]), "Invalid FREQ value, must be >= 0 and <= 255");
+        assert_eq!(err(&mut db, &[b"restore", b"x", b"0", &payload, b"freq", b"1", b"idletime", b"1"]), "syntax error");
+
+        exec_args(&mut db, &[b"restore", b"x", b"0", &payload, b"idletime", b"1000"]).unwrap();
+        assert_eq!(exec_args(&mut db, &[b"object", b"idletime", b"x"]).unwrap(), Response::Number(1000));
+        exec_args(&mut db, &[b"restore", b"x", b"0", &payload, b"replace", b"freq", b"100"]).unwrap();
+        exec_args(&mut db, &[b"config", b"set", b"maxmemory-policy", b"allkeys-lfu"]).unwrap();
+        assert_eq!(exec_args(&mut db, &[b"object", b"freq", b"x"]).unwrap(), Response::Number(100));
+
+        exec_args(&mut db, &[b"restore", b"y", b"4102444800000", &payload, b"absttl"]).unwrap();
+        assert_eq!(exec_args(&mut db, &[b"pexpiretime", b"y"]).unwrap(), Response::Number(4102444800000));
+        // expired keys are not restored, and delete the key they replace
+        exec_args(&mut db, &[b"restore", b"y", b"1", &payload, b"absttl", b"replace"]).unwrap();
+        assert_eq!(exec_args(&mut db, &[b"exists", b"y"]).unwrap(), Response::Number(0));
+    }
+}
@@ -0,0 +1,21 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{Database, Response};
+
+/// RESTORE as sent by MIGRATE in cluster mode, allowed in slots being imported.
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"restore-asking",
+    arity: -4,
+    flags: &[
+        b"write",
+        b"denyoom",
+        b"asking",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+pub fn run(db: &mut Database, cmd: Command) -> anyhow::Result<Response> {
+    super::restore::run(db, cmd)
+}
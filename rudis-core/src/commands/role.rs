@@ -16,12 +16,32 @@ pub static INFO: CommandInfo = CommandInfo {
     step: 0,
 };
 
-pub fn run(_: &mut Database, cmd: Command) -> anyhow::Result<Response> {
+pub fn run(db: &mut Database, cmd: Command) -> anyhow::Result<Response> {
     anyhow::ensure!(!cmd.has_more(), "wrong number of arguments");
-    let res = Response::Array(vec![
-        Response::BulkString(b"master".to_vec()),
-        Response::Number(0),
-        Response::Array(Vec::new()),
-    ]);
+    let replication = db.replication();
+    let offset = replication.offset() as i64;
+    let res = match db.master() {
+        Some((host, port)) => Response::Array(vec![
+            Response::BulkString(b"slave".to_vec()),
+            Response::BulkString(host.into_bytes()),
+            Response::Number(port as _),
+            Response::BulkString(replication.link_state().as_str().as_bytes().to_vec()),
+            Response::Number(offset),
+        ]),
+        None => Response::Array(vec![
+            Response::BulkString(b"master".to_vec()),
+            Response::Number(offset),
+            Response::Array(replication.replicas().map(|r| Response::string_array([
+                r.ip.clone().into_bytes(),
+                r.port.to_string().into_bytes(),
+                r.ack_offset.to_string().into_bytes(),
+            ])).collect()),
+        ]),
+    };
     Ok(res)
 }
+
+#[cfg(test)]
+crate::command_test! {
+    "role"      => ["master", 0, Response::Array(vec![])];
+}
@@ -16,15 +16,19 @@ pub static INFO: CommandInfo = CommandInfo {
 
 pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
     let (key, count) = cmd.parse_args::<(ByteString, Option<i64>)>()?;
-    let Some(a) = db.get_array(&key)? else { return Ok(Response::Nil) };
-    Ok(match count {
+    let Some(a) = db.get_array_mut(&key)? else { return Ok(Response::Nil) };
+    let res = match count {
         Some(n) if n < 0 => anyhow::bail!("value is out of range, must be positive"),
         Some(n) => {
             let n = a.len().min(n as _);
             Response::string_array((0..n).map(|_| a.pop().unwrap()))
         }
         None => a.pop().map(Response::SimpleString).unwrap_or(Response::Nil),
-    })
+    };
+    if res != Response::Nil && res != Response::Array(Vec::new()) {
+        db.notify('l', "rpop", &key);
+    }
+    Ok(res)
 }
 
 #[cfg(test)]
@@ -36,4 +40,5 @@ crate::command_test! {
     "rpop x 3"          => ["4", "3", "2"];
     "rpop x 10"         => ["1"];
     "llen x"            => 0;
+    "exists x"          => 0;
 }
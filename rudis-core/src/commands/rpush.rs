@@ -18,9 +18,11 @@ pub static INFO: CommandInfo = CommandInfo {
 pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
     let (key, elements) = cmd.parse_args::<(ByteString, Vec<ByteString>)>()?;
     anyhow::ensure!(!elements.is_empty(), "expected RPUSH key element [element ...]");
-    let a = db.get_or_insert_array(key)?;
+    let a = db.get_or_insert_array(key.clone())?;
     a.extend(elements);
-    Ok(Response::Number(a.len() as _))
+    let len = a.len();
+    db.notify('l', "rpush", &key);
+    Ok(Response::Number(len as _))
 }
 
 #[cfg(test)]
@@ -18,10 +18,14 @@ pub static INFO: CommandInfo = CommandInfo {
 pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
     let (key, elems) = cmd.parse_args::<(ByteString, Vec<ByteString>)>()?;
     anyhow::ensure!(!elems.is_empty(), "expected SADD member [member ...]");
-    let s = db.get_or_insert_set(key)?;
+    let s = db.get_or_insert_set(key.clone())?;
     let prelen = s.len();
     s.extend(elems);
-    Ok(Response::Number((s.len() - prelen) as _))
+    let added = s.len() - prelen;
+    if added > 0 {
+        db.notify('s', "sadd", &key);
+    }
+    Ok(Response::Number(added as _))
 }
 
 #[cfg(test)]
@@ -0,0 +1,55 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"script",
+    arity: -2,
+    flags: &[
+        b"noscript",
+    ],
+    first_key: 0,
+    last_key: 0,
+    step: 0,
+};
+
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let mut subcommand = cmd.parse_partial_args::<ByteString>()?;
+    subcommand.make_ascii_lowercase();
+    let res = match subcommand.as_slice() {
+        b"load" => {
+            let body = cmd.parse_args::<ByteString>()?;
+            Response::BulkString(db.script_load(&body)?.into_bytes())
+        }
+        b"exists" => {
+            let shas = cmd.parse_args::<Vec<ByteString>>()?;
+            anyhow::ensure!(!shas.is_empty(), "wrong number of arguments for 'script|exists' command");
+            Response::Array(shas.iter().map(|sha| Response::Number(db.script_exists(sha) as _)).collect())
+        }
+        b"flush" => {
+            // scripts are always flushed synchronously
+            if !cmd.parse_option("sync") {
+                cmd.parse_option("async");
+            }
+            cmd.ensure_empty()?;
+            db.script_flush();
+            Response::SimpleString(b"OK".to_vec())
+        }
+        b"kill" => {
+            cmd.ensure_empty()?;
+            db.script_kill()?;
+            Response::SimpleString(b"OK".to_vec())
+        }
+        _ => anyhow::bail!("unknown subcommand '{}'", String::from_utf8_lossy(&subcommand)),
+    };
+    Ok(res)
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "script load return'x'"                                            => "e3dac64e665973fab5c5a16c7378011a9a847dcf";
+    "script exists e3dac64e665973fab5c5a16c7378011a9a847dcf 0000"      => [1, 0];
+    "eval return'y' 0"                                                 => "y";
+    "script flush"                                                     => "OK";
+    "script exists e3dac64e665973fab5c5a16c7378011a9a847dcf"           => [0];
+}
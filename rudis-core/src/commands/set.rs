@@ -1,4 +1,4 @@
-use super::CommandInfo;
+use super::{parse_expire_option, CommandInfo};
 use crate::command::Command;
 use crate::{ByteString, Database, Response, Value};
 
@@ -14,15 +14,123 @@ pub static INFO: CommandInfo = CommandInfo {
     step: 1,
 };
 
+/// Sets a string, replacing the expire of the key with `expiry` unless `keep_ttl`.
+pub fn set_string(db: &mut Database, key: ByteString, value: ByteString, expiry: Option<i64>, keep_ttl: bool) {
+    if keep_ttl {
+        db.set_keep_ttl(key.clone(), Value::String(value.into()));
+    } else {
+        db.set(key.clone(), Value::String(value.into()));
+    }
+    db.notify('$', "set", &key);
+    if let Some(at) = expiry {
+        db.set_expiry(&key, at);
+        db.notify('g', "expire", &key);
+    }
+}
+
+/// SET key value [NX | XX] [GET] [EX seconds | PX milliseconds | EXAT unix-time-seconds |
+/// PXAT unix-time-milliseconds | KEEPTTL]
 pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
-    let (key, value) = cmd.parse_args::<(ByteString, ByteString)>()?;
-    db.set(key, Value::String(value));
-    Ok(Response::SimpleString(b"OK".to_vec()))
+    let (key, value) = cmd.parse_partial_args::<(ByteString, ByteString)>()?;
+    let (mut nx, mut xx, mut get, mut keep_ttl, mut expiry) = (false, false, false, false, None);
+    while cmd.has_more() {
+        if cmd.parse_option("nx") {
+            nx = true;
+        } else if cmd.parse_option("xx") {
+            xx = true;
+        } else if cmd.parse_option("get") {
+            get = true;
+        } else if cmd.parse_option("keepttl") {
+            keep_ttl = true;
+        } else if let Some(at) = parse_expire_option(&mut cmd, "set")? {
+            anyhow::ensure!(expiry.is_none(), "syntax error");
+            expiry = Some(at);
+        } else {
+            anyhow::bail!("syntax error");
+        }
+    }
+    anyhow::ensure!(!(nx && xx || keep_ttl && expiry.is_some()), "syntax error");
+
+    let old = match get {
+        true => Some(db.get_str(&key)?.map(|s| s.to_vec())),
+        false => None,
+    };
+    let exists = match &old {
+        Some(old) => old.is_some(),
+        None => db.contains(&key),
+    };
+    let set = !(nx && exists || xx && !exists);
+    if set {
+        if let Some(at) = expiry {
+            // relative expires would drift on replicas
+            let mut args = vec![b"set".to_vec(), key.clone(), value.clone()];
+            args.extend(nx.then(|| b"nx".to_vec()));
+            args.extend(xx.then(|| b"xx".to_vec()));
+            args.extend([b"pxat".to_vec(), at.to_string().into_bytes()]);
+            db.propagate_as(args);
+        }
+        set_string(db, key, value, expiry, keep_ttl);
+    }
+    let res = match old {
+        Some(old) => old.map(Response::SimpleString).unwrap_or_default(),
+        None if set => Response::SimpleString(b"OK".to_vec()),
+        None => Response::Nil,
+    };
+    Ok(res)
 }
 
 #[cfg(test)]
 crate::command_test! {
-    "set x a" => "OK";
-    "get x"   => "a";
-    "get a"   => ();
+    "set x a"                  => "OK";
+    "get x"                    => "a";
+    "get a"                    => ();
+    "set x b nx"               => ();
+    "set y b xx"               => ();
+    "set x b xx"               => "OK";
+    "set y c nx"               => "OK";
+    "set x c get"              => "b";
+    "set z c get"              => ();
+    "set z d nx get"           => "c";
+    "get z"                    => "c";
+    "set x d px 100000"        => "OK";
+    "ttl x"                    => 100;
+    "set x e keepttl"          => "OK";
+    "ttl x"                    => 100;
+    "set x f"                  => "OK";
+    "ttl x"                    => -1;
+    "set x g exat 4102444800"  => "OK";
+    "expiretime x"             => 4102444800;
+    "rpush l a"                => 1;
+    "set l a"                  => "OK";
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Database, Response};
+    use crate::test_utils::exec;
+
+    #[test]
+    fn test_set_errors() {
+        let mut db = Database::default();
+        for cmd in ["set x a nx xx", "set x a ex 10 px 10", "set x a ex 10 keepttl", "set x a foo"] {
+            assert_eq!(exec(&mut db, cmd).unwrap_err().to_string(), "syntax error", "{cmd}");
+        }
+        for cmd in ["set x a ex 0", "set x a px -1", "set x a ex 9223372036854775807"] {
+            assert_eq!(exec(&mut db, cmd).unwrap_err().to_string(), "invalid expire time in 'set' command", "{cmd}");
+        }
+        exec(&mut db, "rpush l a").unwrap();
+        assert_eq!(exec(&mut db, "set l b get").unwrap_err().to_string(), "expected string value");
+        assert_eq!(exec(&mut db, "lrange l 0 -1").unwrap(), Response::Array(vec![Response::BulkString(b"a".to_vec())]));
+    }
+
+    #[test]
+    fn test_set_expired() {
+        let mut db = Database::default();
+        exec(&mut db, "set x a pxat 1").unwrap();
+        assert_eq!(exec(&mut db, "get x").unwrap(), Response::Nil);
+        assert_eq!(exec(&mut db, "exists x").unwrap(), Response::Number(0));
+        assert_eq!(exec(&mut db, "set x b nx").unwrap(), Response::SimpleString(b"OK".to_vec()));
+        let Response::BulkString(info) = exec(&mut db, "info stats").unwrap() else { panic!("expected a bulk string") };
+        assert!(String::from_utf8(info).unwrap().contains("expired_keys:1"));
+    }
 }
@@ -34,8 +34,9 @@ pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
     anyhow::ensure!(offset >= 0, "offset cannot be negative");
     anyhow::ensure!(offset < u32::MAX as _, "offset larger than 2^32");
     anyhow::ensure!(value == 0 || value == 1, "invalid value");
-    let s = db.get_or_insert_str(key)?;
+    let s = db.get_or_insert_str(key.clone())?.to_mut();
     let bit = set_bit(s, offset, value);
+    db.notify('$', "setbit", &key);
     Ok(Response::Number(bit as _))
 }
 
@@ -0,0 +1,43 @@
+use super::{expire_at, CommandInfo};
+use crate::command::Command;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"setex",
+    arity: 4,
+    flags: &[
+        b"write",
+        b"denyoom",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let (key, time, value) = cmd.parse_args::<(ByteString, i64, ByteString)>()?;
+    anyhow::ensure!(time > 0, "invalid expire time in 'setex' command");
+    let at = expire_at(time, 1000, false, "setex")?;
+    db.propagate_as(vec![b"set".to_vec(), key.clone(), value.clone(), b"pxat".to_vec(), at.to_string().into_bytes()]);
+    super::set::set_string(db, key, value, Some(at), false);
+    Ok(Response::SimpleString(b"OK".to_vec()))
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "setex x 100 a" => "OK";
+    "get x"         => "a";
+    "ttl x"         => 100;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Database;
+    use crate::test_utils::exec;
+
+    #[test]
+    fn test_setex_invalid_time() {
+        let mut db = Database::default();
+        assert_eq!(exec(&mut db, "setex x 0 a").unwrap_err().to_string(), "invalid expire time in 'setex' command");
+    }
+}
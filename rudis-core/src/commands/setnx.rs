@@ -0,0 +1,32 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"setnx",
+    arity: 3,
+    flags: &[
+        b"write",
+        b"denyoom",
+        b"fast",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let (key, value) = cmd.parse_args::<(ByteString, ByteString)>()?;
+    if db.contains(&key) {
+        return Ok(Response::Number(0));
+    }
+    super::set::set_string(db, key, value, None, false);
+    Ok(Response::Number(1))
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "setnx x a" => 1;
+    "setnx x b" => 0;
+    "get x"     => "a";
+}
@@ -0,0 +1,49 @@
+use super::CommandInfo;
+use crate::command::Command;
+use super::parse_from_bytes;
+use crate::string::MAX_STRING_LEN;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"setrange",
+    arity: 4,
+    flags: &[
+        b"write",
+        b"denyoom",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let (key, offset, value) = cmd.parse_args::<(ByteString, ByteString, ByteString)>()?;
+    let offset = parse_from_bytes::<i64>(&offset)?;
+    anyhow::ensure!(offset >= 0, "offset is out of range");
+    let offset = offset as usize;
+    if value.is_empty() {
+        // nothing is created nor padded
+        return Ok(Response::Number(db.get_str(&key)?.map_or(0, |s| s.len()) as _));
+    }
+    anyhow::ensure!(offset + value.len() <= MAX_STRING_LEN, "string exceeds maximum allowed size (proto-max-bulk-len)");
+    let s = db.get_or_insert_str(key.clone())?.to_mut();
+    if s.len() < offset + value.len() {
+        s.resize(offset + value.len(), 0);
+    }
+    s[offset..offset + value.len()].copy_from_slice(&value);
+    let len = s.len();
+    db.notify('$', "setrange", &key);
+    Ok(Response::Number(len as _))
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "set x Hello_World"  => "OK";
+    "setrange x 6 Redis" => 11;
+    "get x"              => "Hello_Redis";
+    "setrange y 2 ab"    => 4;
+    "getrange y 2 3"     => "ab";
+    "strlen y"           => 4;
+    "setrange z 5 "      => 0;
+    "exists z"           => 0;
+}
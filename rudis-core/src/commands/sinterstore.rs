@@ -1,8 +1,6 @@
-use std::collections::HashSet;
-
 use super::CommandInfo;
 use crate::command::Command;
-use crate::{ByteString, Database, Response, Value};
+use crate::{ByteString, Database, Response, Set, Value};
 
 pub static INFO: CommandInfo = CommandInfo {
     name: b"sinterstore",
@@ -19,7 +17,8 @@ pub static INFO: CommandInfo = CommandInfo {
 pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
     let (key, keys) = cmd.parse_args::<(ByteString, Vec<ByteString>)>()?;
     let Some(mut set) = db.get_set(&keys[0])?.cloned() else {
-        db.set(key, Value::Set(HashSet::new()));
+        db.set(key.clone(), Value::Set(Set::new()));
+        db.notify('s', "sinterstore", &key);
         return Ok(Response::Number(0));
     };
     for k in &keys[1..] {
@@ -27,7 +26,8 @@ pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
         set.retain(|e| s.contains(e));
     }
     let len = set.len();
-    db.set(key, Value::Set(set));
+    db.set(key.clone(), Value::Set(set));
+    db.notify('s', "sinterstore", &key);
     Ok(Response::Number(len as _))
 }
 
@@ -42,4 +42,5 @@ crate::command_test! {
     "sinterstore r x y"   => 2;
     "sinterstore r y x"   => 2;
     "sinterstore r q"     => 0;
+    "exists r"            => 0;
 }
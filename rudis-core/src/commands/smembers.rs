@@ -15,7 +15,7 @@ pub static INFO: CommandInfo = CommandInfo {
 
 pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
     let key = cmd.parse_args::<ByteString>()?;
-    let mut members = db.get_set(&key)?.map(|s| s.iter().cloned().collect::<Vec<_>>()).unwrap_or_default();
+    let mut members = db.get_set(&key)?.map(|s| s.iter().map(|m| m.into_owned()).collect::<Vec<_>>()).unwrap_or_default();
     members.sort();
     Ok(Response::string_array(members))
 }
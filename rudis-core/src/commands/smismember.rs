@@ -0,0 +1,30 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"smismember",
+    arity: -3,
+    flags: &[
+        b"readonly",
+        b"fast",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let (key, members) = cmd.parse_args::<(ByteString, Vec<ByteString>)>()?;
+    anyhow::ensure!(!members.is_empty(), "expected SMISMEMBER key member [member ..]");
+    let set = db.get_set(&key)?;
+    let replies = members.iter().map(|m| Response::Number(set.as_ref().is_some_and(|s| s.contains(m)) as _));
+    Ok(Response::Array(replies.collect()))
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "smismember x 1 2"   => [0, 0];
+    "sadd x 1 3"         => 2;
+    "smismember x 1 2 3" => [1, 0, 1];
+}
@@ -17,11 +17,13 @@ pub static INFO: CommandInfo = CommandInfo {
 pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
     let (src, dst, member) = cmd.parse_args::<(ByteString, ByteString, ByteString)>()?;
 
-    let Some(src_set) = db.get_set(&src)? else { return Ok(Response::Number(0)) };
+    let Some(src_set) = db.get_set_mut(&src)? else { return Ok(Response::Number(0)) };
     if !src_set.remove(&member) {
         return Ok(Response::Number(0));
     }
-    db.get_or_insert_set(dst)?.insert(member);
+    db.notify('s', "srem", &src);
+    db.get_or_insert_set(dst.clone())?.insert(member);
+    db.notify('s', "sadd", &dst);
     Ok(Response::Number(1))
 }
 
@@ -36,4 +38,7 @@ crate::command_test! {
     "smembers y"      => ["1", "2"];
     "smove x y 1"     => 0;
     "smove q y 1"     => 0;
+    "smove x y 3"     => 1;
+    "exists x"        => 0;
+    "scard y"         => 3;
 }
@@ -0,0 +1,229 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{ByteString, Database, Response, Value};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"sort",
+    arity: -2,
+    flags: &[
+        b"write",
+        b"denyoom",
+        b"movablekeys",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+/// The value a BY or GET pattern refers to for `element`: `#` is the element itself, otherwise
+/// the first `*` is replaced by the element to get the name of a string key, or of a hash key
+/// when followed by `->field`.
+fn lookup(db: &mut Database, pattern: &[u8], element: &[u8]) -> Option<ByteString> {
+    if pattern == b"#" {
+        return Some(element.to_vec());
+    }
+    let star = pattern.iter().position(|&b| b == b'*')?;
+    let (prefix, rest) = (&pattern[..star], &pattern[star + 1..]);
+    let (suffix, field) = match rest.windows(2).position(|w| w == b"->") {
+        Some(i) if i + 2 < rest.len() => (&rest[..i], Some(&rest[i + 2..])),
+        _ => (rest, None),
+    };
+    let key = [prefix, element, suffix].concat();
+    match field {
+        Some(field) => db.get_hash(&key).ok()??.get(field).map(|v| v.to_vec()),
+        None => db.get_str(&key).ok()?.map(|s| s.to_vec()),
+    }
+}
+
+/// The key and the STORE destination, skipping the arguments of the other options which
+/// could be named like one.
+pub fn keys(cmd: &Command) -> Vec<&[u8]> {
+    let (mut i, mut store) = (1, None);
+    while let Some(arg) = cmd.arg(i) {
+        i += match arg.to_ascii_lowercase().as_slice() {
+            b"limit" => 3,
+            b"by" | b"get" => 2,
+            b"store" => {
+                store = cmd.arg(i + 1);
+                2
+            }
+            _ => 1,
+        };
+    }
+    cmd.arg(0).into_iter().chain(store).collect()
+}
+
+struct Item {
+    element: ByteString,
+    /// The BY value compared when sorting with ALPHA, missing ones sorting first.
+    by: Option<ByteString>,
+    score: f64,
+}
+
+/// SORT and SORT_RO, which does not accept STORE.
+///
+/// SORT key [BY pattern] [LIMIT offset count] [GET pattern [GET pattern ...]] [ASC | DESC]
+/// [ALPHA] [STORE destination]
+pub fn sort(db: &mut Database, mut cmd: Command, readonly: bool) -> anyhow::Result<Response> {
+    let key = cmd.parse_partial_args::<ByteString>()?;
+    let (mut by, mut limit, mut gets, mut desc, mut alpha, mut store) = (None, None, Vec::new(), false, false, None);
+    while cmd.has_more() {
+        if let Some(pattern) = cmd.parse_named_arg("by") {
+            by = Some(pattern);
+        } else if cmd.parse_option("limit") {
+            limit = Some(cmd.parse_partial_args::<(i64, i64)>()?);
+        } else if let Some(pattern) = cmd.parse_named_arg("get") {
+            gets.push(pattern);
+        } else if cmd.parse_option("asc") {
+            desc = false;
+        } else if cmd.parse_option("desc") {
+            desc = true;
+        } else if cmd.parse_option("alpha") {
+            alpha = true;
+        } else if !readonly && let Some(dest) = cmd.parse_named_arg("store") {
+            store = Some(dest);
+        } else {
+            anyhow::bail!("syntax error");
+        }
+    }
+    // patterns without `*` like `nosort` skip sorting
+    let dontsort = by.as_ref().is_some_and(|p| !p.contains(&b'*'));
+    if db.is_cluster_enabled() {
+        anyhow::ensure!(by.is_none() || dontsort, "BY option of SORT denied in Cluster mode.");
+        anyhow::ensure!(gets.iter().all(|p| p == b"#"), "GET option of SORT denied in Cluster mode.");
+    }
+    // the patterns can read any key
+    anyhow::ensure!(by.is_none() || dontsort || db.can_read_all_keys(), "BY option of SORT denied due to insufficient ACL permissions.");
+    anyhow::ensure!(gets.is_empty() || db.can_read_all_keys(), "GET option of SORT denied due to insufficient ACL permissions.");
+
+    let (mut elements, set, zset) = match db.get(&key) {
+        None => (Vec::new(), false, false),
+        Some(Value::Array(v)) => (v.clone(), false, false),
+        Some(Value::Set(s)) => (s.iter().map(Cow::into_owned).collect(), true, false),
+        Some(Value::ZSet(z)) => (z.iter().map(|(_, m)| m.to_vec()).collect(), false, true),
+        Some(_) => anyhow::bail!("expected list, set or sorted set value"),
+    };
+    if dontsort && zset && desc {
+        elements.reverse();
+    }
+    // sets are unordered, so they are sorted anyway by element when stored to be deterministic
+    let (sort, by, alpha) = match dontsort && set && store.is_some() {
+        true => (true, None, true),
+        false => (!dontsort, by, alpha),
+    };
+    if sort {
+        let mut items = Vec::with_capacity(elements.len());
+        for element in elements {
+            let by = match &by {
+                Some(pattern) => lookup(db, pattern, &element),
+                None => Some(element.clone()),
+            };
+            let score = match (&by, alpha) {
+                (Some(by), false) => std::str::from_utf8(by).ok()
+                    .and_then(|s| s.parse::<f64>().ok())
+                    .filter(|s| !s.is_nan())
+                    .ok_or_else(|| anyhow::anyhow!("One or more scores can't be converted into double"))?,
+                _ => 0.0,
+            };
+            items.push(Item { element, by, score });
+        }
+        items.sort_by(|a, b| {
+            let ord = match alpha {
+                true => a.by.cmp(&b.by),
+                // equal scores are ordered by element, for the result to be deterministic
+                false => a.score.partial_cmp(&b.score).unwrap_or(Ordering::Equal).then_with(|| a.element.cmp(&b.element)),
+            };
+            if desc { ord.reverse() } else { ord }
+        });
+        elements = items.into_iter().map(|i| i.element).collect();
+    }
+
+    if let Some((offset, count)) = limit {
+        let start = (offset.max(0) as usize).min(elements.len());
+        let end = if count < 0 { elements.len() } else { start.saturating_add(count as usize).min(elements.len()) };
+        elements = elements[start..end].to_vec();
+    }
+    let results = match gets.is_empty() {
+        true => elements.into_iter().map(Some).collect::<Vec<_>>(),
+        false => elements.iter().flat_map(|e| gets.iter().map(|p| lookup(db, p, e)).collect::<Vec<_>>()).collect(),
+    };
+
+    let Some(dest) = store else {
+        // flagged as a write for STORE, but only propagated with it
+        db.propagate_nothing();
+        return Ok(Response::Array(results.into_iter().map(|r| r.map(Response::BulkString).unwrap_or_default()).collect()));
+    };
+    let len = results.len();
+    db.set(dest.clone(), Value::Array(results.into_iter().map(Option::unwrap_or_default).collect()));
+    if len > 0 {
+        db.notify('l', "sortstore", &dest);
+    }
+    Ok(Response::Number(len as _))
+}
+
+pub fn run(db: &mut Database, cmd: Command) -> anyhow::Result<Response> {
+    sort(db, cmd, false)
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "sort x"                                => [];
+    "rpush x 3 1 2 10"                      => 4;
+    "sort x"                                => ["1", "2", "3", "10"];
+    "sort x desc"                           => ["10", "3", "2", "1"];
+    "sort x alpha"                          => ["1", "10", "2", "3"];
+    "sort x limit 1 2"                      => ["2", "3"];
+    "sort x limit 3 -1"                     => ["10"];
+    "sort x by nosort"                      => ["3", "1", "2", "10"];
+    "mset w_1 30 w_2 20 w_3 10"             => "OK";
+    "sort x by w_*"                         => ["10", "3", "2", "1"];
+    "hset u_1 name ann"                     => 1;
+    "hset u_2 name bob"                     => 1;
+    "sort x by w_* get # get u_*->name"     => ["10", (), "3", (), "2", "bob", "1", "ann"];
+    "sort x by w_* desc get w_*"            => ["30", "20", "10", ()];
+    "sort x store y"                        => 4;
+    "lrange y 0 -1"                         => ["1", "2", "3", "10"];
+    "sort x by w_* get u_*->name store y"   => 4;
+    "lrange y 0 -1"                         => ["", "", "bob", "ann"];
+    "sort missing store y"                  => 0;
+    "exists y"                              => 0;
+    "zadd z 1 c 2 a 3 b"                    => 3;
+    "sort z by nosort desc limit 0 2"       => ["b", "a"];
+    "sort z alpha"                          => ["a", "b", "c"];
+    "sadd s c a b"                          => 3;
+    "sort s by nosort alpha store y"        => 3;
+    "lrange y 0 -1"                         => ["a", "b", "c"];
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Database, Response};
+    use crate::test_utils::exec;
+
+    #[test]
+    fn test_errors() {
+        let mut db = Database::default();
+        exec(&mut db, "rpush x 1 a").unwrap();
+        exec(&mut db, "set s 1").unwrap();
+        let err = |db: &mut Database, cmd| exec(db, cmd).unwrap_err().to_string();
+        assert_eq!(err(&mut db, "sort x"), "One or more scores can't be converted into double");
+        assert_eq!(err(&mut db, "sort x foo"), "syntax error");
+        assert_eq!(err(&mut db, "sort s"), "expected list, set or sorted set value");
+        assert_eq!(err(&mut db, "sort_ro x alpha store y"), "syntax error");
+        assert_eq!(exec(&mut db, "sort_ro x alpha").unwrap(), Response::string_array([b"1".to_vec(), b"a".to_vec()]));
+    }
+
+    #[test]
+    fn test_propagation() {
+        let mut db = Database::default();
+        exec(&mut db, "rpush l 2 1").unwrap();
+        let offset = db.replication().offset();
+        exec(&mut db, "sort l").unwrap();
+        assert_eq!(db.replication().offset(), offset);
+        exec(&mut db, "sort l store d").unwrap();
+        assert!(db.replication().offset() > offset);
+    }
+}
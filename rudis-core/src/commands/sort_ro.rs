@@ -0,0 +1,24 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"sort_ro",
+    arity: -2,
+    flags: &[
+        b"readonly",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+pub fn run(db: &mut Database, cmd: Command) -> anyhow::Result<Response> {
+    super::sort::sort(db, cmd, true)
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "rpush x b c a"                => 3;
+    "sort_ro x alpha desc limit 0 2" => ["c", "b"];
+}
@@ -1,6 +1,4 @@
-use std::collections::HashSet;
-
-use super::CommandInfo;
+use super::{random_indices, CommandInfo};
 use crate::command::Command;
 use crate::{ByteString, Database, Response};
 
@@ -16,23 +14,30 @@ pub static INFO: CommandInfo = CommandInfo {
     step: 1,
 };
 
-fn set_pop(set: &mut HashSet::<ByteString>) -> Option<ByteString> {
-    let e = set.iter().next()?.clone();
-    set.remove(&e);
-    Some(e)
-}
-
+/// SPOP key [count]
 pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
     let (key, maybe_count) = cmd.parse_args::<(ByteString, Option<i64>)>()?;
-    let Some(set) = db.get_set(&key)? else { return Ok(Response::Nil) };
-    let res = match maybe_count {
-        Some(count) => {
-            let elems = (0..count).filter_map(|_| set_pop(set).map(|e| Response::BulkString(e))).collect();
-            Response::Array(elems)
-        }
-        None => {
-            set_pop(set).map(|e| Response::BulkString(e)).unwrap_or_default()
+    anyhow::ensure!(maybe_count.is_none_or(|c| c >= 0), "value is out of range, must be positive");
+    let len = db.get_set(&key)?.map_or(0, |s| s.len());
+    let indices = random_indices(db.rng(), len, maybe_count)?;
+    let popped = match db.get_set_mut(&key)? {
+        Some(set) => {
+            // all picked before removing any, which moves members around
+            let popped = indices.into_iter().filter_map(|i| set.get_index(i).map(|m| m.into_owned())).collect::<Vec<_>>();
+            for member in &popped {
+                set.remove(member);
+            }
+            popped
         }
+        None => Vec::new(),
+    };
+    if !popped.is_empty() {
+        db.notify('s', "spop", &key);
+        db.propagate_as([b"srem".to_vec(), key].into_iter().chain(popped.iter().cloned()).collect());
+    }
+    let res = match maybe_count {
+        Some(_) => Response::string_array(popped),
+        None => popped.into_iter().next().map(Response::BulkString).unwrap_or_default(),
     };
     Ok(res)
 }
@@ -42,6 +47,37 @@ crate::command_test! {
     "sadd x 1 2 3" => 3;
     "spop x 3"     => ["1", "2", "3"] ignore_order;
     "scard x"      => 0;
+    "exists x"     => 0;
+    "dbsize"       => 0;
     "sadd x 1"     => 1;
     "spop x"       => "1";
+    "keys *"       => Response::Array(vec![]);
+    "spop x 2"     => [];
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::{Database, Response};
+    use crate::test_utils::exec;
+
+    #[test]
+    fn test_uniform() {
+        let mut db = Database::default();
+        db.seed_rng(42);
+        let mut counts = [0; 4];
+        for _ in 0..4000 {
+            exec(&mut db, "sadd x 0 1 2 3").unwrap();
+            let Response::BulkString(m) = exec(&mut db, "spop x").unwrap() else { panic!() };
+            counts[(m[0] - b'0') as usize] += 1;
+        }
+        // every member is popped about a quarter of the time
+        assert!(counts.iter().all(|&c| (800..1200).contains(&c)), "{counts:?}");
+
+        let Response::Array(popped) = exec(&mut db, "spop x 2").unwrap() else { panic!() };
+        assert_eq!(popped.iter().map(|m| format!("{m:?}")).collect::<HashSet<_>>().len(), 2);
+        assert_eq!(exec(&mut db, "scard x").unwrap(), Response::Number(1));
+        assert_eq!(exec(&mut db, "spop x -1").unwrap_err().to_string(), "value is out of range, must be positive");
+    }
 }
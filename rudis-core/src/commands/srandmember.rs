@@ -0,0 +1,73 @@
+use super::{random_indices, CommandInfo};
+use crate::command::Command;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"srandmember",
+    arity: -2,
+    flags: &[
+        b"readonly",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+/// Random members, distinct for a positive count and possibly repeated for a negative one.
+///
+/// SRANDMEMBER key [count]
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let (key, count) = cmd.parse_args::<(ByteString, Option<i64>)>()?;
+    let len = db.get_set(&key)?.map_or(0, |s| s.len());
+    let indices = random_indices(db.rng(), len, count)?;
+    let picked = match db.get_set(&key)? {
+        Some(set) => indices.into_iter().filter_map(|i| set.get_index(i).map(|m| m.into_owned())).collect(),
+        None => Vec::new(),
+    };
+    let res = match count {
+        Some(_) => Response::string_array(picked),
+        None => picked.into_iter().next().map(Response::BulkString).unwrap_or_default(),
+    };
+    Ok(res)
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "srandmember x"           => ();
+    "srandmember x 2"         => [];
+    "sadd x 1 2 3"            => 3;
+    "srandmember x 5"         => ["1", "2", "3"] ignore_order;
+    "scard x"                 => 3;
+    "sadd y a"                => 1;
+    "srandmember y"           => "a";
+    "srandmember y -3"        => ["a", "a", "a"];
+    "srandmember y 0"         => [];
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::{Database, Response};
+    use crate::test_utils::exec;
+
+    #[test]
+    fn test_counts() {
+        let mut db = Database::default();
+        db.seed_rng(7);
+        exec(&mut db, "sadd x a b c d e").unwrap();
+        let Response::Array(picked) = exec(&mut db, "srandmember x 3").unwrap() else { panic!() };
+        assert_eq!(picked.iter().map(|m| format!("{m:?}")).collect::<HashSet<_>>().len(), 3);
+        let Response::Array(picked) = exec(&mut db, "srandmember x -20").unwrap() else { panic!() };
+        assert_eq!(picked.len(), 20);
+        // with repeats, 20 picks among 5 members all but certainly repeat and cover several
+        assert!((2..=5).contains(&picked.iter().map(|m| format!("{m:?}")).collect::<HashSet<_>>().len()));
+        // counts that cannot be negated are rejected, whether the key exists or not
+        assert_eq!(exec(&mut db, "srandmember x -9223372036854775808").unwrap_err().to_string(), "value is out of range");
+        assert_eq!(exec(&mut db, "srandmember y 9223372036854775807").unwrap_err().to_string(), "value is out of range");
+        // as are repeated picks too many for a reply to hold
+        assert_eq!(exec(&mut db, "srandmember x -4611686018427387903").unwrap_err().to_string(), "value is out of range");
+        assert_eq!(exec(&mut db, "srandmember x -10000000000").unwrap_err().to_string(), "value is out of range");
+        assert_eq!(exec(&mut db, "srandmember x -1048577").unwrap_err().to_string(), "value is out of range");
+    }
+}
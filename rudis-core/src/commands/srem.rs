@@ -17,9 +17,12 @@ pub static INFO: CommandInfo = CommandInfo {
 pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
     let (key, members) = cmd.parse_args::<(ByteString, Vec<ByteString>)>()?;
     anyhow::ensure!(!members.is_empty(), "expected SREM key member [member ...]");
-    let removed = db.get_set(&key)?
+    let removed = db.get_set_mut(&key)?
         .map(|s| members.iter().filter(|&m| s.remove(m)).count())
         .unwrap_or(0);
+    if removed > 0 {
+        db.notify('s', "srem", &key);
+    }
     Ok(Response::Number(removed as _))
 }
 
@@ -32,5 +35,7 @@ crate::command_test! {
     "srem x 0 1 7" => 0;
     "srem x 2 9 8" => 1;
     "smembers x"   => ["3"];
+    "srem x 3"     => 1;
+    "exists x"     => 0;
     "srem q 1"     => 0;
 }
@@ -0,0 +1,107 @@
+use super::CommandInfo;
+use crate::acl::glob_matches;
+use crate::command::Command;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"sscan",
+    arity: -3,
+    flags: &[
+        b"readonly",
+    ],
+    first_key: 1,
+    last_key: 1,
+    step: 1,
+};
+
+/// SSCAN key cursor [MATCH pattern] [COUNT count]
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let (key, cursor) = cmd.parse_partial_args::<(ByteString, ByteString)>()?;
+    let cursor = std::str::from_utf8(&cursor).ok().and_then(|c| c.parse::<u64>().ok());
+    let cursor = cursor.ok_or_else(|| anyhow::anyhow!("invalid cursor"))?;
+    let (mut pattern, mut count) = (None, 10);
+    while cmd.has_more() {
+        if let Some(p) = cmd.parse_named_arg("match") {
+            pattern = Some(p);
+        } else if cmd.parse_option("count") {
+            count = cmd.parse_partial_args::<i64>()?;
+            anyhow::ensure!(count > 0, "syntax error");
+        } else {
+            anyhow::bail!("syntax error");
+        }
+    }
+    let Some(set) = db.get_set(&key)? else {
+        return Ok(Response::Array(vec![Response::BulkString(b"0".to_vec()), Response::Array(vec![])]));
+    };
+    let (next, members) = set.scan(cursor, count as usize);
+    let members = members.into_iter().filter(|m| pattern.as_ref().is_none_or(|p| glob_matches(p, m)));
+    Ok(Response::Array(vec![
+        Response::BulkString(next.to_string().into_bytes()),
+        Response::string_array(members.map(|m| m.into_owned())),
+    ]))
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "sscan x 0"                 => [Response::BulkString(b"0".to_vec()), Response::Array(vec![])];
+    "sadd x a b ab"             => 3;
+    "sscan x 0 match a?"        => [Response::BulkString(b"0".to_vec()), Response::string_array([b"ab".to_vec()])];
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::{Database, Response};
+    use crate::test_utils::exec;
+
+    #[test]
+    fn test_cursor() {
+        let mut db = Database::default();
+        // listpacks are returned whole
+        exec(&mut db, "config set set-max-listpack-entries 1").unwrap();
+        for i in 0..25 {
+            exec(&mut db, &format!("sadd x m{i}")).unwrap();
+        }
+        let (mut cursor, mut seen) = ("0".to_string(), Vec::new());
+        loop {
+            let Response::Array(res) = exec(&mut db, &format!("sscan x {cursor} count 3")).unwrap() else { panic!() };
+            let [Response::BulkString(next), Response::Array(members)] = res.as_slice() else { panic!() };
+            assert!(members.len() <= 3);
+            seen.extend(members.iter().cloned());
+            cursor = String::from_utf8(next.clone()).unwrap();
+            if cursor == "0" {
+                break;
+            }
+        }
+        seen.sort_by_key(|m| format!("{m:?}"));
+        seen.dedup();
+        assert_eq!(seen.len(), 25);
+    }
+
+    #[test]
+    fn test_large_set() {
+        let mut db = Database::default();
+        let members = (0..100000).map(|i| format!("m{i}")).collect::<Vec<_>>();
+        exec(&mut db, &format!("sadd x {}", members.join(" "))).unwrap();
+        let (mut cursor, mut seen, mut removed) = ("0".to_string(), HashSet::new(), HashSet::new());
+        loop {
+            let Response::Array(res) = exec(&mut db, &format!("sscan x {cursor} count 10")).unwrap() else { panic!() };
+            let [Response::BulkString(next), Response::Array(batch)] = res.as_slice() else { panic!() };
+            assert!(batch.len() <= 10);
+            seen.extend(batch.iter().map(|m| match m {
+                Response::BulkString(m) => String::from_utf8(m.clone()).unwrap(),
+                m => panic!("unexpected {m:?}"),
+            }));
+            // removals moving members around do not make the iteration miss any
+            let member = &members[removed.len() * 7];
+            exec(&mut db, &format!("srem x {member}")).unwrap();
+            removed.insert(member);
+            cursor = String::from_utf8(next.clone()).unwrap();
+            if cursor == "0" {
+                break;
+            }
+        }
+        assert!(members.iter().all(|m| seen.contains(m) || removed.contains(m)));
+    }
+}
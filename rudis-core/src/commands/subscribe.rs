@@ -0,0 +1,77 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"subscribe",
+    arity: -2,
+    flags: &[
+        b"pubsub",
+        b"noscript",
+        b"loading",
+        b"stale",
+    ],
+    first_key: 0,
+    last_key: 0,
+    step: 0,
+};
+
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let channels = cmd.parse_args::<Vec<ByteString>>()?;
+    Ok(db.subscribe(channels))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Database, Response};
+    use crate::test_utils::exec;
+
+    fn message(args: &[&str]) -> Response {
+        Response::string_array(args.iter().map(|a| a.as_bytes().to_vec()))
+    }
+
+    #[test]
+    fn test_subscribe() {
+        let mut db = Database::default();
+        db.accept_client(1, None, false).unwrap();
+        db.accept_client(2, None, false).unwrap();
+
+        db.select_client(1);
+        assert_eq!(exec(&mut db, "subscribe news").unwrap(), Response::Array(vec![
+            Response::BulkString(b"subscribe".to_vec()),
+            Response::BulkString(b"news".to_vec()),
+            Response::Number(1),
+        ]));
+        assert_eq!(exec(&mut db, "psubscribe n* x*").unwrap(), Response::Raw(
+            b"*3\r\n$10\r\npsubscribe\r\n$2\r\nn*\r\n:2\r\n*3\r\n$10\r\npsubscribe\r\n$2\r\nx*\r\n:3\r\n".to_vec()
+        ));
+        assert_eq!(
+            crate::error_reply(&exec(&mut db, "get x").unwrap_err()),
+            "ERR Can't execute 'get': only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / QUIT / RESET are allowed in this context",
+        );
+        assert_eq!(exec(&mut db, "ping").unwrap(), message(&["pong", ""]));
+
+        db.select_client(2);
+        assert_eq!(exec(&mut db, "publish news hi").unwrap(), Response::Number(2));
+        assert_eq!(exec(&mut db, "publish other hi").unwrap(), Response::Number(0));
+        let outbox = db.take_outbox().into_iter().map(|(id, res)| (id, res.unwrap())).collect::<Vec<_>>();
+        assert_eq!(outbox, vec![
+            (1, message(&["message", "news", "hi"])),
+            (1, message(&["pmessage", "n*", "news", "hi"])),
+        ]);
+
+        db.select_client(1);
+        assert_eq!(exec(&mut db, "unsubscribe").unwrap(), Response::Array(vec![
+            Response::BulkString(b"unsubscribe".to_vec()),
+            Response::BulkString(b"news".to_vec()),
+            Response::Number(2),
+        ]));
+        exec(&mut db, "punsubscribe").unwrap();
+        assert_eq!(exec(&mut db, "punsubscribe").unwrap(), Response::Array(vec![
+            Response::BulkString(b"punsubscribe".to_vec()),
+            Response::Nil,
+            Response::Number(0),
+        ]));
+        assert_eq!(exec(&mut db, "get x").unwrap(), Response::Nil);
+    }
+}
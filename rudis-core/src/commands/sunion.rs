@@ -22,8 +22,8 @@ pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
     for k in keys {
         let Some(s) = db.get_set(&k)? else { continue };
         for m in s.iter() {
-            if !set.contains(m) {
-                set.insert(m.clone());
+            if !set.contains(m.as_ref()) {
+                set.insert(m.into_owned());
             }
         }
     }
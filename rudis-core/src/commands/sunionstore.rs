@@ -23,13 +23,14 @@ pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
     for k in &keys {
         let Some(s) = db.get_set(k)? else { continue };
         for m in s.iter() {
-            if !set.contains(m) {
-                set.insert(m.clone());
+            if !set.contains(m.as_ref()) {
+                set.insert(m.into_owned());
             }
         }
     }
     let len = set.len();
-    db.set(dest, Value::Set(set));
+    db.set(dest.clone(), Value::Set(set.into_iter().collect()));
+    db.notify('s', "sunionstore", &dest);
     Ok(Response::Number(len as _))
 }
 
@@ -0,0 +1,31 @@
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{ByteString, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"touch",
+    arity: -2,
+    flags: &[
+        b"readonly",
+        b"fast",
+    ],
+    first_key: 1,
+    last_key: -1,
+    step: 1,
+};
+
+/// TOUCH key [key ...]
+///
+/// Updates the access time of keys, returns how many exist.
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let keys = cmd.parse_args::<Vec<ByteString>>()?;
+    Ok(Response::Number(keys.iter().filter(|&key| db.get(key).is_some()).count() as _))
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "touch x"           => 0;
+    "set x 1"           => "OK";
+    "sadd y a"          => 1;
+    "touch x y z x"     => 3;
+}
@@ -17,7 +17,14 @@ pub static INFO: CommandInfo = CommandInfo {
 pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
     let keys = cmd.parse_args::<Vec<ByteString>>()?;
     anyhow::ensure!(!keys.is_empty(), "expected UNLINK key [key ...]");
-    Ok(Response::Number(keys.iter().filter(|&key| db.del(key).is_some()).count() as _))
+    let mut deleted = 0;
+    for key in keys {
+        if db.del(&key).is_some() {
+            db.notify('g', "del", &key);
+            deleted += 1;
+        }
+    }
+    Ok(Response::Number(deleted))
 }
 
 #[cfg(test)]
@@ -0,0 +1,37 @@
+use std::time::{Duration, Instant};
+
+use super::CommandInfo;
+use crate::command::Command;
+use crate::{Blocked, Database, Response};
+
+pub static INFO: CommandInfo = CommandInfo {
+    name: b"wait",
+    arity: 3,
+    flags: &[
+        b"noscript",
+    ],
+    first_key: 0,
+    last_key: 0,
+    step: 0,
+};
+
+pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
+    let (numreplicas, timeout) = cmd.parse_args::<(i64, i64)>()?;
+    anyhow::ensure!(db.master().is_none(), "WAIT cannot be used with replica instances. Please also note that since Redis 4.0 if a replica is configured to be writable (which is not the default) writes to replicas are just local and are not propagated.");
+    anyhow::ensure!(timeout >= 0, "timeout is negative");
+    let offset = db.replication().offset();
+    let acked = db.replication().acked(offset);
+    if acked as i64 >= numreplicas {
+        return Ok(Response::Number(acked as _));
+    }
+    db.request_acks();
+    let deadline = (timeout > 0).then(|| Instant::now() + Duration::from_millis(timeout as _));
+    db.client().blocked = Some(Blocked::Wait { offset, numreplicas: numreplicas as _, deadline });
+    Ok(Response::NoReply)
+}
+
+#[cfg(test)]
+crate::command_test! {
+    "wait 0 0"      => 0;
+    "wait 0 100"    => 0;
+}
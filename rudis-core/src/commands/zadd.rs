@@ -19,14 +19,18 @@ pub static INFO: CommandInfo = CommandInfo {
 
 pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
     let (key, members) = cmd.parse_args::<(ByteString, Vec<(f64, ByteString)>)>()?;
-    let z = db.get_or_insert_zset(key)?;
-    let mut added = 0;
+    let z = db.get_or_insert_zset(key.clone())?;
+    let (mut added, mut changed) = (0, false);
     for (score, member) in members {
         let score = NotNan::new(score)?;
-        if z.insert(score, member).is_none() {
-            added += 1;
+        match z.insert(score, member) {
+            None => added += 1,
+            Some(old) => changed |= old != score,
         }
     }
+    if added > 0 || changed {
+        db.notify('z', "zadd", &key);
+    }
     Ok(Response::Number(added as _))
 }
 
@@ -37,3 +41,18 @@ crate::command_test! {
     "zadd x 2 a 3 c" => 1;
     "zcard x"        => 3;
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{Database, Response};
+    use crate::test_utils::exec;
+
+    #[test]
+    fn test_failed_zadd_leaves_no_key() {
+        let mut db = Database::default();
+        assert!(exec(&mut db, "zadd x nan a").is_err());
+        assert_eq!(exec(&mut db, "exists x").unwrap(), Response::Number(0));
+        assert_eq!(exec(&mut db, "dbsize").unwrap(), Response::Number(0));
+        assert_eq!(db.used_memory(), 0);
+    }
+}
@@ -19,10 +19,11 @@ pub static INFO: CommandInfo = CommandInfo {
 
 pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
     let (key, increment, member) = cmd.parse_args::<(ByteString, f64, ByteString)>()?;
-    let zset = db.get_or_insert_zset(key)?;
+    let zset = db.get_or_insert_zset(key.clone())?;
     let old_score = zset.remove(member.clone()).map(|s| *s).unwrap_or(0.0);
     let score = old_score + increment;
     zset.insert(NotNan::new(score)?, member);
+    db.notify('z', "zincr", &key);
     Ok(Response::float(score))
 }
 
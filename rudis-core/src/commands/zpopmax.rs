@@ -16,11 +16,14 @@ pub static INFO: CommandInfo = CommandInfo {
 
 pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
     let (key, maybe_count) = cmd.parse_args::<(ByteString, Option<i64>)>()?;
-    let Some(set) = db.get_zset(&key)? else { return Ok(Response::Array(Vec::new())) };
+    let Some(set) = db.get_zset_mut(&key)? else { return Ok(Response::Array(Vec::new())) };
     let res = (0..maybe_count.unwrap_or(1))
         .filter_map(|_| set.popmax())
         .flat_map(|(s, m)| [Response::BulkString(m), Response::float(s)])
-        .collect();
+        .collect::<Vec<_>>();
+    if !res.is_empty() {
+        db.notify('z', "zpopmax", &key);
+    }
     Ok(Response::Array(res))
 }
 
@@ -30,4 +33,5 @@ crate::command_test! {
     "zpopmax x 3" => ["d", "4", "c", "3", "b", "2"];
     "zcard x" => 1;
     "zpopmax x" => ["a", "1"];
+    "exists x" => 0;
 }
@@ -16,11 +16,14 @@ pub static INFO: CommandInfo = CommandInfo {
 
 pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
     let (key, maybe_count) = cmd.parse_args::<(ByteString, Option<i64>)>()?;
-    let Some(set) = db.get_zset(&key)? else { return Ok(Response::Array(Vec::new())) };
+    let Some(set) = db.get_zset_mut(&key)? else { return Ok(Response::Array(Vec::new())) };
     let res = (0..maybe_count.unwrap_or(1))
         .filter_map(|_| set.popmin())
         .flat_map(|(s, m)| [Response::BulkString(m), Response::float(s)])
-        .collect();
+        .collect::<Vec<_>>();
+    if !res.is_empty() {
+        db.notify('z', "zpopmin", &key);
+    }
     Ok(Response::Array(res))
 }
 
@@ -30,4 +33,5 @@ crate::command_test! {
     "zpopmin x 3" => ["a", "1", "b", "2", "c", "3"];
     "zcard x" => 1;
     "zpopmin x" => ["d", "4"];
+    "exists x" => 0;
 }
@@ -17,9 +17,12 @@ pub static INFO: CommandInfo = CommandInfo {
 pub fn run(db: &mut Database, mut cmd: Command) -> anyhow::Result<Response> {
     let (key, members) = cmd.parse_args::<(ByteString, Vec<ByteString>)>()?;
     anyhow::ensure!(!members.is_empty(), "expected ZREM key member [member..]");
-    let removed = db.get_zset(&key)?
+    let removed = db.get_zset_mut(&key)?
         .map(|z| members.into_iter().filter_map(|m| z.remove(m)).count())
         .unwrap_or(0);
+    if removed > 0 {
+        db.notify('z', "zrem", &key);
+    }
     Ok(Response::Number(removed as _))
 }
 
@@ -30,5 +33,6 @@ crate::command_test! {
     "zcard x"        => 1;
     "zrem x x y z"   => 0;
     "zrem x a"       => 1;
+    "exists x"       => 0;
     "zrem q a b"     => 0;
 }
@@ -0,0 +1,306 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::acl::glob_matches;
+use crate::memory::Policy;
+use crate::pubsub::notify_keyspace_events;
+
+struct Param {
+    name: &'static str,
+    default: &'static str,
+    mutable: bool,
+    normalize: fn(&str) -> anyhow::Result<String>,
+}
+
+fn any(v: &str) -> anyhow::Result<String> {
+    Ok(v.to_string())
+}
+
+fn int(v: &str) -> anyhow::Result<String> {
+    let n = v.parse::<i64>().map_err(|_| anyhow::anyhow!("argument couldn't be parsed into an integer"))?;
+    anyhow::ensure!(n >= 0, "argument must be a non-negative integer");
+    Ok(n.to_string())
+}
+
+fn port(v: &str) -> anyhow::Result<String> {
+    let n = v.parse::<u16>().map_err(|_| anyhow::anyhow!("argument must be between 0 and 65535"))?;
+    Ok(n.to_string())
+}
+
+fn octal(v: &str) -> anyhow::Result<String> {
+    u32::from_str_radix(v, 8).map_err(|_| anyhow::anyhow!("argument must be an octal number"))?;
+    Ok(v.to_string())
+}
+
+fn bool(v: &str) -> anyhow::Result<String> {
+    match v.to_ascii_lowercase().as_str() {
+        "yes" => Ok("yes".to_string()),
+        "no" => Ok("no".to_string()),
+        _ => anyhow::bail!("argument must be 'yes' or 'no'"),
+    }
+}
+
+fn tls_auth_clients(v: &str) -> anyhow::Result<String> {
+    match v.to_ascii_lowercase().as_str() {
+        v @ ("yes" | "no" | "optional") => Ok(v.to_string()),
+        _ => anyhow::bail!("argument must be 'yes', 'no' or 'optional'"),
+    }
+}
+
+fn positive(v: &str) -> anyhow::Result<String> {
+    let n = int(v)?;
+    anyhow::ensure!(n != "0", "argument must be greater than 0");
+    Ok(n)
+}
+
+fn maxmemory_policy(v: &str) -> anyhow::Result<String> {
+    let v = v.to_ascii_lowercase();
+    Policy::parse(&v)?;
+    Ok(v)
+}
+
+/// `host port`, or empty when not a replica.
+fn replicaof(v: &str) -> anyhow::Result<String> {
+    let args = v.split_whitespace().collect::<Vec<_>>();
+    match args.as_slice() {
+        [] => Ok(String::new()),
+        [host, port] if port.parse::<u16>().is_ok() => Ok(format!("{host} {port}")),
+        _ => anyhow::bail!("argument must be 'host port'"),
+    }
+}
+
+/// Memory sizes accept the usual redis.conf units, e.g. `100mb` or `1gb`.
+fn memory(v: &str) -> anyhow::Result<String> {
+    let lower = v.to_ascii_lowercase();
+    let split = lower.find(|c: char| !c.is_ascii_digit()).unwrap_or(lower.len());
+    let (digits, unit) = lower.split_at(split);
+    let invalid = || anyhow::anyhow!("argument must be a memory value");
+    let multiplier: u64 = match unit {
+        "" | "b" => 1,
+        "k" => 1000,
+        "kb" => 1 << 10,
+        "m" => 1000 * 1000,
+        "mb" => 1 << 20,
+        "g" => 1000 * 1000 * 1000,
+        "gb" => 1 << 30,
+        _ => return Err(invalid()),
+    };
+    let n = digits.parse::<u64>().map_err(|_| invalid())?;
+    Ok(n.checked_mul(multiplier).ok_or_else(invalid)?.to_string())
+}
+
+static PARAMS: &[Param] = &[
+    Param { name: "aclfile", default: "", mutable: false, normalize: any },
+    Param { name: "bind", default: "127.0.0.1 -::1", mutable: false, normalize: any },
+    Param { name: "busy-reply-threshold", default: "5000", mutable: true, normalize: int },
+    Param { name: "cluster-announce-ip", default: "", mutable: false, normalize: any },
+    Param { name: "cluster-config-file", default: "nodes.conf", mutable: false, normalize: any },
+    Param { name: "cluster-enabled", default: "no", mutable: false, normalize: bool },
+    Param { name: "hash-max-listpack-entries", default: "128", mutable: true, normalize: int },
+    Param { name: "hash-max-listpack-value", default: "64", mutable: true, normalize: int },
+    Param { name: "hll-sparse-max-bytes", default: "3000", mutable: true, normalize: int },
+    Param { name: "lfu-decay-time", default: "1", mutable: true, normalize: int },
+    Param { name: "lfu-log-factor", default: "10", mutable: true, normalize: int },
+    Param { name: "maxmemory", default: "0", mutable: true, normalize: memory },
+    Param { name: "maxmemory-policy", default: "noeviction", mutable: true, normalize: maxmemory_policy },
+    Param { name: "maxmemory-samples", default: "5", mutable: true, normalize: positive },
+    Param { name: "masterauth", default: "", mutable: true, normalize: any },
+    Param { name: "masteruser", default: "", mutable: true, normalize: any },
+    Param { name: "notify-keyspace-events", default: "", mutable: true, normalize: notify_keyspace_events },
+    Param { name: "port", default: "8888", mutable: false, normalize: port },
+    Param { name: "protected-mode", default: "yes", mutable: true, normalize: bool },
+    Param { name: "repl-backlog-size", default: "1048576", mutable: true, normalize: memory },
+    Param { name: "replica-read-only", default: "yes", mutable: true, normalize: bool },
+    Param { name: "replicaof", default: "", mutable: false, normalize: replicaof },
+    Param { name: "requirepass", default: "", mutable: true, normalize: any },
+    Param { name: "set-max-intset-entries", default: "512", mutable: true, normalize: int },
+    Param { name: "set-max-listpack-entries", default: "128", mutable: true, normalize: int },
+    Param { name: "set-max-listpack-value", default: "64", mutable: true, normalize: int },
+    Param { name: "timeout", default: "0", mutable: true, normalize: int },
+    Param { name: "tls-auth-clients", default: "yes", mutable: false, normalize: tls_auth_clients },
+    Param { name: "tls-ca-cert-file", default: "", mutable: false, normalize: any },
+    Param { name: "tls-cert-file", default: "", mutable: false, normalize: any },
+    Param { name: "tls-key-file", default: "", mutable: false, normalize: any },
+    Param { name: "tls-port", default: "0", mutable: false, normalize: port },
+    Param { name: "unixsocket", default: "", mutable: false, normalize: any },
+    Param { name: "unixsocketperm", default: "700", mutable: false, normalize: octal },
+    Param { name: "zset-max-listpack-entries", default: "128", mutable: true, normalize: int },
+    Param { name: "zset-max-listpack-value", default: "64", mutable: true, normalize: int },
+];
+
+fn param(name: &str) -> Option<&'static Param> {
+    PARAMS.iter().find(|p| p.name.eq_ignore_ascii_case(name))
+}
+
+/// Splits a redis.conf line into arguments, handling "double" and 'single' quotes.
+fn split_line(line: &str) -> anyhow::Result<Vec<String>> {
+    let mut args = Vec::new();
+    let mut chars = line.trim().chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let mut arg = String::new();
+        match c {
+            '"' | '\'' => {
+                chars.next();
+                loop {
+                    match chars.next() {
+                        Some(q) if q == c => break,
+                        Some('\\') if c == '"' => match chars.next() {
+                            Some('n') => arg.push('\n'),
+                            Some('r') => arg.push('\r'),
+                            Some('t') => arg.push('\t'),
+                            Some(e) => arg.push(e),
+                            None => anyhow::bail!("unbalanced quotes"),
+                        },
+                        Some(ch) => arg.push(ch),
+                        None => anyhow::bail!("unbalanced quotes"),
+                    }
+                }
+                anyhow::ensure!(chars.peek().is_none_or(|c| c.is_whitespace()), "closing quote must be followed by a space");
+            }
+            _ => {
+                while let Some(&ch) = chars.peek().filter(|c| !c.is_whitespace()) {
+                    arg.push(ch);
+                    chars.next();
+                }
+            }
+        }
+        args.push(arg);
+    }
+    Ok(args)
+}
+
+fn quote(value: &str) -> String {
+    if !value.is_empty() && value.chars().all(|c| c.is_ascii_graphic() && c != '"' && c != '\'') {
+        return value.to_string();
+    }
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    values: BTreeMap<&'static str, String>,
+    file: Option<PathBuf>,
+    /// The directives of the config file we do not support, with their location.
+    unsupported: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let values = PARAMS.iter().map(|p| (p.name, p.default.to_string())).collect();
+        Self { values, file: None, unsupported: Vec::new() }
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("failed to read config file {}: {e}", path.display()))?;
+        let mut config = Self { file: Some(path.to_path_buf()), ..Self::default() };
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let context = || format!("{}:{}", path.display(), i + 1);
+            let args = split_line(line).map_err(|e| anyhow::anyhow!("{}: {e}", context()))?;
+            // skipped rather than refusing to start with a stock redis.conf, which sets `save`,
+            // `appendonly` and the like
+            if param(&args[0]).is_none() {
+                config.unsupported.push(format!("{}: {}", context(), args[0]));
+                continue;
+            }
+            config.set(&args[0], &args[1..].join(" ")).map_err(|e| anyhow::anyhow!("{}: {e}", context()))?;
+        }
+        Ok(config)
+    }
+
+    /// The directives skipped by `load` as unsupported, to be warned about.
+    pub fn unsupported(&self) -> &[String] {
+        &self.unsupported
+    }
+
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+
+    pub fn get(&self, name: &str) -> &str {
+        self.values.get(name).map(|v| v.as_str()).unwrap_or_default()
+    }
+
+    pub fn get_parsed<T: FromStr>(&self, name: &str) -> T {
+        self.get(name).parse().unwrap_or_else(|_| panic!("config {name} is always validated"))
+    }
+
+    pub fn get_bool(&self, name: &str) -> bool {
+        self.get(name) == "yes"
+    }
+
+    /// Empty values mean the parameter is unset.
+    pub fn get_optional(&self, name: &str) -> Option<&str> {
+        Some(self.get(name)).filter(|v| !v.is_empty())
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) -> anyhow::Result<()> {
+        let param = param(name).ok_or_else(|| anyhow::anyhow!("Bad directive or wrong number of arguments: '{name}'"))?;
+        let value = (param.normalize)(value)?;
+        self.values.insert(param.name, value);
+        Ok(())
+    }
+
+    /// Sets the given parameters at runtime, either all of them or none.
+    pub fn set_runtime(&mut self, params: &[(String, String)]) -> anyhow::Result<Vec<&'static str>> {
+        let mut changed = Vec::new();
+        let mut updated = self.values.clone();
+        for (name, value) in params {
+            let Some(param) = param(name) else {
+                anyhow::bail!("Unknown option or number of arguments for CONFIG SET - '{name}'");
+            };
+            anyhow::ensure!(param.mutable, "CONFIG SET failed (possibly related to argument '{name}') - can't set immutable config");
+            anyhow::ensure!(!changed.contains(&param.name), "CONFIG SET failed (possibly related to argument '{name}') - duplicate parameter");
+            let value = (param.normalize)(value).map_err(|e| anyhow::anyhow!("CONFIG SET failed (possibly related to argument '{name}') - {e}"))?;
+            updated.insert(param.name, value);
+            changed.push(param.name);
+        }
+        self.values = updated;
+        Ok(changed)
+    }
+
+    pub fn matching(&self, pattern: &[u8]) -> impl Iterator<Item=(&str, &str)> {
+        let pattern = pattern.to_ascii_lowercase();
+        self.values.iter()
+            .filter(move |(name, _)| glob_matches(&pattern, name.as_bytes()))
+            .map(|(name, value)| (*name, value.as_str()))
+    }
+
+    /// Writes the current configuration back to the config file, keeping comments and
+    /// unrelated lines in place and only adding parameters that differ from the default.
+    pub fn rewrite(&self) -> anyhow::Result<()> {
+        let Some(path) = &self.file else {
+            anyhow::bail!("The server is running without a config file");
+        };
+        let contents = std::fs::read_to_string(path).unwrap_or_default();
+        let mut written = Vec::new();
+        let mut lines = Vec::new();
+        for line in contents.lines() {
+            let name = split_line(line).ok().and_then(|args| args.first().and_then(|n| param(n)).map(|p| p.name));
+            match name {
+                Some(name) if !written.contains(&name) => {
+                    written.push(name);
+                    lines.push(format!("{name} {}", quote(self.get(name))));
+                }
+                Some(_) => {}
+                None => lines.push(line.to_string()),
+            }
+        }
+        for p in PARAMS.iter().filter(|p| !written.contains(&p.name) && self.get(p.name) != p.default) {
+            lines.push(format!("{} {}", p.name, quote(self.get(p.name))));
+        }
+        std::fs::write(path, lines.join("\n") + "\n")?;
+        Ok(())
+    }
+}
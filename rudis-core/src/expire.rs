@@ -0,0 +1,81 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand::Rng;
+
+use crate::{replication, ByteString, Database};
+
+/// Keys with an expire sampled per active expire cycle, see `Database::active_expire_cycle`.
+const ACTIVE_EXPIRE_SAMPLES: usize = 20;
+
+/// The current unix time in milliseconds, the unit expires are stored in.
+pub fn now_ms() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as i64)
+}
+
+impl Database {
+    /// The unix time in milliseconds at which the key expires, if it has an expire.
+    pub fn expiry(&self, key: &[u8]) -> Option<i64> {
+        self.expires.get(key).copied()
+    }
+
+    /// Sets the expire of an existing key, a time in the past making it expire on the next access.
+    pub fn set_expiry(&mut self, key: &[u8], at: i64) {
+        if let Some((key, _)) = self.state.get_key_value(key) {
+            self.expires.insert(key.clone(), at);
+        }
+    }
+
+    /// Removes the expire of a key, returns whether it had one.
+    pub fn persist(&mut self, key: &[u8]) -> bool {
+        self.expires.swap_remove(key).is_some()
+    }
+
+    /// Whether the key expired, in which case it is deleted unless we are a replica: replicas
+    /// wait for the DEL of their master to keep their keyspace consistent with it, and
+    /// commands of the master are applied to the keys as they are.
+    pub(crate) fn expire_if_needed(&mut self, key: &[u8]) -> bool {
+        if self.is_master_client() || self.expires.get(key).is_none_or(|&at| at > now_ms()) {
+            return false;
+        }
+        if self.master().is_none() {
+            self.delete_expired(key.to_vec());
+        }
+        true
+    }
+
+    fn delete_expired(&mut self, key: ByteString) {
+        self.del(&key);
+        self.stats.expired_keys += 1;
+        self.notify('x', "expired", &key);
+        self.propagate(replication::encode(vec![b"DEL".to_vec(), key]));
+    }
+
+    /// Deletes expired keys in the background by sampling the keys with an expire, repeating
+    /// while more than a quarter of the sample turned out to be expired.
+    pub(crate) fn active_expire_cycle(&mut self) {
+        if self.master().is_some() {
+            return;
+        }
+        loop {
+            let now = now_ms();
+            let samples = ACTIVE_EXPIRE_SAMPLES.min(self.expires.len());
+            let expired = (0..samples)
+                .filter_map(|_| {
+                    let i = self.rng.random_range(0..self.expires.len());
+                    self.expires.get_index(i).filter(|(_, at)| **at <= now).map(|(k, _)| k.clone())
+                })
+                .collect::<Vec<_>>();
+            let count = expired.len();
+            for key in expired {
+                if self.expires.contains_key(&key) {
+                    self.delete_expired(key);
+                }
+            }
+            if count * 4 <= samples || self.expires.is_empty() {
+                break;
+            }
+        }
+        self.active_expire_hash_fields();
+        self.refresh_dirty(true);
+    }
+}
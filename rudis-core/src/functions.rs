@@ -0,0 +1,249 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use mlua::{Function, Lua, LuaString, MultiValue, Table, Value as LuaValue};
+
+use crate::scripting::{lua_error_message, new_lua, ScriptArgs, ScriptState};
+use crate::{ByteString, Database, Response};
+
+const FLAGS: &[&str] = &["no-writes", "allow-oom", "allow-stale", "no-cluster", "allow-cross-slot-keys"];
+
+#[derive(Clone)]
+pub(crate) struct LibraryFunction {
+    callback: Function,
+    flags: Vec<String>,
+    description: Option<String>,
+}
+
+#[derive(Clone)]
+pub(crate) struct Library {
+    code: ByteString,
+    functions: BTreeMap<String, LibraryFunction>,
+}
+
+/// What FUNCTION RESTORE does with the libraries that are already loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestorePolicy {
+    /// Fails if one of the libraries already exists.
+    Append,
+    /// Replaces the libraries with the same name.
+    Replace,
+    /// Deletes all the libraries first.
+    Flush,
+}
+
+/// Libraries loaded with FUNCTION LOAD, which run in their own Lua state.
+pub(crate) struct Functions {
+    lua: Lua,
+    libraries: BTreeMap<String, Library>,
+}
+
+impl Functions {
+    pub(crate) fn new(state: &ScriptState) -> Self {
+        Self { lua: new_lua(state), libraries: BTreeMap::new() }
+    }
+
+    fn function(&self, name: &str) -> Option<&LibraryFunction> {
+        self.libraries.values().find_map(|lib| lib.functions.get(name))
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_')
+}
+
+/// Parses the `#!lua name=<library>` first line of a library.
+fn parse_metadata(code: &[u8]) -> anyhow::Result<String> {
+    let Some(header) = code.strip_prefix(b"#!") else {
+        anyhow::bail!("Missing library metadata");
+    };
+    let line = header.split(|&b| b == b'\n').next().unwrap_or_default();
+    let line = String::from_utf8_lossy(line);
+    let mut fields = line.split_whitespace();
+    let engine = fields.next().unwrap_or_default();
+    anyhow::ensure!(engine.eq_ignore_ascii_case("lua"), "Engine '{engine}' not found");
+    let mut name = None;
+    for field in fields {
+        match field.split_once('=') {
+            Some(("name", value)) => name = Some(value.to_string()),
+            _ => anyhow::bail!("Invalid metadata value given: {field}"),
+        }
+    }
+    let name = name.ok_or_else(|| anyhow::anyhow!("Library name was not given"))?;
+    anyhow::ensure!(is_valid_name(&name), "Library names can only contain letters, numbers, or underscores(_) and must be at least one character long");
+    Ok(name)
+}
+
+/// The arguments of `redis.register_function`, either `(name, callback)` or a table with
+/// `function_name`, `callback` and optionally `flags` and `description`.
+fn parse_registration(args: MultiValue) -> anyhow::Result<(String, LibraryFunction)> {
+    let args = args.into_vec();
+    let (name, callback, flags, description) = match args.as_slice() {
+        [LuaValue::String(name), LuaValue::Function(callback)] => (name.to_string_lossy(), callback.clone(), Vec::new(), None),
+        [LuaValue::Table(table)] => {
+            let (mut name, mut callback, mut flags, mut description) = (None, None, Vec::new(), None);
+            for pair in table.pairs::<LuaString, LuaValue>() {
+                let (key, value) = pair?;
+                match (key.to_string_lossy().as_str(), value) {
+                    ("function_name", LuaValue::String(s)) => name = Some(s.to_string_lossy()),
+                    ("callback", LuaValue::Function(f)) => callback = Some(f),
+                    ("description", LuaValue::String(s)) => description = Some(s.to_string_lossy()),
+                    ("flags", LuaValue::Table(t)) => {
+                        for flag in t.sequence_values::<LuaString>() {
+                            let flag = flag?.to_string_lossy();
+                            anyhow::ensure!(FLAGS.contains(&flag.as_str()), "unknown flag given");
+                            flags.push(flag);
+                        }
+                    }
+                    _ => anyhow::bail!("unknown argument given to redis.register_function"),
+                }
+            }
+            let name = name.ok_or_else(|| anyhow::anyhow!("redis.register_function must get a function name argument"))?;
+            let callback = callback.ok_or_else(|| anyhow::anyhow!("redis.register_function must get a callback argument"))?;
+            (name, callback, flags, description)
+        }
+        _ => anyhow::bail!("wrong number of arguments to redis.register_function"),
+    };
+    anyhow::ensure!(is_valid_name(&name), "Function names can only contain letters, numbers, or underscores(_) and must be at least one character long");
+    Ok((name, LibraryFunction { callback, flags, description }))
+}
+
+/// Runs the code of a library, collecting the functions it registers.
+fn load_library(lua: &Lua, code: &[u8]) -> anyhow::Result<(String, Library)> {
+    let name = parse_metadata(code)?;
+    // the metadata line is not Lua, keep it empty so that line numbers stay right
+    let body = &code[code.iter().position(|&b| b == b'\n').unwrap_or(code.len())..];
+    let functions = RefCell::new(BTreeMap::new());
+    let redis = lua.named_registry_value::<Table>("redis").map_err(|e| anyhow::anyhow!("{e}"))?;
+    let res = lua.scope(|scope| {
+        let register = scope.create_function(|_, args: MultiValue| {
+            let (name, function) = parse_registration(args).map_err(mlua::Error::runtime)?;
+            let mut functions = functions.borrow_mut();
+            if functions.contains_key(&name) {
+                return Err(mlua::Error::runtime("Function already exists in the library"));
+            }
+            functions.insert(name, function);
+            Ok(())
+        })?;
+        redis.raw_set("register_function", register)?;
+        lua.load(body).set_name("@user_function").exec()
+    });
+    let _ = redis.raw_set("register_function", LuaValue::Nil);
+    res.map_err(|e| anyhow::anyhow!("Error registering functions: {}", lua_error_message(&e)))?;
+    let functions = functions.into_inner();
+    anyhow::ensure!(!functions.is_empty(), "No functions registered");
+    Ok((name, Library { code: code.to_vec(), functions }))
+}
+
+impl Database {
+    /// Loads the libraries at once, all of them or none.
+    pub fn function_restore(&mut self, codes: &[ByteString], policy: RestorePolicy) -> anyhow::Result<Vec<String>> {
+        let functions = &mut self.scripting.functions;
+        let mut loaded = Vec::new();
+        for code in codes {
+            loaded.push(load_library(&functions.lua, code)?);
+        }
+        let mut libraries = match policy {
+            RestorePolicy::Flush => BTreeMap::new(),
+            _ => functions.libraries.clone(),
+        };
+        let mut names = Vec::new();
+        for (name, library) in loaded {
+            anyhow::ensure!(policy != RestorePolicy::Append || !libraries.contains_key(&name), "Library '{name}' already exists");
+            libraries.insert(name.clone(), library);
+            names.push(name);
+        }
+        let mut function_names = libraries.values().flat_map(|lib| lib.functions.keys()).collect::<Vec<_>>();
+        function_names.sort();
+        if let Some(w) = function_names.windows(2).find(|w| w[0] == w[1]) {
+            anyhow::bail!("Function {} already exists", w[0]);
+        }
+        functions.libraries = libraries;
+        Ok(names)
+    }
+
+    /// FUNCTION LOAD, returns the name of the library.
+    pub fn function_load(&mut self, code: &[u8], replace: bool) -> anyhow::Result<String> {
+        let policy = if replace { RestorePolicy::Replace } else { RestorePolicy::Append };
+        let mut names = self.function_restore(&[code.to_vec()], policy)?;
+        Ok(names.remove(0))
+    }
+
+    pub fn function_delete(&mut self, library: &str) -> anyhow::Result<()> {
+        anyhow::ensure!(self.scripting.functions.libraries.remove(library).is_some(), "Library not found");
+        Ok(())
+    }
+
+    pub fn function_flush(&mut self) {
+        self.scripting.functions.libraries.clear();
+    }
+
+    /// The code of the loaded libraries, as persisted with FUNCTION DUMP and in snapshots.
+    pub fn function_codes(&self) -> Vec<ByteString> {
+        self.scripting.functions.libraries.values().map(|lib| lib.code.clone()).collect()
+    }
+
+    /// FUNCTION LIST, for the libraries whose name matches `pattern`.
+    pub fn function_list(&self, pattern: Option<&[u8]>, with_code: bool) -> Response {
+        let libraries = self.scripting.functions.libraries.iter()
+            .filter(|(name, _)| pattern.is_none_or(|p| glob_match::glob_match(&String::from_utf8_lossy(p), name)))
+            .map(|(name, library)| {
+                let functions = library.functions.iter().map(|(name, function)| Response::Array(vec![
+                    Response::BulkString(b"name".to_vec()),
+                    Response::BulkString(name.as_bytes().to_vec()),
+                    Response::BulkString(b"description".to_vec()),
+                    function.description.as_ref().map(|d| Response::BulkString(d.as_bytes().to_vec())).unwrap_or_default(),
+                    Response::BulkString(b"flags".to_vec()),
+                    Response::string_array(function.flags.iter().map(|f| f.as_bytes().to_vec())),
+                ]));
+                let mut fields = vec![
+                    Response::BulkString(b"library_name".to_vec()),
+                    Response::BulkString(name.as_bytes().to_vec()),
+                    Response::BulkString(b"engine".to_vec()),
+                    Response::BulkString(b"LUA".to_vec()),
+                    Response::BulkString(b"functions".to_vec()),
+                    Response::Array(functions.collect()),
+                ];
+                if with_code {
+                    fields.push(Response::BulkString(b"library_code".to_vec()));
+                    fields.push(Response::BulkString(library.code.clone()));
+                }
+                Response::Array(fields)
+            });
+        Response::Array(libraries.collect())
+    }
+
+    /// FUNCTION STATS, scripts are never reported as running since the database can only
+    /// answer once they are over.
+    pub fn function_stats(&self) -> Response {
+        let libraries = &self.scripting.functions.libraries;
+        let functions = libraries.values().map(|lib| lib.functions.len()).sum::<usize>();
+        Response::Array(vec![
+            Response::BulkString(b"running_script".to_vec()),
+            Response::Nil,
+            Response::BulkString(b"engines".to_vec()),
+            Response::Array(vec![
+                Response::BulkString(b"LUA".to_vec()),
+                Response::Array(vec![
+                    Response::BulkString(b"libraries_count".to_vec()),
+                    Response::Number(libraries.len() as _),
+                    Response::BulkString(b"functions_count".to_vec()),
+                    Response::Number(functions as _),
+                ]),
+            ]),
+        ])
+    }
+
+    /// FCALL and FCALL_RO, `read_only` being set for the latter.
+    pub fn fcall(&mut self, name: &[u8], keys: Vec<ByteString>, args: Vec<ByteString>, read_only: bool) -> anyhow::Result<Response> {
+        let name = String::from_utf8_lossy(name).to_string();
+        let functions = &self.scripting.functions;
+        let Some(function) = functions.function(&name) else {
+            anyhow::bail!("Function not found");
+        };
+        let no_writes = function.flags.iter().any(|f| f == "no-writes");
+        anyhow::ensure!(!read_only || no_writes, "Can not execute a script with write flag using *_ro command.");
+        let (lua, callback) = (functions.lua.clone(), function.callback.clone());
+        self.run_script(&lua, &callback, &name, ScriptArgs::Parameters(keys, args), no_writes)
+    }
+}
@@ -0,0 +1,174 @@
+//! Geohash encoding of coordinates into the 52 bits scores of geo sets, compatible with the
+//! sorted sets Redis stores for the GEO commands.
+
+const LON_MIN: f64 = -180.0;
+const LON_MAX: f64 = 180.0;
+/// The latitudes of the Web Mercator projection.
+const LAT_MIN: f64 = -85.05112878;
+const LAT_MAX: f64 = 85.05112878;
+/// Bits per coordinate, interleaved in a 52 bits geohash.
+const STEP_MAX: u32 = 26;
+const EARTH_RADIUS: f64 = 6372797.560856;
+const ALPHABET: &[u8] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+/// Spreads the bits of `x` over the even bits and the ones of `y` over the odd bits.
+fn interleave(x: u32, y: u32) -> u64 {
+    (0..32).fold(0, |bits, i| bits | ((x as u64 >> i) & 1) << (2 * i) | ((y as u64 >> i) & 1) << (2 * i + 1))
+}
+
+fn deinterleave(bits: u64) -> (u32, u32) {
+    (0..32).fold((0, 0), |(x, y), i| (x | (((bits >> (2 * i)) & 1) as u32) << i, y | (((bits >> (2 * i + 1)) & 1) as u32) << i))
+}
+
+fn encode_with(lon: f64, lat: f64, lat_min: f64, lat_max: f64, step: u32) -> u64 {
+    let cells = (1u64 << step) as f64;
+    let lat_offset = ((lat - lat_min) / (lat_max - lat_min) * cells).min(cells - 1.0);
+    let lon_offset = ((lon - LON_MIN) / (LON_MAX - LON_MIN) * cells).min(cells - 1.0);
+    interleave(lat_offset as u32, lon_offset as u32)
+}
+
+pub(crate) fn validate(lon: f64, lat: f64) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        (LON_MIN..=LON_MAX).contains(&lon) && (LAT_MIN..=LAT_MAX).contains(&lat),
+        "invalid longitude,latitude pair {lon:.6},{lat:.6}",
+    );
+    Ok(())
+}
+
+/// The 52 bits geohash of valid coordinates, used as score.
+pub(crate) fn encode(lon: f64, lat: f64) -> u64 {
+    encode_with(lon, lat, LAT_MIN, LAT_MAX, STEP_MAX)
+}
+
+/// The longitude and latitude at the center of the area of a geohash.
+pub(crate) fn decode(bits: u64) -> (f64, f64) {
+    let (lat, lon) = deinterleave(bits);
+    let cells = (1u64 << STEP_MAX) as f64;
+    let center = |i: u32, min: f64, max: f64| {
+        let low = min + i as f64 / cells * (max - min);
+        let high = min + (i as f64 + 1.0) / cells * (max - min);
+        ((low + high) / 2.0).clamp(min, max)
+    };
+    (center(lon, LON_MIN, LON_MAX), center(lat, LAT_MIN, LAT_MAX))
+}
+
+/// The standard 11 characters geohash, whose latitudes go from -90 to 90.
+pub(crate) fn hash_string(bits: u64) -> String {
+    let (lon, lat) = decode(bits);
+    let bits = encode_with(lon, lat, -90.0, 90.0, STEP_MAX);
+    (0..11)
+        .map(|i| {
+            // only 52 bits for 55 bits of characters, the last one is always 0
+            let idx = if i == 10 { 0 } else { (bits >> (52 - (i + 1) * 5)) & 0x1f };
+            ALPHABET[idx as usize] as char
+        })
+        .collect()
+}
+
+fn lat_distance(lat1: f64, lat2: f64) -> f64 {
+    EARTH_RADIUS * (lat2.to_radians() - lat1.to_radians()).abs()
+}
+
+/// The haversine distance in meters between two points.
+pub(crate) fn distance((lon1, lat1): (f64, f64), (lon2, lat2): (f64, f64)) -> f64 {
+    let v = ((lon2.to_radians() - lon1.to_radians()) / 2.0).sin();
+    if v == 0.0 {
+        return lat_distance(lat1, lat2);
+    }
+    let u = ((lat2.to_radians() - lat1.to_radians()) / 2.0).sin();
+    let a = u * u + lat1.to_radians().cos() * lat2.to_radians().cos() * v * v;
+    2.0 * EARTH_RADIUS * a.sqrt().asin()
+}
+
+/// Meters per unit of the GEO commands.
+pub(crate) fn parse_unit(unit: &[u8]) -> anyhow::Result<f64> {
+    match unit.to_ascii_lowercase().as_slice() {
+        b"m" => Ok(1.0),
+        b"km" => Ok(1000.0),
+        b"ft" => Ok(0.3048),
+        b"mi" => Ok(1609.34),
+        _ => anyhow::bail!("unsupported unit provided. please use M, KM, FT, MI"),
+    }
+}
+
+/// The area searched by GEOSEARCH, in meters.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Shape {
+    Radius(f64),
+    Box { width: f64, height: f64 },
+}
+
+impl Shape {
+    /// The distance between the center and a point within the shape.
+    pub(crate) fn distance_within(&self, center: (f64, f64), point: (f64, f64)) -> Option<f64> {
+        match *self {
+            Shape::Radius(radius) => Some(distance(center, point)).filter(|&d| d <= radius),
+            Shape::Box { width, height } => {
+                if lat_distance(point.1, center.1) > height / 2.0 {
+                    return None;
+                }
+                if distance((point.0, point.1), (center.0, point.1)) > width / 2.0 {
+                    return None;
+                }
+                Some(distance(center, point))
+            }
+        }
+    }
+
+    /// The inclusive score ranges of the geohash cells covering the shape: the cell of the
+    /// center and its neighbors, at the finest step where a cell is larger than half the shape.
+    pub(crate) fn score_ranges(&self, (lon, lat): (f64, f64)) -> Vec<(f64, f64)> {
+        let (half_width, half_height) = match *self {
+            Shape::Radius(radius) => (radius, radius),
+            Shape::Box { width, height } => (width / 2.0, height / 2.0),
+        };
+        let dlat = (half_height / EARTH_RADIUS).to_degrees();
+        // longitudes get closer towards the poles
+        let edge = (lat.abs() + dlat).min(90.0);
+        let dlon = (half_width / (EARTH_RADIUS * edge.to_radians().cos())).to_degrees();
+        let fits = |step: u32| {
+            let cells = (1u64 << step) as f64;
+            (LON_MAX - LON_MIN) / cells >= dlon && (LAT_MAX - LAT_MIN) / cells >= dlat
+        };
+        let Some(step) = (1..=STEP_MAX).rev().find(|&step| fits(step)) else {
+            return vec![(0.0, ((1u64 << (2 * STEP_MAX)) - 1) as f64)];
+        };
+        let shift = 2 * (STEP_MAX - step);
+        let (y, x) = deinterleave(encode(lon, lat) >> shift);
+        let cells = 1i64 << step;
+        let mut hashes = Vec::with_capacity(9);
+        for dy in -1..=1 {
+            let y = y as i64 + dy;
+            if !(0..cells).contains(&y) {
+                continue;
+            }
+            for dx in -1..=1 {
+                hashes.push(interleave(y as u32, (x as i64 + dx).rem_euclid(cells) as u32));
+            }
+        }
+        hashes.sort_unstable();
+        hashes.dedup();
+        hashes.into_iter().map(|h| ((h << shift) as f64, (((h + 1) << shift) - 1) as f64)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        // scores and geohashes of the examples of the Redis documentation
+        assert_eq!(encode(13.361389, 38.115556), 3479099956230698);
+        assert_eq!(hash_string(3479099956230698), "sqc8b49rny0");
+        assert_eq!(hash_string(encode(15.087269, 37.502669)), "sqdtr74hyu0");
+        let (lon, lat) = decode(3479099956230698);
+        assert!((lon - 13.361389).abs() < 1e-5 && (lat - 38.115556).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_distance() {
+        let d = distance((13.361389, 38.115556), (15.087269, 37.502669));
+        assert!((d - 166274.1516).abs() < 1.0, "{d}");
+    }
+}
@@ -0,0 +1,335 @@
+use std::collections::HashMap;
+
+use indexmap::IndexMap;
+use rand::Rng;
+
+use crate::commands::scan_step;
+use crate::expire::now_ms;
+use crate::listpack::Listpack;
+use crate::memory::{ListpackLimits, MemoryConfig};
+use crate::{memory, replication, ByteString, Database, Value};
+
+/// Hashes with fields expiring sampled per active expire cycle.
+const ACTIVE_EXPIRE_SAMPLES: usize = 20;
+
+/// A field with its value.
+type Pair<'a> = (&'a [u8], &'a [u8]);
+
+#[derive(Debug, Clone)]
+enum Encoding {
+    /// Fields followed by their value.
+    Listpack(Listpack),
+    /// Indexed for picking random fields.
+    HashTable(IndexMap<ByteString, ByteString>),
+}
+
+/// A hash value, whose fields can expire independently of the key. It is in a compact
+/// encoding while small, see `Hash::fit_encoding`.
+#[derive(Debug, Clone)]
+pub struct Hash {
+    fields: Encoding,
+    /// The unix time in milliseconds at which fields expire.
+    expires: HashMap<ByteString, i64>,
+    /// The limits of the listpack encoding, checked as fields are set.
+    limits: ListpackLimits,
+}
+
+impl Default for Hash {
+    fn default() -> Self {
+        Self { fields: Encoding::Listpack(Listpack::new()), expires: HashMap::new(), limits: MemoryConfig::default().hash }
+    }
+}
+
+impl PartialEq for Hash {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.expires == other.expires && self.iter().all(|(k, v)| other.get(k) == Some(v))
+    }
+}
+
+impl Eq for Hash {}
+
+impl FromIterator<(ByteString, ByteString)> for Hash {
+    fn from_iter<T: IntoIterator<Item = (ByteString, ByteString)>>(iter: T) -> Self {
+        Self { fields: Encoding::HashTable(iter.into_iter().collect()), ..Self::default() }
+    }
+}
+
+/// The position of a field in a listpack.
+fn position(lp: &Listpack, field: &[u8]) -> Option<usize> {
+    lp.iter().step_by(2).position(|f| f == field).map(|i| 2 * i)
+}
+
+impl Hash {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.fields {
+            Encoding::Listpack(lp) => lp.len() / 2,
+            Encoding::HashTable(h) => h.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, field: &[u8]) -> Option<&[u8]> {
+        match &self.fields {
+            Encoding::Listpack(lp) => position(lp, field).and_then(|i| lp.get(i + 1)),
+            Encoding::HashTable(h) => h.get(field).map(|v| v.as_slice()),
+        }
+    }
+
+    pub fn contains_key(&self, field: &[u8]) -> bool {
+        self.get(field).is_some()
+    }
+
+    pub fn iter(&self) -> Box<dyn Iterator<Item = (&[u8], &[u8])> + '_> {
+        match &self.fields {
+            Encoding::Listpack(lp) => {
+                let mut elements = lp.iter();
+                Box::new(std::iter::from_fn(move || Some((elements.next()?, elements.next()?))))
+            }
+            Encoding::HashTable(h) => Box::new(h.iter().map(|(k, v)| (k.as_slice(), v.as_slice()))),
+        }
+    }
+
+    /// The field at `index` in the order of `iter` with its value, found without iterating for
+    /// the hash table encoding, as done to pick random fields.
+    pub fn get_index(&self, index: usize) -> Option<(&[u8], &[u8])> {
+        match &self.fields {
+            Encoding::Listpack(lp) => Some((lp.get(2 * index)?, lp.get(2 * index + 1)?)),
+            Encoding::HashTable(h) => h.get_index(index).map(|(k, v)| (k.as_slice(), v.as_slice())),
+        }
+    }
+
+    /// One step of HSCAN, see [`scan_step`]: the hash table encoding is visited by index while
+    /// the listpack one, small, is returned whole.
+    pub fn scan(&self, cursor: u64, count: usize) -> (u64, Vec<Pair<'_>>) {
+        match &self.fields {
+            Encoding::HashTable(h) => {
+                let (next, indices) = scan_step(h.len(), cursor, count);
+                (next, indices.rev().filter_map(|i| h.get_index(i)).map(|(k, v)| (k.as_slice(), v.as_slice())).collect())
+            }
+            Encoding::Listpack(_) => (0, self.iter().collect()),
+        }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &[u8]> {
+        self.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &[u8]> {
+        self.iter().map(|(_, v)| v)
+    }
+
+    /// Sets a field, removing its expire like HSET does.
+    pub fn insert(&mut self, field: ByteString, value: ByteString) -> Option<ByteString> {
+        self.expires.remove(&field);
+        self.insert_keep_ttl(field, value)
+    }
+
+    /// Sets a field keeping its expire, like HINCRBY does.
+    pub fn insert_keep_ttl(&mut self, field: ByteString, value: ByteString) -> Option<ByteString> {
+        // converted before outgrowing the listpack rather than after the command, for setting
+        // many fields not to scan it for each of them
+        let limits = self.limits;
+        if matches!(self.fields, Encoding::Listpack(_))
+            && (field.len() > limits.value || value.len() > limits.value || (self.len() >= limits.entries && !self.contains_key(&field)))
+        {
+            self.convert_to_hash_table();
+        }
+        match &mut self.fields {
+            Encoding::Listpack(lp) => match position(lp, &field) {
+                Some(i) => {
+                    let old = lp.get(i + 1).map(|v| v.to_vec());
+                    lp.replace(i + 1, &value);
+                    old
+                }
+                None => {
+                    lp.push(&field);
+                    lp.push(&value);
+                    None
+                }
+            },
+            Encoding::HashTable(h) => h.insert(field, value),
+        }
+    }
+
+    pub fn remove(&mut self, field: &[u8]) -> Option<ByteString> {
+        self.expires.remove(field);
+        match &mut self.fields {
+            Encoding::Listpack(lp) => {
+                let i = position(lp, field)?;
+                let old = lp.get(i + 1).map(|v| v.to_vec());
+                lp.remove(i, 2);
+                old
+            }
+            Encoding::HashTable(h) => h.swap_remove(field),
+        }
+    }
+
+    pub fn expiry(&self, field: &[u8]) -> Option<i64> {
+        self.expires.get(field).copied()
+    }
+
+    /// Sets the expire of an existing field.
+    pub fn set_expiry(&mut self, field: &[u8], at: i64) {
+        if self.contains_key(field) {
+            self.expires.insert(field.to_vec(), at);
+        }
+    }
+
+    /// Removes the expire of a field, returns whether it had one.
+    pub fn persist(&mut self, field: &[u8]) -> bool {
+        self.expires.remove(field).is_some()
+    }
+
+    pub fn has_expires(&self) -> bool {
+        !self.expires.is_empty()
+    }
+
+    /// Fields with an expire and the time at which they expire.
+    pub fn expires(&self) -> impl Iterator<Item = (&ByteString, i64)> {
+        self.expires.iter().map(|(f, &at)| (f, at))
+    }
+
+    /// Removes the fields whose expire is in the past, returns their names.
+    fn remove_expired(&mut self, now: i64) -> Vec<ByteString> {
+        let expired = self.expires.iter().filter(|&(_, &at)| at <= now).map(|(f, _)| f.clone()).collect::<Vec<_>>();
+        for field in &expired {
+            self.remove(field);
+        }
+        expired
+    }
+
+    /// The name of the encoding, as reported by OBJECT ENCODING.
+    pub fn encoding(&self) -> &'static str {
+        match &self.fields {
+            Encoding::Listpack(_) => "listpack",
+            Encoding::HashTable(_) => "hashtable",
+        }
+    }
+
+    /// The memory used by the fields in the compact encoding.
+    pub(crate) fn compact_bytes(&self) -> Option<usize> {
+        match &self.fields {
+            Encoding::Listpack(lp) => Some(lp.bytes()),
+            Encoding::HashTable(_) => None,
+        }
+    }
+
+    fn convert_to_hash_table(&mut self) {
+        self.fields = Encoding::HashTable(self.iter().map(|(k, v)| (k.to_vec(), v.to_vec())).collect());
+    }
+
+    /// Converts the hash to the hash table encoding once it outgrows the listpack one, and
+    /// with `shrink` back to the listpack encoding when fitting, as done for new values.
+    pub(crate) fn fit_encoding(&mut self, config: &MemoryConfig, shrink: bool) {
+        let limits = config.hash;
+        self.limits = limits;
+        let listpack = matches!(self.fields, Encoding::Listpack(_));
+        if !listpack && !shrink {
+            return;
+        }
+        let fits = self.len() <= limits.entries && self.iter().all(|(k, v)| k.len() <= limits.value && v.len() <= limits.value);
+        if listpack && !fits {
+            self.convert_to_hash_table();
+        } else if !listpack && fits {
+            let mut lp = Listpack::new();
+            for (k, v) in self.iter() {
+                lp.push(k);
+                lp.push(v);
+            }
+            self.fields = Encoding::Listpack(lp);
+        }
+    }
+}
+
+impl Database {
+    /// Remembers that a hash has fields with an expire, for the active expire cycle.
+    pub(crate) fn track_hash_expires(&mut self, key: &[u8]) {
+        if !self.hash_expires.contains(key) {
+            self.hash_expires.insert(key.to_vec());
+        }
+    }
+
+    /// Deletes the expired fields of a hash, as done before accessing it. Like keys, they are
+    /// only deleted by masters, which propagate an HDEL.
+    pub(crate) fn expire_hash_fields(&mut self, key: &[u8]) {
+        if self.is_master_client() || self.master().is_some() {
+            return;
+        }
+        let Some(entry) = self.state.get_mut(key) else { return };
+        let Value::Hash(h) = &mut entry.value else { return };
+        if !h.has_expires() {
+            return;
+        }
+        let expired = h.remove_expired(now_ms());
+        if expired.is_empty() {
+            return;
+        }
+        // accounted for now as read only commands do not refresh the keys they access, the
+        // hash being deleted after the command if no field is left
+        let size = key.len() + entry.value.memory_usage(memory::ACCOUNTING_SAMPLES);
+        self.used_memory = self.used_memory + size - entry.size;
+        entry.size = size;
+        self.dirty.push(key.to_vec());
+        self.key_modified(key);
+        self.notify('h', "hexpired", key);
+        let hdel = [b"HDEL".to_vec(), key.to_vec()].into_iter().chain(expired);
+        self.propagate(replication::encode(hdel.collect()));
+    }
+
+    /// Deletes expired hash fields in the background, sampling the hashes with fields expiring.
+    pub(crate) fn active_expire_hash_fields(&mut self) {
+        if self.master().is_some() {
+            return;
+        }
+        for _ in 0..ACTIVE_EXPIRE_SAMPLES.min(self.hash_expires.len()) {
+            let i = self.rng.random_range(0..self.hash_expires.len());
+            let key = self.hash_expires[i].clone();
+            self.expire_hash_fields(&key);
+            if !matches!(self.state.get(&key).map(|e| &e.value), Some(Value::Hash(h)) if h.has_expires()) {
+                self.hash_expires.swap_remove(&key);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_index() {
+        let mut h = Hash::new();
+        for i in 0..200 {
+            h.insert(format!("f{i}").into_bytes(), i.to_string().into_bytes());
+            assert!(h.iter().enumerate().all(|(i, pair)| h.get_index(i) == Some(pair)), "{}", h.encoding());
+        }
+        assert_eq!(h.encoding(), "hashtable");
+        h.remove(b"f0");
+        assert!(h.iter().enumerate().all(|(i, pair)| h.get_index(i) == Some(pair)));
+        assert_eq!(h.get_index(h.len()), None);
+    }
+
+    #[test]
+    fn test_insert_converts() {
+        let mut h = Hash::new();
+        let entries = MemoryConfig::default().hash.entries;
+        for i in 0..entries {
+            h.insert(i.to_string().into_bytes(), b"v".to_vec());
+        }
+        h.insert(b"0".to_vec(), b"v".to_vec());
+        assert_eq!(h.encoding(), "listpack");
+        // converted as soon as the listpack is full, without waiting for `fit_encoding`
+        h.insert(b"new".to_vec(), b"v".to_vec());
+        assert_eq!((h.encoding(), h.len()), ("hashtable", entries + 1));
+        let mut h = Hash::new();
+        h.insert(b"f".to_vec(), vec![b'x'; 100]);
+        assert_eq!(h.encoding(), "hashtable");
+    }
+}
@@ -0,0 +1,387 @@
+//! HyperLogLog cardinality estimation, stored in strings with the sparse and dense encodings
+//! of Redis so values can be moved between servers with GET/SET, DUMP or replication.
+//!
+//! A HyperLogLog starts with a 16 bytes header: the `HYLL` magic, the encoding, three unused
+//! bytes and the cached cardinality, little endian, its most significant bit set when stale.
+
+use rand::Rng;
+
+use crate::ByteString;
+
+/// Bits of the hash selecting the register.
+const P: u32 = 14;
+pub(crate) const REGISTERS: usize = 1 << P;
+/// Bits of the hash in which the run of zeros is counted.
+const Q: u32 = 64 - P;
+const MAGIC: &[u8] = b"HYLL";
+const HEADER_SIZE: usize = 16;
+const DENSE: u8 = 0;
+const SPARSE: u8 = 1;
+/// Registers are 6 bits wide in the dense encoding.
+const DENSE_SIZE: usize = HEADER_SIZE + (REGISTERS * 6).div_ceil(8);
+/// The largest register value a sparse VAL opcode can hold.
+const SPARSE_MAX_VALUE: u8 = 32;
+const ALPHA_INF: f64 = 0.721_347_520_444_481_7;
+const SEED: u64 = 0xadc83b19;
+
+fn invalid() -> anyhow::Error {
+    anyhow::anyhow!("WRONGTYPE Key is not a valid HyperLogLog string value.")
+}
+
+fn corrupted() -> anyhow::Error {
+    anyhow::anyhow!("INVALIDOBJ Corrupted HLL object detected")
+}
+
+/// MurmurHash64A, the hash Redis uses for HyperLogLog.
+fn murmur_hash64a(key: &[u8], seed: u64) -> u64 {
+    const M: u64 = 0xc6a4a7935bd1e995;
+    const R: u32 = 47;
+    let mut h = seed ^ (key.len() as u64).wrapping_mul(M);
+    let mut chunks = key.chunks_exact(8);
+    for chunk in &mut chunks {
+        let mut k = u64::from_le_bytes(chunk.try_into().unwrap());
+        k = k.wrapping_mul(M);
+        k ^= k >> R;
+        k = k.wrapping_mul(M);
+        h ^= k;
+        h = h.wrapping_mul(M);
+    }
+    let rest = chunks.remainder();
+    if !rest.is_empty() {
+        for (i, &b) in rest.iter().enumerate() {
+            h ^= (b as u64) << (8 * i);
+        }
+        h = h.wrapping_mul(M);
+    }
+    h ^= h >> R;
+    h = h.wrapping_mul(M);
+    h ^= h >> R;
+    h
+}
+
+/// The register an element maps to and the value it sets it to: the position of the first
+/// set bit in the rest of its hash.
+fn pattern(element: &[u8]) -> (usize, u8) {
+    let hash = murmur_hash64a(element, SEED);
+    let index = (hash & (REGISTERS as u64 - 1)) as usize;
+    let hash = (hash >> P) | (1 << Q);
+    (index, hash.trailing_zeros() as u8 + 1)
+}
+
+fn header(encoding: u8, card: &[u8]) -> ByteString {
+    let mut header = MAGIC.to_vec();
+    header.extend([encoding, 0, 0, 0]);
+    header.extend(card);
+    header
+}
+
+/// An empty HyperLogLog: a sparse one made of a single run of zeros.
+pub(crate) fn new() -> ByteString {
+    let mut s = header(SPARSE, &[0; 8]);
+    let len = REGISTERS - 1;
+    s.extend([0x40 | (len >> 8) as u8, len as u8]);
+    s
+}
+
+fn validate(s: &[u8]) -> anyhow::Result<()> {
+    anyhow::ensure!(s.len() >= HEADER_SIZE && s.starts_with(MAGIC), invalid());
+    match s[4] {
+        DENSE => anyhow::ensure!(s.len() == DENSE_SIZE, invalid()),
+        SPARSE => {}
+        _ => anyhow::bail!(invalid()),
+    }
+    Ok(())
+}
+
+pub(crate) fn is_sparse(s: &[u8]) -> anyhow::Result<bool> {
+    validate(s)?;
+    Ok(s[4] == SPARSE)
+}
+
+fn dense_get(data: &[u8], i: usize) -> u8 {
+    let (byte, bit) = (i * 6 / 8, i * 6 % 8);
+    let v = data[byte] as u16 | (data.get(byte + 1).copied().unwrap_or(0) as u16) << 8;
+    ((v >> bit) & 63) as u8
+}
+
+fn dense_set(data: &mut [u8], i: usize, value: u8) {
+    let (byte, bit) = (i * 6 / 8, i * 6 % 8);
+    let mut v = data[byte] as u16 | (data.get(byte + 1).copied().unwrap_or(0) as u16) << 8;
+    v = (v & !(63 << bit)) | (value as u16) << bit;
+    data[byte] = v as u8;
+    if let Some(next) = data.get_mut(byte + 1) {
+        *next = (v >> 8) as u8;
+    }
+}
+
+/// Calls `f` with the value and length of each run of registers of a sparse HyperLogLog,
+/// `kind` being `z` for ZERO, `Z` for XZERO and `v` for VAL opcodes.
+fn sparse_runs(data: &[u8], mut f: impl FnMut(char, u8, usize)) -> anyhow::Result<()> {
+    let mut total = 0;
+    let mut i = 0;
+    while i < data.len() {
+        let op = data[i];
+        let (kind, value, len) = if op & 0x80 != 0 {
+            ('v', ((op >> 2) & 0x1f) + 1, (op & 3) as usize + 1)
+        } else if op & 0x40 != 0 {
+            i += 1;
+            let next = *data.get(i).ok_or_else(corrupted)?;
+            ('Z', 0, (((op & 0x3f) as usize) << 8 | next as usize) + 1)
+        } else {
+            ('z', 0, (op & 0x3f) as usize + 1)
+        };
+        i += 1;
+        total += len;
+        anyhow::ensure!(total <= REGISTERS, corrupted());
+        f(kind, value, len);
+    }
+    anyhow::ensure!(total == REGISTERS, corrupted());
+    Ok(())
+}
+
+/// The registers of a HyperLogLog, whatever its encoding.
+pub(crate) fn registers(s: &[u8]) -> anyhow::Result<Vec<u8>> {
+    validate(s)?;
+    let data = &s[HEADER_SIZE..];
+    if s[4] == DENSE {
+        return Ok((0..REGISTERS).map(|i| dense_get(data, i)).collect());
+    }
+    let mut registers = Vec::with_capacity(REGISTERS);
+    sparse_runs(data, |_, value, len| registers.resize(registers.len() + len, value))?;
+    Ok(registers)
+}
+
+/// The sparse encoding of the registers, `None` when a value is too large for it.
+fn encode_sparse(registers: &[u8], card: &[u8]) -> Option<ByteString> {
+    let mut s = header(SPARSE, card);
+    let mut i = 0;
+    while i < registers.len() {
+        let value = registers[i];
+        let run = registers[i..].iter().take_while(|&&v| v == value).count();
+        i += run;
+        if value == 0 && run <= 64 {
+            s.push((run - 1) as u8);
+        } else if value == 0 {
+            s.extend([0x40 | ((run - 1) >> 8) as u8, (run - 1) as u8]);
+        } else if value <= SPARSE_MAX_VALUE {
+            for len in std::iter::repeat_n(4, run / 4).chain((run % 4 > 0).then_some(run % 4)) {
+                s.push(0x80 | (value - 1) << 2 | (len - 1) as u8);
+            }
+        } else {
+            return None;
+        }
+    }
+    Some(s)
+}
+
+fn encode_dense(registers: &[u8], card: &[u8]) -> ByteString {
+    let mut s = header(DENSE, card);
+    s.resize(DENSE_SIZE, 0);
+    for (i, &v) in registers.iter().enumerate() {
+        dense_set(&mut s[HEADER_SIZE..], i, v);
+    }
+    s
+}
+
+fn invalidate_cache(s: &mut [u8]) {
+    s[HEADER_SIZE - 1] |= 0x80;
+}
+
+/// Replaces the registers of a HyperLogLog. Sparse ones are promoted to the dense encoding
+/// once larger than `sparse_max` bytes.
+pub(crate) fn store(s: &mut ByteString, registers: &[u8], sparse_max: usize) -> anyhow::Result<()> {
+    let sparse = match is_sparse(s)? {
+        true => encode_sparse(registers, &s[8..HEADER_SIZE]).filter(|e| e.len() <= sparse_max),
+        false => None,
+    };
+    *s = sparse.unwrap_or_else(|| encode_dense(registers, &s[8..HEADER_SIZE]));
+    invalidate_cache(s);
+    Ok(())
+}
+
+/// Adds elements to a HyperLogLog, returns whether one of its registers changed.
+pub(crate) fn add(s: &mut ByteString, elements: &[ByteString], sparse_max: usize) -> anyhow::Result<bool> {
+    let mut changed = false;
+    if is_sparse(s)? {
+        let mut registers = registers(s)?;
+        for element in elements {
+            let (i, count) = pattern(element);
+            if registers[i] < count {
+                registers[i] = count;
+                changed = true;
+            }
+        }
+        if changed {
+            store(s, &registers, sparse_max)?;
+        }
+        return Ok(changed);
+    }
+    let data = &mut s[HEADER_SIZE..];
+    for element in elements {
+        let (i, count) = pattern(element);
+        if dense_get(data, i) < count {
+            dense_set(data, i, count);
+            changed = true;
+        }
+    }
+    if changed {
+        invalidate_cache(s);
+    }
+    Ok(changed)
+}
+
+/// Merges the registers of a HyperLogLog into `registers`, keeping the largest values.
+pub(crate) fn merge(registers: &mut [u8], s: &[u8]) -> anyhow::Result<()> {
+    for (r, v) in registers.iter_mut().zip(self::registers(s)?) {
+        *r = (*r).max(v);
+    }
+    Ok(())
+}
+
+/// Converts a HyperLogLog to the dense encoding, returns whether it was sparse.
+pub(crate) fn to_dense(s: &mut ByteString) -> anyhow::Result<bool> {
+    if !is_sparse(s)? {
+        return Ok(false);
+    }
+    *s = encode_dense(&registers(s)?, &s[8..HEADER_SIZE]);
+    Ok(true)
+}
+
+/// The opcodes of a sparse HyperLogLog in a human readable form, as shown by PFDEBUG DECODE.
+pub(crate) fn decode_sparse(s: &[u8]) -> anyhow::Result<String> {
+    anyhow::ensure!(is_sparse(s)?, "HLL encoding is not sparse");
+    let mut ops = Vec::new();
+    sparse_runs(&s[HEADER_SIZE..], |kind, value, len| match kind {
+        'v' => ops.push(format!("v:{value},{len}")),
+        _ => ops.push(format!("{kind}:{len}")),
+    })?;
+    Ok(ops.join(" "))
+}
+
+/// The estimated cardinality of a HyperLogLog, cached in its header until a register changes.
+pub(crate) fn count(s: &mut ByteString) -> anyhow::Result<u64> {
+    validate(s)?;
+    if s[HEADER_SIZE - 1] & 0x80 == 0 {
+        return Ok(u64::from_le_bytes(s[8..HEADER_SIZE].try_into().unwrap()));
+    }
+    let card = estimate(&registers(s)?);
+    s[8..HEADER_SIZE].copy_from_slice(&card.to_le_bytes());
+    Ok(card)
+}
+
+fn sigma(mut x: f64) -> f64 {
+    if x == 1.0 {
+        return f64::INFINITY;
+    }
+    let (mut y, mut z) = (1.0, x);
+    loop {
+        x *= x;
+        let previous = z;
+        z += x * y;
+        y += y;
+        if previous == z {
+            return z;
+        }
+    }
+}
+
+fn tau(mut x: f64) -> f64 {
+    if x == 0.0 || x == 1.0 {
+        return 0.0;
+    }
+    let (mut y, mut z) = (1.0, 1.0 - x);
+    loop {
+        x = x.sqrt();
+        let previous = z;
+        y *= 0.5;
+        z -= (1.0 - x).powi(2) * y;
+        if previous == z {
+            return z / 3.0;
+        }
+    }
+}
+
+/// Estimates the cardinality from the registers with the improved estimator of Otmar Ertl,
+/// as Redis does.
+pub(crate) fn estimate(registers: &[u8]) -> u64 {
+    let m = REGISTERS as f64;
+    let mut histogram = [0u32; 64];
+    for &r in registers {
+        histogram[r as usize] += 1;
+    }
+    let mut z = m * tau((m - histogram[Q as usize + 1] as f64) / m);
+    for &count in histogram[1..=Q as usize].iter().rev() {
+        z += count as f64;
+        z *= 0.5;
+    }
+    z += m * sigma(histogram[0] as f64 / m);
+    (ALPHA_INF * m * m / z).round() as u64
+}
+
+/// Checks the encodings and the estimation error, as run by PFSELFTEST.
+pub(crate) fn self_test(rng: &mut impl Rng) -> anyhow::Result<()> {
+    // every value in every register of the dense encoding
+    let mut data = vec![0; DENSE_SIZE - HEADER_SIZE];
+    for _ in 0..100 {
+        let values = (0..REGISTERS).map(|_| rng.random_range(0..64)).collect::<Vec<u8>>();
+        for (i, &v) in values.iter().enumerate() {
+            dense_set(&mut data, i, v);
+        }
+        for (i, &v) in values.iter().enumerate() {
+            anyhow::ensure!(dense_get(&data, i) == v, "TESTFAILED Register error");
+        }
+    }
+    // the sparse and dense encodings agree, within the expected error
+    let (mut sparse, mut dense) = (new(), new());
+    to_dense(&mut dense)?;
+    let relative_error = 1.04 / (REGISTERS as f64).sqrt();
+    let mut added = 0u64;
+    for batch in 0..100 {
+        let elements = (0..1000).map(|i| format!("{}", batch * 1000 + i).into_bytes()).collect::<Vec<_>>();
+        add(&mut sparse, &elements, usize::MAX)?;
+        add(&mut dense, &elements, usize::MAX)?;
+        added += elements.len() as u64;
+        let (a, b) = (count(&mut sparse)?, count(&mut dense)?);
+        anyhow::ensure!(a == b, "TESTFAILED sparse and dense cardinalities differ: {a} != {b}");
+        let error = (a as f64 - added as f64).abs() / added as f64;
+        // 5 standard errors, as Redis allows
+        anyhow::ensure!(error <= relative_error * 5.0, "TESTFAILED too big error. card:{added} abserr:{}", a.abs_diff(added));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_murmur_hash64a() {
+        // values computed with the C implementation of Redis
+        assert_eq!(murmur_hash64a(b"", SEED), 0xd8dfea6585bc9732);
+        assert_eq!(murmur_hash64a(b"a", SEED), 0x53d2470a9b43b1a7);
+        assert_eq!(murmur_hash64a(b"hello world", SEED), 0xa919bc3051f624b7);
+    }
+
+    #[test]
+    fn test_encodings() {
+        let mut s = new();
+        assert_eq!(decode_sparse(&s).unwrap(), "Z:16384");
+        let mut registers = vec![0; REGISTERS];
+        registers[0] = 3;
+        registers[1] = 3;
+        registers[100] = 32;
+        store(&mut s, &registers, 3000).unwrap();
+        assert_eq!(decode_sparse(&s).unwrap(), "v:3,2 Z:98 v:32,1 Z:16283");
+        assert_eq!(self::registers(&s).unwrap(), registers);
+        registers[100] = 33;
+        store(&mut s, &registers, 3000).unwrap();
+        assert!(!is_sparse(&s).unwrap());
+        assert_eq!(self::registers(&s).unwrap(), registers);
+        assert_eq!(s.len(), DENSE_SIZE);
+    }
+
+    #[test]
+    fn test_self_test() {
+        self_test(&mut rand::rng()).unwrap();
+    }
+}
@@ -1,31 +1,89 @@
 use std::collections::{HashMap, HashSet};
 use std::io::Write;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::time::Duration;
 
+use indexmap::{IndexMap, IndexSet};
+use rand::{Rng, SeedableRng};
+use rand::rngs::SmallRng;
+
+mod acl;
+mod client;
+mod cluster;
 mod command;
 mod commands;
+mod config;
+mod expire;
+mod functions;
+mod geo;
+mod hash;
+mod hyperloglog;
+mod listpack;
+mod memory;
+mod pubsub;
+mod rdb;
+mod replication;
+mod scripting;
+mod set;
 mod sorted_set;
+mod string;
+mod tracking;
+use expire::now_ms;
+pub use hash::Hash;
+pub use set::Set;
+pub use string::Str;
+use memory::{Access, MemoryConfig};
+use scripting::Scripting;
 use sorted_set::SortedSet;
+use tracking::TrackingTable;
+pub use acl::Acl;
+pub use client::{Blocked, Client};
+pub use cluster::{key_hash_slot, Cluster, Node, SlotState};
+pub use config::Config;
+pub use functions::RestorePolicy;
 pub use command::Command;
 pub use commands::COMMANDS;
+pub use replication::{LinkState, Replication, Sync};
+pub use scripting::ScriptState;
+pub use tracking::Tracking;
 
 pub type ByteString = Vec<u8>;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Value {
-    String(ByteString),
+    String(Str),
     Array(Vec<ByteString>),
-    Hash(HashMap<ByteString, ByteString>),
-    Set(HashSet<ByteString>),
+    Hash(Hash),
+    Set(Set),
     ZSet(SortedSet),
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+impl Value {
+    /// Whether the value is an aggregate without elements, which is never kept in the keyspace.
+    pub fn is_empty_collection(&self) -> bool {
+        match self {
+            Value::String(_) => false,
+            Value::Array(v) => v.is_empty(),
+            Value::Hash(h) => h.is_empty(),
+            Value::Set(s) => s.is_empty(),
+            Value::ZSet(z) => z.is_empty(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub enum Response {
     SimpleString(ByteString),
     BulkString(ByteString),
     Number(i64),
     Array(Vec<Response>),
+    #[default]
     Nil,
+    /// Written as is, for replies that are not a single RESP value like the replication stream.
+    Raw(ByteString),
+    /// Nothing is written, the client expects no reply or is blocked and answered later.
+    NoReply,
 }
 
 impl Response {
@@ -38,29 +96,343 @@ impl Response {
     }
 }
 
-impl Default for Response {
-    fn default() -> Self { Self::Nil }
+/// Counters reported by INFO and reset with CONFIG RESETSTAT.
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    pub total_connections_received: u64,
+    pub total_commands_processed: u64,
+    pub rejected_connections: u64,
+    pub evicted_keys: u64,
+    pub expired_keys: u64,
+}
+
+struct Entry {
+    value: Value,
+    size: usize,
+    access: Access,
 }
 
-#[derive(Default)]
 pub struct Database {
-    state: HashMap<ByteString, Value>
+    state: IndexMap<ByteString, Entry>,
+    /// The unix time in milliseconds at which keys with an expire expire.
+    expires: IndexMap<ByteString, i64>,
+    /// Hashes that may have fields with an expire, sampled by the active expire cycle.
+    hash_expires: IndexSet<ByteString>,
+    used_memory: usize,
+    memory: MemoryConfig,
+    /// Keys handed out mutably during the current command, whose size needs refreshing.
+    dirty: Vec<ByteString>,
+    rng: SmallRng,
+    replication: Replication,
+    /// What the current command propagates instead of itself, nothing when empty.
+    propagate_as: Option<ByteString>,
+    /// Replies to clients other than the current one, delivered by the server.
+    outbox: Vec<(u64, anyhow::Result<Response>)>,
+    migrations: Vec<Migration>,
+    /// The keys being transferred by the MIGRATE of each client, and whether they are copied.
+    /// Writes to them wait for the transfer, for none to be lost when the keys are deleted.
+    migrating: HashMap<u64, (Vec<ByteString>, bool)>,
+    cluster: Option<Cluster>,
+    /// The keys of each slot in cluster mode, empty otherwise.
+    slot_keys: Vec<HashSet<ByteString>>,
+    scripting: Scripting,
+    tracking: TrackingTable,
+    acl: Acl,
+    config: Config,
+    stats: Stats,
+    clients: HashMap<u64, Client>,
+    current_client: u64,
+}
+
+impl Default for Database {
+    fn default() -> Self {
+        Self {
+            state: IndexMap::new(),
+            expires: IndexMap::new(),
+            hash_expires: IndexSet::new(),
+            used_memory: 0,
+            memory: MemoryConfig::default(),
+            dirty: Vec::new(),
+            rng: SmallRng::from_os_rng(),
+            replication: Replication::default(),
+            propagate_as: None,
+            outbox: Vec::new(),
+            migrations: Vec::new(),
+            migrating: HashMap::new(),
+            cluster: None,
+            slot_keys: Vec::new(),
+            scripting: Scripting::default(),
+            tracking: TrackingTable::default(),
+            acl: Acl::default(),
+            config: Config::default(),
+            stats: Stats::default(),
+            clients: HashMap::new(),
+            current_client: 0,
+        }
+    }
 }
 
 impl Database {
-    pub fn get(&mut self, key: &[u8]) -> Option<&mut Value> {
-        self.state.get_mut(key)
+    pub fn from_config(config: Config) -> anyhow::Result<Self> {
+        let memory = MemoryConfig::from_config(&config);
+        let mut db = Self { config, memory, ..Self::default() };
+        db.replication.backlog_size = db.config.get_parsed("repl-backlog-size");
+        if let Some(aclfile) = db.config.get_optional("aclfile") {
+            db.acl.set_file(Some(aclfile.into()));
+            db.acl.load()?;
+        }
+        db.apply_requirepass();
+        if db.config.get_bool("cluster-enabled") {
+            let file = Path::new(db.config.get("cluster-config-file"));
+            db.cluster = Some(Cluster::load(file, &db.announce_ip(), db.config.get_parsed("port"))?);
+            db.slot_keys = vec![HashSet::new(); cluster::SLOTS];
+        }
+        Ok(db)
+    }
+
+    /// The address other cluster nodes reach us at, `cluster-announce-ip` or the first bind address.
+    fn announce_ip(&self) -> String {
+        if let Some(ip) = self.config.get_optional("cluster-announce-ip") {
+            return ip.to_string();
+        }
+        let bind = self.config.get("bind").split_whitespace().next().unwrap_or_default().trim_start_matches('-');
+        match bind {
+            "" | "*" | "0.0.0.0" | "::" | "::*" => "127.0.0.1".to_string(),
+            ip => ip.to_string(),
+        }
+    }
+
+    pub fn acl(&mut self) -> &mut Acl {
+        &mut self.acl
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, params: &[(String, String)]) -> anyhow::Result<()> {
+        let changed = self.config.set_runtime(params)?;
+        if changed.contains(&"requirepass") {
+            self.apply_requirepass();
+        }
+        self.memory = MemoryConfig::from_config(&self.config);
+        self.replication.backlog_size = self.config.get_parsed("repl-backlog-size");
+        Ok(())
+    }
+
+    /// `requirepass` is a shorthand for setting the password of the default user.
+    fn apply_requirepass(&mut self) {
+        let rules = match self.config.get_optional("requirepass") {
+            Some(password) => vec![b"resetpass".to_vec(), format!(">{password}").into_bytes()],
+            None if self.config.get_optional("aclfile").is_some() => return,
+            None => vec![b"nopass".to_vec()],
+        };
+        self.acl.set_user(b"default".to_vec(), &rules).expect("password rules are valid");
+    }
+
+    pub fn stats(&mut self) -> &mut Stats {
+        &mut self.stats
+    }
+
+    pub fn client_count(&self) -> usize {
+        self.clients.len()
+    }
+
+    /// Registers a new client, `exposed` clients are remote clients connecting through a
+    /// wildcard address which are refused in protected mode unless the default user has a password.
+    pub fn accept_client(&mut self, id: u64, addr: Option<SocketAddr>, exposed: bool) -> anyhow::Result<()> {
+        self.stats.total_connections_received += 1;
+        if exposed && self.config.get_bool("protected-mode") && self.acl.default_user_nopass() {
+            self.stats.rejected_connections += 1;
+            anyhow::bail!("{PROTECTED_MODE_ERROR}");
+        }
+        self.clients.insert(id, Client { addr, ..Client::default() });
+        Ok(())
+    }
+
+    pub fn select_client(&mut self, id: u64) {
+        self.current_client = id;
+    }
+
+    pub fn client_id(&self) -> u64 {
+        self.current_client
+    }
+
+    pub fn remove_client(&mut self, id: u64) {
+        self.clients.remove(&id);
+        self.remove_replication_client(id);
+        self.remove_tracking_client(id);
+    }
+
+    /// Replies for other clients, produced by the last commands.
+    pub fn take_outbox(&mut self) -> Vec<(u64, anyhow::Result<Response>)> {
+        std::mem::take(&mut self.outbox)
+    }
+
+    /// Key transfers requested by MIGRATE, performed by the server.
+    pub fn take_migrations(&mut self) -> Vec<Migration> {
+        std::mem::take(&mut self.migrations)
+    }
+
+    pub(crate) fn migrate(&mut self, migration: Migration, keys: Vec<ByteString>, copy: bool) {
+        self.client().blocked = Some(Blocked::Migrate);
+        self.migrating.insert(migration.client, (keys, copy));
+        self.migrations.push(migration);
+    }
+
+    /// Called by the server once a migration is over, the keys are deleted on success unless
+    /// COPY was given, even if the client disconnected in the meantime.
+    pub fn migrated(&mut self, client: u64, res: anyhow::Result<()>) {
+        let Some((keys, copy)) = self.migrating.remove(&client) else { return };
+        if res.is_ok() && !copy {
+            for key in keys {
+                self.del(&key);
+                self.notify('g', "del", &key);
+                self.propagate(replication::encode(vec![b"DEL".to_vec(), key]));
+            }
+            self.flush_invalidations();
+        }
+        if self.clients.contains_key(&client) {
+            self.unblock(client, res.map(|_| Response::SimpleString(b"OK".to_vec())));
+        }
+        let waiting = self.clients.iter()
+            .filter(|(_, c)| matches!(c.blocked, Some(Blocked::MigratingKeys { client: by }) if by == client))
+            .map(|(&id, _)| id)
+            .collect::<Vec<_>>();
+        for id in waiting {
+            self.resume(id);
+        }
+    }
+
+    /// The client whose MIGRATE holds some of the keys `cmd` writes to, if any.
+    fn migrating_client(&self, cmd: &Command) -> Option<u64> {
+        // scripts are held on the keys they declare, before they run
+        if self.migrating.is_empty() || self.is_running_script() {
+            return None;
+        }
+        let (_, info) = COMMANDS.get(cmd.cmd().as_bytes())?;
+        if !info.flags.contains(&b"write".as_slice()) {
+            return None;
+        }
+        let keys = info.keys(cmd);
+        self.migrating.iter()
+            .find(|(_, (held, _))| held.iter().any(|k| keys.contains(&k.as_slice())))
+            .map(|(&client, _)| client)
+    }
+
+    /// Answers a blocked client and runs the commands it sent in the meantime.
+    fn unblock(&mut self, id: u64, res: anyhow::Result<Response>) {
+        self.outbox.push((id, res));
+        self.resume(id);
+    }
+
+    /// Runs the commands a client sent while blocked, until one blocks it again.
+    fn resume(&mut self, id: u64) {
+        let current = self.current_client;
+        self.select_client(id);
+        self.client().blocked = None;
+        while self.client().blocked.is_none() {
+            let Some(cmd) = self.client().deferred.pop_front() else { break };
+            let res = execute_command(self, cmd);
+            if !matches!(res, Ok(Response::NoReply)) {
+                self.outbox.push((id, res));
+            }
+        }
+        self.select_client(current);
     }
 
-    pub fn get_str(&mut self, key: &[u8]) -> anyhow::Result<Option<&mut ByteString>> {
+    pub fn client(&mut self) -> &mut Client {
+        self.clients.entry(self.current_client).or_default()
+    }
+
+    /// The user of the current client, clients are implicitly the default user until they
+    /// authenticate unless the default user requires a password.
+    pub fn username(&mut self) -> Option<ByteString> {
+        if let Some(user) = &self.client().user {
+            return Some(user.clone());
+        }
+        self.acl.default_user_nopass().then(|| b"default".to_vec())
+    }
+
+    /// Whether the user of the current client may read any key.
+    pub fn can_read_all_keys(&mut self) -> bool {
+        self.username().is_some_and(|user| self.acl.can_read_all_keys(&user))
+    }
+
+    pub(crate) fn rng(&mut self) -> &mut SmallRng {
+        &mut self.rng
+    }
+
+    /// Makes the random choices of commands like SPOP reproducible.
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = SmallRng::seed_from_u64(seed);
+    }
+
+    /// Looks up a key to read it, see `get_mut` to modify it.
+    pub fn get(&mut self, key: &[u8]) -> Option<&Value> {
+        if self.expire_if_needed(key) {
+            return None;
+        }
+        let entry = self.state.get_mut(key)?;
+        entry.access.touch(&self.memory, &mut self.rng);
+        Some(&entry.value)
+    }
+
+    /// Looks up a key to modify it, which refreshes its size and invalidates it for the
+    /// clients caching it after the command.
+    pub fn get_mut(&mut self, key: &[u8]) -> Option<&mut Value> {
+        if self.expire_if_needed(key) {
+            return None;
+        }
+        let entry = self.state.get_mut(key)?;
+        entry.access.touch(&self.memory, &mut self.rng);
+        self.dirty.push(key.to_vec());
+        Some(&mut entry.value)
+    }
+
+    fn get_or_insert(&mut self, key: ByteString, default: impl FnOnce() -> Value) -> &mut Value {
+        self.expire_if_needed(&key);
+        self.dirty.push(key.clone());
+        self.index_slot_key(&key);
+        let entry = self.state.entry(key).or_insert_with(|| {
+            // new aggregates take the limits of their compact encodings from the config
+            let mut value = default();
+            value.fit_encoding(&self.memory, true);
+            Entry { value, size: 0, access: Access::new() }
+        });
+        entry.access.touch(&self.memory, &mut self.rng);
+        &mut entry.value
+    }
+
+    /// Looks up a key without counting it as an access, as done by OBJECT.
+    pub fn peek(&self, key: &[u8]) -> Option<(&Value, &Access)> {
+        if self.is_expired(key) {
+            return None;
+        }
+        self.state.get(key).map(|e| (&e.value, &e.access))
+    }
+
+    fn is_expired(&self, key: &[u8]) -> bool {
+        self.expires.get(key).is_some_and(|&at| at <= now_ms())
+    }
+
+    pub fn get_str(&mut self, key: &[u8]) -> anyhow::Result<Option<&Str>> {
         match self.get(key) {
-            Some(Value::String(s)) => Ok(Some(s)),
+            Some(Value::String(v)) => Ok(Some(v)),
             Some(_) => anyhow::bail!("expected string value"),
             None => Ok(None)
         }
     }
 
-    pub fn get_array(&mut self, key: &[u8]) -> anyhow::Result<Option<&mut Vec<ByteString>>> {
+    pub fn get_str_mut(&mut self, key: &[u8]) -> anyhow::Result<Option<&mut Str>> {
+        match self.get_mut(key) {
+            Some(Value::String(v)) => Ok(Some(v)),
+            Some(_) => anyhow::bail!("expected string value"),
+            None => Ok(None)
+        }
+    }
+
+    pub fn get_array(&mut self, key: &[u8]) -> anyhow::Result<Option<&Vec<ByteString>>> {
         match self.get(key) {
             Some(Value::Array(v)) => Ok(Some(v)),
             Some(_) => anyhow::bail!("expected array value"),
@@ -68,7 +440,16 @@ impl Database {
         }
     }
 
-    pub fn get_hash(&mut self, key: &[u8]) -> anyhow::Result<Option<&mut HashMap<ByteString, ByteString>>> {
+    pub fn get_array_mut(&mut self, key: &[u8]) -> anyhow::Result<Option<&mut Vec<ByteString>>> {
+        match self.get_mut(key) {
+            Some(Value::Array(v)) => Ok(Some(v)),
+            Some(_) => anyhow::bail!("expected array value"),
+            None => Ok(None)
+        }
+    }
+
+    pub fn get_hash(&mut self, key: &[u8]) -> anyhow::Result<Option<&Hash>> {
+        self.expire_hash_fields(key);
         match self.get(key) {
             Some(Value::Hash(v)) => Ok(Some(v)),
             Some(_) => anyhow::bail!("expected hash value"),
@@ -76,7 +457,16 @@ impl Database {
         }
     }
 
-    pub fn get_set(&mut self, key: &[u8]) -> anyhow::Result<Option<&mut HashSet<ByteString>>> {
+    pub fn get_hash_mut(&mut self, key: &[u8]) -> anyhow::Result<Option<&mut Hash>> {
+        self.expire_hash_fields(key);
+        match self.get_mut(key) {
+            Some(Value::Hash(v)) => Ok(Some(v)),
+            Some(_) => anyhow::bail!("expected hash value"),
+            None => Ok(None)
+        }
+    }
+
+    pub fn get_set(&mut self, key: &[u8]) -> anyhow::Result<Option<&Set>> {
         match self.get(key) {
             Some(Value::Set(v)) => Ok(Some(v)),
             Some(_) => anyhow::bail!("expected set value"),
@@ -84,7 +474,15 @@ impl Database {
         }
     }
 
-    pub fn get_zset(&mut self, key: &[u8]) -> anyhow::Result<Option<&mut SortedSet>> {
+    pub fn get_set_mut(&mut self, key: &[u8]) -> anyhow::Result<Option<&mut Set>> {
+        match self.get_mut(key) {
+            Some(Value::Set(v)) => Ok(Some(v)),
+            Some(_) => anyhow::bail!("expected set value"),
+            None => Ok(None)
+        }
+    }
+
+    pub fn get_zset(&mut self, key: &[u8]) -> anyhow::Result<Option<&SortedSet>> {
         match self.get(key) {
             Some(Value::ZSet(v)) => Ok(Some(v)),
             Some(_) => anyhow::bail!("expected zset value"),
@@ -92,8 +490,16 @@ impl Database {
         }
     }
 
-    pub fn get_or_insert_str(&mut self, key: Vec<u8>) -> anyhow::Result<&mut ByteString> {
-        let v = self.state.entry(key).or_insert_with(|| Value::String(Vec::new()));
+    pub fn get_zset_mut(&mut self, key: &[u8]) -> anyhow::Result<Option<&mut SortedSet>> {
+        match self.get_mut(key) {
+            Some(Value::ZSet(v)) => Ok(Some(v)),
+            Some(_) => anyhow::bail!("expected zset value"),
+            None => Ok(None)
+        }
+    }
+
+    pub fn get_or_insert_str(&mut self, key: Vec<u8>) -> anyhow::Result<&mut Str> {
+        let v = self.get_or_insert(key, || Value::String(Str::default()));
         match v {
             Value::String(v) => Ok(v),
             _ => anyhow::bail!("expected string value"),
@@ -101,23 +507,24 @@ impl Database {
     }
 
     pub fn get_or_insert_array(&mut self, key: Vec<u8>) -> anyhow::Result<&mut Vec<ByteString>> {
-        let v = self.state.entry(key).or_insert_with(|| Value::Array(Vec::new()));
+        let v = self.get_or_insert(key, || Value::Array(Vec::new()));
         match v {
             Value::Array(v) => Ok(v),
             _ => anyhow::bail!("expected array value"),
         }
     }
 
-    pub fn get_or_insert_hash(&mut self, key: Vec<u8>) -> anyhow::Result<&mut HashMap<ByteString, ByteString>> {
-        let v = self.state.entry(key).or_insert_with(|| Value::Hash(HashMap::new()));
+    pub fn get_or_insert_hash(&mut self, key: Vec<u8>) -> anyhow::Result<&mut Hash> {
+        self.expire_hash_fields(&key);
+        let v = self.get_or_insert(key, || Value::Hash(Hash::new()));
         match v {
             Value::Hash(v) => Ok(v),
             _ => anyhow::bail!("expected hash value"),
         }
     }
 
-    pub fn get_or_insert_set(&mut self, key: Vec<u8>) -> anyhow::Result<&mut HashSet<ByteString>> {
-        let v = self.state.entry(key).or_insert_with(|| Value::Set(HashSet::new()));
+    pub fn get_or_insert_set(&mut self, key: Vec<u8>) -> anyhow::Result<&mut Set> {
+        let v = self.get_or_insert(key, || Value::Set(Set::new()));
         match v {
             Value::Set(v) => Ok(v),
             _ => anyhow::bail!("expected set value"),
@@ -125,43 +532,279 @@ impl Database {
     }
 
     pub fn get_or_insert_zset(&mut self, key: Vec<u8>) -> anyhow::Result<&mut SortedSet> {
-        let v = self.state.entry(key).or_insert_with(|| Value::ZSet(SortedSet::new()));
+        let v = self.get_or_insert(key, || Value::ZSet(SortedSet::new()));
         match v {
             Value::ZSet(v) => Ok(v),
             _ => anyhow::bail!("expected zset value"),
         }
     }
 
+    /// Sets the value of a key, removing its expire.
     pub fn set(&mut self, key: ByteString, value: Value) -> Option<Value> {
-        self.state.insert(key, value)
+        self.expires.swap_remove(&key);
+        self.set_keep_ttl(key, value)
+    }
+
+    /// Sets the value of a key, keeping its expire like commands modifying a value do. Empty
+    /// aggregates delete the key instead.
+    pub fn set_keep_ttl(&mut self, key: ByteString, mut value: Value) -> Option<Value> {
+        if value.is_empty_collection() {
+            let old = self.del(&key)?;
+            self.notify('g', "del", &key);
+            return Some(old);
+        }
+        value.fit_encoding(&self.memory, true);
+        if matches!(&value, Value::Hash(h) if h.has_expires()) {
+            self.track_hash_expires(&key);
+        }
+        let size = key.len() + value.memory_usage(memory::ACCOUNTING_SAMPLES);
+        self.used_memory += size;
+        let entry = Entry { value, size, access: Access::new() };
+        self.key_modified(&key);
+        self.index_slot_key(&key);
+        let old = self.state.insert(key, entry)?;
+        self.used_memory -= old.size;
+        Some(old.value)
     }
 
     pub fn del(&mut self, key: &[u8]) -> Option<Value> {
-        self.state.remove(key)
+        let old = self.state.swap_remove(key)?;
+        self.expires.swap_remove(key);
+        self.unindex_slot_key(key);
+        self.used_memory -= old.size;
+        self.key_modified(key);
+        Some(old.value)
     }
 
-    pub fn contains(&self, key: &[u8]) -> bool {
-        self.state.contains_key(key)
+    pub fn contains(&mut self, key: &[u8]) -> bool {
+        !self.expire_if_needed(key) && self.state.contains_key(key)
     }
 
     pub fn clear(&mut self) {
         self.state.clear();
+        self.expires.clear();
+        self.hash_expires.clear();
+        self.slot_keys.iter_mut().for_each(HashSet::clear);
+        self.used_memory = 0;
+        self.invalidate_all();
     }
 
+    /// The keys that did not expire, expired keys being left for the active expire cycle.
     pub fn keys(&mut self) -> impl Iterator<Item=&[u8]> {
-        self.state.keys().map(|k| k.as_slice())
+        let (state, expires, now) = (&self.state, &self.expires, now_ms());
+        state.keys().filter(move |k| expires.get(*k).is_none_or(|&at| at > now)).map(|k| k.as_slice())
+    }
+
+    pub fn used_memory(&self) -> usize {
+        self.used_memory
+    }
+
+    pub fn memory_config(&self) -> &MemoryConfig {
+        &self.memory
     }
+
+    /// Updates the memory accounting of the keys modified by the last command and sends the
+    /// invalidations for them.
+    fn refresh_dirty(&mut self, modified: bool) {
+        for key in std::mem::take(&mut self.dirty) {
+            let Some(entry) = self.state.get_mut(&key) else { continue };
+            // aggregates are deleted with their last element, or when the command creating
+            // them failed, in which case they were never accounted for
+            if entry.value.is_empty_collection() {
+                let existed = entry.size > 0;
+                self.del(&key);
+                if existed {
+                    self.notify('g', "del", &key);
+                }
+                continue;
+            }
+            if !modified {
+                continue;
+            }
+            entry.value.fit_encoding(&self.memory, false);
+            let size = key.len() + entry.value.memory_usage(memory::ACCOUNTING_SAMPLES);
+            self.used_memory = self.used_memory + size - entry.size;
+            entry.size = size;
+            self.key_modified(&key);
+        }
+        self.flush_invalidations();
+    }
+
+    /// Evicts keys according to `maxmemory-policy` until memory usage is below `maxmemory`.
+    fn free_memory(&mut self) -> anyhow::Result<()> {
+        let config = self.memory;
+        while config.maxmemory != 0 && self.used_memory > config.maxmemory {
+            let Some(key) = self.eviction_candidate() else {
+                anyhow::bail!("{}", memory::OOM_ERROR);
+            };
+            self.del(&key);
+            self.notify('e', "evicted", &key);
+            self.propagate(replication::encode(vec![b"DEL".to_vec(), key]));
+            self.stats.evicted_keys += 1;
+        }
+        Ok(())
+    }
+
+    /// Approximates LRU/LFU by sampling `maxmemory-samples` keys, like Redis does.
+    fn eviction_candidate(&mut self) -> Option<ByteString> {
+        let config = self.memory;
+        if config.policy == memory::Policy::NoEviction || self.state.is_empty() {
+            return None;
+        }
+        // volatile policies only consider keys with an expire set
+        if config.policy.is_volatile() {
+            if self.expires.is_empty() {
+                return None;
+            }
+            let samples = (0..config.samples).map(|_| self.rng.random_range(0..self.expires.len())).collect::<Vec<_>>();
+            let best = match config.policy {
+                memory::Policy::VolatileRandom => samples[0],
+                memory::Policy::VolatileTtl => samples.into_iter().min_by_key(|&i| self.expires[i])?,
+                _ => samples.into_iter()
+                    .filter_map(|i| Some((i, self.state.get(self.expires.get_index(i)?.0)?.access.eviction_score(&config))))
+                    .min_by_key(|&(_, score)| score)?.0,
+            };
+            return self.expires.get_index(best).map(|(k, _)| k.clone());
+        }
+        let samples = (0..config.samples).map(|_| self.rng.random_range(0..self.state.len())).collect::<Vec<_>>();
+        let best = match config.policy {
+            memory::Policy::AllKeysRandom => samples[0],
+            _ => samples.into_iter().min_by_key(|&i| self.state[i].access.eviction_score(&config))?,
+        };
+        self.state.get_index(best).map(|(k, _)| k.clone())
+    }
+}
+
+const PROTECTED_MODE_ERROR: &str = "DENIED Redis is running in protected mode because protected mode is enabled and no password is set for the default user. \
+In this mode connections are only accepted from the loopback interface. \
+If you want to connect from external computers to Redis you may adopt one of the following solutions: \
+1) Just disable protected mode sending the command 'CONFIG SET protected-mode no' from the loopback interface by connecting to Redis from the same host the server is running, however MAKE SURE Redis is not publicly accessible from internet if you do so. Use CONFIG REWRITE to make this change permanent. \
+2) Alternatively you can just disable the protected mode by editing the Redis configuration file, and setting the protected mode option to 'no', and then restarting the server. \
+3) If you started the server manually just for testing, restart it with the '--protected-mode no' option. \
+4) Set up an authentication password for the default user. \
+NOTE: You only need to do one of the above things in order for the server to start accepting connections from the outside.";
+
+/// 40 random hex characters, as used for replication ids and cluster node ids.
+pub(crate) fn random_id() -> String {
+    let mut rng = rand::rng();
+    (0..40).map(|_| char::from_digit(rng.random_range(0..16), 16).unwrap()).collect()
+}
+
+/// The integer a string represents, when it is its canonical form: no sign for positive
+/// numbers, no leading zeros or spaces.
+pub(crate) fn canonical_int(bytes: &[u8]) -> Option<i64> {
+    let n = std::str::from_utf8(bytes).ok()?.parse::<i64>().ok()?;
+    (n.to_string().as_bytes() == bytes).then_some(n)
 }
 
 pub fn escape_bytes(bytes: &[u8]) -> String {
     bytes.iter().flat_map(|&b| std::ascii::escape_default(b)).map(|b| b as char).collect()
 }
 
+/// Keys to transfer to another instance with RESTORE, see MIGRATE.
+#[derive(Debug, Clone)]
+pub struct Migration {
+    pub client: u64,
+    pub host: String,
+    pub port: u16,
+    pub timeout: Duration,
+    /// AUTH followed by the RESTORE commands to send.
+    pub commands: Vec<Vec<ByteString>>,
+}
+
+/// The commands a client can send once it subscribed to a channel.
+const SUBSCRIBED_COMMANDS: &[&str] = &["subscribe", "psubscribe", "unsubscribe", "punsubscribe", "ping", "quit", "reset"];
+
 pub fn execute_command(db: &mut Database, cmd: Command) -> anyhow::Result<Response> {
-    match COMMANDS.get(cmd.cmd().as_bytes()) {
-        Some((command, _)) => command(db, cmd),
-        None => anyhow::bail!("Unrecognized command: {:?}", cmd.cmd()),
+    if db.client().blocked.is_some() {
+        db.client().deferred.push_back(cmd);
+        return Ok(Response::NoReply);
+    }
+    if let Some(client) = db.migrating_client(&cmd) {
+        // in front of the commands sent after it, when it comes from them
+        db.client().blocked = Some(Blocked::MigratingKeys { client });
+        db.client().deferred.push_front(cmd);
+        return Ok(Response::NoReply);
+    }
+    let asking = std::mem::take(&mut db.client().asking);
+    let caching = db.client().caching.take();
+    let Some((command, info)) = COMMANDS.get(cmd.cmd().as_bytes()) else {
+        anyhow::bail!("Unrecognized command: {:?}", cmd.cmd());
+    };
+    db.stats.total_commands_processed += 1;
+    if !info.flags.contains(&b"no_auth".as_slice()) {
+        let Some(username) = db.username() else {
+            anyhow::bail!("NOAUTH Authentication required.");
+        };
+        if let Err((reason, object)) = db.acl.check(&username, info, &cmd) {
+            let client_id = db.client_id();
+            db.acl.log_denial(reason, object, username.clone(), client_id);
+            match reason {
+                "command" => anyhow::bail!("NOPERM User {} has no permissions to run the '{}' command", escape_bytes(&username), cmd.cmd()),
+                _ => anyhow::bail!("NOPERM No permissions to access a {reason}"),
+            }
+        }
+    }
+    if db.is_subscribed() && !SUBSCRIBED_COMMANDS.contains(&cmd.cmd()) {
+        anyhow::bail!("Can't execute '{}': only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / QUIT / RESET are allowed in this context", cmd.cmd());
+    }
+    db.check_cluster_redirect(info, &cmd, asking)?;
+    let write = info.flags.contains(&b"write".as_slice());
+    if write && db.master().is_some() && db.config.get_bool("replica-read-only") {
+        anyhow::bail!("READONLY You can't write against a read only replica.");
+    }
+    if info.flags.contains(&b"denyoom".as_slice()) {
+        db.free_memory()?;
+    }
+    let propagated = write.then(|| cmd.encode());
+    let tracked = (db.client().tracking.is_some() && info.flags.contains(&b"readonly".as_slice()))
+        .then(|| info.keys(&cmd).into_iter().map(|k| k.to_vec()).collect());
+    let res = command(db, cmd);
+    if let (Ok(_), Some(keys)) = (&res, tracked) {
+        db.track_keys(keys, caching);
+    }
+    db.refresh_dirty(write);
+    let propagate_as = db.propagate_as.take();
+    if let (Ok(_), Some(data)) = (&res, propagate_as.or(propagated)) && !data.is_empty() {
+        db.propagate(data);
     }
+    res
+}
+
+/// Commands from our master are applied without checks and forwarded to our own replicas
+/// as `raw`, their bytes as read from the master, only REPLCONF GETACK is answered.
+pub fn execute_master_command(db: &mut Database, cmd: Command, raw: ByteString) -> anyhow::Result<Response> {
+    anyhow::ensure!(db.is_master_client(), "replication link {} is no longer in use", db.client_id());
+    db.propagate(raw);
+    let Some((command, _)) = COMMANDS.get(cmd.cmd().as_bytes()) else {
+        return Ok(Response::NoReply);
+    };
+    db.stats.total_commands_processed += 1;
+    let replconf = cmd.cmd() == "replconf";
+    let res = command(db, cmd);
+    db.refresh_dirty(true);
+    db.propagate_as = None;
+    match res {
+        Ok(res) if replconf => Ok(res),
+        _ => Ok(Response::NoReply),
+    }
+}
+
+/// Errors starting with an all-caps word (e.g. `NOAUTH ...`) carry their own
+/// error code, everything else is reported as a generic `ERR`.
+pub fn error_reply(e: &anyhow::Error) -> String {
+    let msg = e.to_string();
+    let code = msg.split(' ').next().unwrap_or_default();
+    if code.len() > 1 && code.bytes().all(|b| b.is_ascii_uppercase()) {
+        msg
+    } else {
+        format!("ERR {msg}")
+    }
+}
+
+pub fn write_error(writer: &mut impl Write, e: &anyhow::Error) -> anyhow::Result<()> {
+    write!(writer, "-{}\r\n", error_reply(e))?;
+    Ok(())
 }
 
 pub fn write_response(writer: &mut impl Write, res: Response) -> anyhow::Result<()> {
@@ -186,6 +829,8 @@ pub fn write_response(writer: &mut impl Write, res: Response) -> anyhow::Result<
             }
         }
         Response::Nil => write!(writer, "$-1\r\n")?,
+        Response::Raw(value) => writer.write_all(&value)?,
+        Response::NoReply => {}
     }
     Ok(())
 }
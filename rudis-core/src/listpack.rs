@@ -0,0 +1,135 @@
+//! The compact encoding of small aggregates: their elements packed in a single buffer, each
+//! prefixed with its length. Lookups are linear, which is fast enough for the few elements
+//! stored this way while using a fraction of the memory of a hash table.
+
+/// Elements packed one after the other, each prefixed with its length as a varint.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Listpack {
+    data: Vec<u8>,
+    len: usize,
+}
+
+/// The length of the element at `pos` and the size of its prefix.
+fn header(data: &[u8], pos: usize) -> (usize, usize) {
+    let (mut len, mut size) = (0, 0);
+    loop {
+        let b = data[pos + size];
+        len |= ((b & 0x7f) as usize) << (7 * size);
+        size += 1;
+        if b & 0x80 == 0 {
+            return (len, size);
+        }
+    }
+}
+
+fn write_header(buf: &mut Vec<u8>, mut len: usize) {
+    while len >= 0x80 {
+        buf.push((len & 0x7f) as u8 | 0x80);
+        len >>= 7;
+    }
+    buf.push(len as u8);
+}
+
+impl Listpack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// The size of the buffer, for memory accounting.
+    pub fn bytes(&self) -> usize {
+        self.data.capacity()
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter { data: &self.data, pos: 0 }
+    }
+
+    /// The byte offset of element `index`, the end of the buffer for `len`.
+    fn offset(&self, index: usize) -> usize {
+        let mut pos = 0;
+        for _ in 0..index {
+            let (len, size) = header(&self.data, pos);
+            pos += size + len;
+        }
+        pos
+    }
+
+    pub fn get(&self, index: usize) -> Option<&[u8]> {
+        self.iter().nth(index)
+    }
+
+    pub fn push(&mut self, element: &[u8]) {
+        write_header(&mut self.data, element.len());
+        self.data.extend_from_slice(element);
+        self.len += 1;
+    }
+
+    pub fn insert(&mut self, index: usize, element: &[u8]) {
+        let pos = self.offset(index);
+        let mut encoded = Vec::with_capacity(element.len() + 2);
+        write_header(&mut encoded, element.len());
+        encoded.extend_from_slice(element);
+        self.data.splice(pos..pos, encoded);
+        self.len += 1;
+    }
+
+    /// Removes `count` elements starting at `index`.
+    pub fn remove(&mut self, index: usize, count: usize) {
+        let start = self.offset(index);
+        let mut end = start;
+        for _ in 0..count {
+            let (len, size) = header(&self.data, end);
+            end += size + len;
+        }
+        self.data.drain(start..end);
+        self.len -= count;
+    }
+
+    pub fn replace(&mut self, index: usize, element: &[u8]) {
+        self.remove(index, 1);
+        self.insert(index, element);
+    }
+}
+
+pub struct Iter<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.data.len() {
+            return None;
+        }
+        let (len, size) = header(self.data, self.pos);
+        let element = &self.data[self.pos + size..self.pos + size + len];
+        self.pos += size + len;
+        Some(element)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_listpack() {
+        let mut lp = Listpack::new();
+        let long = vec![b'x'; 300];
+        lp.push(b"a");
+        lp.push(&long);
+        lp.insert(1, b"");
+        lp.insert(0, b"b");
+        assert_eq!(lp.iter().collect::<Vec<_>>(), [b"b".as_slice(), b"a", b"", &long]);
+        lp.replace(2, b"c");
+        lp.remove(0, 2);
+        assert_eq!(lp.iter().collect::<Vec<_>>(), [b"c".as_slice(), &long]);
+        assert_eq!((lp.len(), lp.get(1)), (2, Some(long.as_slice())));
+    }
+}
@@ -0,0 +1,216 @@
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+use rand::rngs::SmallRng;
+
+use crate::{Config, Value};
+
+/// Rough per-allocation overhead, used to make small values count for more than their bytes.
+const OVERHEAD: usize = 16;
+
+/// Elements sampled when estimating the size of aggregate values during accounting.
+pub const ACCOUNTING_SAMPLES: usize = 16;
+
+const LFU_INIT_VAL: u8 = 5;
+
+fn average(sizes: impl Iterator<Item=usize>, samples: usize, len: usize) -> usize {
+    let (n, total) = sizes.take(samples.max(1)).fold((0, 0), |(n, total), s| (n + 1, total + s));
+    (total * len).checked_div(n).unwrap_or(0)
+}
+
+impl Value {
+    /// Approximate memory used by the value, aggregates are estimated from up to `samples` elements.
+    pub fn memory_usage(&self, samples: usize) -> usize {
+        OVERHEAD + match self {
+            Value::String(s) => s.heap_bytes(),
+            Value::Array(v) => average(v.iter().map(|e| e.len() + OVERHEAD), samples, v.len()),
+            Value::Hash(h) => h.compact_bytes()
+                .unwrap_or_else(|| average(h.iter().map(|(k, v)| k.len() + v.len() + 2 * OVERHEAD), samples, h.len())),
+            Value::Set(s) => s.compact_bytes().unwrap_or_else(|| average(s.iter().map(|e| e.len() + OVERHEAD), samples, s.len())),
+            Value::ZSet(z) => z.compact_bytes()
+                .unwrap_or_else(|| average(z.iter().map(|(_, e)| e.len() + 3 * OVERHEAD), samples, z.len())),
+        }
+    }
+
+    /// The name of the encoding, as reported by OBJECT ENCODING.
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            Value::String(s) => s.encoding(),
+            Value::Array(_) => "quicklist",
+            Value::Hash(h) => h.encoding(),
+            Value::Set(s) => s.encoding(),
+            Value::ZSet(z) => z.encoding(),
+        }
+    }
+
+    /// Converts aggregates outgrowing their compact encoding, and with `shrink` picks the
+    /// most compact encoding fitting them, as done for new values.
+    pub fn fit_encoding(&mut self, config: &MemoryConfig, shrink: bool) {
+        match self {
+            Value::String(_) | Value::Array(_) => {}
+            Value::Hash(h) => h.fit_encoding(config, shrink),
+            Value::Set(s) => s.fit_encoding(config, shrink),
+            Value::ZSet(z) => z.fit_encoding(config, shrink),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+    NoEviction,
+    AllKeysLru,
+    AllKeysLfu,
+    AllKeysRandom,
+    VolatileLru,
+    VolatileLfu,
+    VolatileRandom,
+    VolatileTtl,
+}
+
+impl Policy {
+    pub fn parse(s: &str) -> anyhow::Result<Self> {
+        let policy = match s {
+            "noeviction" => Self::NoEviction,
+            "allkeys-lru" => Self::AllKeysLru,
+            "allkeys-lfu" => Self::AllKeysLfu,
+            "allkeys-random" => Self::AllKeysRandom,
+            "volatile-lru" => Self::VolatileLru,
+            "volatile-lfu" => Self::VolatileLfu,
+            "volatile-random" => Self::VolatileRandom,
+            "volatile-ttl" => Self::VolatileTtl,
+            _ => anyhow::bail!("argument(s) must be one of the following: volatile-lru, volatile-lfu, volatile-random, volatile-ttl, allkeys-lru, allkeys-lfu, allkeys-random, noeviction"),
+        };
+        Ok(policy)
+    }
+
+    pub fn is_lfu(self) -> bool {
+        matches!(self, Self::AllKeysLfu | Self::VolatileLfu)
+    }
+
+    pub fn is_volatile(self) -> bool {
+        matches!(self, Self::VolatileLru | Self::VolatileLfu | Self::VolatileRandom | Self::VolatileTtl)
+    }
+}
+
+/// The limits up to which aggregates keep the listpack encoding.
+#[derive(Debug, Clone, Copy)]
+pub struct ListpackLimits {
+    pub entries: usize,
+    /// The largest element, in bytes.
+    pub value: usize,
+}
+
+impl ListpackLimits {
+    fn from_config(config: &Config, prefix: &str) -> Self {
+        Self {
+            entries: config.get_parsed(&format!("{prefix}-max-listpack-entries")),
+            value: config.get_parsed(&format!("{prefix}-max-listpack-value")),
+        }
+    }
+}
+
+/// The memory related config, parsed once whenever the config changes.
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryConfig {
+    pub maxmemory: usize,
+    pub policy: Policy,
+    pub samples: usize,
+    pub lfu_log_factor: u32,
+    pub lfu_decay_time: u64,
+    pub hash: ListpackLimits,
+    pub set: ListpackLimits,
+    pub set_max_intset_entries: usize,
+    pub zset: ListpackLimits,
+}
+
+impl MemoryConfig {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            maxmemory: config.get_parsed("maxmemory"),
+            policy: Policy::parse(config.get("maxmemory-policy")).expect("maxmemory-policy is always validated"),
+            samples: config.get_parsed("maxmemory-samples"),
+            lfu_log_factor: config.get_parsed("lfu-log-factor"),
+            lfu_decay_time: config.get_parsed("lfu-decay-time"),
+            hash: ListpackLimits::from_config(config, "hash"),
+            set: ListpackLimits::from_config(config, "set"),
+            set_max_intset_entries: config.get_parsed("set-max-intset-entries"),
+            zset: ListpackLimits::from_config(config, "zset"),
+        }
+    }
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        // parsed once, as new aggregates take their default limits from it
+        static DEFAULT: LazyLock<MemoryConfig> = LazyLock::new(|| MemoryConfig::from_config(&Config::default()));
+        *DEFAULT
+    }
+}
+
+/// Access metadata of a key, backing both LRU and LFU eviction.
+#[derive(Debug, Clone, Copy)]
+pub struct Access {
+    last_access: Instant,
+    lfu_counter: u8,
+    lfu_decrement: Instant,
+}
+
+impl Access {
+    pub fn new() -> Self {
+        let now = Instant::now();
+        Self { last_access: now, lfu_counter: LFU_INIT_VAL, lfu_decrement: now }
+    }
+
+    pub fn idle_time(&self) -> u64 {
+        self.last_access.elapsed().as_secs()
+    }
+
+    /// The logarithmic access counter, decremented once per `lfu-decay-time` minutes without access.
+    pub fn frequency(&self, config: &MemoryConfig) -> u8 {
+        if config.lfu_decay_time == 0 {
+            return self.lfu_counter;
+        }
+        let periods = self.lfu_decrement.elapsed().as_secs() / 60 / config.lfu_decay_time;
+        self.lfu_counter.saturating_sub(periods.min(u8::MAX as _) as u8)
+    }
+
+    /// Makes the last access `seconds` ago, as done by RESTORE IDLETIME.
+    pub fn set_idle_time(&mut self, seconds: u64) {
+        let now = Instant::now();
+        self.last_access = now.checked_sub(Duration::from_secs(seconds)).unwrap_or(now);
+    }
+
+    /// Sets the logarithmic access counter, as done by RESTORE FREQ.
+    pub fn set_frequency(&mut self, counter: u8) {
+        self.lfu_counter = counter;
+        self.lfu_decrement = Instant::now();
+    }
+
+    pub fn touch(&mut self, config: &MemoryConfig, rng: &mut SmallRng) {
+        let counter = self.frequency(config);
+        if counter != self.lfu_counter {
+            self.lfu_decrement = Instant::now();
+        }
+        let base = counter.saturating_sub(LFU_INIT_VAL) as f64;
+        let p = 1.0 / (base * config.lfu_log_factor as f64 + 1.0);
+        self.lfu_counter = if counter < u8::MAX && rng.random::<f64>() < p { counter + 1 } else { counter };
+        self.last_access = Instant::now();
+    }
+
+    /// Lower scores are evicted first.
+    pub fn eviction_score(&self, config: &MemoryConfig) -> u64 {
+        match config.policy {
+            Policy::AllKeysLfu | Policy::VolatileLfu => self.frequency(config) as _,
+            _ => u64::MAX - self.last_access.elapsed().as_nanos() as u64,
+        }
+    }
+}
+
+impl Default for Access {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub const OOM_ERROR: &str = "OOM command not allowed when used memory > 'maxmemory'.";
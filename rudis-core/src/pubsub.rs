@@ -0,0 +1,127 @@
+use crate::acl::glob_matches;
+use crate::{write_response, ByteString, Database, Response};
+
+/// Event classes of `notify-keyspace-events`, `A` being an alias for all of them.
+const CLASSES: &str = "g$lshzxetmdn";
+const ALL_CLASSES: &str = "g$lshzxetd";
+
+/// Validates `notify-keyspace-events`, an empty value disabling notifications.
+pub(crate) fn notify_keyspace_events(v: &str) -> anyhow::Result<String> {
+    anyhow::ensure!(v.chars().all(|c| c == 'K' || c == 'E' || c == 'A' || CLASSES.contains(c)), "Invalid event class character. Use 'Ag$lshzxeKEtmdn'.");
+    Ok(v.to_string())
+}
+
+fn message(kind: &[u8], channel: &[u8], count: usize) -> Response {
+    Response::Array(vec![
+        Response::BulkString(kind.to_vec()),
+        Response::BulkString(channel.to_vec()),
+        Response::Number(count as _),
+    ])
+}
+
+/// (P)SUBSCRIBE and (P)UNSUBSCRIBE answer with one message per channel.
+fn replies(replies: Vec<Response>) -> Response {
+    if replies.len() == 1 {
+        return replies.into_iter().next().unwrap();
+    }
+    let mut buf = Vec::new();
+    for reply in replies {
+        write_response(&mut buf, reply).expect("writing to a Vec can't fail");
+    }
+    Response::Raw(buf)
+}
+
+impl Database {
+    /// The number of channels and patterns the current client is subscribed to.
+    fn subscription_count(&mut self) -> usize {
+        let client = self.client();
+        client.channels.len() + client.patterns.len()
+    }
+
+    pub fn is_subscribed(&mut self) -> bool {
+        self.subscription_count() > 0
+    }
+
+    pub fn subscribe(&mut self, channels: Vec<ByteString>) -> Response {
+        let res = channels.into_iter().map(|channel| {
+            self.client().channels.insert(channel.clone());
+            message(b"subscribe", &channel, self.subscription_count())
+        });
+        replies(res.collect())
+    }
+
+    pub fn psubscribe(&mut self, patterns: Vec<ByteString>) -> Response {
+        let res = patterns.into_iter().map(|pattern| {
+            self.client().patterns.insert(pattern.clone());
+            message(b"psubscribe", &pattern, self.subscription_count())
+        });
+        replies(res.collect())
+    }
+
+    /// Unsubscribes from the given channels, or from all of them when none are given.
+    pub fn unsubscribe(&mut self, channels: Vec<ByteString>) -> Response {
+        let channels = match channels.is_empty() {
+            true => self.client().channels.drain().collect(),
+            false => channels,
+        };
+        if channels.is_empty() {
+            return Response::Array(vec![Response::BulkString(b"unsubscribe".to_vec()), Response::Nil, Response::Number(0)]);
+        }
+        let res = channels.into_iter().map(|channel| {
+            self.client().channels.remove(&channel);
+            message(b"unsubscribe", &channel, self.subscription_count())
+        });
+        replies(res.collect())
+    }
+
+    pub fn punsubscribe(&mut self, patterns: Vec<ByteString>) -> Response {
+        let patterns = match patterns.is_empty() {
+            true => self.client().patterns.drain().collect(),
+            false => patterns,
+        };
+        if patterns.is_empty() {
+            return Response::Array(vec![Response::BulkString(b"punsubscribe".to_vec()), Response::Nil, Response::Number(0)]);
+        }
+        let res = patterns.into_iter().map(|pattern| {
+            self.client().patterns.remove(&pattern);
+            message(b"punsubscribe", &pattern, self.subscription_count())
+        });
+        replies(res.collect())
+    }
+
+    /// Sends `message` to the subscribers of `channel`, returns the number of clients reached.
+    pub fn publish(&mut self, channel: &[u8], message: &[u8]) -> usize {
+        let mut receivers = 0;
+        for (&id, client) in &self.clients {
+            if client.channels.contains(channel) {
+                self.outbox.push((id, Ok(Response::string_array([b"message".to_vec(), channel.to_vec(), message.to_vec()]))));
+                receivers += 1;
+            }
+            for pattern in client.patterns.iter().filter(|p| glob_matches(p, channel)) {
+                let res = Response::string_array([b"pmessage".to_vec(), pattern.clone(), channel.to_vec(), message.to_vec()]);
+                self.outbox.push((id, Ok(res)));
+                receivers += 1;
+            }
+        }
+        receivers
+    }
+
+    /// Publishes a keyspace notification according to `notify-keyspace-events`, `class` being
+    /// the type of event, e.g. `$` for string commands or `g` for generic ones.
+    pub(crate) fn notify(&mut self, class: char, event: &str, key: &[u8]) {
+        let flags = self.config.get("notify-keyspace-events");
+        let enabled = flags.contains(class) || (flags.contains('A') && ALL_CLASSES.contains(class));
+        if !enabled {
+            return;
+        }
+        let (keyspace, keyevent) = (flags.contains('K'), flags.contains('E'));
+        if keyspace {
+            let channel = [b"__keyspace@0__:".as_slice(), key].concat();
+            self.publish(&channel, event.as_bytes());
+        }
+        if keyevent {
+            let channel = format!("__keyevent@0__:{event}");
+            self.publish(channel.as_bytes(), key);
+        }
+    }
+}
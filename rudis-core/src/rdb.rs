@@ -0,0 +1,494 @@
+
+use ordered_float::NotNan;
+
+use crate::sorted_set::SortedSet;
+use crate::string::MAX_STRING_LEN;
+use crate::{ByteString, Database, Hash, RestorePolicy, Set, Value};
+
+const VERSION: &[u8] = b"0012";
+
+const TYPE_STRING: u8 = 0;
+const TYPE_LIST: u8 = 1;
+const TYPE_SET: u8 = 2;
+const TYPE_HASH: u8 = 4;
+const TYPE_ZSET_2: u8 = 5;
+// compact encodings only written by Redis, read when restoring its payloads or syncing from it
+const TYPE_SET_INTSET: u8 = 11;
+const TYPE_HASH_LISTPACK: u8 = 16;
+const TYPE_ZSET_LISTPACK: u8 = 17;
+const TYPE_LIST_QUICKLIST_2: u8 = 18;
+const TYPE_SET_LISTPACK: u8 = 20;
+/// A hash with fields expiring, only written for those as older versions cannot read it.
+const TYPE_HASH_METADATA: u8 = 24;
+
+const OPCODE_FUNCTION2: u8 = 0xf5;
+const OPCODE_EXPIRETIME_MS: u8 = 0xfc;
+const OPCODE_EXPIRETIME: u8 = 0xfd;
+const OPCODE_SELECTDB: u8 = 0xfe;
+const OPCODE_EOF: u8 = 0xff;
+
+const ENC_INT8: usize = 0;
+const ENC_INT16: usize = 1;
+const ENC_INT32: usize = 2;
+const ENC_LZF: usize = 3;
+
+/// The most bytes LZF can decompress 3 bytes of input to, with a back reference of 264 bytes.
+const LZF_MAX_EXPANSION: usize = 88;
+
+const QUICKLIST_NODE_PLAIN: usize = 1;
+const QUICKLIST_NODE_PACKED: usize = 2;
+
+/// CRC-64/Jones as used by Redis for RDB files and DUMP payloads.
+pub fn crc64(data: &[u8]) -> u64 {
+    const POLY: u64 = 0x95ac9329ac4bc9b5;
+    data.iter().fold(0, |mut crc, &b| {
+        crc ^= b as u64;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ POLY } else { crc >> 1 };
+        }
+        crc
+    })
+}
+
+fn write_len(buf: &mut Vec<u8>, len: usize) {
+    match len {
+        0..0x40 => buf.push(len as u8),
+        0x40..0x4000 => buf.extend_from_slice(&(0x4000 | len as u16).to_be_bytes()),
+        _ if len <= u32::MAX as usize => {
+            buf.push(0x80);
+            buf.extend_from_slice(&(len as u32).to_be_bytes());
+        }
+        _ => {
+            buf.push(0x81);
+            buf.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+}
+
+fn write_string(buf: &mut Vec<u8>, s: &[u8]) {
+    write_len(buf, s.len());
+    buf.extend_from_slice(s);
+}
+
+/// Writes integers in the smallest of the integer encodings, larger ones as strings.
+fn write_int(buf: &mut Vec<u8>, n: i64) {
+    if let Ok(n) = i8::try_from(n) {
+        buf.push(0xc0 | ENC_INT8 as u8);
+        buf.extend_from_slice(&n.to_le_bytes());
+    } else if let Ok(n) = i16::try_from(n) {
+        buf.push(0xc0 | ENC_INT16 as u8);
+        buf.extend_from_slice(&n.to_le_bytes());
+    } else if let Ok(n) = i32::try_from(n) {
+        buf.push(0xc0 | ENC_INT32 as u8);
+        buf.extend_from_slice(&n.to_le_bytes());
+    } else {
+        write_string(buf, n.to_string().as_bytes());
+    }
+}
+
+/// Appends the type byte and the encoding of `value`.
+pub fn write_value(buf: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::String(s) => {
+            buf.push(TYPE_STRING);
+            match s.as_int() {
+                Some(n) => write_int(buf, n),
+                None => write_string(buf, &s.as_bytes()),
+            }
+        }
+        Value::Array(v) => {
+            buf.push(TYPE_LIST);
+            write_len(buf, v.len());
+            v.iter().for_each(|e| write_string(buf, e));
+        }
+        Value::Set(s) => {
+            buf.push(TYPE_SET);
+            write_len(buf, s.len());
+            s.iter().for_each(|e| write_string(buf, &e));
+        }
+        Value::Hash(h) if h.has_expires() => {
+            // expires are stored relative to the earliest one, 0 meaning no expire
+            let min = h.expires().map(|(_, at)| at).min().unwrap_or(0);
+            buf.push(TYPE_HASH_METADATA);
+            buf.extend_from_slice(&min.to_le_bytes());
+            write_len(buf, h.len());
+            for (k, v) in h.iter() {
+                write_len(buf, h.expiry(k).map_or(0, |at| (at - min) as usize + 1));
+                write_string(buf, k);
+                write_string(buf, v);
+            }
+        }
+        Value::Hash(h) => {
+            buf.push(TYPE_HASH);
+            write_len(buf, h.len());
+            for (k, v) in h.iter() {
+                write_string(buf, k);
+                write_string(buf, v);
+            }
+        }
+        Value::ZSet(z) => {
+            buf.push(TYPE_ZSET_2);
+            write_len(buf, z.len());
+            for (score, member) in z.iter() {
+                write_string(buf, member);
+                buf.extend_from_slice(&score.to_le_bytes());
+            }
+        }
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> anyhow::Result<&'a [u8]> {
+        anyhow::ensure!(self.0.len() >= n, "unexpected end of RDB data");
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn byte(&mut self) -> anyhow::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    /// Returns the length, or the special encoding when the top bits are `11`.
+    fn len_or_encoding(&mut self) -> anyhow::Result<(usize, bool)> {
+        let b = self.byte()?;
+        let len = match b >> 6 {
+            0 => (b & 0x3f) as usize,
+            1 => ((b as usize & 0x3f) << 8) | self.byte()? as usize,
+            2 if b == 0x80 => u32::from_be_bytes(self.take(4)?.try_into()?) as usize,
+            2 if b == 0x81 => u64::from_be_bytes(self.take(8)?.try_into()?) as usize,
+            3 => return Ok(((b & 0x3f) as usize, true)),
+            _ => anyhow::bail!("invalid RDB length encoding"),
+        };
+        Ok((len, false))
+    }
+
+    fn len(&mut self) -> anyhow::Result<usize> {
+        match self.len_or_encoding()? {
+            (len, false) => Ok(len),
+            _ => anyhow::bail!("unexpected encoded RDB length"),
+        }
+    }
+
+    fn string(&mut self) -> anyhow::Result<ByteString> {
+        let n = match self.len_or_encoding()? {
+            (len, false) => return Ok(self.take(len)?.to_vec()),
+            (ENC_INT8, true) => self.byte()? as i8 as i64,
+            (ENC_INT16, true) => i16::from_le_bytes(self.take(2)?.try_into()?) as i64,
+            (ENC_INT32, true) => i32::from_le_bytes(self.take(4)?.try_into()?) as i64,
+            (ENC_LZF, true) => {
+                let (compressed, len) = (self.len()?, self.len()?);
+                return lzf_decompress(self.take(compressed)?, len);
+            }
+            _ => anyhow::bail!("unsupported RDB string encoding"),
+        };
+        Ok(n.to_string().into_bytes())
+    }
+
+    fn strings(&mut self) -> anyhow::Result<Vec<ByteString>> {
+        (0..self.len()?).map(|_| self.string()).collect()
+    }
+}
+
+/// Decompresses a string compressed with LZF, as Redis does for long strings.
+fn lzf_decompress(input: &[u8], len: usize) -> anyhow::Result<ByteString> {
+    let invalid = || anyhow::anyhow!("invalid LZF compressed string");
+    anyhow::ensure!(len <= MAX_STRING_LEN, invalid());
+    // the length comes from the payload, so no more is reserved than the input can expand to
+    let mut out = Vec::with_capacity(len.min(input.len().saturating_mul(LZF_MAX_EXPANSION)));
+    let mut input = Reader(input);
+    while !input.0.is_empty() {
+        let ctrl = input.byte()? as usize;
+        if ctrl < 32 {
+            anyhow::ensure!(out.len() + ctrl < len, invalid());
+            out.extend_from_slice(input.take(ctrl + 1)?);
+            continue;
+        }
+        // a back reference to `n` bytes already decompressed
+        let mut n = ctrl >> 5;
+        if n == 7 {
+            n += input.byte()? as usize;
+        }
+        let distance = ((ctrl & 0x1f) << 8) + input.byte()? as usize + 1;
+        let start = out.len().checked_sub(distance).ok_or_else(invalid)?;
+        anyhow::ensure!(out.len() + n + 2 <= len, invalid());
+        for i in start..start + n + 2 {
+            out.push(out[i]);
+        }
+    }
+    anyhow::ensure!(out.len() == len, invalid());
+    Ok(out)
+}
+
+/// The elements of a Redis listpack: a header with its size and number of elements, followed
+/// by the elements each with an encoding, its data and its size backwards, then `0xff`.
+fn listpack_elements(data: &[u8]) -> anyhow::Result<Vec<ByteString>> {
+    let int = |n: i64| n.to_string().into_bytes();
+    let mut r = Reader(data);
+    r.take(6)?;
+    let mut elements = Vec::new();
+    loop {
+        let b = r.byte()?;
+        let (element, size) = match b {
+            0xff => break,
+            _ if b & 0x80 == 0 => (int(b as i64), 1),
+            _ if b & 0xc0 == 0x80 => {
+                let len = (b & 0x3f) as usize;
+                (r.take(len)?.to_vec(), 1 + len)
+            }
+            _ if b & 0xe0 == 0xc0 => {
+                let n = (((b as u16 & 0x1f) << 8 | r.byte()? as u16) << 3) as i16 >> 3;
+                (int(n as i64), 2)
+            }
+            _ if b & 0xf0 == 0xe0 => {
+                let len = (b as usize & 0x0f) << 8 | r.byte()? as usize;
+                (r.take(len)?.to_vec(), 2 + len)
+            }
+            0xf0 => {
+                let len = u32::from_le_bytes(r.take(4)?.try_into()?) as usize;
+                (r.take(len)?.to_vec(), 5 + len)
+            }
+            0xf1 => (int(i16::from_le_bytes(r.take(2)?.try_into()?) as i64), 3),
+            0xf2 => {
+                let bytes = r.take(3)?;
+                (int((i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8) as i64), 4)
+            }
+            0xf3 => (int(i32::from_le_bytes(r.take(4)?.try_into()?) as i64), 5),
+            0xf4 => (int(i64::from_le_bytes(r.take(8)?.try_into()?)), 9),
+            _ => anyhow::bail!("invalid listpack encoding"),
+        };
+        r.take(match size {
+            0..128 => 1,
+            128..16384 => 2,
+            16384..2097152 => 3,
+            2097152..268435456 => 4,
+            _ => 5,
+        })?;
+        elements.push(element);
+    }
+    Ok(elements)
+}
+
+/// The elements of a Redis intset: the width of the integers, their number, then the integers.
+fn intset_elements(data: &[u8]) -> anyhow::Result<Vec<ByteString>> {
+    let mut r = Reader(data);
+    let width = u32::from_le_bytes(r.take(4)?.try_into()?) as usize;
+    let len = u32::from_le_bytes(r.take(4)?.try_into()?) as usize;
+    anyhow::ensure!(matches!(width, 2 | 4 | 8), "invalid intset encoding");
+    (0..len).map(|_| {
+        let bytes = r.take(width)?;
+        let n = match width {
+            2 => i16::from_le_bytes(bytes.try_into()?) as i64,
+            4 => i32::from_le_bytes(bytes.try_into()?) as i64,
+            _ => i64::from_le_bytes(bytes.try_into()?),
+        };
+        Ok(n.to_string().into_bytes())
+    }).collect()
+}
+
+fn read_value(r: &mut Reader, ty: u8) -> anyhow::Result<Value> {
+    let value = match ty {
+        TYPE_STRING => Value::String(r.string()?.into()),
+        TYPE_LIST => Value::Array(r.strings()?),
+        TYPE_SET => Value::Set(r.strings()?.into_iter().collect::<Set>()),
+        TYPE_HASH => {
+            let mut h = Hash::new();
+            for _ in 0..r.len()? {
+                h.insert(r.string()?, r.string()?);
+            }
+            Value::Hash(h)
+        }
+        TYPE_HASH_METADATA => {
+            let min = i64::from_le_bytes(r.take(8)?.try_into()?);
+            let mut h = Hash::new();
+            for _ in 0..r.len()? {
+                let ttl = r.len()?;
+                let field = r.string()?;
+                h.insert(field.clone(), r.string()?);
+                if ttl > 0 {
+                    let at = i64::try_from(ttl - 1).ok().and_then(|ttl| min.checked_add(ttl));
+                    h.set_expiry(&field, at.ok_or_else(|| anyhow::anyhow!("Bad data format"))?);
+                }
+            }
+            Value::Hash(h)
+        }
+        TYPE_ZSET_2 => {
+            let mut z = SortedSet::new();
+            for _ in 0..r.len()? {
+                let member = r.string()?;
+                let score = NotNan::new(f64::from_le_bytes(r.take(8)?.try_into()?))?;
+                z.insert(score, member);
+            }
+            Value::ZSet(z)
+        }
+        TYPE_SET_INTSET => Value::Set(intset_elements(&r.string()?)?.into_iter().collect::<Set>()),
+        TYPE_SET_LISTPACK => Value::Set(listpack_elements(&r.string()?)?.into_iter().collect::<Set>()),
+        TYPE_HASH_LISTPACK => {
+            let elements = listpack_elements(&r.string()?)?;
+            anyhow::ensure!(elements.len() % 2 == 0, "invalid hash listpack");
+            let mut elements = elements.into_iter();
+            Value::Hash(std::iter::from_fn(|| Some((elements.next()?, elements.next()?))).collect())
+        }
+        TYPE_ZSET_LISTPACK => {
+            let elements = listpack_elements(&r.string()?)?;
+            anyhow::ensure!(elements.len() % 2 == 0, "invalid sorted set listpack");
+            let mut z = SortedSet::new();
+            for pair in elements.chunks(2) {
+                let score = std::str::from_utf8(&pair[1])?.parse::<f64>()?;
+                z.insert(NotNan::new(score)?, pair[0].clone());
+            }
+            Value::ZSet(z)
+        }
+        TYPE_LIST_QUICKLIST_2 => {
+            let mut v = Vec::new();
+            for _ in 0..r.len()? {
+                match (r.len()?, r.string()?) {
+                    (QUICKLIST_NODE_PLAIN, element) => v.push(element),
+                    (QUICKLIST_NODE_PACKED, lp) => v.extend(listpack_elements(&lp)?),
+                    _ => anyhow::bail!("invalid quicklist node"),
+                }
+            }
+            Value::Array(v)
+        }
+        _ => anyhow::bail!("unsupported RDB value type {ty}"),
+    };
+    Ok(value)
+}
+
+/// The RDB version of DUMP payloads, and the one needed for hashes with fields expiring.
+const DUMP_VERSION: u16 = 11;
+const DUMP_VERSION_HASH_METADATA: u16 = 12;
+
+/// The payload of DUMP and RESTORE: the value followed by the RDB version and a checksum. The
+/// version is the oldest able to read the value, so that older servers can restore it.
+pub fn dump_payload(value: &Value) -> ByteString {
+    let mut buf = Vec::new();
+    write_value(&mut buf, value);
+    let version = if buf[0] == TYPE_HASH_METADATA { DUMP_VERSION_HASH_METADATA } else { DUMP_VERSION };
+    buf.extend_from_slice(&version.to_le_bytes());
+    let crc = crc64(&buf);
+    buf.extend_from_slice(&crc.to_le_bytes());
+    buf
+}
+
+pub fn restore_payload(payload: &[u8]) -> anyhow::Result<Value> {
+    anyhow::ensure!(payload.len() >= 10, "DUMP payload version or checksum are wrong");
+    let (data, checksum) = payload.split_at(payload.len() - 8);
+    let version = u16::from_le_bytes(data[data.len() - 2..].try_into()?);
+    let checksum = u64::from_le_bytes(checksum.try_into()?);
+    // unlike RDB files, DUMP payloads always carry a checksum, so a zero one is not skipped
+    anyhow::ensure!(version <= DUMP_VERSION_HASH_METADATA && checksum == crc64(data), "DUMP payload version or checksum are wrong");
+    let mut r = Reader(&data[..data.len() - 2]);
+    let ty = r.byte()?;
+    let value = read_value(&mut r, ty).map_err(|_| anyhow::anyhow!("Bad data format"))?;
+    anyhow::ensure!(r.0.is_empty() && !value.is_empty_collection(), "Bad data format");
+    Ok(value)
+}
+
+/// The payload of FUNCTION DUMP: the code of each library, followed by the RDB version and a
+/// checksum like a DUMP payload.
+pub fn dump_functions(codes: &[ByteString]) -> ByteString {
+    let mut buf = Vec::new();
+    for code in codes {
+        buf.push(OPCODE_FUNCTION2);
+        write_string(&mut buf, code);
+    }
+    buf.extend_from_slice(&DUMP_VERSION.to_le_bytes());
+    let crc = crc64(&buf);
+    buf.extend_from_slice(&crc.to_le_bytes());
+    buf
+}
+
+pub fn restore_functions(payload: &[u8]) -> anyhow::Result<Vec<ByteString>> {
+    anyhow::ensure!(payload.len() >= 10, "payload version or checksum are wrong");
+    let (data, checksum) = payload.split_at(payload.len() - 8);
+    let version = u16::from_le_bytes(data[data.len() - 2..].try_into()?);
+    let checksum = u64::from_le_bytes(checksum.try_into()?);
+    anyhow::ensure!(version <= DUMP_VERSION_HASH_METADATA && checksum == crc64(data), "payload version or checksum are wrong");
+    let mut r = Reader(&data[..data.len() - 2]);
+    let mut codes = Vec::new();
+    while !r.0.is_empty() {
+        anyhow::ensure!(r.byte()? == OPCODE_FUNCTION2, "given type is not a function");
+        codes.push(r.string().map_err(|_| anyhow::anyhow!("Bad data format"))?);
+    }
+    Ok(codes)
+}
+
+impl Database {
+    /// Serializes the keyspace in the RDB format, used for full resynchronization of replicas.
+    pub fn save_rdb(&self) -> ByteString {
+        let mut buf = b"REDIS".to_vec();
+        buf.extend_from_slice(VERSION);
+        for code in self.function_codes() {
+            buf.push(OPCODE_FUNCTION2);
+            write_string(&mut buf, &code);
+        }
+        buf.extend_from_slice(&[OPCODE_SELECTDB, 0]);
+        for (key, entry) in &self.state {
+            if let Some(at) = self.expiry(key) {
+                buf.push(OPCODE_EXPIRETIME_MS);
+                buf.extend_from_slice(&at.to_le_bytes());
+            }
+            let mut value = Vec::new();
+            write_value(&mut value, &entry.value);
+            buf.push(value[0]);
+            write_string(&mut buf, key);
+            buf.extend_from_slice(&value[1..]);
+        }
+        buf.push(OPCODE_EOF);
+        let crc = crc64(&buf);
+        buf.extend_from_slice(&crc.to_le_bytes());
+        buf
+    }
+
+    /// Replaces the keyspace and the functions with the contents of an RDB file, leaving it untouched on errors.
+    pub fn load_rdb(&mut self, data: &[u8]) -> anyhow::Result<()> {
+        anyhow::ensure!(data.len() >= 18 && data.starts_with(b"REDIS"), "invalid RDB header");
+        let (body, checksum) = data.split_at(data.len() - 8);
+        let checksum = u64::from_le_bytes(checksum.try_into()?);
+        anyhow::ensure!(checksum == 0 || checksum == crc64(body), "RDB checksum mismatch");
+
+        let mut r = Reader(&body[9..]);
+        let mut entries = Vec::new();
+        let mut functions = Vec::new();
+        let mut expiry = None;
+        loop {
+            match r.byte()? {
+                OPCODE_EOF => break,
+                OPCODE_SELECTDB => {
+                    r.len()?;
+                }
+                OPCODE_FUNCTION2 => functions.push(r.string()?),
+                OPCODE_EXPIRETIME_MS => expiry = Some(i64::from_le_bytes(r.take(8)?.try_into()?)),
+                OPCODE_EXPIRETIME => expiry = Some(i64::from(u32::from_le_bytes(r.take(4)?.try_into()?)) * 1000),
+                // resizedb hints
+                0xfb => {
+                    r.len()?;
+                    r.len()?;
+                }
+                // auxiliary fields
+                0xfa => {
+                    r.string()?;
+                    r.string()?;
+                }
+                ty => {
+                    let key = r.string()?;
+                    entries.push((key, read_value(&mut r, ty)?, expiry.take()));
+                }
+            }
+        }
+        self.function_restore(&functions, RestorePolicy::Flush)?;
+        self.clear();
+        for (key, value, expiry) in entries {
+            if let Some(at) = expiry {
+                self.set(key.clone(), value);
+                self.set_expiry(&key, at);
+            } else {
+                self.set(key, value);
+            }
+        }
+        Ok(())
+    }
+}
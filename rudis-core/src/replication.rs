@@ -0,0 +1,326 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::client::Blocked;
+use crate::{random_id, write_response, ByteString, Client, Database, Response};
+
+const ACK_PERIOD: Duration = Duration::from_secs(1);
+const PING_PERIOD: Duration = Duration::from_secs(10);
+
+/// State of the link to our master, as reported by ROLE.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkState {
+    Connect,
+    Connecting,
+    Connected,
+}
+
+impl LinkState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Connect => "connect",
+            Self::Connecting => "connecting",
+            Self::Connected => "connected",
+        }
+    }
+}
+
+/// How the master answered our PSYNC.
+#[derive(Debug, Clone)]
+pub enum Sync {
+    Full { replid: String, offset: u64, rdb: ByteString },
+    /// The master may announce a new replication id when it was promoted in the meantime.
+    Partial { replid: Option<String> },
+}
+
+/// A replica connected to us, keyed by its client id.
+#[derive(Debug, Clone)]
+pub struct Replica {
+    pub ip: String,
+    pub port: u16,
+    pub ack_offset: u64,
+    pub last_ack: Instant,
+    /// Set once the replica sent PSYNC and receives the replication stream.
+    pub online: bool,
+}
+
+pub struct Replication {
+    replid: String,
+    /// Bytes of replication stream produced, or received from our master, so far.
+    offset: u64,
+    backlog: VecDeque<u8>,
+    pub(crate) backlog_size: usize,
+    replicas: BTreeMap<u64, Replica>,
+    master_client: Option<u64>,
+    link_state: LinkState,
+    /// Whether `replid` and `offset` belong to our master so that we can attempt a partial resync.
+    cached_master: bool,
+    last_ack: Instant,
+    last_ping: Instant,
+}
+
+impl Default for Replication {
+    fn default() -> Self {
+        Self {
+            replid: random_id(),
+            offset: 0,
+            backlog: VecDeque::new(),
+            backlog_size: 1 << 20,
+            replicas: BTreeMap::new(),
+            master_client: None,
+            link_state: LinkState::Connect,
+            cached_master: false,
+            last_ack: Instant::now(),
+            last_ping: Instant::now(),
+        }
+    }
+}
+
+impl Replication {
+    pub fn replid(&self) -> &str {
+        &self.replid
+    }
+
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn link_state(&self) -> LinkState {
+        self.link_state
+    }
+
+    pub fn backlog_len(&self) -> usize {
+        self.backlog.len()
+    }
+
+    /// Offset of the first byte in the backlog, replication offsets start at 1.
+    pub fn backlog_first_offset(&self) -> u64 {
+        self.offset + 1 - self.backlog.len() as u64
+    }
+
+    pub fn replicas(&self) -> impl Iterator<Item=&Replica> {
+        self.replicas.values().filter(|r| r.online)
+    }
+
+    /// Number of replicas that acknowledged all the stream up to `offset`.
+    pub fn acked(&self, offset: u64) -> usize {
+        self.replicas().filter(|r| r.ack_offset >= offset).count()
+    }
+
+    fn feed(&mut self, data: &[u8]) {
+        self.offset += data.len() as u64;
+        self.backlog.extend(data);
+        let excess = self.backlog.len().saturating_sub(self.backlog_size);
+        self.backlog.drain(..excess);
+    }
+
+    /// The stream from `offset` on, if it is still in the backlog.
+    fn backlog_from(&self, offset: u64) -> Option<ByteString> {
+        if offset < self.backlog_first_offset() || offset > self.offset + 1 {
+            return None;
+        }
+        let skip = (offset - self.backlog_first_offset()) as usize;
+        Some(self.backlog.iter().skip(skip).copied().collect())
+    }
+}
+
+pub(crate) fn encode(args: Vec<ByteString>) -> ByteString {
+    let mut buf = Vec::new();
+    write_response(&mut buf, Response::string_array(args)).expect("writing to a Vec never fails");
+    buf
+}
+
+impl Database {
+    pub fn replication(&self) -> &Replication {
+        &self.replication
+    }
+
+    /// The master set with REPLICAOF or the `replicaof` config, if we are a replica.
+    pub fn master(&self) -> Option<(String, u16)> {
+        let (host, port) = self.config.get_optional("replicaof")?.split_once(' ')?;
+        Some((host.to_string(), port.parse().ok()?))
+    }
+
+    pub fn is_master_client(&self) -> bool {
+        self.replication.master_client == Some(self.current_client)
+    }
+
+    pub fn replica_of(&mut self, master: Option<(String, u16)>) {
+        let value = master.map(|(host, port)| format!("{host} {port}")).unwrap_or_default();
+        self.config.set("replicaof", &value).expect("replicaof is valid");
+        if let Some(id) = self.replication.master_client.take() {
+            self.clients.remove(&id);
+        }
+        self.replication.link_state = LinkState::Connect;
+        if value.is_empty() {
+            // our history diverges from the old master from now on
+            self.replication.replid = random_id();
+            self.replication.cached_master = false;
+        }
+    }
+
+    /// Appends data to the replication stream and sends it to the online replicas.
+    pub(crate) fn propagate(&mut self, data: ByteString) {
+        self.replication.feed(&data);
+        for (&id, _) in self.replication.replicas.iter().filter(|(_, r)| r.online) {
+            self.outbox.push((id, Ok(Response::Raw(data.clone()))));
+        }
+    }
+
+    /// Replaces what the current write command propagates, for commands whose effect is not
+    /// deterministic, like SPOP which is propagated as SREM of the popped members.
+    pub fn propagate_as(&mut self, args: Vec<ByteString>) {
+        self.propagate_as = Some(encode(args));
+    }
+
+    /// Keeps the current write command from being propagated, for those writing nothing like
+    /// SORT without STORE.
+    pub fn propagate_nothing(&mut self) {
+        self.propagate_as = Some(Vec::new());
+    }
+
+    /// Registers the current client as a replica listening on `port`.
+    pub fn add_replica(&mut self, port: u16) {
+        let ip = self.client().addr.map(|a| a.ip().to_canonical().to_string()).unwrap_or_else(|| "127.0.0.1".to_string());
+        let replica = Replica { ip, port, ack_offset: 0, last_ack: Instant::now(), online: false };
+        self.replication.replicas.entry(self.current_client).or_insert(replica).port = port;
+    }
+
+    pub fn replica_ack(&mut self, offset: u64) {
+        if let Some(replica) = self.replication.replicas.get_mut(&self.current_client) {
+            replica.ack_offset = replica.ack_offset.max(offset);
+            replica.last_ack = Instant::now();
+            self.unblock_clients();
+        }
+    }
+
+    /// Answers a PSYNC from the current client and starts streaming to it. The reply is either
+    /// `+CONTINUE` followed by the missing part of the stream or `+FULLRESYNC` followed by a snapshot.
+    pub fn psync(&mut self, replid: &[u8], offset: i64) -> anyhow::Result<Response> {
+        if self.master().is_some() && self.replication.link_state != LinkState::Connected {
+            anyhow::bail!("NOMASTERLINK Can't SYNC while not connected with my master");
+        }
+        let port = self.replication.replicas.get(&self.current_client).map(|r| r.port).unwrap_or_default();
+        self.add_replica(port);
+        let partial = (replid == self.replication.replid.as_bytes())
+            .then(|| self.replication.backlog_from(offset.max(0) as u64))
+            .flatten();
+        let (reply, ack_offset) = match partial {
+            Some(stream) => {
+                let mut reply = format!("+CONTINUE {}\r\n", self.replication.replid).into_bytes();
+                reply.extend_from_slice(&stream);
+                (reply, offset as u64 - 1)
+            }
+            None => {
+                let rdb = self.save_rdb();
+                let mut reply = format!("+FULLRESYNC {} {}\r\n${}\r\n", self.replication.replid, self.replication.offset, rdb.len()).into_bytes();
+                reply.extend_from_slice(&rdb);
+                (reply, 0)
+            }
+        };
+        let replica = self.replication.replicas.get_mut(&self.current_client).expect("replica was just added");
+        replica.online = true;
+        replica.ack_offset = ack_offset;
+        Ok(Response::Raw(reply))
+    }
+
+    /// Starts a new link to our master, handled by the server with the client id `id`.
+    pub fn start_master_link(&mut self, id: u64) {
+        if let Some(old) = self.replication.master_client.replace(id) {
+            self.clients.remove(&old);
+        }
+        self.replication.link_state = LinkState::Connect;
+    }
+
+    /// Called when the master link connected, returns the handshake commands to send to the master.
+    pub fn master_link_connecting(&mut self, id: u64) -> anyhow::Result<Response> {
+        anyhow::ensure!(self.replication.master_client == Some(id), "replication link {id} is no longer in use");
+        self.clients.insert(id, Client::default());
+        self.replication.link_state = LinkState::Connecting;
+        let mut handshake = Vec::new();
+        if let Some(password) = self.config.get_optional("masterauth") {
+            let mut auth = vec![b"AUTH".to_vec()];
+            auth.extend(self.config.get_optional("masteruser").map(|u| u.as_bytes().to_vec()));
+            auth.push(password.as_bytes().to_vec());
+            handshake.push(auth);
+        }
+        handshake.push(vec![b"PING".to_vec()]);
+        handshake.push(vec![b"REPLCONF".to_vec(), b"listening-port".to_vec(), self.config.get("port").as_bytes().to_vec()]);
+        handshake.push(vec![b"REPLCONF".to_vec(), b"capa".to_vec(), b"psync2".to_vec()]);
+        let (replid, offset) = match self.replication.cached_master {
+            true => (self.replication.replid.clone(), (self.replication.offset + 1).to_string()),
+            false => ("?".to_string(), "-1".to_string()),
+        };
+        handshake.push(vec![b"PSYNC".to_vec(), replid.into_bytes(), offset.into_bytes()]);
+        Ok(Response::Array(handshake.into_iter().map(Response::string_array).collect()))
+    }
+
+    /// Called once the master answered PSYNC, the link then receives the replication stream.
+    pub fn master_link_synced(&mut self, id: u64, sync: Sync) -> anyhow::Result<()> {
+        anyhow::ensure!(self.replication.master_client == Some(id), "replication link {id} is no longer in use");
+        match sync {
+            Sync::Full { replid, offset, rdb } => {
+                self.load_rdb(&rdb)?;
+                self.replication.replid = replid;
+                self.replication.offset = offset;
+                self.replication.backlog.clear();
+            }
+            Sync::Partial { replid } => {
+                if let Some(replid) = replid {
+                    self.replication.replid = replid;
+                }
+            }
+        }
+        self.replication.cached_master = true;
+        self.replication.link_state = LinkState::Connected;
+        self.replication.last_ack = Instant::now();
+        Ok(())
+    }
+
+    /// Forgets about a disconnected client, a disconnected master link will be retried by the server.
+    pub(crate) fn remove_replication_client(&mut self, id: u64) {
+        if self.replication.master_client == Some(id) {
+            self.replication.link_state = LinkState::Connect;
+        }
+        self.replication.replicas.remove(&id);
+    }
+
+    /// Answers the WAIT clients which have enough acknowledgements or timed out.
+    fn unblock_clients(&mut self) {
+        let now = Instant::now();
+        let ready = self.clients.iter()
+            .filter_map(|(&id, client)| match client.blocked {
+                Some(Blocked::Wait { offset, numreplicas, deadline }) => {
+                    let acked = self.replication.acked(offset);
+                    (acked >= numreplicas || deadline.is_some_and(|d| d <= now)).then_some((id, acked))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        for (id, acked) in ready {
+            self.unblock(id, Ok(Response::Number(acked as _)));
+        }
+    }
+
+    /// Asks all replicas to acknowledge the stream, answered with REPLCONF ACK.
+    pub fn request_acks(&mut self) {
+        self.propagate(encode(vec![b"REPLCONF".to_vec(), b"GETACK".to_vec(), b"*".to_vec()]));
+    }
+
+    /// Periodic work: timing out blocked clients, acknowledging our master's stream and pinging replicas.
+    pub fn cron(&mut self) {
+        self.unblock_clients();
+        self.active_expire_cycle();
+        let now = Instant::now();
+        if let Some(id) = self.replication.master_client.filter(|_| self.replication.link_state == LinkState::Connected) {
+            if now - self.replication.last_ack >= ACK_PERIOD {
+                let ack = vec![b"REPLCONF".to_vec(), b"ACK".to_vec(), self.replication.offset.to_string().into_bytes()];
+                self.outbox.push((id, Ok(Response::string_array(ack))));
+                self.replication.last_ack = now;
+            }
+        } else if now - self.replication.last_ping >= PING_PERIOD && self.replication.replicas().next().is_some() {
+            self.propagate(encode(vec![b"PING".to_vec()]));
+            self.replication.last_ping = now;
+        }
+    }
+}
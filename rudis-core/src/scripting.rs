@@ -0,0 +1,327 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use mlua::{Function, HookTriggers, Lua, LuaOptions, LuaString, MultiValue, StdLib, Table, Value as LuaValue, VmState};
+use sha1::{Digest, Sha1};
+
+use crate::commands::COMMANDS;
+use crate::functions::Functions;
+use crate::{error_reply, execute_command, ByteString, Command, Database, Response};
+
+/// How often running scripts check whether they were killed, in Lua VM instructions.
+const KILL_CHECK_PERIOD: u32 = 10000;
+
+const PRELUDE: &str = r#"
+redis.call = function(...)
+    local reply = redis.pcall(...)
+    if type(reply) == "table" and reply.err then
+        error(reply.err, 0)
+    end
+    return reply
+end
+redis.error_reply = function(msg) return { err = msg } end
+redis.status_reply = function(msg) return { ok = msg } end
+redis.log = function(level, ...) end
+redis.LOG_DEBUG, redis.LOG_VERBOSE, redis.LOG_NOTICE, redis.LOG_WARNING = 0, 1, 2, 3
+loadfile, dofile, setfenv = nil, nil, nil
+
+-- scripts share the Lua state, so everything they can reach is read-only: globals live in a
+-- hidden table behind an empty _G and the libraries behind empty proxies
+local protected = {}
+local function readonly_error()
+    error("Attempt to modify a readonly table", 2)
+end
+local function readonly(lib)
+    local proxy = setmetatable({}, { __index = lib, __newindex = readonly_error, __metatable = false })
+    protected[proxy] = true
+    return proxy
+end
+redis, string, table, math = readonly(redis), readonly(string), readonly(table), readonly(math)
+getmetatable("").__metatable = false
+local raw_set = rawset
+rawset = function(t, ...)
+    if protected[t] then readonly_error() end
+    return raw_set(t, ...)
+end
+
+local G, globals, setmetatable = _G, {}, setmetatable
+for name, value in pairs(G) do globals[name] = value end
+for name in pairs(globals) do G[name] = nil end
+protected[G] = true
+setmetatable(G, {
+    __newindex = function(_, name)
+        if globals[name] ~= nil then readonly_error() end
+        error("Script attempted to create global variable '" .. tostring(name) .. "'", 2)
+    end,
+    __index = function(_, name)
+        local value = globals[name]
+        if value == nil then
+            error("Script attempted to access nonexistent global variable '" .. tostring(name) .. "'", 2)
+        end
+        return value
+    end,
+    __metatable = false,
+})
+"#;
+
+pub fn sha1_hex(data: &[u8]) -> String {
+    Sha1::digest(data).iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[derive(Default)]
+struct RunningScript {
+    started: Option<Instant>,
+    busy_after: Duration,
+}
+
+/// The script being run, shared with the server so that it can answer other clients while
+/// the database is busy running a long script.
+#[derive(Clone, Default)]
+pub struct ScriptState {
+    running: Arc<Mutex<RunningScript>>,
+    wrote: Arc<AtomicBool>,
+    killed: Arc<AtomicBool>,
+}
+
+impl ScriptState {
+    fn start(&self, busy_after: Duration) {
+        *self.running.lock().unwrap() = RunningScript { started: Some(Instant::now()), busy_after };
+        self.wrote.store(false, Ordering::Relaxed);
+        self.killed.store(false, Ordering::Relaxed);
+    }
+
+    fn stop(&self) {
+        self.running.lock().unwrap().started = None;
+    }
+
+    pub(crate) fn is_running(&self) -> bool {
+        self.running.lock().unwrap().started.is_some()
+    }
+
+    /// Whether a script has been running for longer than `busy-reply-threshold`.
+    pub fn is_busy(&self) -> bool {
+        let running = self.running.lock().unwrap();
+        running.started.is_some_and(|started| started.elapsed() >= running.busy_after)
+    }
+
+    fn kill(&self) -> anyhow::Result<()> {
+        anyhow::ensure!(self.running.lock().unwrap().started.is_some(), "NOTBUSY No scripts in execution right now.");
+        anyhow::ensure!(!self.wrote.load(Ordering::Relaxed), "UNKILLABLE Sorry the script already executed write commands against the dataset. You can either wait the script termination or kill the server in a hard way using the SHUTDOWN NOSAVE command.");
+        self.killed.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Answers a command received while a script is busy: SCRIPT KILL stops it if it did not
+    /// write yet and all other commands are refused. Returns `None` when no script is busy.
+    pub fn intercept(&self, cmd: &Command) -> Option<anyhow::Result<Response>> {
+        if !self.is_busy() {
+            return None;
+        }
+        if matches!(cmd.cmd(), "script" | "function") && cmd.arg(0).is_some_and(|a| a.eq_ignore_ascii_case(b"kill")) {
+            return Some(self.kill().map(|_| Response::SimpleString(b"OK".to_vec())));
+        }
+        Some(Err(anyhow::anyhow!("BUSY Redis is busy running a script. You can only call SCRIPT KILL or SHUTDOWN NOSAVE.")))
+    }
+}
+
+/// How keys and arguments are passed: as the KEYS and ARGV globals to EVAL scripts or as
+/// the two parameters of functions.
+pub(crate) enum ScriptArgs {
+    Globals(Vec<ByteString>, Vec<ByteString>),
+    Parameters(Vec<ByteString>, Vec<ByteString>),
+}
+
+pub(crate) struct Scripting {
+    lua: Lua,
+    scripts: HashMap<String, Function>,
+    pub(crate) functions: Functions,
+    state: ScriptState,
+}
+
+impl Default for Scripting {
+    fn default() -> Self {
+        let state = ScriptState::default();
+        Self { lua: new_lua(&state), scripts: HashMap::new(), functions: Functions::new(&state), state }
+    }
+}
+
+/// A sandboxed Lua state with the `redis` library, whose scripts stop once `state` is killed.
+pub(crate) fn new_lua(state: &ScriptState) -> Lua {
+    let lua = Lua::new_with(StdLib::TABLE | StdLib::STRING | StdLib::MATH, LuaOptions::default())
+        .expect("the standard libraries are safe");
+    let killed = state.killed.clone();
+    lua.set_hook(HookTriggers::new().every_nth_instruction(KILL_CHECK_PERIOD), move |_, _| {
+        match killed.load(Ordering::Relaxed) {
+            true => Err(mlua::Error::runtime("ERR Script killed by user with SCRIPT KILL...")),
+            false => Ok(VmState::Continue),
+        }
+    }).expect("setting a hook never fails");
+    let init = || {
+        let redis = lua.create_table()?;
+        redis.set("sha1hex", lua.create_function(|_, s: LuaString| Ok(sha1_hex(&s.as_bytes())))?)?;
+        // the global only gets a read-only proxy, the library itself stays reachable from Rust
+        lua.set_named_registry_value("redis", &redis)?;
+        lua.globals().set("redis", redis)?;
+        lua.load(PRELUDE).set_name("@prelude").exec()
+    };
+    init().expect("the prelude is valid");
+    lua
+}
+
+/// The innermost message of a Lua error, without the tracebacks of Rust callbacks.
+pub(crate) fn lua_error_message(e: &mlua::Error) -> String {
+    match e {
+        mlua::Error::RuntimeError(msg) => msg.split("\nstack traceback:").next().unwrap_or_default().to_string(),
+        mlua::Error::SyntaxError { message, .. } => message.clone(),
+        mlua::Error::CallbackError { cause, .. } => lua_error_message(cause),
+        e => e.to_string(),
+    }
+}
+
+/// Converts a reply to Lua: status replies and errors become tables with an `ok` or `err`
+/// field and nil replies become false.
+fn to_lua(lua: &Lua, res: anyhow::Result<Response>) -> mlua::Result<LuaValue> {
+    let value = match res {
+        Err(e) => LuaValue::Table(lua.create_table_from([("err", error_reply(&e))])?),
+        Ok(Response::SimpleString(s)) => LuaValue::Table(lua.create_table_from([("ok", lua.create_string(s)?)])?),
+        Ok(Response::BulkString(s) | Response::Raw(s)) => LuaValue::String(lua.create_string(s)?),
+        Ok(Response::Number(n)) => LuaValue::Integer(n as _),
+        Ok(Response::Array(items)) => {
+            let table = lua.create_table()?;
+            for item in items {
+                table.push(to_lua(lua, Ok(item))?)?;
+            }
+            LuaValue::Table(table)
+        }
+        Ok(Response::Nil | Response::NoReply) => LuaValue::Boolean(false),
+    };
+    Ok(value)
+}
+
+/// Converts a value returned by a script: numbers are truncated to integers, arrays stop at
+/// the first nil and tables with an `ok` or `err` field become status replies or errors.
+fn from_lua(value: LuaValue) -> anyhow::Result<Response> {
+    let res = match value {
+        LuaValue::Boolean(true) => Response::Number(1),
+        LuaValue::Integer(n) => Response::Number(n as _),
+        LuaValue::Number(n) => Response::Number(n as _),
+        LuaValue::String(s) => Response::BulkString(s.as_bytes().to_vec()),
+        LuaValue::Table(table) => {
+            if let Ok(err) = table.raw_get::<LuaString>("err") {
+                anyhow::bail!("{}", err.to_string_lossy());
+            }
+            if let Ok(ok) = table.raw_get::<LuaString>("ok") {
+                return Ok(Response::SimpleString(ok.as_bytes().to_vec()));
+            }
+            let mut items = Vec::new();
+            for i in 1.. {
+                match table.raw_get::<LuaValue>(i)? {
+                    LuaValue::Nil => break,
+                    item => items.push(from_lua(item)?),
+                }
+            }
+            Response::Array(items)
+        }
+        _ => Response::Nil,
+    };
+    Ok(res)
+}
+
+fn command_from_lua(args: MultiValue) -> anyhow::Result<Command> {
+    let args = args.into_iter()
+        .map(|arg| match arg {
+            LuaValue::String(s) => Ok(s.as_bytes().to_vec()),
+            LuaValue::Integer(n) => Ok(n.to_string().into_bytes()),
+            LuaValue::Number(n) => Ok((n as i64).to_string().into_bytes()),
+            _ => anyhow::bail!("Lua redis lib command arguments must be strings or integers"),
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    anyhow::ensure!(!args.is_empty(), "Please specify at least one argument for this redis lib call");
+    Command::new(args)
+}
+
+impl Database {
+    pub fn script_state(&self) -> ScriptState {
+        self.scripting.state.clone()
+    }
+
+    pub(crate) fn is_running_script(&self) -> bool {
+        self.scripting.state.is_running()
+    }
+
+    /// Compiles a script and caches it under its SHA1 digest, which is returned.
+    pub fn script_load(&mut self, body: &[u8]) -> anyhow::Result<String> {
+        let sha = sha1_hex(body);
+        if !self.scripting.scripts.contains_key(&sha) {
+            let function = self.scripting.lua.load(body).set_name("@user_script").into_function()
+                .map_err(|e| anyhow::anyhow!("Error compiling script (new function): {}", lua_error_message(&e)))?;
+            self.scripting.scripts.insert(sha.clone(), function);
+        }
+        Ok(sha)
+    }
+
+    pub fn script_exists(&self, sha: &[u8]) -> bool {
+        let sha = String::from_utf8_lossy(sha).to_ascii_lowercase();
+        self.scripting.scripts.contains_key(&sha)
+    }
+
+    pub fn script_flush(&mut self) {
+        self.scripting.scripts.clear();
+    }
+
+    pub fn script_kill(&mut self) -> anyhow::Result<()> {
+        self.scripting.state.kill()
+    }
+
+    /// Runs a cached script atomically, its `redis.call`s being executed as the current client.
+    pub fn eval_script(&mut self, sha: &[u8], keys: Vec<ByteString>, args: Vec<ByteString>) -> anyhow::Result<Response> {
+        let sha = String::from_utf8_lossy(sha).to_ascii_lowercase();
+        let Some(function) = self.scripting.scripts.get(&sha).cloned() else {
+            anyhow::bail!("NOSCRIPT No matching script. Please use EVAL.");
+        };
+        let lua = self.scripting.lua.clone();
+        self.run_script(&lua, &function, &sha, ScriptArgs::Globals(keys, args), false)
+    }
+
+    /// Runs `function` of `lua`, named `name` in errors. Scripts which are `read_only` may not
+    /// call write commands.
+    pub(crate) fn run_script(&mut self, lua: &Lua, function: &Function, name: &str, args: ScriptArgs, read_only: bool) -> anyhow::Result<Response> {
+        let state = self.scripting.state.clone();
+        state.start(Duration::from_millis(self.config.get_parsed("busy-reply-threshold")));
+        let db = RefCell::new(&mut *self);
+        let res = lua.scope(|scope| {
+            let strings = |strings: Vec<ByteString>| lua.create_sequence_from(strings.into_iter().map(|s| lua.create_string(s)).collect::<mlua::Result<Vec<_>>>()?);
+            let pcall = scope.create_function_mut(|lua, args: MultiValue| {
+                let res = command_from_lua(args).and_then(|cmd| {
+                    let info = COMMANDS.get(cmd.cmd().as_bytes()).map(|(_, info)| *info);
+                    anyhow::ensure!(info.is_some(), "Unknown Redis command called from script");
+                    anyhow::ensure!(!info.is_some_and(|i| i.flags.contains(&b"noscript".as_slice())), "This Redis command is not allowed from script");
+                    if info.is_some_and(|i| i.flags.contains(&b"write".as_slice())) {
+                        anyhow::ensure!(!read_only, "Write commands are not allowed from read-only scripts.");
+                        state.wrote.store(true, Ordering::Relaxed);
+                    }
+                    execute_command(&mut db.borrow_mut(), cmd)
+                });
+                to_lua(lua, res)
+            })?;
+            let globals = lua.globals();
+            lua.named_registry_value::<Table>("redis")?.raw_set("pcall", pcall)?;
+            match args {
+                ScriptArgs::Globals(keys, args) => {
+                    globals.raw_set("KEYS", strings(keys)?)?;
+                    globals.raw_set("ARGV", strings(args)?)?;
+                    function.call::<LuaValue>(())
+                }
+                ScriptArgs::Parameters(keys, args) => function.call::<LuaValue>((strings(keys)?, strings(args)?)),
+            }
+        });
+        self.scripting.state.stop();
+        match res {
+            Ok(value) => from_lua(value),
+            Err(e) => anyhow::bail!("{} script: {name}", lua_error_message(&e)),
+        }
+    }
+}
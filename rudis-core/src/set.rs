@@ -0,0 +1,288 @@
+use std::borrow::Cow;
+
+use indexmap::IndexSet;
+
+use crate::commands::scan_step;
+use crate::listpack::Listpack;
+use crate::memory::{ListpackLimits, MemoryConfig};
+use crate::{canonical_int, ByteString};
+
+#[derive(Debug, Clone)]
+enum Encoding {
+    /// Sorted integers, while all members are integers.
+    IntSet(Vec<i64>),
+    Listpack(Listpack),
+    /// Indexed for picking random members.
+    HashTable(IndexSet<ByteString>),
+}
+
+/// A set value, in a compact encoding while small, see `Set::fit_encoding`.
+#[derive(Debug, Clone)]
+pub struct Set {
+    members: Encoding,
+    /// The limits of the compact encodings, checked as members are added.
+    limits: ListpackLimits,
+    max_intset_entries: usize,
+}
+
+impl Default for Set {
+    /// An empty set, in the encoding `fit_encoding` picks for it with the default config.
+    fn default() -> Self {
+        let config = MemoryConfig::default();
+        Self { members: Encoding::IntSet(Vec::new()), limits: config.set, max_intset_entries: config.set_max_intset_entries }
+    }
+}
+
+impl PartialEq for Set {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().all(|m| other.contains(&m))
+    }
+}
+
+impl Eq for Set {}
+
+impl FromIterator<ByteString> for Set {
+    fn from_iter<T: IntoIterator<Item = ByteString>>(iter: T) -> Self {
+        Self { members: Encoding::HashTable(iter.into_iter().collect()), ..Self::default() }
+    }
+}
+
+impl Extend<ByteString> for Set {
+    fn extend<T: IntoIterator<Item = ByteString>>(&mut self, iter: T) {
+        for member in iter {
+            self.insert(member);
+        }
+    }
+}
+
+impl IntoIterator for Set {
+    type Item = ByteString;
+    type IntoIter = std::vec::IntoIter<ByteString>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        match self.members {
+            Encoding::HashTable(s) => s.into_iter().collect::<Vec<_>>().into_iter(),
+            _ => self.iter().map(Cow::into_owned).collect::<Vec<_>>().into_iter(),
+        }
+    }
+}
+
+impl Set {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.members {
+            Encoding::IntSet(v) => v.len(),
+            Encoding::Listpack(lp) => lp.len(),
+            Encoding::HashTable(s) => s.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains(&self, member: &[u8]) -> bool {
+        match &self.members {
+            Encoding::IntSet(v) => canonical_int(member).is_some_and(|n| v.binary_search(&n).is_ok()),
+            Encoding::Listpack(lp) => lp.iter().any(|m| m == member),
+            Encoding::HashTable(s) => s.contains(member),
+        }
+    }
+
+    pub fn iter(&self) -> Box<dyn Iterator<Item = Cow<'_, [u8]>> + '_> {
+        match &self.members {
+            Encoding::IntSet(v) => Box::new(v.iter().map(|n| Cow::Owned(n.to_string().into_bytes()))),
+            Encoding::Listpack(lp) => Box::new(lp.iter().map(Cow::Borrowed)),
+            Encoding::HashTable(s) => Box::new(s.iter().map(|m| Cow::Borrowed(m.as_slice()))),
+        }
+    }
+
+    /// The member at `index` in the order of `iter`, found without iterating for the large
+    /// encodings, as done to pick random members.
+    pub fn get_index(&self, index: usize) -> Option<Cow<'_, [u8]>> {
+        match &self.members {
+            Encoding::IntSet(v) => v.get(index).map(|n| Cow::Owned(n.to_string().into_bytes())),
+            Encoding::Listpack(lp) => lp.get(index).map(Cow::Borrowed),
+            Encoding::HashTable(s) => s.get_index(index).map(|m| Cow::Borrowed(m.as_slice())),
+        }
+    }
+
+    /// One step of SSCAN, see [`scan_step`]: the hash table encoding is visited by index while
+    /// the compact ones, small and reordered by insertions, are returned whole.
+    pub fn scan(&self, cursor: u64, count: usize) -> (u64, Vec<Cow<'_, [u8]>>) {
+        match &self.members {
+            Encoding::HashTable(s) => {
+                let (next, indices) = scan_step(s.len(), cursor, count);
+                (next, indices.rev().filter_map(|i| s.get_index(i)).map(|m| Cow::Borrowed(m.as_slice())).collect())
+            }
+            _ => (0, self.iter().collect()),
+        }
+    }
+
+    /// Adds a member, returns whether it was not already in the set.
+    pub fn insert(&mut self, member: ByteString) -> bool {
+        if self.contains(&member) {
+            return false;
+        }
+        // converted before outgrowing the encoding rather than after the command, for adding
+        // many members not to scan the compact encoding for each of them
+        let (len, limits) = (self.len() + 1, self.limits);
+        match &self.members {
+            Encoding::IntSet(_) if len > self.max_intset_entries || canonical_int(&member).is_none() => {
+                let listpack = len <= limits.entries && member.len() <= limits.value && self.iter().all(|m| m.len() <= limits.value);
+                self.convert(if listpack { Encoding::Listpack(Listpack::new()) } else { Encoding::HashTable(IndexSet::new()) });
+            }
+            Encoding::Listpack(_) if len > limits.entries || member.len() > limits.value => {
+                self.convert(Encoding::HashTable(IndexSet::new()));
+            }
+            _ => {}
+        }
+        match &mut self.members {
+            Encoding::IntSet(v) => {
+                let n = canonical_int(&member).expect("intsets only hold integers");
+                let i = v.binary_search(&n).expect_err("the member is not in the set");
+                v.insert(i, n);
+            }
+            Encoding::Listpack(lp) => lp.push(&member),
+            Encoding::HashTable(s) => {
+                s.insert(member);
+            }
+        }
+        true
+    }
+
+    /// Removes a member, returns whether it was in the set.
+    pub fn remove(&mut self, member: &[u8]) -> bool {
+        match &mut self.members {
+            Encoding::IntSet(v) => match canonical_int(member).map(|n| v.binary_search(&n)) {
+                Some(Ok(i)) => {
+                    v.remove(i);
+                    true
+                }
+                _ => false,
+            },
+            Encoding::Listpack(lp) => match lp.iter().position(|m| m == member) {
+                Some(i) => {
+                    lp.remove(i, 1);
+                    true
+                }
+                None => false,
+            },
+            Encoding::HashTable(s) => s.swap_remove(member),
+        }
+    }
+
+    pub fn retain(&mut self, mut f: impl FnMut(&[u8]) -> bool) {
+        let removed = self.iter().filter(|m| !f(m)).map(Cow::into_owned).collect::<Vec<_>>();
+        for member in removed {
+            self.remove(&member);
+        }
+    }
+
+    /// The name of the encoding, as reported by OBJECT ENCODING.
+    pub fn encoding(&self) -> &'static str {
+        match &self.members {
+            Encoding::IntSet(_) => "intset",
+            Encoding::Listpack(_) => "listpack",
+            Encoding::HashTable(_) => "hashtable",
+        }
+    }
+
+    /// The memory used by the members in compact encodings.
+    pub(crate) fn compact_bytes(&self) -> Option<usize> {
+        match &self.members {
+            Encoding::IntSet(v) => Some(v.capacity() * size_of::<i64>()),
+            Encoding::Listpack(lp) => Some(lp.bytes()),
+            Encoding::HashTable(_) => None,
+        }
+    }
+
+    fn convert(&mut self, mut to: Encoding) {
+        let members = self.iter().map(Cow::into_owned).collect::<Vec<_>>();
+        for member in members {
+            match &mut to {
+                Encoding::IntSet(v) => v.push(canonical_int(&member).expect("intsets only hold integers")),
+                Encoding::Listpack(lp) => lp.push(&member),
+                Encoding::HashTable(s) => {
+                    s.insert(member);
+                }
+            }
+        }
+        if let Encoding::IntSet(v) = &mut to {
+            v.sort_unstable();
+        }
+        self.members = to;
+    }
+
+    fn rank(&self) -> u8 {
+        match &self.members {
+            Encoding::IntSet(_) => 0,
+            Encoding::Listpack(_) => 1,
+            Encoding::HashTable(_) => 2,
+        }
+    }
+
+    /// Converts the set to a larger encoding once it outgrows its compact one, and with
+    /// `shrink` to the most compact encoding fitting it, as done for new values.
+    pub(crate) fn fit_encoding(&mut self, config: &MemoryConfig, shrink: bool) {
+        (self.limits, self.max_intset_entries) = (config.set, config.set_max_intset_entries);
+        if !shrink && matches!(self.members, Encoding::HashTable(_)) {
+            return;
+        }
+        let len = self.len();
+        let intset = len <= config.set_max_intset_entries
+            && (matches!(self.members, Encoding::IntSet(_)) || self.iter().all(|m| canonical_int(&m).is_some()));
+        let listpack = || len <= config.set.entries && self.iter().all(|m| m.len() <= config.set.value);
+        let target = if intset { 0 } else if listpack() { 1 } else { 2 };
+        if target > self.rank() || (shrink && target != self.rank()) {
+            self.convert(match target {
+                0 => Encoding::IntSet(Vec::new()),
+                1 => Encoding::Listpack(Listpack::new()),
+                _ => Encoding::HashTable(IndexSet::new()),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encodings() {
+        let config = MemoryConfig::default();
+        let mut set = Set::new();
+        assert!(set.insert(b"3".to_vec()) && set.insert(b"-1".to_vec()) && !set.insert(b"3".to_vec()));
+        // not canonical, so not stored as an integer
+        assert!(!set.contains(b"03"));
+        assert_eq!(set.encoding(), "intset");
+        set.insert(b"03".to_vec());
+        assert_eq!(set.encoding(), "listpack");
+        assert!(set.contains(b"03") && set.contains(b"-1"));
+        // converted as soon as the listpack is full, without waiting for `fit_encoding`
+        set.extend((0..200).map(|i| i.to_string().into_bytes()));
+        assert_eq!(set.encoding(), "hashtable");
+        set.retain(|m| m.len() < 2);
+        set.fit_encoding(&config, true);
+        assert_eq!((set.encoding(), set.len()), ("intset", 10));
+        set.extend((0..=config.set_max_intset_entries as i64).map(|i| i.to_string().into_bytes()));
+        assert_eq!(set.encoding(), "hashtable");
+    }
+
+    #[test]
+    fn test_get_index() {
+        let intset = (0..10).map(|i| i.to_string());
+        let listpack = ["a", "b", "c"].map(String::from).into_iter();
+        let hashtable = (0..600).map(|i| format!("m{i}"));
+        for (members, encoding) in [(intset.collect::<Vec<_>>(), "intset"), (listpack.collect(), "listpack"), (hashtable.collect(), "hashtable")] {
+            let mut set = Set::new();
+            set.extend(members.into_iter().map(String::into_bytes));
+            assert_eq!(set.encoding(), encoding);
+            assert!(set.iter().enumerate().all(|(i, m)| set.get_index(i) == Some(m)));
+            assert_eq!(set.get_index(set.len()), None);
+        }
+    }
+}
@@ -5,85 +5,280 @@ use std::ops::Bound;
 use skiplist::ordered_skip_list::OrderedSkipList;
 use ordered_float::NotNan;
 
+use crate::listpack::Listpack;
+use crate::memory::{ListpackLimits, MemoryConfig};
 use crate::ByteString;
 
-#[derive(Clone, Default)]
+#[derive(Clone)]
+enum Encoding {
+    /// Members followed by their score as 8 bytes, ordered by score and member.
+    Listpack(Listpack),
+    Skiplist {
+        map: HashMap<ByteString, NotNan<f64>>,
+        smap: OrderedSkipList<(NotNan<f64>, ByteString)>,
+    },
+}
+
+/// A sorted set value, in a compact encoding while small, see `SortedSet::fit_encoding`.
+#[derive(Clone)]
 pub struct SortedSet {
-    map: HashMap<ByteString, NotNan<f64>>,
-    smap: OrderedSkipList<(NotNan<f64>, ByteString)>,
+    members: Encoding,
+    /// The limits of the listpack encoding, checked as members are added.
+    limits: ListpackLimits,
+}
+
+impl Default for SortedSet {
+    fn default() -> Self {
+        Self { members: Encoding::Listpack(Listpack::new()), limits: MemoryConfig::default().zset }
+    }
+}
+
+fn decode_score(bytes: &[u8]) -> f64 {
+    f64::from_le_bytes(bytes.try_into().expect("scores are 8 bytes"))
+}
+
+/// The members of a listpack with their score, in order.
+fn listpack_iter(lp: &Listpack) -> impl Iterator<Item=(f64, &[u8])> {
+    let mut elements = lp.iter();
+    std::iter::from_fn(move || {
+        let member = elements.next()?;
+        Some((decode_score(elements.next()?), member))
+    })
 }
 
 impl SortedSet {
     pub fn new() -> Self {
-        Self { map: HashMap::new(), smap: OrderedSkipList::new() }
+        Self::default()
     }
 }
 
 impl SortedSet {
     pub fn insert(&mut self, s: NotNan<f64>, t: ByteString) -> Option<NotNan<f64>> {
-        let old_s = self.map.insert(t.clone(), s);
-        if let Some(old_s) = &old_s {
-            self.smap.remove_by_value(&(*old_s, t.clone()));
+        // converted before outgrowing the listpack rather than after the command, for adding
+        // many members not to scan it for each of them
+        let limits = self.limits;
+        if matches!(self.members, Encoding::Listpack(_))
+            && (t.len() > limits.value || (self.len() >= limits.entries && self.get_score(&t).is_none()))
+        {
+            self.convert_to_skiplist();
+        }
+        match &mut self.members {
+            Encoding::Listpack(_) => {
+                let old_s = self.remove(t.clone());
+                let Encoding::Listpack(lp) = &mut self.members else { unreachable!() };
+                let i = listpack_iter(lp).take_while(|&(score, member)| (score, member) < (*s, t.as_slice())).count();
+                lp.insert(2 * i, &t);
+                lp.insert(2 * i + 1, &s.to_le_bytes());
+                old_s
+            }
+            Encoding::Skiplist { map, smap } => {
+                let old_s = map.insert(t.clone(), s);
+                if let Some(old_s) = &old_s {
+                    smap.remove_by_value(&(*old_s, t.clone()));
+                }
+                smap.insert((s, t));
+                old_s
+            }
         }
-        self.smap.insert((s, t));
-        old_s
     }
 
     pub fn remove(&mut self, t: ByteString) -> Option<NotNan<f64>> {
-        let s = self.map.remove(&t);
-        if let Some(s) = &s {
-            self.smap.remove_by_value(&(s.clone(), t));
+        match &mut self.members {
+            Encoding::Listpack(lp) => {
+                let (i, (s, _)) = listpack_iter(lp).enumerate().find(|(_, (_, member))| *member == t)?;
+                lp.remove(2 * i, 2);
+                NotNan::new(s).ok()
+            }
+            Encoding::Skiplist { map, smap } => {
+                let s = map.remove(&t);
+                if let Some(s) = &s {
+                    smap.remove_by_value(&(*s, t));
+                }
+                s
+            }
         }
-        s
     }
 
-    pub fn len(&self) -> usize { self.map.len() }
+    pub fn len(&self) -> usize {
+        match &self.members {
+            Encoding::Listpack(lp) => lp.len() / 2,
+            Encoding::Skiplist { map, .. } => map.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
 
     pub fn get_score(&self, t: &[u8]) -> Option<f64> {
-        self.map.get(t).map(|&s| *s)
+        match &self.members {
+            Encoding::Listpack(lp) => listpack_iter(lp).find(|(_, member)| *member == t).map(|(s, _)| s),
+            Encoding::Skiplist { map, .. } => map.get(t).map(|&s| *s),
+        }
     }
 
-    pub fn iter(&self) -> impl Iterator<Item=(f64, &[u8])> {
-        self.smap.iter().map(|(s, t)| (**s, t.as_slice()))
+    pub fn iter(&self) -> Box<dyn Iterator<Item=(f64, &[u8])> + '_> {
+        match &self.members {
+            Encoding::Listpack(lp) => Box::new(listpack_iter(lp)),
+            Encoding::Skiplist { smap, .. } => Box::new(smap.iter().map(|(s, t)| (**s, t.as_slice()))),
+        }
     }
 
-    pub fn riter(&self) -> impl Iterator<Item=(f64, &[u8])> {
-        self.smap.iter().rev().map(|(s, t)| (**s, t.as_slice()))
+    pub fn riter(&self) -> Box<dyn Iterator<Item=(f64, &[u8])> + '_> {
+        match &self.members {
+            Encoding::Listpack(lp) => Box::new(listpack_iter(lp).collect::<Vec<_>>().into_iter().rev()),
+            Encoding::Skiplist { smap, .. } => Box::new(smap.iter().rev().map(|(s, t)| (**s, t.as_slice()))),
+        }
     }
 
-    pub fn range(&self, min: NotNan<f64>, max: NotNan<f64>) -> impl Iterator<Item=(f64, &[u8])> {
-        let min = Bound::Included((min, Vec::new()));
-        let max = Bound::Excluded((NotNan::new(max.next_up()).unwrap(), Vec::new()));
-        self.smap.range((min, max)).map(|(s, t)| (**s, t.as_slice()))
+    pub fn range(&self, min: NotNan<f64>, max: NotNan<f64>) -> Box<dyn Iterator<Item=(f64, &[u8])> + '_> {
+        match &self.members {
+            Encoding::Listpack(lp) => {
+                Box::new(listpack_iter(lp).skip_while(move |&(s, _)| s < *min).take_while(move |&(s, _)| s <= *max))
+            }
+            Encoding::Skiplist { smap, .. } => {
+                let min = Bound::Included((min, Vec::new()));
+                let max = Bound::Excluded((NotNan::new(max.next_up()).unwrap(), Vec::new()));
+                Box::new(smap.range((min, max)).map(|(s, t)| (**s, t.as_slice())))
+            }
+        }
     }
 
     pub fn rank(&self, t: ByteString) -> Option<(f64, usize)> {
-        let s = self.map.get(&t)?.clone();
-        let r = self.smap.rank(&(s.clone(), t))?;
-        Some((*s, r))
+        match &self.members {
+            Encoding::Listpack(lp) => listpack_iter(lp).enumerate().find(|(_, (_, member))| *member == t).map(|(r, (s, _))| (s, r)),
+            Encoding::Skiplist { map, smap } => {
+                let s = *map.get(&t)?;
+                let r = smap.rank(&(s, t))?;
+                Some((*s, r))
+            }
+        }
     }
 
     pub fn popmax(&mut self) -> Option<(f64, ByteString)> {
-        let (s, t) = self.smap.pop_last()?;
-        self.map.remove(&t);
-        Some((*s, t))
+        match &mut self.members {
+            Encoding::Listpack(lp) => {
+                let (s, t) = listpack_iter(lp).last().map(|(s, t)| (s, t.to_vec()))?;
+                lp.remove(lp.len() - 2, 2);
+                Some((s, t))
+            }
+            Encoding::Skiplist { map, smap } => {
+                let (s, t) = smap.pop_last()?;
+                map.remove(&t);
+                Some((*s, t))
+            }
+        }
     }
 
     pub fn popmin(&mut self) -> Option<(f64, ByteString)> {
-        let (s, t) = self.smap.pop_first()?;
-        self.map.remove(&t);
-        Some((*s, t))
+        match &mut self.members {
+            Encoding::Listpack(lp) => {
+                let (s, t) = listpack_iter(lp).next().map(|(s, t)| (s, t.to_vec()))?;
+                lp.remove(0, 2);
+                Some((s, t))
+            }
+            Encoding::Skiplist { map, smap } => {
+                let (s, t) = smap.pop_first()?;
+                map.remove(&t);
+                Some((*s, t))
+            }
+        }
+    }
+
+    /// The name of the encoding, as reported by OBJECT ENCODING.
+    pub fn encoding(&self) -> &'static str {
+        match &self.members {
+            Encoding::Listpack(_) => "listpack",
+            Encoding::Skiplist { .. } => "skiplist",
+        }
+    }
+
+    /// The memory used by the members in the compact encoding.
+    pub(crate) fn compact_bytes(&self) -> Option<usize> {
+        match &self.members {
+            Encoding::Listpack(lp) => Some(lp.bytes()),
+            Encoding::Skiplist { .. } => None,
+        }
+    }
+
+    fn convert_to_skiplist(&mut self) {
+        let mut converted = Self { members: Encoding::Skiplist { map: HashMap::new(), smap: OrderedSkipList::new() }, limits: self.limits };
+        for (s, t) in self.iter() {
+            converted.insert(NotNan::new(s).expect("scores are never NaN"), t.to_vec());
+        }
+        *self = converted;
+    }
+
+    /// Converts the sorted set to the skiplist encoding once it outgrows the listpack one,
+    /// and with `shrink` back to the listpack encoding when fitting, as done for new values.
+    pub(crate) fn fit_encoding(&mut self, config: &MemoryConfig, shrink: bool) {
+        let limits = config.zset;
+        self.limits = limits;
+        let listpack = matches!(self.members, Encoding::Listpack(_));
+        if !listpack && !shrink {
+            return;
+        }
+        let fits = self.len() <= limits.entries && self.iter().all(|(_, t)| t.len() <= limits.value);
+        if listpack && !fits {
+            self.convert_to_skiplist();
+        } else if !listpack && fits {
+            let mut lp = Listpack::new();
+            for (s, t) in self.iter() {
+                lp.push(t);
+                lp.push(&s.to_le_bytes());
+            }
+            self.members = Encoding::Listpack(lp);
+        }
     }
 }
 
 impl Debug for SortedSet {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("SortedSet").field("map", &self.smap).finish()
+        f.debug_struct("SortedSet").field("members", &self.iter().collect::<Vec<_>>()).finish()
     }
 }
 
 impl PartialEq for SortedSet {
-    fn eq(&self, other: &Self) -> bool { self.map == other.map }
+    fn eq(&self, other: &Self) -> bool { self.len() == other.len() && self.iter().eq(other.iter()) }
 }
 
 impl Eq for SortedSet {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encodings_agree() {
+        let mut compact = SortedSet::new();
+        let mut skiplist = SortedSet::new();
+        skiplist.convert_to_skiplist();
+        for (i, score) in [3.0, 1.0, 2.0, 1.0, -5.5, 2.0, 0.0].into_iter().enumerate() {
+            let member = format!("m{}", i % 5).into_bytes();
+            let score = NotNan::new(score).unwrap();
+            assert_eq!(compact.insert(score, member.clone()), skiplist.insert(score, member));
+        }
+        assert_eq!(compact.remove(b"m2".to_vec()), skiplist.remove(b"m2".to_vec()));
+        assert_eq!(compact.encoding(), "listpack");
+        assert!(compact.iter().eq(skiplist.iter()) && compact.riter().eq(skiplist.riter()));
+        let (min, max) = (NotNan::new(0.0).unwrap(), NotNan::new(2.0).unwrap());
+        assert!(compact.range(min, max).eq(skiplist.range(min, max)));
+        assert_eq!(compact.rank(b"m1".to_vec()), skiplist.rank(b"m1".to_vec()));
+        assert_eq!((compact.popmin(), compact.popmax()), (skiplist.popmin(), skiplist.popmax()));
+        assert_eq!(compact, skiplist);
+    }
+
+    #[test]
+    fn test_insert_converts() {
+        let mut z = SortedSet::new();
+        let entries = MemoryConfig::default().zset.entries;
+        for i in 0..entries {
+            z.insert(NotNan::new(i as f64).unwrap(), i.to_string().into_bytes());
+        }
+        z.insert(NotNan::new(0.0).unwrap(), b"0".to_vec());
+        assert_eq!(z.encoding(), "listpack");
+        z.insert(NotNan::new(0.0).unwrap(), b"new".to_vec());
+        assert_eq!((z.encoding(), z.len()), ("skiplist", entries + 1));
+        let mut z = SortedSet::new();
+        z.insert(NotNan::new(0.0).unwrap(), vec![b'x'; 100]);
+        assert_eq!(z.encoding(), "skiplist");
+    }
+}
@@ -0,0 +1,139 @@
+use std::borrow::Cow;
+
+use crate::{canonical_int, ByteString};
+
+/// The longest strings stored in the embedded encoding.
+const EMBSTR_MAX_LEN: usize = 44;
+
+/// The largest string that can be created, 512MB like proto-max-bulk-len.
+pub const MAX_STRING_LEN: usize = 512 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+enum Encoding {
+    /// The canonical form of an integer, stored as one so counters update without allocating.
+    Int(i64),
+    /// Short strings set as a whole.
+    Embstr(ByteString),
+    /// Long strings, or strings modified in place like by APPEND.
+    Raw(ByteString),
+}
+
+/// A string value, which commands see as bytes whatever its encoding.
+#[derive(Debug, Clone)]
+pub struct Str(Encoding);
+
+impl Default for Str {
+    fn default() -> Self {
+        Self(Encoding::Embstr(Vec::new()))
+    }
+}
+
+impl From<ByteString> for Str {
+    fn from(s: ByteString) -> Self {
+        match canonical_int(&s) {
+            Some(n) => Self(Encoding::Int(n)),
+            None if s.len() <= EMBSTR_MAX_LEN => Self(Encoding::Embstr(s)),
+            None => Self(Encoding::Raw(s)),
+        }
+    }
+}
+
+impl From<i64> for Str {
+    fn from(n: i64) -> Self {
+        Self(Encoding::Int(n))
+    }
+}
+
+impl PartialEq for Str {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_bytes() == other.as_bytes()
+    }
+}
+
+impl Eq for Str {}
+
+impl Str {
+    pub fn as_bytes(&self) -> Cow<'_, [u8]> {
+        match &self.0 {
+            Encoding::Int(n) => Cow::Owned(n.to_string().into_bytes()),
+            Encoding::Embstr(s) | Encoding::Raw(s) => Cow::Borrowed(s),
+        }
+    }
+
+    pub fn to_vec(&self) -> ByteString {
+        self.as_bytes().into_owned()
+    }
+
+    pub fn into_bytes(self) -> ByteString {
+        match self.0 {
+            Encoding::Int(n) => n.to_string().into_bytes(),
+            Encoding::Embstr(s) | Encoding::Raw(s) => s,
+        }
+    }
+
+    /// The integer stored, when int encoded.
+    pub fn as_int(&self) -> Option<i64> {
+        match self.0 {
+            Encoding::Int(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.0 {
+            Encoding::Int(n) => n.unsigned_abs().checked_ilog10().map_or(1, |d| d as usize + 1) + (*n < 0) as usize,
+            Encoding::Embstr(s) | Encoding::Raw(s) => s.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The bytes for in place modification, converting the string to the raw encoding.
+    pub fn to_mut(&mut self) -> &mut ByteString {
+        match &mut self.0 {
+            Encoding::Int(n) => self.0 = Encoding::Raw(n.to_string().into_bytes()),
+            Encoding::Embstr(s) => self.0 = Encoding::Raw(std::mem::take(s)),
+            Encoding::Raw(_) => {}
+        }
+        let Encoding::Raw(s) = &mut self.0 else { unreachable!() };
+        s
+    }
+
+    /// The name of the encoding, as reported by OBJECT ENCODING.
+    pub fn encoding(&self) -> &'static str {
+        match self.0 {
+            Encoding::Int(_) => "int",
+            Encoding::Embstr(_) => "embstr",
+            Encoding::Raw(_) => "raw",
+        }
+    }
+
+    /// The memory used by the bytes of the string, none for integers.
+    pub(crate) fn heap_bytes(&self) -> usize {
+        match &self.0 {
+            Encoding::Int(_) => 0,
+            Encoding::Embstr(s) | Encoding::Raw(s) => s.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encodings() {
+        for n in [0, 7, -7, 10, -10, 99, i64::MAX, i64::MIN] {
+            let s = Str::from(n.to_string().into_bytes());
+            assert_eq!((s.encoding(), s.len()), ("int", n.to_string().len()));
+        }
+        assert_eq!(Str::from(b"007".to_vec()).encoding(), "embstr");
+        assert_eq!(Str::from(vec![b'x'; 45]).encoding(), "raw");
+        let mut s = Str::from(12);
+        s.to_mut().push(b'3');
+        assert_eq!((s.encoding(), s.as_bytes().as_ref()), ("raw", b"123".as_slice()));
+        assert_eq!(s, Str::from(123));
+    }
+}
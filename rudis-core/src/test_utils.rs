@@ -1,5 +1,5 @@
 use std::cmp::Ordering;
-use crate::Response;
+use crate::{execute_command, Command, Database, Response};
 
 pub trait AsResponse: Sized {
     fn as_response(s: Self) -> Response;
@@ -9,6 +9,10 @@ impl AsResponse for () {
     fn as_response(_: Self) -> Response { Response::Nil }
 }
 
+impl AsResponse for Response {
+    fn as_response(s: Self) -> Response { s }
+}
+
 impl AsResponse for &str {
     fn as_response(s: Self) -> Response { Response::BulkString(s.to_string().into_bytes()) }
 }
@@ -21,6 +25,16 @@ impl AsResponse for f64 {
     fn as_response(s: Self) -> Response { Response::BulkString(s.to_string().into_bytes()) }
 }
 
+/// Executes a command given as space separated words.
+pub fn exec(db: &mut Database, cmd: &str) -> anyhow::Result<Response> {
+    execute_command(db, Command::new(cmd.split(' ').map(|w| w.as_bytes().to_vec()).collect())?)
+}
+
+/// Executes a command given as separate arguments, for those with spaces or binary data.
+pub fn exec_args(db: &mut Database, args: &[&[u8]]) -> anyhow::Result<Response> {
+    execute_command(db, Command::new(args.iter().map(|a| a.to_vec()).collect())?)
+}
+
 pub fn _sort_response(r: &mut Response) {
     let Response::Array(v) = r else { return };
     v.sort_by(|a, b| match (a, b) {
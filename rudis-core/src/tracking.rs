@@ -0,0 +1,166 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{ByteString, Database, Response};
+
+const INVALIDATE_CHANNEL: &[u8] = b"__redis__:invalidate";
+
+/// The CLIENT TRACKING options of a client.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tracking {
+    /// The client receiving the invalidation messages. Without one nothing is sent since
+    /// RESP2 connections can't mix push messages with replies.
+    pub redirect: Option<u64>,
+    /// Invalidates every key starting with one of `prefixes`, or all keys when there are none,
+    /// instead of the keys the client read.
+    pub bcast: bool,
+    pub prefixes: Vec<ByteString>,
+    /// Only track the keys read right after CLIENT CACHING yes.
+    pub optin: bool,
+    /// Track the keys read unless CLIENT CACHING no was sent right before.
+    pub optout: bool,
+    /// Don't invalidate the keys the client modified itself.
+    pub noloop: bool,
+}
+
+/// The clients to invalidate when a key is modified.
+#[derive(Default)]
+pub(crate) struct TrackingTable {
+    keys: HashMap<ByteString, HashSet<u64>>,
+    bcast: HashSet<u64>,
+    /// Keys modified by the current command, invalidated once it is over.
+    pending: Vec<ByteString>,
+}
+
+impl TrackingTable {
+    fn forget(&mut self, id: u64) {
+        self.bcast.remove(&id);
+        self.keys.retain(|_, clients| {
+            clients.remove(&id);
+            !clients.is_empty()
+        });
+    }
+}
+
+impl Database {
+    pub fn enable_tracking(&mut self, tracking: Tracking) -> anyhow::Result<()> {
+        if let Some(redirect) = tracking.redirect {
+            anyhow::ensure!(self.clients.contains_key(&redirect), "The client ID you want redirect to does not exist");
+        }
+        anyhow::ensure!(!(tracking.optin && tracking.optout), "You can't use both OPTIN and OPTOUT");
+        anyhow::ensure!(tracking.bcast || tracking.prefixes.is_empty(), "ERR PREFIX option requires BCAST mode to be enabled");
+        anyhow::ensure!(
+            !(tracking.bcast && (tracking.optin || tracking.optout)),
+            "ERR OPTIN and OPTOUT are not compatible with BCAST",
+        );
+        let mut tracking = tracking;
+        if let Some(current) = &self.client().tracking {
+            anyhow::ensure!(
+                current.bcast == tracking.bcast,
+                "You can't switch BCAST mode on/off before disabling tracking for this client, and then re-enabling it with a different mode.",
+            );
+            for prefix in &current.prefixes {
+                if !tracking.prefixes.contains(prefix) {
+                    tracking.prefixes.push(prefix.clone());
+                }
+            }
+        }
+        for (i, a) in tracking.prefixes.iter().enumerate() {
+            for b in &tracking.prefixes[i + 1..] {
+                anyhow::ensure!(
+                    !a.starts_with(b) && !b.starts_with(a),
+                    "Prefix '{}' overlaps with another provided prefix '{}'. Prefixes for a single client must not overlap.",
+                    String::from_utf8_lossy(a),
+                    String::from_utf8_lossy(b),
+                );
+            }
+        }
+        let id = self.current_client;
+        if tracking.bcast {
+            self.tracking.bcast.insert(id);
+        }
+        self.client().tracking = Some(tracking);
+        Ok(())
+    }
+
+    pub fn disable_tracking(&mut self) {
+        let id = self.current_client;
+        self.client().tracking = None;
+        self.tracking.forget(id);
+    }
+
+    pub(crate) fn remove_tracking_client(&mut self, id: u64) {
+        self.tracking.forget(id);
+    }
+
+    /// Remembers the keys read by the current client if it tracks them, `caching` being the
+    /// CLIENT CACHING sent right before the command.
+    pub(crate) fn track_keys(&mut self, keys: Vec<ByteString>, caching: Option<bool>) {
+        let id = self.current_client;
+        let Some(tracking) = &self.client().tracking else { return };
+        let track = match (tracking.optin, tracking.optout) {
+            _ if tracking.bcast => false,
+            (true, _) => caching == Some(true),
+            (_, true) => caching != Some(false),
+            _ => true,
+        };
+        if track {
+            for key in keys {
+                self.tracking.keys.entry(key).or_default().insert(id);
+            }
+        }
+    }
+
+    /// Marks a key as modified, its tracking clients are sent an invalidation after the command.
+    pub(crate) fn key_modified(&mut self, key: &[u8]) {
+        if !self.tracking.keys.is_empty() || !self.tracking.bcast.is_empty() {
+            self.tracking.pending.push(key.to_vec());
+        }
+    }
+
+    /// Sends the invalidations for the keys modified by the last command.
+    pub(crate) fn flush_invalidations(&mut self) {
+        let mut pending = std::mem::take(&mut self.tracking.pending);
+        pending.sort();
+        pending.dedup();
+        for key in pending {
+            let mut targets = self.tracking.keys.remove(&key).unwrap_or_default();
+            for &id in &self.tracking.bcast {
+                let Some(tracking) = self.clients.get(&id).and_then(|c| c.tracking.as_ref()) else { continue };
+                if tracking.prefixes.is_empty() || tracking.prefixes.iter().any(|p| key.starts_with(p)) {
+                    targets.insert(id);
+                }
+            }
+            for id in targets {
+                self.send_invalidation(id, Response::string_array([key.clone()]));
+            }
+        }
+    }
+
+    /// Invalidates every key, as done when the keyspace is flushed.
+    pub(crate) fn invalidate_all(&mut self) {
+        self.tracking.keys.clear();
+        self.tracking.pending.clear();
+        let ids = self.clients.iter().filter(|(_, c)| c.tracking.is_some()).map(|(&id, _)| id).collect::<Vec<_>>();
+        for id in ids {
+            self.send_invalidation(id, Response::Nil);
+        }
+    }
+
+    fn send_invalidation(&mut self, id: u64, keys: Response) {
+        let Some(tracking) = self.clients.get(&id).and_then(|c| c.tracking.as_ref()) else { return };
+        if tracking.noloop && id == self.current_client {
+            return;
+        }
+        // like Redis, RESP2 targets only get the messages once subscribed to the channel
+        let Some(redirect) = tracking.redirect else { return };
+        if !self.clients.get(&redirect).is_some_and(|c| c.channels.contains(INVALIDATE_CHANNEL)) {
+            return;
+        }
+        let message = Response::Array(vec![
+            Response::BulkString(b"message".to_vec()),
+            Response::BulkString(INVALIDATE_CHANNEL.to_vec()),
+            keys,
+        ]);
+        self.outbox.push((redirect, Ok(message)));
+    }
+}
@@ -1,11 +1,16 @@
 use smol::channel::{Receiver, Sender};
 
-#[derive(Clone)]
 pub struct AsyncPipe<C, R> {
     tx: Sender<(C, Sender<anyhow::Result<R>>)>,
     rx: Receiver<(C, Sender<anyhow::Result<R>>)>,
 }
 
+impl<C, R> Clone for AsyncPipe<C, R> {
+    fn clone(&self) -> Self {
+        Self { tx: self.tx.clone(), rx: self.rx.clone() }
+    }
+}
+
 impl<C, R> AsyncPipe<C, R> {
     pub fn new(cap: usize) -> Self {
         let (tx, rx) = smol::channel::bounded(cap);
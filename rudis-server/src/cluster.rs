@@ -0,0 +1,102 @@
+use std::time::Duration;
+
+use smol::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader};
+use smol::net::TcpStream;
+
+use rudis::{write_response, Migration, Response};
+
+use crate::async_pipe::AsyncPipe;
+use crate::Request;
+
+const GOSSIP_PERIOD: Duration = Duration::from_secs(1);
+
+async fn send(stream: &mut TcpStream, args: Vec<Vec<u8>>) -> anyhow::Result<()> {
+    let mut buf = Vec::new();
+    write_response(&mut buf, Response::string_array(args))?;
+    stream.write_all(&buf).await?;
+    Ok(())
+}
+
+/// Reads a simple reply or the header of a bulk string, failing on errors.
+async fn read_line(reader: &mut BufReader<impl AsyncRead + Unpin>) -> anyhow::Result<String> {
+    let mut line = Vec::new();
+    anyhow::ensure!(reader.read_until(b'\n', &mut line).await? > 0, "connection closed");
+    let line = String::from_utf8_lossy(&line).trim_end().to_string();
+    match line.strip_prefix('-') {
+        Some(e) => anyhow::bail!("{e}"),
+        None => Ok(line),
+    }
+}
+
+async fn read_bulk(reader: &mut BufReader<impl AsyncRead + Unpin>) -> anyhow::Result<String> {
+    let line = read_line(reader).await?;
+    let len = line.strip_prefix('$').and_then(|l| l.parse::<usize>().ok()).ok_or_else(|| anyhow::anyhow!("unexpected reply {line}"))?;
+    let mut buf = vec![0; len + 2];
+    reader.read_exact(&mut buf).await?;
+    buf.truncate(len);
+    Ok(String::from_utf8(buf)?)
+}
+
+/// Introduces ourselves to the node at `ip:port` and then polls its CLUSTER NODES until the
+/// connection breaks, so that both nodes learn about each other's slots and peers.
+async fn gossip(ip: &str, port: u16, me: &(String, u16), auth: Option<&[Vec<u8>]>, pipe: &AsyncPipe<Request, Response>) -> anyhow::Result<()> {
+    let mut stream = TcpStream::connect((ip, port)).await?;
+    let mut reader = BufReader::new(stream.clone());
+    if let Some(auth) = auth {
+        send(&mut stream, auth.to_vec()).await?;
+        read_line(&mut reader).await?;
+    }
+    send(&mut stream, vec![b"CLUSTER".to_vec(), b"MEET".to_vec(), me.0.as_bytes().to_vec(), me.1.to_string().into_bytes()]).await?;
+    read_line(&mut reader).await?;
+    let (tx, _rx) = smol::channel::bounded(1);
+    loop {
+        send(&mut stream, vec![b"CLUSTER".to_vec(), b"NODES".to_vec()]).await?;
+        let nodes = read_bulk(&mut reader).await?;
+        pipe.send(Request::ClusterGossip(ip.to_string(), port, Some(nodes)), tx.clone()).await;
+        smol::Timer::after(GOSSIP_PERIOD).await;
+    }
+}
+
+/// The link to another cluster node, `me` being the address we are reachable at. Gossip goes
+/// through the client port of the nodes, there is no separate cluster bus.
+pub async fn link_task(ip: String, port: u16, me: (String, u16), auth: Option<Vec<Vec<u8>>>, pipe: AsyncPipe<Request, Response>) {
+    let (tx, _rx) = smol::channel::bounded(1);
+    loop {
+        if let Err(e) = gossip(&ip, port, &me, auth.as_deref(), &pipe).await {
+            eprintln!("Cluster link to {ip}:{port} failed: {e}");
+        }
+        pipe.send(Request::ClusterGossip(ip.clone(), port, None), tx.clone()).await;
+        smol::Timer::after(GOSSIP_PERIOD).await;
+    }
+}
+
+async fn migrate(migration: &Migration) -> anyhow::Result<()> {
+    let connect = async {
+        let mut stream = TcpStream::connect((migration.host.as_str(), migration.port)).await?;
+        let mut reader = BufReader::new(stream.clone());
+        for cmd in &migration.commands {
+            send(&mut stream, cmd.clone()).await?;
+            let mut reply = Vec::new();
+            anyhow::ensure!(reader.read_until(b'\n', &mut reply).await? > 0, "IOERR error or timeout connecting to the client");
+            if let Some(e) = reply.strip_prefix(b"-") {
+                anyhow::bail!("Target instance replied with error: {}", String::from_utf8_lossy(e).trim_end());
+            }
+        }
+        Ok(())
+    };
+    let timeout = async {
+        smol::Timer::after(migration.timeout).await;
+        anyhow::bail!("IOERR error or timeout connecting to the client")
+    };
+    smol::future::or(connect, timeout).await
+}
+
+/// Sends the keys of a MIGRATE to the target instance and reports back to the database.
+pub async fn migrate_task(migration: Migration, pipe: AsyncPipe<Request, Response>) {
+    let res = migrate(&migration).await.map_err(|e| match e.downcast_ref::<std::io::Error>() {
+        Some(_) => anyhow::anyhow!("IOERR error or timeout connecting to the client"),
+        None => e,
+    });
+    let (tx, _rx) = smol::channel::bounded(1);
+    pipe.send(Request::Migrated(migration.client, res), tx).await;
+}
@@ -4,11 +4,24 @@ use smol::io::{AsyncRead, AsyncReadExt};
 pub struct CmdParser<R> {
     reader: R,
     peeked: Option<u8>,
+    /// The bytes consumed since the last `take_raw`, when kept.
+    raw: Option<ByteString>,
 }
 
 impl<R: AsyncRead + Unpin> CmdParser<R> {
     pub fn new(reader: R) -> Self {
-        Self { reader, peeked: None }
+        Self { reader, peeked: None, raw: None }
+    }
+
+    /// Keeps the bytes the commands are read from, see `take_raw`.
+    pub fn keep_raw(mut self) -> Self {
+        self.raw = Some(Vec::new());
+        self
+    }
+
+    /// The bytes consumed since the last call, empty unless kept.
+    pub fn take_raw(&mut self) -> ByteString {
+        self.raw.as_mut().map(std::mem::take).unwrap_or_default()
     }
 
     async fn read_byte(&mut self) -> anyhow::Result<u8> {
@@ -25,11 +38,14 @@ impl<R: AsyncRead + Unpin> CmdParser<R> {
     }
 
     async fn consume_byte(&mut self) -> anyhow::Result<u8> {
-        if let Some(b) = self.peeked.take() {
-            Ok(b)
-        } else {
-            self.read_byte().await
+        let b = match self.peeked.take() {
+            Some(b) => b,
+            None => self.read_byte().await?,
+        };
+        if let Some(raw) = &mut self.raw {
+            raw.push(b);
         }
+        Ok(b)
     }
 
     async fn expect(&mut self, pat: &[u8]) -> anyhow::Result<()> {
@@ -107,4 +123,15 @@ mod test {
 
         assert!(parser.read_command().await.is_err())
     }
+
+    #[apply(test!)]
+    async fn test_raw() {
+        let stream = b"*1\r\n$4\r\nPING\r\n*2\r\n$3\r\nDEL\r\n$02\r\nxy\r\n";
+        let mut parser = CmdParser::new(stream.as_slice()).keep_raw();
+        parser.read_command().await.unwrap();
+        assert_eq!(parser.take_raw(), b"*1\r\n$4\r\nPING\r\n");
+        // as sent, not as re-encoded
+        parser.read_command().await.unwrap();
+        assert_eq!(parser.take_raw(), b"*2\r\n$3\r\nDEL\r\n$02\r\nxy\r\n");
+    }
 }
@@ -1,101 +1,517 @@
-use std::io::Write;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::net::SocketAddr;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
 use clap::Parser;
+use futures_rustls::TlsAcceptor;
 use macro_rules_attribute::apply;
 use smol_macros::main;
-use smol::channel::{Receiver, Sender};
-use smol::io::{AsyncWriteExt, BufReader};
-use smol::net::{TcpListener, TcpStream};
+use smol::channel::{Receiver, Sender, TrySendError};
+use smol::Task;
+use smol::io::{AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use smol::net::TcpListener;
+use smol::net::unix::UnixListener;
 
-use rudis::{execute_command, write_response, Command, Database, Response};
+use rudis::{execute_command, execute_master_command, write_error, write_response, ByteString, Command, Config, Database, Response, ScriptState, Sync};
 
 mod cmd_parser;
 mod async_pipe;
+mod cluster;
+mod replica;
+mod tls;
 use cmd_parser::CmdParser;
 use async_pipe::AsyncPipe;
 
+/// Gives each address following `--bind` its own `--bind`: like Redis, it takes all the
+/// arguments up to the next option, even those starting with '-' that clap would otherwise
+/// either reject or, allowed for a list, take `--port` and the like for addresses too.
+fn split_binds(argv: impl IntoIterator<Item = impl Into<OsString>>) -> Vec<OsString> {
+    let (mut res, mut binding, mut first) = (Vec::new(), false, false);
+    for arg in argv.into_iter().map(Into::into) {
+        if arg.to_string_lossy().starts_with("--") {
+            (binding, first) = (arg == "--bind", true);
+        } else if binding && !std::mem::take(&mut first) {
+            res.push("--bind".into());
+        }
+        res.push(arg);
+    }
+    res
+}
+
+/// Options override the values in the config file, see `CONFIG GET *` for their defaults.
 #[derive(clap::Parser)]
 #[command(version, about)]
 struct Args {
-    /// port to listen to
-    #[arg(short, long, default_value = "8888")]
-    port: u16,
+    /// redis.conf-style config file
+    config: Option<PathBuf>,
+
+    /// port to listen to, 0 disables the TCP listener
+    #[arg(short, long)]
+    port: Option<String>,
+
+    /// ips to bind to, up to the next option, addresses prefixed with '-' are skipped if unavailable
+    #[arg(long, allow_hyphen_values = true)]
+    bind: Option<Vec<String>>,
+
+    /// only accept loopback clients on wildcard addresses unless the default user has a password
+    #[arg(long)]
+    protected_mode: Option<String>,
+
+    /// require clients to AUTH with this password
+    #[arg(long)]
+    requirepass: Option<String>,
+
+    /// file to load ACL users from, and to save them to with ACL SAVE
+    #[arg(long)]
+    aclfile: Option<String>,
+
+    /// close connections after being idle for this many seconds, 0 to never close them
+    #[arg(long)]
+    timeout: Option<String>,
+
+    /// path of a unix socket to listen to
+    #[arg(long)]
+    unixsocket: Option<String>,
+
+    /// octal permissions of the unix socket
+    #[arg(long)]
+    unixsocketperm: Option<String>,
 
-    /// ip to bind to
-    #[arg(long, default_value = "0.0.0.0")]
-    bind: String,
+    /// port to accept TLS connections on, 0 disables the TLS listener
+    #[arg(long)]
+    tls_port: Option<String>,
+
+    /// server certificate chain in PEM format
+    #[arg(long)]
+    tls_cert_file: Option<String>,
+
+    /// server private key in PEM format
+    #[arg(long)]
+    tls_key_file: Option<String>,
+
+    /// CA certificates in PEM format used to verify client certificates
+    #[arg(long)]
+    tls_ca_cert_file: Option<String>,
+
+    /// whether clients must present a certificate: yes, no or optional
+    #[arg(long)]
+    tls_auth_clients: Option<String>,
+
+    /// "host port" of a master to replicate from
+    #[arg(long)]
+    replicaof: Option<String>,
+
+    /// password to authenticate to the master with
+    #[arg(long)]
+    masterauth: Option<String>,
+
+    /// run as a cluster node, yes or no
+    #[arg(long)]
+    cluster_enabled: Option<String>,
+
+    /// file where the cluster node saves its id, peers and slots
+    #[arg(long)]
+    cluster_config_file: Option<String>,
 }
 
+impl Args {
+    fn parse_argv(argv: impl IntoIterator<Item = impl Into<OsString>>) -> Self {
+        Self::parse_from(split_binds(argv))
+    }
+
+    fn config(&self) -> anyhow::Result<Config> {
+        let mut config = match &self.config {
+            Some(path) => {
+                let config = Config::load(path)?;
+                for directive in config.unsupported() {
+                    eprintln!("Warning: ignoring unsupported directive at {directive}");
+                }
+                config
+            }
+            None => Config::default(),
+        };
+        // stored space separated like in config files
+        let bind = self.bind.as_ref().map(|ips| ips.join(" "));
+        let overrides = [
+            ("port", &self.port),
+            ("bind", &bind),
+            ("protected-mode", &self.protected_mode),
+            ("requirepass", &self.requirepass),
+            ("aclfile", &self.aclfile),
+            ("timeout", &self.timeout),
+            ("unixsocket", &self.unixsocket),
+            ("unixsocketperm", &self.unixsocketperm),
+            ("tls-port", &self.tls_port),
+            ("tls-cert-file", &self.tls_cert_file),
+            ("tls-key-file", &self.tls_key_file),
+            ("tls-ca-cert-file", &self.tls_ca_cert_file),
+            ("tls-auth-clients", &self.tls_auth_clients),
+            ("replicaof", &self.replicaof),
+            ("masterauth", &self.masterauth),
+            ("cluster-enabled", &self.cluster_enabled),
+            ("cluster-config-file", &self.cluster_config_file),
+        ];
+        for (name, value) in overrides {
+            if let Some(value) = value {
+                config.set(name, value).map_err(|e| anyhow::anyhow!("--{name}: {e}"))?;
+            }
+        }
+        Ok(config)
+    }
+}
+
+enum Request {
+    Connect(u64, Option<SocketAddr>, bool),
+    Command(u64, Command),
+    /// A command of the replication stream with its bytes as sent by our master.
+    MasterCommand(u64, Command, ByteString),
+    Disconnect(u64),
+    /// Periodic work like timing out blocked clients, see `Database::cron`.
+    Tick,
+    MasterConnecting(u64),
+    MasterSync(u64, Sync),
+    /// The CLUSTER NODES output of the node at `ip:port`, `None` when it can't be reached.
+    ClusterGossip(String, u16, Option<String>),
+    Migrated(u64, anyhow::Result<()>),
+}
+
+static NEXT_CLIENT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// The replies queued for a client before it is disconnected for not reading them, like the
+/// client-output-buffer-limit of Redis.
+const OUTPUT_BUFFER_LIMIT: usize = 1024;
+
+/// The `timeout` config in seconds, kept here as it is enforced by the connection tasks.
+static IDLE_TIMEOUT: AtomicU64 = AtomicU64::new(0);
+
+/// Clients are disconnected after `timeout` seconds without a command. Commands are answered
+/// here while the database is busy running a script.
 async fn read_command_task(
-    stream: TcpStream,
-    pipe: AsyncPipe<Command, Response>,
+    stream: impl AsyncRead + Unpin,
+    id: u64,
+    scripts: ScriptState,
+    pipe: AsyncPipe<Request, Response>,
     tx: Sender<anyhow::Result<Response>>,
 ) -> anyhow::Result<()> {
     let mut parser = CmdParser::new(BufReader::new(stream));
     loop {
-        match parser.read_command().await {
+        let res = match IDLE_TIMEOUT.load(Ordering::Relaxed) {
+            0 => parser.read_command().await,
+            timeout => {
+                let idle = async {
+                    smol::Timer::after(Duration::from_secs(timeout)).await;
+                    None
+                };
+                match smol::future::or(async { Some(parser.read_command().await) }, idle).await {
+                    Some(res) => res,
+                    None => return Ok(()),
+                }
+            }
+        };
+        match res {
             Ok(cmd) => {
-                println!("Got command: {}", cmd);
-                pipe.send(cmd, tx.clone()).await;
+                match scripts.intercept(&cmd) {
+                    Some(res) => tx.send(res).await?,
+                    None => pipe.send(Request::Command(id, cmd), tx.clone()).await,
+                }
             }
             Err(e) => tx.send(Err(e)).await?,
         }
     }
 }
 
-async fn send_response_task(mut stream: TcpStream, rx: Receiver<anyhow::Result<Response>>) -> anyhow::Result<()> {
+async fn send_response_task(mut stream: impl AsyncWrite + Unpin, rx: Receiver<anyhow::Result<Response>>) -> anyhow::Result<()> {
     let mut buf = Vec::with_capacity(1 << 16);
     loop {
         buf.clear();
         match rx.recv().await? {
             Ok(res) => write_response(&mut buf, res)?,
-            Err(e) => write!(&mut buf, "-ERR {e}\r\n")?,
+            Err(e) => write_error(&mut buf, &e)?,
         };
         stream.write_all(&buf).await?;
     }
 }
 
-async fn handle_connection(stream: TcpStream, pipe: AsyncPipe<Command, Response>) {
-    let (tx, rx) = smol::channel::bounded(128);
-    let _ = smol::future::zip(
-        read_command_task(stream.clone(), pipe, tx),
-        send_response_task(stream, rx),
+/// Queues a reply without waiting for the client to read the previous ones, which would stall
+/// the database: the channel of a client whose buffer is full is closed instead, which ends
+/// its connection.
+fn send_reply(tx: &Sender<anyhow::Result<Response>>, res: anyhow::Result<Response>) {
+    if let Err(TrySendError::Full(_)) = tx.try_send(res) {
+        tx.close();
+    }
+}
+
+async fn handle_connection(
+    mut stream: impl AsyncRead + AsyncWrite + Unpin,
+    peer: Option<SocketAddr>,
+    exposed: bool,
+    scripts: ScriptState,
+    pipe: AsyncPipe<Request, Response>,
+) {
+    let id = NEXT_CLIENT_ID.fetch_add(1, Ordering::Relaxed);
+    let (tx, rx) = smol::channel::bounded(OUTPUT_BUFFER_LIMIT);
+    pipe.send(Request::Connect(id, peer, exposed), tx.clone()).await;
+    if let Ok(Err(e)) = rx.recv().await {
+        let mut buf = Vec::new();
+        let _ = write_error(&mut buf, &e);
+        let _ = stream.write_all(&buf).await;
+        return;
+    }
+    let (reader, writer) = smol::io::split(stream);
+    let closed = async {
+        tx.closed().await;
+        anyhow::bail!("output buffer limit reached")
+    };
+    let _ = smol::future::race(
+        smol::future::race(
+            read_command_task(reader, id, scripts, pipe.clone(), tx.clone()),
+            send_response_task(writer, rx),
+        ),
+        closed,
     ).await;
+    pipe.send(Request::Disconnect(id), tx).await;
+}
+
+/// The link to our master: its address, client id and the task running it.
+type MasterLink = Option<(String, u16, u64, Task<()>)>;
+
+/// Starts or stops replicating when the master changed, e.g. after REPLICAOF, and returns
+/// the client id of the link that was stopped.
+fn update_master_link(db: &mut Database, link: &mut MasterLink, pipe: &AsyncPipe<Request, Response>) -> Option<u64> {
+    let master = db.master();
+    if link.as_ref().map(|(host, port, ..)| (host.clone(), *port)) == master {
+        return None;
+    }
+    let new = master.map(|(host, port)| {
+        let id = NEXT_CLIENT_ID.fetch_add(1, Ordering::Relaxed);
+        db.start_master_link(id);
+        let task = smol::spawn(replica::link_task(host.clone(), port, id, pipe.clone()));
+        (host, port, id, task)
+    });
+    std::mem::replace(link, new).map(|(_, _, id, _)| id)
 }
 
-async fn database_task(pipe: AsyncPipe<Command, Response>) {
-    let mut db = Database::default();
+/// Keeps a link to every other known cluster node, see `cluster::link_task`.
+fn update_cluster_links(db: &mut Database, links: &mut HashMap<(String, u16), Task<()>>, pipe: &AsyncPipe<Request, Response>) {
+    let Ok(cluster) = db.cluster() else { return };
+    let myself = cluster.myself();
+    let me = (myself.ip.clone(), myself.port);
+    let peers = cluster.peers().map(|n| (n.ip.clone(), n.port)).collect::<Vec<_>>();
+    links.retain(|addr, _| peers.contains(addr));
+    let auth = db.config().get_optional("masterauth").map(|password| {
+        let user = db.config().get_optional("masteruser").map(|u| u.as_bytes().to_vec());
+        [b"AUTH".to_vec()].into_iter().chain(user).chain([password.as_bytes().to_vec()]).collect::<Vec<_>>()
+    });
+    for (ip, port) in peers {
+        links.entry((ip.clone(), port)).or_insert_with(|| {
+            smol::spawn(cluster::link_task(ip, port, me.clone(), auth.clone(), pipe.clone()))
+        });
+    }
+}
+
+async fn database_task(pipe: AsyncPipe<Request, Response>, mut db: Database) {
+    let mut clients = HashMap::new();
+    let mut link = None;
+    let mut cluster_links = HashMap::new();
+    update_master_link(&mut db, &mut link, &pipe);
+    update_cluster_links(&mut db, &mut cluster_links, &pipe);
+    loop {
+        match pipe.recv().await {
+            (Request::Connect(id, peer, exposed), tx) => {
+                let res = db.accept_client(id, peer, exposed).map(|_| Response::Nil);
+                if res.is_ok() {
+                    clients.insert(id, tx.clone());
+                }
+                send_reply(&tx, res);
+            }
+            (Request::Command(id, cmd), tx) => {
+                db.select_client(id);
+                let is_config = cmd.cmd() == "config";
+                let res = execute_command(&mut db, cmd);
+                if is_config {
+                    IDLE_TIMEOUT.store(db.config().get_parsed("timeout"), Ordering::Relaxed);
+                }
+                if !matches!(res, Ok(Response::NoReply)) {
+                    send_reply(&tx, res);
+                }
+            }
+            (Request::MasterCommand(id, cmd, raw), tx) => {
+                db.select_client(id);
+                let res = execute_master_command(&mut db, cmd, raw);
+                if !matches!(res, Ok(Response::NoReply)) {
+                    send_reply(&tx, res);
+                }
+            }
+            (Request::Disconnect(id), _) => {
+                clients.remove(&id);
+                db.remove_client(id);
+            }
+            (Request::Tick, _) => db.cron(),
+            (Request::MasterConnecting(id), tx) => {
+                let res = db.master_link_connecting(id);
+                if res.is_ok() {
+                    clients.insert(id, tx.clone());
+                }
+                send_reply(&tx, res);
+            }
+            (Request::MasterSync(id, sync), tx) => {
+                let res = db.master_link_synced(id, sync).map(|_| Response::Nil);
+                send_reply(&tx, res);
+            }
+            (Request::ClusterGossip(ip, port, nodes), _) => {
+                if let Err(e) = db.cluster().and_then(|c| c.gossip(&ip, port, nodes.as_deref())) {
+                    eprintln!("Invalid gossip from {ip}:{port}: {e}");
+                }
+            }
+            (Request::Migrated(id, res), _) => db.migrated(id, res),
+        }
+        for migration in db.take_migrations() {
+            smol::spawn(cluster::migrate_task(migration, pipe.clone())).detach();
+        }
+        for (id, res) in db.take_outbox() {
+            if let Some(tx) = clients.get(&id) {
+                send_reply(tx, res);
+            }
+        }
+        if let Some(id) = update_master_link(&mut db, &mut link, &pipe) {
+            clients.remove(&id);
+        }
+        update_cluster_links(&mut db, &mut cluster_links, &pipe);
+    }
+}
+
+async fn tick_task(pipe: AsyncPipe<Request, Response>) {
+    let (tx, _rx) = smol::channel::bounded(1);
     loop {
-        let (cmd, tx) = pipe.recv().await;
-        let res = execute_command(&mut db, cmd);
-        let _ = tx.send(res).await;
+        smol::Timer::after(Duration::from_millis(100)).await;
+        pipe.send(Request::Tick, tx.clone()).await;
     }
 }
 
-async fn run_server(bind: &str, port: u16) -> anyhow::Result<()> {
-    let listener = TcpListener::bind((bind, port)).await?;
-    let pipe = AsyncPipe::new(1024);
-    smol::spawn(database_task(pipe.clone())).detach();
+/// Clients connecting from another host to a wildcard address are subject to protected mode.
+fn is_exposed(local: SocketAddr, peer: SocketAddr) -> bool {
+    local.ip().is_unspecified() && !peer.ip().to_canonical().is_loopback()
+}
+
+async fn accept_task(listener: TcpListener, scripts: ScriptState, pipe: AsyncPipe<Request, Response>) -> anyhow::Result<()> {
+    let local = listener.local_addr()?;
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        smol::spawn(handle_connection(stream, Some(peer), is_exposed(local, peer), scripts.clone(), pipe.clone())).detach();
+    }
+}
+
+async fn accept_tls_task(listener: TcpListener, acceptor: TlsAcceptor, scripts: ScriptState, pipe: AsyncPipe<Request, Response>) -> anyhow::Result<()> {
+    let local = listener.local_addr()?;
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let (acceptor, scripts, pipe) = (acceptor.clone(), scripts.clone(), pipe.clone());
+        smol::spawn(async move {
+            match acceptor.accept(stream).await {
+                Ok(stream) => handle_connection(stream, Some(peer), is_exposed(local, peer), scripts, pipe).await,
+                Err(e) => eprintln!("TLS handshake with {peer} failed: {e}"),
+            }
+        }).detach();
+    }
+}
+
+async fn accept_unix_task(listener: UnixListener, scripts: ScriptState, pipe: AsyncPipe<Request, Response>) -> anyhow::Result<()> {
     loop {
         let (stream, _) = listener.accept().await?;
-        smol::spawn(handle_connection(stream, pipe.clone())).detach();
+        smol::spawn(handle_connection(stream, None, false, scripts.clone(), pipe.clone())).detach();
+    }
+}
+
+async fn bind_tcp(binds: &str, port: u16) -> anyhow::Result<Vec<TcpListener>> {
+    let mut listeners = Vec::new();
+    for bind in binds.split_whitespace() {
+        let (addr, optional) = match bind.strip_prefix('-') {
+            Some(addr) => (addr, true),
+            None => (bind, false),
+        };
+        let addr = if addr == "*" { "0.0.0.0" } else { addr };
+        match TcpListener::bind((addr, port)).await {
+            Ok(listener) => listeners.push(listener),
+            Err(e) if optional => eprintln!("Warning: could not bind to {addr}: {e}"),
+            Err(e) => return Err(e.into()),
+        }
+    }
+    anyhow::ensure!(!listeners.is_empty(), "failed to bind to any address");
+    Ok(listeners)
+}
+
+fn bind_unix(path: &Path, perm: &str) -> anyhow::Result<UnixListener> {
+    let mode = u32::from_str_radix(perm, 8).map_err(|_| anyhow::anyhow!("invalid unixsocketperm: {perm}"))?;
+    if path.exists() {
+        std::fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+    Ok(listener)
+}
+
+async fn run_server(args: Args) -> anyhow::Result<()> {
+    let db = Database::from_config(args.config()?)?;
+    let config = db.config().clone();
+    let (port, tls_port) = (config.get_parsed::<u16>("port"), config.get_parsed::<u16>("tls-port"));
+    let unixsocket = config.get_optional("unixsocket");
+    anyhow::ensure!(port != 0 || tls_port != 0 || unixsocket.is_some(), "no TCP port or unix socket to listen to");
+    IDLE_TIMEOUT.store(config.get_parsed("timeout"), Ordering::Relaxed);
+    let scripts = db.script_state();
+
+    let pipe = AsyncPipe::new(1024);
+    let mut tasks = Vec::new();
+    if port != 0 {
+        for listener in bind_tcp(config.get("bind"), port).await? {
+            tasks.push(smol::spawn(accept_task(listener, scripts.clone(), pipe.clone())));
+        }
+    }
+    if tls_port != 0 {
+        let (Some(cert_file), Some(key_file)) = (config.get_optional("tls-cert-file"), config.get_optional("tls-key-file")) else {
+            anyhow::bail!("tls-port requires tls-cert-file and tls-key-file");
+        };
+        let ca_cert_file = config.get_optional("tls-ca-cert-file").map(Path::new);
+        let acceptor = tls::acceptor(Path::new(cert_file), Path::new(key_file), ca_cert_file, config.get("tls-auth-clients"))?;
+        for listener in bind_tcp(config.get("bind"), tls_port).await? {
+            tasks.push(smol::spawn(accept_tls_task(listener, acceptor.clone(), scripts.clone(), pipe.clone())));
+        }
+    }
+    if let Some(path) = unixsocket {
+        let listener = bind_unix(Path::new(path), config.get("unixsocketperm"))?;
+        tasks.push(smol::spawn(accept_unix_task(listener, scripts.clone(), pipe.clone())));
     }
+    smol::spawn(tick_task(pipe.clone())).detach();
+    // the database runs on its own thread so that connections are served while it runs a script
+    std::thread::spawn(move || smol::block_on(database_task(pipe, db)));
+    for task in tasks {
+        task.await?;
+    }
+    Ok(())
 }
 
 #[apply(main!)]
 async fn main() -> anyhow::Result<()> {
-    let args = Args::parse();
-    run_server(&args.bind, args.port).await
+    run_server(Args::parse_argv(std::env::args_os())).await
 }
 
 #[cfg(test)]
 mod tests {
-    use std::time::Duration;
+    use std::io::Write;
 
     use super::*;
     use smol::io::AsyncBufReadExt;
+    use smol::net::TcpStream;
+    use smol::net::unix::UnixStream;
     use smol_macros::test;
 
-    async fn send_cmd(stream: &mut TcpStream, cmd: &[&str]) {
+    fn args(argv: &[&str]) -> Args {
+        Args::parse_argv(["rudis-server"].iter().chain(argv))
+    }
+
+    async fn send_cmd(stream: &mut (impl AsyncWrite + Unpin), cmd: &[&str]) {
         let mut buf = Vec::new();
         write!(buf, "*{}\r\n", cmd.len()).unwrap();
         for c in cmd {
@@ -104,7 +520,7 @@ mod tests {
         stream.write_all(&buf).await.unwrap();
     }
 
-    async fn read_resp(reader: &mut BufReader<TcpStream>) -> Vec<u8> {
+    async fn read_resp(reader: &mut BufReader<impl AsyncRead + Unpin>) -> Vec<u8> {
         let mut buf = Vec::new();
         reader.read_until(b'\n', &mut buf).await.unwrap();
         buf
@@ -112,7 +528,7 @@ mod tests {
 
     #[apply(test!)]
     async fn test_server_communication() {
-        smol::spawn(run_server("127.0.0.1", 61111)).detach();
+        smol::spawn(run_server(args(&["--bind", "127.0.0.1", "--port", "61111"]))).detach();
         smol::Timer::after(Duration::from_millis(100)).await;
         let mut stream = TcpStream::connect(("127.0.0.1", 61111)).await.unwrap();
         send_cmd(&mut stream, &["set", "x", "123"]).await;
@@ -123,4 +539,329 @@ mod tests {
         assert_eq!(read_resp(&mut reader).await, b"+123\r\n");
         assert_eq!(read_resp(&mut reader).await, b"$-1\r\n");
     }
+
+    #[apply(test!)]
+    async fn test_requirepass() {
+        smol::spawn(run_server(args(&["--bind", "127.0.0.1", "--port", "61112", "--requirepass", "secret"]))).detach();
+        smol::Timer::after(Duration::from_millis(100)).await;
+        let mut stream = TcpStream::connect(("127.0.0.1", 61112)).await.unwrap();
+        send_cmd(&mut stream, &["get", "x"]).await;
+        send_cmd(&mut stream, &["auth", "wrong"]).await;
+        send_cmd(&mut stream, &["auth", "secret"]).await;
+        send_cmd(&mut stream, &["get", "x"]).await;
+        let mut reader = BufReader::new(stream);
+        assert_eq!(read_resp(&mut reader).await, b"-NOAUTH Authentication required.\r\n");
+        assert_eq!(read_resp(&mut reader).await, b"-WRONGPASS invalid username-password pair or user is disabled.\r\n");
+        assert_eq!(read_resp(&mut reader).await, b"+OK\r\n");
+        assert_eq!(read_resp(&mut reader).await, b"$-1\r\n");
+    }
+
+    #[apply(test!)]
+    async fn test_multiple_binds() {
+        smol::spawn(run_server(args(&["--bind", "127.0.0.1", "127.0.0.2", "-256.0.0.1", "--port", "61113"]))).detach();
+        smol::Timer::after(Duration::from_millis(100)).await;
+        for ip in ["127.0.0.1", "127.0.0.2"] {
+            let mut stream = TcpStream::connect((ip, 61113)).await.unwrap();
+            send_cmd(&mut stream, &["ping"]).await;
+            assert_eq!(read_resp(&mut BufReader::new(stream)).await, b"+PONG\r\n");
+        }
+        assert!(run_server(args(&["--bind", "256.0.0.1", "--port", "61114"])).await.is_err());
+        // the addresses stop at the next option, repeating --bind works too
+        let parsed = args(&["--bind", "127.0.0.1", "-::1", "--port", "0", "--bind", "127.0.0.2"]);
+        assert_eq!(parsed.bind.unwrap(), ["127.0.0.1", "-::1", "127.0.0.2"]);
+        assert_eq!(parsed.port.as_deref(), Some("0"));
+    }
+
+    #[apply(test!)]
+    async fn test_unixsocket() {
+        let path = std::env::temp_dir().join(format!("rudis-test-{}.sock", std::process::id()));
+        smol::spawn(run_server(args(&["--port", "0", "--unixsocket", path.to_str().unwrap(), "--unixsocketperm", "600"]))).detach();
+        smol::Timer::after(Duration::from_millis(100)).await;
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        let mut stream = UnixStream::connect(&path).await.unwrap();
+        send_cmd(&mut stream, &["set", "x", "1"]).await;
+        send_cmd(&mut stream, &["get", "x"]).await;
+        let mut reader = BufReader::new(stream);
+        assert_eq!(read_resp(&mut reader).await, b"+OK\r\n");
+        assert_eq!(read_resp(&mut reader).await, b"+1\r\n");
+        std::fs::remove_file(path).unwrap();
+        assert!(run_server(args(&["--port", "0"])).await.is_err());
+    }
+
+    #[test]
+    fn test_is_exposed() {
+        let addr = |s: &str| s.parse::<SocketAddr>().unwrap();
+        assert!(is_exposed(addr("0.0.0.0:1"), addr("192.0.2.1:2")));
+        assert!(is_exposed(addr("[::]:1"), addr("[2001:db8::1]:2")));
+        assert!(!is_exposed(addr("0.0.0.0:1"), addr("127.0.0.1:2")));
+        assert!(!is_exposed(addr("[::]:1"), addr("[::ffff:127.0.0.1]:2")));
+        assert!(!is_exposed(addr("192.0.2.2:1"), addr("192.0.2.1:2")));
+    }
+
+    #[test]
+    fn test_protected_mode() {
+        let mut db = Database::default();
+        assert!(db.accept_client(1, None, false).is_ok());
+        assert!(db.accept_client(2, None, true).unwrap_err().to_string().starts_with("DENIED"));
+        db.set_config(&[("requirepass".to_string(), "secret".to_string())]).unwrap();
+        assert!(db.accept_client(3, None, true).is_ok());
+    }
+
+    #[apply(test!)]
+    async fn test_config_file() {
+        let path = std::env::temp_dir().join(format!("rudis-server-test-{}.conf", std::process::id()));
+        std::fs::write(&path, "port 61118\nbind 127.0.0.1\ntimeout 1\n").unwrap();
+        smol::spawn(run_server(args(&[path.to_str().unwrap(), "--port", "61117"]))).detach();
+        smol::Timer::after(Duration::from_millis(100)).await;
+        assert!(TcpStream::connect(("127.0.0.1", 61118)).await.is_err());
+        let mut stream = TcpStream::connect(("127.0.0.1", 61117)).await.unwrap();
+        send_cmd(&mut stream, &["config", "get", "port"]).await;
+        let mut reader = BufReader::new(stream);
+        assert_eq!(read_resp(&mut reader).await, b"*2\r\n");
+        assert_eq!(read_resp(&mut reader).await, b"$4\r\n");
+        assert_eq!(read_resp(&mut reader).await, b"port\r\n");
+        assert_eq!(read_resp(&mut reader).await, b"$5\r\n");
+        assert_eq!(read_resp(&mut reader).await, b"61117\r\n");
+
+        let mut buf = Vec::new();
+        let closed = smol::future::or(
+            async { reader.read_until(b'\n', &mut buf).await.unwrap() == 0 },
+            async { smol::Timer::after(Duration::from_secs(3)).await; false },
+        );
+        assert!(closed.await, "idle connection should be closed after the timeout");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[apply(test!)]
+    async fn test_replication() {
+        smol::spawn(run_server(args(&["--bind", "127.0.0.1", "--port", "61119"]))).detach();
+        smol::Timer::after(Duration::from_millis(100)).await;
+        let mut master = BufReader::new(TcpStream::connect(("127.0.0.1", 61119)).await.unwrap());
+        send_cmd(master.get_mut(), &["set", "before", "1"]).await;
+        assert_eq!(read_resp(&mut master).await, b"+OK\r\n");
+
+        smol::spawn(run_server(args(&["--bind", "127.0.0.1", "--port", "61120", "--replicaof", "127.0.0.1 61119"]))).detach();
+        smol::Timer::after(Duration::from_millis(300)).await;
+        send_cmd(master.get_mut(), &["rpush", "after", "a", "b"]).await;
+        send_cmd(master.get_mut(), &["wait", "1", "1000"]).await;
+        assert_eq!(read_resp(&mut master).await, b":2\r\n");
+        assert_eq!(read_resp(&mut master).await, b":1\r\n");
+
+        let mut replica = BufReader::new(TcpStream::connect(("127.0.0.1", 61120)).await.unwrap());
+        send_cmd(replica.get_mut(), &["get", "before"]).await;
+        send_cmd(replica.get_mut(), &["llen", "after"]).await;
+        send_cmd(replica.get_mut(), &["set", "x", "1"]).await;
+        send_cmd(replica.get_mut(), &["role"]).await;
+        assert_eq!(read_resp(&mut replica).await, b"+1\r\n");
+        assert_eq!(read_resp(&mut replica).await, b":2\r\n");
+        assert_eq!(read_resp(&mut replica).await, b"-READONLY You can't write against a read only replica.\r\n");
+        let role = ["*5", "$5", "slave", "$9", "127.0.0.1", ":61119", "$9", "connected"];
+        for line in role {
+            assert_eq!(read_resp(&mut replica).await, format!("{line}\r\n").into_bytes());
+        }
+        assert!(read_resp(&mut replica).await.starts_with(b":"));
+
+        send_cmd(replica.get_mut(), &["replicaof", "no", "one"]).await;
+        send_cmd(replica.get_mut(), &["set", "x", "1"]).await;
+        assert_eq!(read_resp(&mut replica).await, b"+OK\r\n");
+        assert_eq!(read_resp(&mut replica).await, b"+OK\r\n");
+    }
+
+    #[apply(test!)]
+    async fn test_cluster() {
+        let dir = std::env::temp_dir().join(format!("rudis-cluster-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut nodes = Vec::new();
+        for (port, slots) in [(61121, ["0", "5460"]), (61122, ["5461", "10922"]), (61123, ["10923", "16383"])] {
+            let nodes_file = dir.join(format!("nodes-{port}.conf")).to_str().unwrap().to_string();
+            let port = port.to_string();
+            let argv = ["--bind", "127.0.0.1", "--port", &port, "--cluster-enabled", "yes", "--cluster-config-file", &nodes_file];
+            smol::spawn(run_server(args(&argv))).detach();
+            smol::Timer::after(Duration::from_millis(100)).await;
+            let mut node = BufReader::new(TcpStream::connect(("127.0.0.1", port.parse::<u16>().unwrap())).await.unwrap());
+            send_cmd(node.get_mut(), &["cluster", "addslotsrange", slots[0], slots[1]]).await;
+            assert_eq!(read_resp(&mut node).await, b"+OK\r\n");
+            send_cmd(node.get_mut(), &["cluster", "myid"]).await;
+            read_resp(&mut node).await;
+            let id = String::from_utf8(read_resp(&mut node).await).unwrap().trim_end().to_string();
+            nodes.push((node, id));
+        }
+        for port in ["61122", "61123"] {
+            send_cmd(nodes[0].0.get_mut(), &["cluster", "meet", "127.0.0.1", port]).await;
+            assert_eq!(read_resp(&mut nodes[0].0).await, b"+OK\r\n");
+        }
+
+        // the second and third nodes learn about each other from the first one
+        for _ in 0..50 {
+            smol::Timer::after(Duration::from_millis(100)).await;
+            send_cmd(nodes[1].0.get_mut(), &["get", "foo"]).await;
+            if read_resp(&mut nodes[1].0).await == b"-MOVED 12182 127.0.0.1:61123\r\n" {
+                break;
+            }
+        }
+        let [mut n1, mut n2, mut n3] = nodes.try_into().unwrap_or_else(|_| unreachable!());
+        send_cmd(n2.0.get_mut(), &["get", "foo"]).await;
+        assert_eq!(read_resp(&mut n2.0).await, b"-MOVED 12182 127.0.0.1:61123\r\n");
+        send_cmd(n1.0.get_mut(), &["mset", "a", "1", "b", "2"]).await;
+        assert_eq!(read_resp(&mut n1.0).await, b"-CROSSSLOT Keys in request don't hash to the same slot\r\n");
+
+        // move slot 16287 of "x" from the third node to the first one
+        send_cmd(n3.0.get_mut(), &["set", "x", "1"]).await;
+        send_cmd(n1.0.get_mut(), &["cluster", "setslot", "16287", "importing", &n3.1]).await;
+        send_cmd(n3.0.get_mut(), &["cluster", "setslot", "16287", "migrating", &n1.1]).await;
+        send_cmd(n3.0.get_mut(), &["get", "{x}y"]).await;
+        send_cmd(n3.0.get_mut(), &["migrate", "127.0.0.1", "61121", "x", "0", "1000"]).await;
+        send_cmd(n3.0.get_mut(), &["get", "x"]).await;
+        assert_eq!(read_resp(&mut n3.0).await, b"+OK\r\n");
+        assert_eq!(read_resp(&mut n1.0).await, b"+OK\r\n");
+        assert_eq!(read_resp(&mut n3.0).await, b"+OK\r\n");
+        assert_eq!(read_resp(&mut n3.0).await, b"-ASK 16287 127.0.0.1:61121\r\n");
+        assert_eq!(read_resp(&mut n3.0).await, b"+OK\r\n");
+        assert_eq!(read_resp(&mut n3.0).await, b"-ASK 16287 127.0.0.1:61121\r\n");
+        send_cmd(n1.0.get_mut(), &["get", "x"]).await;
+        send_cmd(n1.0.get_mut(), &["asking"]).await;
+        send_cmd(n1.0.get_mut(), &["get", "x"]).await;
+        assert_eq!(read_resp(&mut n1.0).await, b"-MOVED 16287 127.0.0.1:61123\r\n");
+        assert_eq!(read_resp(&mut n1.0).await, b"+OK\r\n");
+        assert_eq!(read_resp(&mut n1.0).await, b"+1\r\n");
+
+        send_cmd(n1.0.get_mut(), &["cluster", "setslot", "16287", "node", &n1.1]).await;
+        send_cmd(n3.0.get_mut(), &["cluster", "setslot", "16287", "node", &n1.1]).await;
+        send_cmd(n1.0.get_mut(), &["get", "x"]).await;
+        send_cmd(n3.0.get_mut(), &["get", "x"]).await;
+        assert_eq!(read_resp(&mut n1.0).await, b"+OK\r\n");
+        assert_eq!(read_resp(&mut n3.0).await, b"+OK\r\n");
+        assert_eq!(read_resp(&mut n1.0).await, b"+1\r\n");
+        assert_eq!(read_resp(&mut n3.0).await, b"-MOVED 16287 127.0.0.1:61121\r\n");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[apply(test!)]
+    async fn test_script_kill() {
+        smol::spawn(run_server(args(&["--bind", "127.0.0.1", "--port", "61124"]))).detach();
+        smol::Timer::after(Duration::from_millis(100)).await;
+        let mut script = BufReader::new(TcpStream::connect(("127.0.0.1", 61124)).await.unwrap());
+        let mut other = BufReader::new(TcpStream::connect(("127.0.0.1", 61124)).await.unwrap());
+        send_cmd(other.get_mut(), &["script", "kill"]).await;
+        send_cmd(other.get_mut(), &["config", "set", "busy-reply-threshold", "100"]).await;
+        assert_eq!(read_resp(&mut other).await, b"-NOTBUSY No scripts in execution right now.\r\n");
+        assert_eq!(read_resp(&mut other).await, b"+OK\r\n");
+
+        send_cmd(script.get_mut(), &["eval", "while true do end", "0"]).await;
+        smol::Timer::after(Duration::from_millis(300)).await;
+        send_cmd(other.get_mut(), &["get", "x"]).await;
+        send_cmd(other.get_mut(), &["script", "kill"]).await;
+        assert_eq!(read_resp(&mut other).await, b"-BUSY Redis is busy running a script. You can only call SCRIPT KILL or SHUTDOWN NOSAVE.\r\n");
+        assert_eq!(read_resp(&mut other).await, b"+OK\r\n");
+        assert!(read_resp(&mut script).await.starts_with(b"-ERR Script killed by user with SCRIPT KILL..."));
+        send_cmd(other.get_mut(), &["ping"]).await;
+        assert_eq!(read_resp(&mut other).await, b"+PONG\r\n");
+    }
+
+    #[apply(test!)]
+    async fn test_keyspace_notifications() {
+        smol::spawn(run_server(args(&["--bind", "127.0.0.1", "--port", "61125"]))).detach();
+        smol::Timer::after(Duration::from_millis(100)).await;
+        let mut subscriber = BufReader::new(TcpStream::connect(("127.0.0.1", 61125)).await.unwrap());
+        let mut other = BufReader::new(TcpStream::connect(("127.0.0.1", 61125)).await.unwrap());
+        send_cmd(other.get_mut(), &["config", "set", "notify-keyspace-events", "E$"]).await;
+        assert_eq!(read_resp(&mut other).await, b"+OK\r\n");
+        send_cmd(subscriber.get_mut(), &["subscribe", "__keyevent@0__:set"]).await;
+        let mut reply = Vec::new();
+        for _ in 0..6 {
+            reply.extend(read_resp(&mut subscriber).await);
+        }
+        assert_eq!(reply, b"*3\r\n$9\r\nsubscribe\r\n$18\r\n__keyevent@0__:set\r\n:1\r\n");
+
+        send_cmd(other.get_mut(), &["set", "x", "1"]).await;
+        assert_eq!(read_resp(&mut other).await, b"+OK\r\n");
+        let mut message = Vec::new();
+        for _ in 0..7 {
+            message.extend(read_resp(&mut subscriber).await);
+        }
+        assert_eq!(message, b"*3\r\n$7\r\nmessage\r\n$18\r\n__keyevent@0__:set\r\n$1\r\nx\r\n");
+    }
+
+    #[apply(test!)]
+    async fn test_output_buffer_limit() {
+        use smol::io::AsyncReadExt;
+
+        smol::spawn(run_server(args(&["--bind", "127.0.0.1", "--port", "61126"]))).detach();
+        smol::Timer::after(Duration::from_millis(100)).await;
+        let mut subscriber = BufReader::new(TcpStream::connect(("127.0.0.1", 61126)).await.unwrap());
+        let mut other = BufReader::new(TcpStream::connect(("127.0.0.1", 61126)).await.unwrap());
+        send_cmd(subscriber.get_mut(), &["subscribe", "c"]).await;
+        for _ in 0..6 {
+            read_resp(&mut subscriber).await;
+        }
+
+        // the subscriber doesn't read the messages, which must not stall the publisher
+        let message = "x".repeat(1 << 14);
+        for _ in 0..2 * OUTPUT_BUFFER_LIMIT {
+            send_cmd(other.get_mut(), &["publish", "c", &message]).await;
+            read_resp(&mut other).await;
+        }
+        send_cmd(other.get_mut(), &["ping"]).await;
+        assert_eq!(read_resp(&mut other).await, b"+PONG\r\n");
+        let mut buf = Vec::new();
+        assert!(subscriber.read_to_end(&mut buf).await.is_err() || buf.len() < 2 * OUTPUT_BUFFER_LIMIT * message.len());
+    }
+
+    #[apply(test!)]
+    async fn test_tls() {
+        use std::sync::Arc;
+        use futures_rustls::TlsConnector;
+        use futures_rustls::rustls::{ClientConfig, RootCertStore};
+        use futures_rustls::rustls::crypto::ring;
+        use futures_rustls::rustls::pki_types::{PrivateKeyDer, ServerName};
+        use rcgen::{BasicConstraints, CertificateParams, ExtendedKeyUsagePurpose, IsCa, KeyPair};
+
+        let ca_key = KeyPair::generate().unwrap();
+        let mut ca_params = CertificateParams::new(Vec::new()).unwrap();
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = ca_params.self_signed(&ca_key).unwrap();
+        let server_key = KeyPair::generate().unwrap();
+        let server_cert = CertificateParams::new(vec!["localhost".to_string()]).unwrap().signed_by(&server_key, &ca, &ca_key).unwrap();
+        let client_key = KeyPair::generate().unwrap();
+        let mut client_params = CertificateParams::new(Vec::new()).unwrap();
+        client_params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+        let client_cert = client_params.signed_by(&client_key, &ca, &ca_key).unwrap();
+
+        let dir = std::env::temp_dir().join(format!("rudis-tls-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("ca.crt"), ca.pem()).unwrap();
+        std::fs::write(dir.join("server.crt"), server_cert.pem()).unwrap();
+        std::fs::write(dir.join("server.key"), server_key.serialize_pem()).unwrap();
+        let path = |f: &str| dir.join(f).to_str().unwrap().to_string();
+        smol::spawn(run_server(args(&[
+            "--port", "0",
+            "--bind", "127.0.0.1",
+            "--tls-port", "61116",
+            "--tls-cert-file", &path("server.crt"),
+            "--tls-key-file", &path("server.key"),
+            "--tls-ca-cert-file", &path("ca.crt"),
+        ]))).detach();
+        smol::Timer::after(Duration::from_millis(100)).await;
+
+        let mut roots = RootCertStore::empty();
+        roots.add(ca.der().clone()).unwrap();
+        let client_config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots);
+        let connect = |config: ClientConfig| async move {
+            let stream = TcpStream::connect(("127.0.0.1", 61116)).await.unwrap();
+            let server_name = ServerName::try_from("localhost").unwrap();
+            let mut stream = TlsConnector::from(Arc::new(config)).connect(server_name, stream).await.unwrap();
+            send_cmd(&mut stream, &["ping"]).await;
+            let mut buf = Vec::new();
+            BufReader::new(stream).read_until(b'\n', &mut buf).await.map(|_| buf)
+        };
+
+        let key = PrivateKeyDer::try_from(client_key.serialize_der()).unwrap();
+        let config = client_config.clone().with_client_auth_cert(vec![client_cert.der().clone()], key).unwrap();
+        assert_eq!(connect(config).await.unwrap(), b"+PONG\r\n");
+        assert!(connect(client_config.with_no_client_auth()).await.is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 }
@@ -0,0 +1,88 @@
+use std::time::Duration;
+
+use smol::channel::Sender;
+use smol::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader};
+use smol::net::TcpStream;
+
+use rudis::{write_response, Response, Sync};
+
+use crate::async_pipe::AsyncPipe;
+use crate::cmd_parser::CmdParser;
+use crate::{send_response_task, Request, OUTPUT_BUFFER_LIMIT};
+
+async fn read_line(reader: &mut BufReader<impl AsyncRead + Unpin>) -> anyhow::Result<String> {
+    let mut line = Vec::new();
+    anyhow::ensure!(reader.read_until(b'\n', &mut line).await? > 0, "connection closed by master");
+    Ok(String::from_utf8_lossy(&line).trim_end().to_string())
+}
+
+/// Connects to the master, performs the handshake sent by the database and then applies
+/// the replication stream until the connection breaks.
+async fn sync_with_master(host: &str, port: u16, id: u64, pipe: &AsyncPipe<Request, Response>) -> anyhow::Result<()> {
+    let stream = TcpStream::connect((host, port)).await?;
+    let (tx, rx) = smol::channel::bounded(OUTPUT_BUFFER_LIMIT);
+    pipe.send(Request::MasterConnecting(id), tx.clone()).await;
+    let Response::Array(handshake) = rx.recv().await?? else {
+        anyhow::bail!("unexpected handshake");
+    };
+    let (reader, mut writer) = smol::io::split(stream);
+    let mut reader = BufReader::new(reader);
+    let mut reply = String::new();
+    for cmd in handshake {
+        let mut buf = Vec::new();
+        write_response(&mut buf, cmd)?;
+        writer.write_all(&buf).await?;
+        reply = read_line(&mut reader).await?;
+        anyhow::ensure!(!reply.starts_with('-'), "master replied {reply}");
+    }
+
+    let sync = if let Some(header) = reply.strip_prefix("+FULLRESYNC ") {
+        let (replid, offset) = header.split_once(' ').ok_or_else(|| anyhow::anyhow!("invalid reply {reply}"))?;
+        // the master may send newlines to keep the connection alive while preparing the snapshot
+        let len = loop {
+            let line = read_line(&mut reader).await?;
+            if let Some(len) = line.strip_prefix('$') {
+                break len.parse()?;
+            }
+        };
+        let mut rdb = vec![0; len];
+        reader.read_exact(&mut rdb).await?;
+        Sync::Full { replid: replid.to_string(), offset: offset.parse()?, rdb }
+    } else if let Some(replid) = reply.strip_prefix("+CONTINUE") {
+        Sync::Partial { replid: Some(replid.trim()).filter(|r| !r.is_empty()).map(str::to_string) }
+    } else {
+        anyhow::bail!("unexpected reply to PSYNC: {reply}");
+    };
+    pipe.send(Request::MasterSync(id, sync), tx.clone()).await;
+    rx.recv().await??;
+    smol::future::race(
+        read_master_task(reader, id, pipe.clone(), tx),
+        send_response_task(writer, rx),
+    ).await
+}
+
+/// Reads the replication stream, passing each command along with its bytes as sent by the
+/// master, which are what the replication offset counts.
+async fn read_master_task(
+    reader: impl AsyncRead + Unpin,
+    id: u64,
+    pipe: AsyncPipe<Request, Response>,
+    tx: Sender<anyhow::Result<Response>>,
+) -> anyhow::Result<()> {
+    let mut parser = CmdParser::new(reader).keep_raw();
+    loop {
+        let cmd = parser.read_command().await?;
+        pipe.send(Request::MasterCommand(id, cmd, parser.take_raw()), tx.clone()).await;
+    }
+}
+
+pub async fn link_task(host: String, port: u16, id: u64, pipe: AsyncPipe<Request, Response>) {
+    loop {
+        if let Err(e) = sync_with_master(&host, port, id, &pipe).await {
+            eprintln!("Replication from {host}:{port} failed: {e}");
+        }
+        let (tx, _) = smol::channel::bounded(1);
+        pipe.send(Request::Disconnect(id), tx).await;
+        smol::Timer::after(Duration::from_secs(1)).await;
+    }
+}
@@ -0,0 +1,43 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use futures_rustls::rustls::crypto::ring;
+use futures_rustls::rustls::pki_types::pem::PemObject;
+use futures_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use futures_rustls::rustls::server::WebPkiClientVerifier;
+use futures_rustls::rustls::{RootCertStore, ServerConfig};
+use futures_rustls::TlsAcceptor;
+
+fn load_certs(path: &Path) -> anyhow::Result<Vec<CertificateDer<'static>>> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| anyhow::anyhow!("failed to load certificates from {}: {e}", path.display()))?;
+    anyhow::ensure!(!certs.is_empty(), "no certificates found in {}", path.display());
+    Ok(certs)
+}
+
+/// `auth_clients` is the `tls-auth-clients` config, one of yes, no or optional.
+pub fn acceptor(cert_file: &Path, key_file: &Path, ca_cert_file: Option<&Path>, auth_clients: &str) -> anyhow::Result<TlsAcceptor> {
+    let provider = Arc::new(ring::default_provider());
+    let builder = ServerConfig::builder_with_provider(provider.clone()).with_safe_default_protocol_versions()?;
+    let builder = match (ca_cert_file, auth_clients) {
+        (Some(ca_cert_file), "yes" | "optional") => {
+            let mut roots = RootCertStore::empty();
+            for cert in load_certs(ca_cert_file)? {
+                roots.add(cert)?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+            let verifier = match auth_clients {
+                "optional" => verifier.allow_unauthenticated().build()?,
+                _ => verifier.build()?,
+            };
+            builder.with_client_cert_verifier(verifier)
+        }
+        (None, "yes") => anyhow::bail!("tls-auth-clients requires tls-ca-cert-file, set it to no to accept all clients"),
+        _ => builder.with_no_client_auth(),
+    };
+    let key = PrivateKeyDer::from_pem_file(key_file)
+        .map_err(|e| anyhow::anyhow!("failed to load private key from {}: {e}", key_file.display()))?;
+    let config = builder.with_single_cert(load_certs(cert_file)?, key)?;
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}